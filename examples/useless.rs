@@ -1,3 +1,10 @@
+// This example is deliberately full of dead code: keep clippy quiet about it,
+// while `cargo check` (which is what cargo-minify uses) still reports it.
+#![cfg_attr(
+    clippy,
+    allow(dead_code, unused_macros, unused_doc_comments, clippy::all)
+)]
+
 fn main() {
     println!("Hello, world!");
 }
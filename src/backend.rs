@@ -0,0 +1,194 @@
+//! This module runs the tools that report the unused code, each of which is a
+//! [`Backend`] that produces compiler messages in the JSON format of cargo.
+//! Which of these messages are used, and what is taken from them, is decided
+//! by [`crate::unused`], in the same way for every backend.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    path::Path,
+    process::Stdio,
+    str::FromStr,
+    sync::{atomic::Ordering, mpsc},
+    time::{Duration, Instant},
+};
+
+use cargo_metadata::Message;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::{
+    cargo,
+    error::Result,
+    interrupt, resolver,
+    unused::{CheckOptions, TIMED_OUT},
+    CrateResolutionOptions,
+};
+
+/// A source of compiler messages, such as `cargo check`
+pub trait Backend {
+    /// What the messages come from, e.g. "cargo check"
+    fn name(&self) -> String;
+
+    /// Passes the messages about the given targets (as selected by arguments
+    /// of cargo, such as `--all-targets`) to `visit`, as they come in
+    fn messages(&self, targets: &[&str], visit: &mut dyn FnMut(Message)) -> Result<()>;
+}
+
+/// The backend to use (see `--backend`)
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum BackendKind {
+    /// `cargo check`
+    #[default]
+    Check,
+    /// `cargo clippy`, which reports the same unused items as `cargo check`,
+    /// for crates that are only ever checked with clippy
+    Clippy,
+    /// The output of either, recorded earlier (see `--from-json`)
+    Json,
+}
+
+impl FromStr for BackendKind {
+    type Err = UnsupportedBackend;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "check" => Ok(BackendKind::Check),
+            "clippy" => Ok(BackendKind::Clippy),
+            "json" => Ok(BackendKind::Json),
+            _ => Err(UnsupportedBackend),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported backend, pick any of: check, clippy, json")]
+pub struct UnsupportedBackend;
+
+/// The backend that the options select
+pub fn backend<'a>(
+    manifest_path: Option<&'a Path>,
+    crate_resolution: &'a CrateResolutionOptions<'a>,
+    check: &'a CheckOptions<'a>,
+) -> Box<dyn Backend + 'a> {
+    match (check.backend, check.recorded) {
+        (BackendKind::Json, Some(recorded)) => Box::new(Recorded { recorded }),
+        (BackendKind::Clippy, _) => Box::new(Cargo {
+            subcommand: "clippy",
+            manifest_path,
+            crate_resolution,
+            check,
+        }),
+        _ => Box::new(Cargo {
+            subcommand: "check",
+            manifest_path,
+            crate_resolution,
+            check,
+        }),
+    }
+}
+
+/// Runs a cargo subcommand that checks the crates, such as `cargo check`
+struct Cargo<'a> {
+    subcommand: &'static str,
+    manifest_path: Option<&'a Path>,
+    crate_resolution: &'a CrateResolutionOptions<'a>,
+    check: &'a CheckOptions<'a>,
+}
+
+impl Backend for Cargo<'_> {
+    fn name(&self) -> String {
+        format!("cargo {}", self.subcommand)
+    }
+
+    fn messages(&self, targets: &[&str], visit: &mut dyn FnMut(Message)) -> Result<()> {
+        let mut command = cargo::command(self.subcommand, self.manifest_path)?;
+
+        command.args(targets);
+        command.args(["--quiet", "--message-format", "json"]);
+        if self.check.keep_going {
+            command.arg("--keep-going");
+        }
+        if self.check.warn_dead_code {
+            // flags given to rustc last take precedence over the `[lints]`
+            // table; unlike `RUSTFLAGS`, `--config` adds to the flags of the
+            // user
+            match std::env::var("RUSTFLAGS") {
+                Ok(flags) => command.env("RUSTFLAGS", format!("{flags} -Wdead_code")),
+                Err(_) => command.args(["--config", "build.rustflags=[\"-Wdead_code\"]"]),
+            };
+        }
+
+        match self.crate_resolution {
+            CrateResolutionOptions::Root => {}
+            CrateResolutionOptions::Workspace { exclude, .. } => {
+                command.arg("--workspace");
+
+                for package in *exclude {
+                    command.args(["--exclude", package]);
+                }
+            }
+            CrateResolutionOptions::Package { packages } => {
+                let metadata = resolver::get_cargo_metadata(self.manifest_path)?;
+                for package in resolver::package_names(&metadata.packages, packages)? {
+                    command.args(["-p", &package]);
+                }
+            }
+        }
+
+        // a check that would start after the deadline is stopped right away
+        let remaining = self
+            .check
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining == Some(Duration::ZERO) {
+            TIMED_OUT.store(true, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        debug!("running {command:?}");
+        let stdout = interrupt::spawn(command.stdout(Stdio::piped()))?;
+        let reader = BufReader::new(stdout);
+
+        // stopping cargo closes its output, which ends the parsing below
+        let (finished, watched) = mpsc::channel::<()>();
+        let watchdog = remaining.map(|remaining| {
+            std::thread::spawn(move || {
+                if watched.recv_timeout(remaining) == Err(mpsc::RecvTimeoutError::Timeout) {
+                    TIMED_OUT.store(true, Ordering::SeqCst);
+                    interrupt::kill_child();
+                }
+            })
+        });
+
+        Message::parse_stream(reader).flatten().for_each(visit);
+        drop(finished);
+        if let Some(watchdog) = watchdog {
+            let _ = watchdog.join();
+        }
+        interrupt::wait_child();
+
+        Ok(())
+    }
+}
+
+/// Reads the output of `cargo check --message-format json` (or of clippy)
+/// that was recorded to a file (see `--from-json`); the targets that were
+/// checked are those of the recording
+struct Recorded<'a> {
+    recorded: &'a Path,
+}
+
+impl Backend for Recorded<'_> {
+    fn name(&self) -> String {
+        self.recorded.display().to_string()
+    }
+
+    fn messages(&self, _: &[&str], visit: &mut dyn FnMut(Message)) -> Result<()> {
+        debug!("reading diagnostics from {}", self.recorded.display());
+        let reader = BufReader::new(File::open(self.recorded)?);
+        Message::parse_stream(reader).flatten().for_each(visit);
+
+        Ok(())
+    }
+}
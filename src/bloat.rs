@@ -0,0 +1,116 @@
+//! This module reads the output of `cargo bloat --message-format json`, to
+//! tell how many bytes of code removing a function saves.
+
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// The functions in a binary, by their (demangled) path
+pub struct Bloat {
+    functions: Vec<(String, usize)>,
+}
+
+impl Bloat {
+    pub fn parse(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let Some(functions) = value["functions"].as_array() else {
+            return Err(Error::Bloat("no list of functions"));
+        };
+
+        let functions = functions
+            .iter()
+            .filter_map(|function| {
+                let name = function["name"].as_str()?;
+                let size = function["size"].as_u64()?;
+                Some((name.to_owned(), size as usize))
+            })
+            .collect();
+        Ok(Bloat { functions })
+    }
+
+    pub fn read(file_name: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(file_name)?)
+    }
+
+    /// The total size of the function with the given path, e.g.
+    /// `mycrate::Thing::new` (including all of its monomorphizations), if it
+    /// is part of the binary
+    pub fn size_of(&self, path: &str) -> Option<usize> {
+        let mut sizes = self
+            .functions
+            .iter()
+            .filter(|(name, _)| simplify(name) == path)
+            .map(|(_, size)| size)
+            .peekable();
+
+        sizes.peek()?;
+        Some(sizes.sum())
+    }
+}
+
+/// Strips the generic arguments, the hash and the angle brackets around a
+/// self type from a path, e.g. `<mycrate::Thing<u8>>::new::h0123456789abcdef`
+/// becomes `mycrate::Thing::new`
+fn simplify(path: &str) -> String {
+    let mut simple = String::new();
+    let mut depth = 0;
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '<' if simple.is_empty() || simple.ends_with("::") => {}
+            '<' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            '>' => {}
+            // the trait of a trait impl
+            ' ' if depth == 0 => {
+                while chars.peek().is_some_and(|&c| c != '>') {
+                    chars.next();
+                }
+            }
+            _ if depth == 0 => simple.push(c),
+            _ => {}
+        }
+    }
+
+    match simple.rsplit_once("::") {
+        Some((rest, hash))
+            if hash.len() == 17
+                && hash.starts_with('h')
+                && hash[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            rest.to_owned()
+        }
+        _ => simple,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        let bloat = Bloat::parse(
+            r#"{
+                "file-size": 4096,
+                "text-section-size": 2048,
+                "functions": [
+                    {"crate": "app", "name": "app::parse_legacy", "size": 120},
+                    {"crate": "app", "name": "<app::Thing<u8>>::new", "size": 30},
+                    {"crate": "app", "name": "<app::Thing<u16>>::new", "size": 34},
+                    {"crate": "app", "name": "<app::Thing as core::fmt::Debug>::fmt", "size": 50},
+                    {"crate": "app", "name": "app::main::h0123456789abcdef", "size": 10},
+                    {"crate": "std", "name": "std::rt::lang_start", "size": 80}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(bloat.size_of("app::parse_legacy"), Some(120));
+        assert_eq!(bloat.size_of("app::Thing::new"), Some(64));
+        assert_eq!(bloat.size_of("app::Thing::fmt"), Some(50));
+        assert_eq!(bloat.size_of("app::main"), Some(10));
+        assert_eq!(bloat.size_of("std::rt::lang_start"), Some(80));
+        assert_eq!(bloat.size_of("app::missing"), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! This module finds the items in invocations of well-known macros that only
+//! wrap items in conditions, such as `cfg_if::cfg_if!` and `std::cfg_select!`,
+//! which syn sees as opaque macro calls. The items are parsed with their
+//! original spans, so they can be found (and removed) like any other item, and
+//! each is given a `#[cfg(...)]` attribute for the branch it is in.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{braced, parse::ParseStream, Token};
+
+/// A branch of a block macro: its condition (`None` for the `else` branch)
+/// and the items in it
+type Branch = (Option<TokenStream>, Vec<syn::Item>);
+
+/// Replaces the invocations of block macros by the items in them, descending
+/// into inline modules and nested invocations; invocations that cannot be
+/// parsed are left alone
+pub fn flatten(items: &mut Vec<syn::Item>) {
+    let mut flattened = Vec::with_capacity(items.len());
+    for mut item in items.drain(..) {
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = &mut item
+        {
+            flatten(items);
+        }
+
+        match block_items(&item) {
+            Some(mut items) => {
+                flatten(&mut items);
+                flattened.extend(items);
+            }
+            None => flattened.push(item),
+        }
+    }
+
+    *items = flattened;
+}
+
+/// The items of an invocation of a block macro, each with the `#[cfg(...)]`
+/// under which it is compiled
+fn block_items(item: &syn::Item) -> Option<Vec<syn::Item>> {
+    let syn::Item::Macro(invocation) = item else {
+        return None;
+    };
+    if invocation.ident.is_some() {
+        return None;
+    }
+
+    let name = invocation.mac.path.segments.last()?.ident.to_string();
+    let branches = match name.as_str() {
+        "cfg_if" => invocation.mac.parse_body_with(cfg_if_branches).ok()?,
+        "cfg_select" | "cfg_match" => invocation.mac.parse_body_with(cfg_select_branches).ok()?,
+        _ => return None,
+    };
+    // the invocation itself may be configured out as well
+    let outer: Vec<_> = invocation
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .filter_map(|attr| Some(attr.meta.require_list().ok()?.tokens.clone()))
+        .collect();
+
+    // a branch only applies if none of the ones before it do
+    let mut previous = Vec::new();
+    let mut found = Vec::new();
+    for (predicate, items) in branches {
+        let mut conditions = outer.clone();
+        if !previous.is_empty() {
+            conditions.push(quote!(not(any(#(#previous),*))));
+        }
+        conditions.extend(predicate.clone());
+        previous.extend(predicate);
+
+        for mut item in items {
+            let span = item.to_token_stream().into_iter().next()?.span();
+            let cfg: syn::Attribute =
+                syn::parse_quote_spanned!(span=> #[cfg(all(#(#conditions),*))]);
+            attrs_mut(&mut item)?.insert(0, cfg);
+            found.push(item);
+        }
+    }
+
+    Some(found)
+}
+
+/// Parses `if #[cfg(a)] { ... } else if #[cfg(b)] { ... } else { ... }`
+fn cfg_if_branches(input: ParseStream) -> syn::Result<Vec<Branch>> {
+    let mut branches = Vec::new();
+    loop {
+        input.parse::<Token![if]>()?;
+        let predicate = cfg_predicate(input)?;
+        branches.push((Some(predicate), braced_items(input)?));
+        if input.is_empty() {
+            return Ok(branches);
+        }
+
+        input.parse::<Token![else]>()?;
+        if !input.peek(Token![if]) {
+            branches.push((None, braced_items(input)?));
+            return Ok(branches);
+        }
+    }
+}
+
+/// Parses `a => { ... } b => { ... } _ => { ... }`, where the arms may be
+/// separated by commas
+fn cfg_select_branches(input: ParseStream) -> syn::Result<Vec<Branch>> {
+    let mut branches = Vec::new();
+    while !input.is_empty() {
+        let predicate = if input.peek(Token![_]) {
+            input.parse::<Token![_]>()?;
+            None
+        } else {
+            Some(input.parse::<syn::Meta>()?.to_token_stream())
+        };
+        input.parse::<Token![=>]>()?;
+        branches.push((predicate, braced_items(input)?));
+        input.parse::<Option<Token![,]>>()?;
+    }
+
+    Ok(branches)
+}
+
+/// The predicate of a `#[cfg(...)]` attribute
+fn cfg_predicate(input: ParseStream) -> syn::Result<TokenStream> {
+    match input.call(syn::Attribute::parse_outer)?.as_slice() {
+        [attr] if attr.path().is_ident("cfg") => Ok(attr.meta.require_list()?.tokens.clone()),
+        _ => Err(input.error("expected a single #[cfg(...)] attribute")),
+    }
+}
+
+fn braced_items(input: ParseStream) -> syn::Result<Vec<syn::Item>> {
+    let body;
+    braced!(body in input);
+
+    let mut items = Vec::new();
+    while !body.is_empty() {
+        items.push(body.parse()?);
+    }
+
+    Ok(items)
+}
+
+fn attrs_mut(item: &mut syn::Item) -> Option<&mut Vec<syn::Attribute>> {
+    use syn::Item;
+
+    match item {
+        Item::Const(obj) => Some(&mut obj.attrs),
+        Item::Enum(obj) => Some(&mut obj.attrs),
+        Item::ExternCrate(obj) => Some(&mut obj.attrs),
+        Item::Fn(obj) => Some(&mut obj.attrs),
+        Item::ForeignMod(obj) => Some(&mut obj.attrs),
+        Item::Impl(obj) => Some(&mut obj.attrs),
+        Item::Macro(obj) => Some(&mut obj.attrs),
+        Item::Mod(obj) => Some(&mut obj.attrs),
+        Item::Static(obj) => Some(&mut obj.attrs),
+        Item::Struct(obj) => Some(&mut obj.attrs),
+        Item::Trait(obj) => Some(&mut obj.attrs),
+        Item::TraitAlias(obj) => Some(&mut obj.attrs),
+        Item::Type(obj) => Some(&mut obj.attrs),
+        Item::Union(obj) => Some(&mut obj.attrs),
+        Item::Use(obj) => Some(&mut obj.attrs),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use syn::spanned::Spanned;
+
+    use super::*;
+    use crate::cfg;
+
+    #[test]
+    fn flattens() {
+        let src = "cfg_if::cfg_if! {
+    if #[cfg(windows)] {
+        fn imp() {}
+    } else if #[cfg(unix)] {
+        fn imp() {}
+        cfg_select! {
+            target_pointer_width = \"64\" => { struct Wide; }
+            _ => { struct Narrow; }
+        }
+    } else {
+        fn imp() {}
+    }
+}
+fn after() {}
+";
+        let mut ast = syn::parse_file(src).unwrap();
+        flatten(&mut ast.items);
+
+        let found: Vec<_> = ast
+            .items
+            .iter()
+            .map(|item| {
+                let attrs = match item {
+                    syn::Item::Fn(obj) => &obj.attrs,
+                    syn::Item::Struct(obj) => &obj.attrs,
+                    _ => panic!("unexpected item"),
+                };
+                let start = item.span().start();
+                (start.line, start.column, cfg::is_possibly_active(attrs))
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (3, 8, cfg!(windows)),
+                (5, 8, cfg!(unix)),
+                (7, 45, cfg!(unix) && cfg!(target_pointer_width = "64")),
+                (8, 19, cfg!(unix) && !cfg!(target_pointer_width = "64")),
+                (11, 8, !cfg!(windows) && !cfg!(unix)),
+                (14, 0, true),
+            ]
+        );
+    }
+}
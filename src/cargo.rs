@@ -0,0 +1,87 @@
+//! This module runs cargo the way the user would: using the cargo that runs
+//! this tool (`$CARGO`), in the directory of the workspace, so its
+//! `.cargo/config.toml` applies, and with the flags that are passed on to every
+//! cargo command (`--offline`, `--locked` and `--frozen`), so cargo only uses
+//! the network and changes the lock file when the user allows it.
+
+use std::{env, path::Path, process::Command, sync::OnceLock};
+
+static FLAGS: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+pub fn init(offline: bool, locked: bool, frozen: bool) {
+    // the flags are the same for all commands of a run
+    let _ = FLAGS.set(given_flags(offline, locked, frozen));
+}
+
+fn given_flags(offline: bool, locked: bool, frozen: bool) -> Vec<&'static str> {
+    [
+        ("--offline", offline),
+        ("--locked", locked),
+        ("--frozen", frozen),
+    ]
+    .into_iter()
+    .filter(|(_, given)| *given)
+    .map(|(flag, _)| flag)
+    .collect()
+}
+
+/// The flags to pass to cargo
+pub fn flags() -> &'static [&'static str] {
+    FLAGS.get().map_or(&[], Vec::as_slice)
+}
+
+/// A cargo command for the workspace with the given manifest (or the one in
+/// the current directory), with the flags of the run
+pub fn command(subcommand: &str, manifest_path: Option<&Path>) -> std::io::Result<Command> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let mut command = Command::new(cargo);
+    command.arg(subcommand);
+    if let Some(manifest_path) = manifest_path {
+        // cargo reads its configuration from the directory it runs in
+        let manifest_path = manifest_path.canonicalize()?;
+        if let Some(dir) = manifest_path.parent() {
+            command.current_dir(dir);
+        }
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+    command.args(flags());
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn run_flags() {
+        assert_eq!(given_flags(false, false, false), [] as [&str; 0]);
+        assert_eq!(given_flags(true, false, false), ["--offline"]);
+        assert_eq!(given_flags(false, true, true), ["--locked", "--frozen"]);
+    }
+
+    #[test]
+    fn workspace_directory() {
+        let dir = std::env::temp_dir().join(format!("minify-cargo-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest_path = dir.join("Cargo.toml");
+        std::fs::write(&manifest_path, "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let command = command("check", Some(&manifest_path)).unwrap();
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        assert_eq!(command.get_program(), cargo);
+        assert_eq!(command.get_current_dir(), Some(dir.as_path()));
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "check".as_ref(),
+                "--manifest-path".as_ref(),
+                dir.join("Cargo.toml").as_os_str()
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
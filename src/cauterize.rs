@@ -1,19 +1,60 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
     ops::Range,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use syn::{spanned::Spanned, File};
+use cargo_metadata::{semver::Version, Edition};
+use proc_macro2::{Group, Ident, Span, TokenStream, TokenTree};
+use syn::{ext::IdentExt, punctuated::Punctuated, spanned::Spanned, visit::Visit, File, Token};
+use thiserror::Error;
+use tracing::{info, warn};
 
-use crate::unused::{UnusedDiagnostic, UnusedDiagnosticKind};
+use crate::{
+    bloat::Bloat,
+    block_macros, cfg, doctests,
+    error::{Error, Result as MinifyResult},
+    expansion, features,
+    graph::{self, Graph},
+    history,
+    includes::{self, Include},
+    interrupt,
+    items::{item_attrs, item_name},
+    lints::Level,
+    macros,
+    manifest::Manifest,
+    modules, sizes,
+    unused::{self, Severity, UnusedDiagnostic, UnusedDiagnosticKind},
+    vcs, vendor, CauterizeOptions,
+};
+
+/// An item, identified by its kind and name
+type ItemName = (UnusedDiagnosticKind, String);
+/// An item, identified by the path of its module from the crate root and its
+/// name
+type ItemPath = (Vec<String>, String);
 
 const SPACE: u8 = b' ';
 const NEWLINE: u8 = b'\n';
+const CARRIAGE_RETURN: u8 = b'\r';
+/// The attribute that `--mode deprecate-pub` puts on unused public items
+const DEPRECATION: &[u8] = b"#[deprecated(note = \"unused internally, scheduled for removal\")]";
+/// Lets deprecated items (and their impls) use each other without warnings
+const ALLOW_DEPRECATED: &[u8] = b"#[allow(deprecated)]";
+/// The Rust version in which `todo!()` was stabilized
+const TODO_MACRO_SINCE: (u64, u64) = (1, 40);
 
 pub struct Change {
     file_name: PathBuf,
     original_content: Vec<u8>,
-    proposed_content: Vec<u8>,
+    /// `None` if the file is to be removed altogether
+    proposed_content: Option<Vec<u8>>,
+    removed_items: Vec<ItemName>,
+    /// The fields that are never read, and go as their struct becomes a unit
+    /// struct, named `Struct::field`
+    unread_fields: Vec<ItemName>,
 }
 
 impl Change {
@@ -26,10 +67,465 @@ impl Change {
     }
 
     pub fn proposed_content(&self) -> &[u8] {
-        &self.proposed_content
+        self.proposed_content.as_deref().unwrap_or_default()
+    }
+
+    pub fn removes_file(&self) -> bool {
+        self.proposed_content.is_none()
+    }
+
+    /// The items that are removed, once each (the compiler reports items once
+    /// for every target they are part of)
+    pub fn removed_items(&self) -> BTreeSet<&(UnusedDiagnosticKind, String)> {
+        self.removed_items.iter().collect()
+    }
+
+    /// The removed items (see [`Change::removed_items`]) and fields, with how
+    /// unused each of them is
+    pub fn severities(&self) -> Vec<(Severity, &ItemName)> {
+        let removed = self.removed_items().into_iter();
+        let unread = self
+            .unread_fields
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter();
+        removed
+            .map(|item| (Severity::Unused, item))
+            .chain(unread.map(|field| (Severity::Unread, field)))
+            .collect()
+    }
+
+    /// The number of lines of the original content that are removed or
+    /// replaced, as a measure of the impact of the change
+    pub fn lines_removed(&self) -> usize {
+        let original: Vec<&[u8]> = self
+            .original_content
+            .split_inclusive(|&c| c == NEWLINE)
+            .collect();
+        let proposed: Vec<&[u8]> = self
+            .proposed_content()
+            .split_inclusive(|&c| c == NEWLINE)
+            .collect();
+
+        diff::slice(&original, &proposed)
+            .iter()
+            .filter(|result| matches!(result, diff::Result::Left(_)))
+            .count()
+    }
+}
+
+/// A removed item, and the (estimated) number of bytes it takes
+pub struct ItemSize {
+    pub file_name: PathBuf,
+    pub kind: UnusedDiagnosticKind,
+    pub ident: String,
+    /// `None` if the size cannot be told from the source
+    pub bytes: Option<usize>,
+}
+
+/// Estimates the size of the data of the statics and constants that the
+/// changes remove, largest first
+pub fn data_sizes(changes: &[Change], options: &CauterizeOptions) -> Vec<ItemSize> {
+    use syn::{ImplItem, Item};
+
+    let mut sizes = Vec::new();
+    for change in changes {
+        let edition = edition_of(options, &change.file_name);
+        let Ok(ast) = parse_file(&change.original_content, edition) else {
+            continue;
+        };
+
+        // the compiler reports items once for every target they are part of
+        let removed: BTreeSet<_> = change.removed_items.iter().collect();
+        for (kind, ident) in removed {
+            for item in find_items(&ast.items, kind, ident) {
+                let bytes = match item {
+                    ItemRef::Item(Item::Static(obj)) => sizes::estimate(&obj.ty, &obj.expr),
+                    ItemRef::Item(Item::Const(obj)) => sizes::estimate(&obj.ty, &obj.expr),
+                    ItemRef::Impl(ImplItem::Const(obj)) => sizes::estimate(&obj.ty, &obj.expr),
+                    _ => continue,
+                };
+                sizes.push(ItemSize {
+                    file_name: change.file_name.clone(),
+                    kind: kind.clone(),
+                    ident: ident.clone(),
+                    bytes,
+                });
+            }
+        }
+    }
+
+    sizes.sort_by_key(|size| std::cmp::Reverse(size.bytes));
+    sizes
+}
+
+/// Looks up the size of the code of the functions that the changes remove in
+/// the output of `cargo bloat`, largest first
+pub fn function_sizes(
+    changes: &[Change],
+    bloat: &Bloat,
+    options: &CauterizeOptions,
+) -> Vec<ItemSize> {
+    // the paths of the functions, as they appear in the binary
+    let graphs = package_graphs(options);
+    let nodes: Vec<_> = graphs.iter().flat_map(|graph| &graph.nodes).collect();
+
+    let mut sizes = Vec::new();
+    for change in changes {
+        let removed: BTreeSet<_> = change.removed_items.iter().collect();
+        for (kind, ident) in removed {
+            if matches!(
+                kind,
+                UnusedDiagnosticKind::Function | UnusedDiagnosticKind::AssociatedFunction
+            ) {
+                let bytes = nodes
+                    .iter()
+                    .filter(|node| {
+                        node.kind == *kind
+                            && node.ident == *ident
+                            && same_file(&node.file, &change.file_name)
+                    })
+                    .filter_map(|node| bloat.size_of(&node.path))
+                    .reduce(|total, bytes| total + bytes);
+                sizes.push(ItemSize {
+                    file_name: change.file_name.clone(),
+                    kind: kind.clone(),
+                    ident: ident.clone(),
+                    bytes,
+                });
+            }
+        }
+    }
+
+    sizes.sort_by_key(|size| std::cmp::Reverse(size.bytes));
+    sizes
+}
+
+/// A removed item, and where it is (see `--list` and `--message-format quickfix`)
+pub struct ListedItem {
+    pub file_name: PathBuf,
+    /// The line on which the item starts (with its attributes); `None` if the
+    /// item cannot be found in the source
+    pub line: Option<usize>,
+    /// The column on which the item starts, counting from 1
+    pub column: Option<usize>,
+    pub kind: UnusedDiagnosticKind,
+    pub ident: String,
+    pub severity: Severity,
+    /// The number of lines that the item takes, as an estimate of what
+    /// removing it saves
+    pub lines: Option<usize>,
+}
+
+/// Lists the items (and fields) that the changes remove, by file and line
+pub fn listed_items(changes: &[Change], options: &CauterizeOptions) -> Vec<ListedItem> {
+    let mut listed = Vec::new();
+    for change in changes {
+        let edition = edition_of(options, &change.file_name);
+        let ast = parse_file(&change.original_content, edition).ok();
+
+        for (severity, (kind, ident)) in change.severities() {
+            let spans: Vec<_> = match &ast {
+                Some(ast) if *kind == UnusedDiagnosticKind::Field => {
+                    find_field(&ast.items, ident).into_iter().collect()
+                }
+                Some(ast) => find_items(&ast.items, kind, ident)
+                    .iter()
+                    .map(ItemRef::span)
+                    .collect(),
+                None => Vec::new(),
+            };
+            for span in &spans {
+                listed.push(ListedItem {
+                    file_name: change.file_name.clone(),
+                    line: Some(span.start().line),
+                    column: Some(span.start().column + 1),
+                    kind: kind.clone(),
+                    ident: ident.clone(),
+                    severity,
+                    lines: Some(span.end().line - span.start().line + 1),
+                });
+            }
+            if spans.is_empty() {
+                listed.push(ListedItem {
+                    file_name: change.file_name.clone(),
+                    line: None,
+                    column: None,
+                    kind: kind.clone(),
+                    ident: ident.clone(),
+                    severity,
+                    lines: None,
+                });
+            }
+        }
+    }
+
+    listed.sort_by(|a, b| (&a.file_name, a.line).cmp(&(&b.file_name, b.line)));
+    listed
+}
+
+/// The span of a field, named `Struct::field` (or `Struct::0` for the field of
+/// a tuple struct)
+fn find_field(items: &[syn::Item], name: &str) -> Option<Span> {
+    let (ident, field) = name.split_once("::")?;
+    let found = find_items(items, &UnusedDiagnosticKind::Struct, ident);
+    let [ItemRef::Item(syn::Item::Struct(obj))] = found.as_slice() else {
+        return None;
+    };
+
+    obj.fields
+        .iter()
+        .enumerate()
+        .find(|(index, candidate)| match &candidate.ident {
+            Some(candidate) => candidate.unraw() == field,
+            None => index.to_string() == field,
+        })
+        .map(|(_, field)| field.span())
+}
+
+/// A public item of a library that is removed, for the release notes (see
+/// `--emit-changelog`)
+pub struct PublicItem {
+    /// The path by which other crates refer to the item, e.g. `mycrate::run`
+    pub path: String,
+    /// The declaration of the item without its body, e.g. `pub fn run()`
+    pub signature: String,
+}
+
+/// Finds the public items that the changes remove from libraries, i.e. the
+/// items that other crates may use, sorted by path
+pub fn removed_public_items(changes: &[Change], options: &CauterizeOptions) -> Vec<PublicItem> {
+    let graphs = package_graphs(options);
+    // entry points (such as tests) are roots of the graph as well, but these
+    // are never public
+    let exported: Vec<_> = graphs
+        .iter()
+        .flat_map(|graph| &graph.nodes)
+        .filter(|node| node.root)
+        .collect();
+
+    let mut items = Vec::new();
+    for change in changes {
+        let src = &change.original_content;
+        let Ok(ast) = parse_file(src, edition_of(options, &change.file_name)) else {
+            continue;
+        };
+        let offsets = line_offsets(src);
+
+        for (kind, ident) in change.removed_items() {
+            let Some(item) = find_items(&ast.items, kind, ident)
+                .into_iter()
+                .find(|item| matches!(item.vis(), Some(syn::Visibility::Public(_))))
+            else {
+                continue;
+            };
+            for node in exported.iter().filter(|node| {
+                node.kind == *kind
+                    && node.ident == *ident
+                    && same_file(&node.file, &change.file_name)
+            }) {
+                items.push(PublicItem {
+                    path: node.path.clone(),
+                    signature: signature(src, &offsets, &item),
+                });
+            }
+        }
+    }
+
+    items.sort_by(|a, b| a.path.cmp(&b.path));
+    items.dedup_by(|a, b| a.path == b.path);
+    items
+}
+
+/// The declaration of a public item, from its visibility up to its body (or
+/// its fields, or its value), on a single line
+fn signature(src: &[u8], offsets: &LineOffsets, item: &ItemRef) -> String {
+    let Some(vis) = item.vis() else {
+        return String::new();
+    };
+    let start = to_range(offsets, vis.span()).start;
+    let end = match item.body() {
+        Some(body) => to_range(offsets, body.span()).start,
+        None => {
+            let end = to_range(offsets, item.span()).end;
+            // stop at the first `{`, `=` or `;` outside of brackets, such as
+            // those of `[u8; 4]` or `<T = u8>`
+            let mut depth = 0usize;
+            (start..end)
+                .find(|&pos| {
+                    match src[pos] {
+                        b'(' | b'[' | b'<' => depth += 1,
+                        b')' | b']' => depth = depth.saturating_sub(1),
+                        b'>' if src[pos - 1] != b'-' => depth = depth.saturating_sub(1),
+                        b'{' | b'=' | b';' => return depth == 0,
+                        _ => {}
+                    }
+                    false
+                })
+                .unwrap_or(end)
+        }
+    };
+
+    String::from_utf8_lossy(&src[start..end])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The unused items that the changes remove, for the history of runs (see
+/// [`history::record`](crate::history::record))
+pub fn findings(changes: &[Change], options: &CauterizeOptions) -> BTreeSet<history::Finding> {
+    changes
+        .iter()
+        .flat_map(|change| {
+            let file = change
+                .file_name
+                .canonicalize()
+                .ok()
+                .and_then(|file| Some(file.strip_prefix(&options.workspace_root).ok()?.to_owned()))
+                .unwrap_or_else(|| change.file_name.clone());
+            change
+                .removed_items()
+                .into_iter()
+                .map(move |(kind, ident)| history::Finding {
+                    file: file.clone(),
+                    kind: kind.name().to_owned(),
+                    name: ident.clone(),
+                })
+        })
+        .collect()
+}
+
+/// The files that a run may change: the Rust files and manifests of all
+/// packages, and the checksums of vendored packages, relative to the workspace
+/// root (see `cargo minify snapshot`)
+pub fn source_files(options: &CauterizeOptions) -> Vec<PathBuf> {
+    let mut files = BTreeSet::new();
+    files.insert(options.workspace_root.join("Cargo.toml"));
+    for package_root in options.editions.keys() {
+        files.extend(rust_files(package_root, options));
+        files.insert(package_root.join("Cargo.toml"));
+    }
+    files.extend(options.vendored.iter().map(vendor::Vendored::checksum_file));
+
+    files
+        .into_iter()
+        .filter(|file| file.is_file())
+        .filter_map(|file| {
+            Some(
+                file.strip_prefix(&options.workspace_root)
+                    .ok()?
+                    .to_path_buf(),
+            )
+        })
+        .collect()
+}
+
+/// Whether a file still contains an item of the given kind and name; if the
+/// file cannot be parsed, the item is assumed to be there
+pub fn contains_item(
+    file_name: &Path,
+    kind: &UnusedDiagnosticKind,
+    ident: &str,
+    options: &CauterizeOptions,
+) -> bool {
+    let Ok(src) = std::fs::read(file_name) else {
+        return false;
+    };
+    match parse_file(&src, edition_of(options, file_name)) {
+        Ok(ast) => !find_items(&ast.items, kind, ident).is_empty(),
+        Err(_) => true,
+    }
+}
+
+/// Orders changes by the number of bytes their items take, largest first
+pub fn rank_by_size(changes: &mut [Change], sizes: &[ItemSize]) {
+    changes.sort_by_key(|change| {
+        let bytes: usize = sizes
+            .iter()
+            .filter(|size| size.file_name == change.file_name)
+            .filter_map(|size| size.bytes)
+            .sum();
+        std::cmp::Reverse(bytes)
+    });
+}
+
+/// Orders changes by the number of lines they remove, largest first; changes
+/// with the same impact keep their order
+pub fn rank_by_impact(changes: &mut [Change]) {
+    changes.sort_by_cached_key(|change| std::cmp::Reverse(change.lines_removed()));
+}
+
+/// A file that was left alone, and why
+pub struct Skipped {
+    file_name: PathBuf,
+    /// The number of unused items reported in the file
+    unused: usize,
+    reason: String,
+}
+
+impl Skipped {
+    pub fn file_name(&self) -> &Path {
+        &self.file_name
+    }
+
+    pub fn unused(&self) -> usize {
+        self.unused
+    }
+
+    /// Completes the sentence "skipping the file, since it ..."
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// What to do with unused code
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Mode {
+    /// Remove it
+    #[default]
+    Delete,
+    /// Keep the signatures of (non-private) functions, but replace their bodies
+    /// with `todo!()`, while private functions are removed; other items are
+    /// kept, since the remaining signatures may refer to them
+    Stub,
+    /// Keep it around, but commented out
+    Comment,
+    /// Mark items that are only used by tests as `#[cfg(test)]`, instead of
+    /// removing unused items
+    CfgTest,
+    /// Put library items that only a single binary uses behind a feature named
+    /// after it, which the binary is made to require, instead of removing
+    /// unused items
+    BinFeature,
+    /// Mark the public items of libraries that the workspace does not use as
+    /// `#[deprecated]`, instead of removing them, while unused private items
+    /// are removed
+    DeprecatePub,
+}
+
+impl FromStr for Mode {
+    type Err = UnsupportedMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(Mode::Delete),
+            "stub" => Ok(Mode::Stub),
+            "comment" => Ok(Mode::Comment),
+            "cfg-test" => Ok(Mode::CfgTest),
+            "bin-feature" => Ok(Mode::BinFeature),
+            "deprecate-pub" => Ok(Mode::DeprecatePub),
+            _ => Err(UnsupportedMode),
+        }
     }
 }
 
+#[derive(Debug, Error)]
+#[error(
+    "unsupported mode, pick any of: delete, stub, comment, cfg-test, bin-feature, deprecate-pub"
+)]
+pub struct UnsupportedMode;
+
 /// Finds the position of the first whitespace that is considered belonging
 /// to the next definition/declaration (this is a kind of "heuristic")
 /// Current heuristic:
@@ -47,402 +543,5486 @@ fn find_prefix_whitespace(src: &[u8]) -> usize {
 /// Finds the position of the first whitespace that is not considered belonging
 /// to the previous definition/declaration (this is kind of "heuristic")
 /// Current heuristic:
-/// - if there is a newline, eat all space before it, and the newline
+/// - if there is a newline, eat all space before it, and the newline (which
+///   may be a `\r\n`)
 /// - if there is no newline, eat all trailing whitespace until the next token
 fn find_suffix_whitespace(src: &[u8]) -> usize {
     src.iter()
         .position(|c| *c != SPACE)
-        .map(|pos| if src[pos] == NEWLINE { pos + 1 } else { pos })
+        .map(|pos| match src[pos..] {
+            [NEWLINE, ..] => pos + 1,
+            [CARRIAGE_RETURN, NEWLINE, ..] => pos + 2,
+            _ => pos,
+        })
         .unwrap_or(src.len())
 }
 
+/// The line ending used in a file: `\r\n` if its first line ends that way
+fn line_ending(src: &[u8]) -> &'static [u8] {
+    match src.iter().position(|&c| c == NEWLINE) {
+        Some(pos) if pos > 0 && src[pos - 1] == CARRIAGE_RETURN => b"\r\n",
+        _ => b"\n",
+    }
+}
+
 /// Turns a list of "locations of identifiers" into a list of "chunks"
 fn diagnostics_to_ranges<'a>(
     src: &'a [u8],
+    module: &[String],
     idents: impl IntoIterator<Item = (UnusedDiagnosticKind, String)> + 'a,
+    edition: Edition,
 ) -> Result<impl Iterator<Item = Range<usize>> + 'a, syn::Error> {
-    let s = String::from_utf8_lossy(src);
-    let parsed = syn::parse_str::<syn::File>(&s)?;
+    let parsed = parse_file(src, edition)?;
 
     let cumulative_lengths = line_offsets(src);
 
     let ranges = idents
         .into_iter()
-        .flat_map(move |(kind, ident)| {
-            parsed.items.iter().find_map(|item| {
-                use syn::{ForeignItem, ImplItem, Item};
-                use UnusedDiagnosticKind::*;
-                let item_ident = match item {
-                    Item::Const(obj) if kind == Constant => &obj.ident,
-                    Item::Enum(obj) if kind == Enum => &obj.ident,
-                    Item::Fn(obj) if kind == Function => &obj.sig.ident,
-                    Item::Macro(syn::ItemMacro {
-                        ident: Some(name), ..
-                    }) if kind == MacroDefinition => name,
-                    Item::Static(obj) if kind == Static => &obj.ident,
-                    Item::Struct(obj) if kind == Struct => &obj.ident,
-                    Item::Type(obj) if kind == TypeAlias => &obj.ident,
-                    Item::Union(obj) if kind == Union => &obj.ident,
-                    Item::Mod(block) => return handle_mod_diagnostic(block, &kind, &ident),
-                    Item::ForeignMod(block) => {
-                        return block.items.iter().find_map(|item| {
-                            let item_ident = match item {
-                                ForeignItem::Fn(obj) if kind == Function => &obj.sig.ident,
-                                ForeignItem::Static(obj) if kind == Static => &obj.ident,
-                                ForeignItem::Type(obj) if kind == TypeAlias => &obj.ident,
-                                _ => return None,
-                            };
-
-                            if *item_ident == ident {
-                                Some(item.span())
-                            } else {
-                                None
-                            }
-                        })
-                    }
-                    Item::Impl(block) => {
-                        return block.items.iter().find_map(|item| {
-                            let item_ident = match item {
-                                ImplItem::Const(obj) if kind == Constant => &obj.ident,
-                                ImplItem::Fn(obj) if kind == AssociatedFunction => &obj.sig.ident,
-                                ImplItem::Type(obj) if kind == TypeAlias => &obj.ident,
-                                _ => return None,
-                            };
-
-                            if *item_ident == ident {
-                                Some(item.span())
-                            } else {
-                                None
-                            }
-                        })
-                    }
-                    _ => return None,
-                };
+        .flat_map(|(kind, ident)| {
+            let Some(item) = find_item(&parsed.items, &kind, &ident) else {
+                return Vec::new();
+            };
 
-                if *item_ident == ident {
-                    Some(item.span())
-                } else {
-                    None
+            let mut spans = vec![item.span()];
+            if let ItemRef::Item(definition) = item {
+                if kind.is_type_definition() {
+                    spans.extend(find_impl_spans(&parsed.items, module, definition));
                 }
-            })
+            }
+
+            spans
         })
-        .map(move |span| to_range(&cumulative_lengths, span));
+        .map(|span| to_range(&cumulative_lengths, span))
+        .collect();
 
-    Ok(ranges)
+    Ok(merge_impl_ranges(&parsed.items, &cumulative_lengths, ranges).into_iter())
 }
 
-/// Handles (inline) module content
-fn handle_mod_diagnostic(block: &syn::ItemMod, kind: &UnusedDiagnosticKind, ident: &str) -> Option<proc_macro2::Span> {
-    use syn::Item;
-    use UnusedDiagnosticKind::*;
-
-    block.content.iter().find_map(|(_b, items)| {
-        items.iter().find_map(|item| {
-            let item_ident = match item {
-                Item::Fn(obj) if *kind == Function => &obj.sig.ident,
-                Item::Static(obj) if *kind == Static => &obj.ident,
-                Item::Type(obj) if *kind == TypeAlias => &obj.ident,
-                Item::Mod(obj) => return handle_mod_diagnostic(obj, kind, ident),
-                _ => return None,
-            };
+/// If every item of an inherent impl block is to be removed, the block is
+/// removed as a whole in a single edit, instead of item-by-item
+fn merge_impl_ranges(
+    items: &[syn::Item],
+    offsets: &LineOffsets,
+    mut ranges: Vec<Range<usize>>,
+) -> Vec<Range<usize>> {
+    for item in items {
+        match item {
+            syn::Item::Impl(block) if block.trait_.is_none() && !block.items.is_empty() => {
+                let all_removed = block.items.iter().all(|item| {
+                    let range = to_range(offsets, span_with_attrs(impl_item_attrs(item), item));
+                    ranges.contains(&range)
+                });
 
-            if item_ident == ident {
-                Some(item.span())
-            } else {
-                None
+                if all_removed {
+                    let block_range = to_range(offsets, span_with_attrs(&block.attrs, block));
+                    ranges.retain(|range| {
+                        !(block_range.start <= range.start && range.end <= block_range.end)
+                    });
+                    ranges.push(block_range);
+                }
             }
-        })
-    })
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => ranges = merge_impl_ranges(items, offsets, ranges),
+            _ => {}
+        }
+    }
+
+    ranges
 }
 
-fn expand_ranges_to_include_whitespace<'a>(
-    src: &'a [u8],
-    iter: impl Iterator<Item = Range<usize>> + 'a,
-) -> impl Iterator<Item = Range<usize>> + 'a {
-    iter.map(|range| {
-        find_prefix_whitespace(&src[..range.start])
-            ..find_suffix_whitespace(&src[range.end..]) + range.end
-    })
+fn impl_item_attrs(item: &syn::ImplItem) -> &[syn::Attribute] {
+    match item {
+        syn::ImplItem::Const(obj) => &obj.attrs,
+        syn::ImplItem::Fn(obj) => &obj.attrs,
+        syn::ImplItem::Type(obj) => &obj.attrs,
+        syn::ImplItem::Macro(obj) => &obj.attrs,
+        _ => &[],
+    }
 }
 
-/// Deletes a list-of-positions-of-identifiers from a bytearray that is valid
-/// rust code BUGS: if the position is in the body of a function, it will try to
-/// delete identifiers there ...  probably?
-pub fn delete_chunks(src: &[u8], chunks_to_delete: &[Range<usize>]) -> Vec<u8> {
-    src.iter()
-        .enumerate()
-        .filter_map(|(i, &byte)| {
-            if chunks_to_delete.iter().any(|range| range.contains(&i)) {
-                None
-            } else {
-                Some(byte)
-            }
-        })
-        .collect()
+/// A reference to an item, which may be nested in an impl or extern block
+#[derive(Clone, Copy)]
+enum ItemRef<'a> {
+    Item(&'a syn::Item),
+    Impl(&'a syn::ImplItem),
+    Foreign(&'a syn::ForeignItem),
 }
 
-/// Deletes a list-of-positions-of-identifiers from a bytearray that is valid
-/// rust code BUGS: if the position is in the body of a function, it will try to
-/// delete identifiers there ...  probably?
-pub fn rust_delete(
-    src: &[u8],
-    diagnostics: impl IntoIterator<Item = (UnusedDiagnosticKind, String)>,
-) -> Result<Vec<u8>, syn::Error> {
-    let chunks_to_delete =
-        expand_ranges_to_include_whitespace(src, diagnostics_to_ranges(src, diagnostics)?);
+impl<'a> ItemRef<'a> {
+    fn attrs(&self) -> &'a [syn::Attribute] {
+        match *self {
+            ItemRef::Item(item) => item_attrs(item),
+            ItemRef::Impl(item) => impl_item_attrs(item),
+            ItemRef::Foreign(item) => match item {
+                syn::ForeignItem::Fn(obj) => &obj.attrs,
+                syn::ForeignItem::Static(obj) => &obj.attrs,
+                syn::ForeignItem::Type(obj) => &obj.attrs,
+                syn::ForeignItem::Macro(obj) => &obj.attrs,
+                _ => &[],
+            },
+        }
+    }
 
-    Ok(delete_chunks(src, &chunks_to_delete.collect::<Vec<_>>()))
-}
+    /// The span of the item, including its outer attributes
+    fn span(&self) -> Span {
+        match *self {
+            ItemRef::Item(item) => span_with_attrs(self.attrs(), item),
+            ItemRef::Impl(item) => span_with_attrs(self.attrs(), item),
+            ItemRef::Foreign(item) => span_with_attrs(self.attrs(), item),
+        }
+    }
 
-/// Processes a list of file+list-of-edits into an iterator of
-/// filenames+proposed new contents
-fn process_files<Iter: IntoIterator<Item = UnusedDiagnostic>>(
-    diagnostics: impl IntoIterator<Item = (PathBuf, Iter)>,
-) -> impl Iterator<Item = Change> {
-    diagnostics
-        .into_iter()
-        .filter_map(|(file_name, diagnostic)| {
-            let original_content = std::fs::read(&file_name).ok()?;
-            let removed_unused = rust_delete(
-                &original_content,
-                diagnostic.into_iter().map(|warn| (warn.kind, warn.ident)),
-            )
-            .expect("syntax error");
-            let proposed_content = remove_empty_blocks(&removed_unused).expect("syntax error");
+    /// The name of the item, if it has one
+    fn ident(&self) -> Option<&'a Ident> {
+        match *self {
+            ItemRef::Item(item) => match item {
+                syn::Item::Const(obj) => Some(&obj.ident),
+                syn::Item::Enum(obj) => Some(&obj.ident),
+                syn::Item::Fn(obj) => Some(&obj.sig.ident),
+                syn::Item::Macro(obj) => obj.ident.as_ref(),
+                syn::Item::Static(obj) => Some(&obj.ident),
+                syn::Item::Struct(obj) => Some(&obj.ident),
+                syn::Item::Type(obj) => Some(&obj.ident),
+                syn::Item::Union(obj) => Some(&obj.ident),
+                _ => None,
+            },
+            ItemRef::Impl(item) => match item {
+                syn::ImplItem::Const(obj) => Some(&obj.ident),
+                syn::ImplItem::Fn(obj) => Some(&obj.sig.ident),
+                syn::ImplItem::Type(obj) => Some(&obj.ident),
+                _ => None,
+            },
+            ItemRef::Foreign(item) => match item {
+                syn::ForeignItem::Fn(obj) => Some(&obj.sig.ident),
+                syn::ForeignItem::Static(obj) => Some(&obj.ident),
+                syn::ForeignItem::Type(obj) => Some(&obj.ident),
+                _ => None,
+            },
+        }
+    }
 
-            let change = Change {
-                file_name,
-                original_content,
-                proposed_content,
-            };
+    /// The keyword that introduces the item, e.g. `fn`
+    fn keyword(&self) -> &'static str {
+        match *self {
+            ItemRef::Item(item) => match item {
+                syn::Item::Const(_) => "const",
+                syn::Item::Enum(_) => "enum",
+                syn::Item::Fn(_) => "fn",
+                syn::Item::Macro(_) => "macro",
+                syn::Item::Static(_) => "static",
+                syn::Item::Struct(_) => "struct",
+                syn::Item::Type(_) => "type",
+                syn::Item::Union(_) => "union",
+                _ => "item",
+            },
+            ItemRef::Impl(item) => match item {
+                syn::ImplItem::Const(_) => "const",
+                syn::ImplItem::Fn(_) => "fn",
+                syn::ImplItem::Type(_) => "type",
+                _ => "item",
+            },
+            ItemRef::Foreign(item) => match item {
+                syn::ForeignItem::Fn(_) => "fn",
+                syn::ForeignItem::Static(_) => "static",
+                syn::ForeignItem::Type(_) => "type",
+                _ => "item",
+            },
+        }
+    }
 
-            Some(change)
-        })
+    /// The visibility of the item; `None` for items that cannot have one
+    fn vis(&self) -> Option<&'a syn::Visibility> {
+        match *self {
+            ItemRef::Item(item) => match item {
+                syn::Item::Const(obj) => Some(&obj.vis),
+                syn::Item::Enum(obj) => Some(&obj.vis),
+                syn::Item::Fn(obj) => Some(&obj.vis),
+                syn::Item::Static(obj) => Some(&obj.vis),
+                syn::Item::Struct(obj) => Some(&obj.vis),
+                syn::Item::Type(obj) => Some(&obj.vis),
+                syn::Item::Union(obj) => Some(&obj.vis),
+                _ => None,
+            },
+            ItemRef::Impl(item) => match item {
+                syn::ImplItem::Const(obj) => Some(&obj.vis),
+                syn::ImplItem::Fn(obj) => Some(&obj.vis),
+                syn::ImplItem::Type(obj) => Some(&obj.vis),
+                _ => None,
+            },
+            ItemRef::Foreign(item) => match item {
+                syn::ForeignItem::Fn(obj) => Some(&obj.vis),
+                syn::ForeignItem::Static(obj) => Some(&obj.vis),
+                syn::ForeignItem::Type(obj) => Some(&obj.vis),
+                _ => None,
+            },
+        }
+    }
+
+    /// The body of the item, if it is a function that has one
+    fn body(&self) -> Option<&'a syn::Block> {
+        match *self {
+            ItemRef::Item(syn::Item::Fn(obj)) => Some(&obj.block),
+            ItemRef::Impl(syn::ImplItem::Fn(obj)) => Some(&obj.block),
+            _ => None,
+        }
+    }
 }
 
-/// Process a list of UnusedDiagnostics into an iterator of filenames+proposed contents
-pub fn process_diagnostics(
-    diagnostics: impl IntoIterator<Item = UnusedDiagnostic>,
-) -> impl Iterator<Item = Change> {
-    process_files(
-        diagnostics
-            .into_iter()
-            .map(|diagnostic| {
-                let path = PathBuf::from(&diagnostic.span.file_name);
-                (path, diagnostic)
-            })
-            .collect::<multimap::MultiMap<_, _>>(),
-    )
+/// Finds the item a diagnostic refers to, descending into (inline) modules,
+/// extern blocks and impl blocks; items that are configured out (see
+/// [`cfg::is_possibly_active`]) are ignored, and if several items remain, none
+/// is returned, since it is unclear which one the diagnostic is about
+fn find_item<'a>(
+    items: &'a [syn::Item],
+    kind: &UnusedDiagnosticKind,
+    ident: &str,
+) -> Option<ItemRef<'a>> {
+    match find_items(items, kind, ident).as_slice() {
+        [item] => Some(*item),
+        _ => None,
+    }
 }
 
-/// Create a table of byte locations of newline symbols,
-/// to translate LineColumn's into exact offsets
-fn line_offsets(bytes: &[u8]) -> Vec<usize> {
-    let mut offsets: Vec<usize> = bytes
+/// Finds all (possibly active) items with the given kind and name
+fn find_items<'a>(
+    items: &'a [syn::Item],
+    kind: &UnusedDiagnosticKind,
+    ident: &str,
+) -> Vec<ItemRef<'a>> {
+    use syn::{ForeignItem, ImplItem, Item};
+    use UnusedDiagnosticKind::*;
+
+    let mut found = Vec::new();
+    for item in items
         .iter()
-        .enumerate()
-        .filter_map(|(pos, b)| match b {
-            // TODO: Support \r\n
-            b'\n' => Some(pos + 1),
-            _ => None,
-        })
-        .collect();
-    // First line has no offset
-    offsets.insert(0, 0);
+        .filter(|item| cfg::is_possibly_active(item_attrs(item)))
+    {
+        let item_ident = match item {
+            Item::Const(obj) if *kind == Constant => &obj.ident,
+            Item::Enum(obj) if *kind == Enum => &obj.ident,
+            Item::Fn(obj) if *kind == Function => &obj.sig.ident,
+            Item::Macro(obj) if *kind == MacroInvocation => {
+                if obj.ident.is_none() && expansion::is_invocation(&obj.mac, ident) {
+                    found.push(ItemRef::Item(item));
+                }
+                continue;
+            }
+            Item::Macro(syn::ItemMacro {
+                ident: Some(name), ..
+            }) if *kind == MacroDefinition => name,
+            Item::Static(obj) if *kind == Static => &obj.ident,
+            Item::Struct(obj) if *kind == Struct => &obj.ident,
+            Item::Type(obj) if *kind == TypeAlias => &obj.ident,
+            Item::Union(obj) if *kind == Union => &obj.ident,
+            Item::Mod(block) => {
+                if let Some((_, items)) = &block.content {
+                    found.extend(find_items(items, kind, ident));
+                }
+                continue;
+            }
+            Item::ForeignMod(block) => {
+                found.extend(block.items.iter().filter_map(|item| {
+                    let item_ident = match item {
+                        ForeignItem::Fn(obj) if *kind == Function => &obj.sig.ident,
+                        ForeignItem::Static(obj) if *kind == Static => &obj.ident,
+                        ForeignItem::Type(obj) if *kind == TypeAlias => &obj.ident,
+                        _ => return None,
+                    };
+                    let item = ItemRef::Foreign(item);
 
-    offsets
-}
+                    (item_ident.unraw() == ident && cfg::is_possibly_active(item.attrs()))
+                        .then_some(item)
+                }));
+                continue;
+            }
+            Item::Impl(block) => {
+                found.extend(block.items.iter().filter_map(|item| {
+                    let item_ident = match item {
+                        ImplItem::Const(obj) if matches!(kind, Constant | AssociatedItem) => {
+                            &obj.ident
+                        }
+                        ImplItem::Fn(obj)
+                            if matches!(kind, AssociatedFunction | AssociatedItem) =>
+                        {
+                            &obj.sig.ident
+                        }
+                        ImplItem::Type(obj) if matches!(kind, TypeAlias | AssociatedItem) => {
+                            &obj.ident
+                        }
+                        _ => return None,
+                    };
+                    let item = ItemRef::Impl(item);
 
-fn to_range(offsets: &[usize], span: proc_macro2::Span) -> Range<usize> {
-    let byte_offset = |pos: proc_macro2::LineColumn| offsets[pos.line - 1] + pos.column;
+                    (item_ident.unraw() == ident && cfg::is_possibly_active(item.attrs()))
+                        .then_some(item)
+                }));
+                continue;
+            }
+            _ => continue,
+        };
 
-    byte_offset(span.start())..byte_offset(span.end())
+        if item_ident.unraw() == ident {
+            found.push(ItemRef::Item(item));
+        }
+    }
+
+    found
 }
 
-fn remove_empty_blocks(bytes: &[u8]) -> Result<Vec<u8>, syn::Error> {
-    let s = String::from_utf8_lossy(bytes).to_string();
-    let ast: File = syn::parse_str(&s)?;
+/// Finds the span of the item a diagnostic refers to (see [`find_item`])
+fn find_item_span(items: &[syn::Item], kind: &UnusedDiagnosticKind, ident: &str) -> Option<Span> {
+    find_item(items, kind, ident).map(|item| item.span())
+}
 
-    let cumulative_lengths = line_offsets(bytes);
+/// The attributes of items that nothing refers to by name, since the linker
+/// keeps them (`#[used]`), or finds them in a linker section (such as
+/// interrupt vectors, or the registries of `linkme`), or the (embedded)
+/// runtime calls them; rustc may report these items as unused, but removing
+/// them breaks the build at link time. Each comes with the reason it is kept
+const PROTECTING_ATTRIBUTES: &[(&str, &str)] = &[
+    ("used", "linker-referenced"),
+    ("link_section", "linker-referenced"),
+    ("panic_handler", "runtime-referenced"),
+    ("alloc_error_handler", "runtime-referenced"),
+    ("global_allocator", "runtime-referenced"),
+    ("interrupt", "runtime-referenced"),
+    ("exception", "runtime-referenced"),
+];
 
-    let spans = ast
-        .items
+/// The attribute that protects an item from removal, if any, and the reason;
+/// attributes are matched by the last segment of their path (so that e.g.
+/// `#[cortex_m_rt::exception]` is recognized), and `extra` holds the names
+/// given with `--keep-attribute`
+pub fn protection(attrs: &[syn::Attribute], extra: &[String]) -> Option<(String, &'static str)> {
+    attrs.iter().find_map(|attr| {
+        let name = attr.path().segments.last()?.ident.to_string();
+        let reason = PROTECTING_ATTRIBUTES
+            .iter()
+            .find(|(protecting, _)| *protecting == name)
+            .map(|(_, reason)| *reason)
+            .or_else(|| extra.contains(&name).then_some("--keep-attribute"))?;
+        Some((name, reason))
+    })
+}
+
+/// Finds the spans of the impl blocks (inherent as well as trait impls) of a
+/// type, defined by one of the items of the file of the module `module` (see
+/// [`type_impls`])
+fn find_impl_spans(items: &[syn::Item], module: &[String], definition: &syn::Item) -> Vec<Span> {
+    type_impls(items, module, definition)
+        .into_iter()
+        .map(|block| span_with_attrs(&block.attrs, block))
+        .collect()
+}
+
+/// Finds the impl blocks of a type, defined by one of the items of the file of
+/// the module `module`, in that file and the inline modules in it; only the
+/// blocks whose self type resolves to that type (see [`names_type`]) are
+/// found, and blocks for which that cannot be told are left out, with a warning
+fn type_impls<'a>(
+    items: &'a [syn::Item],
+    module: &[String],
+    definition: &syn::Item,
+) -> Vec<&'a syn::ItemImpl> {
+    let Some(ident) = defined_type(definition) else {
+        return Vec::new();
+    };
+    let modules = inline_modules(items);
+    let Some((inline_path, _)) = modules
         .iter()
-        .filter_map(|item| match item {
-            syn::Item::ForeignMod(block) => {
-                (block.items.is_empty() && block.attrs.is_empty()).then(|| block.span())
-            }
-            syn::Item::Impl(block) => {
-                (block.items.is_empty() && block.attrs.is_empty() && block.trait_.is_none())
-                    .then(|| block.span())
+        .find(|(_, items)| items.iter().any(|item| std::ptr::eq(item, definition)))
+    else {
+        return Vec::new();
+    };
+    let defined_in = [module, inline_path.as_slice()].concat();
+
+    let mut found = Vec::new();
+    for (inline_path, items) in &modules {
+        let here = [module, inline_path.as_slice()].concat();
+        let blocks = items.iter().filter_map(|item| match item {
+            syn::Item::Impl(block)
+                if self_type_ident(&block.self_ty).is_some_and(|name| name.unraw() == ident) =>
+            {
+                Some(block)
             }
             _ => None,
-        })
-        .map(|span| to_range(&cumulative_lengths, span));
+        });
 
-    let expanded_spans: Vec<Range<usize>> =
-        expand_ranges_to_include_whitespace(bytes, spans).collect();
+        for block in blocks {
+            let Some(path) = self_type_path(&block.self_ty) else {
+                continue;
+            };
+            match names_type(path, &here, items, &defined_in, &ident) {
+                Some(true) => found.push(block),
+                Some(false) => {}
+                None => warn!(
+                    "`impl` block at line {} may be for `{ident}`, but it is left alone, \
+                     since it is unclear which type it is for",
+                    span_with_attrs(&block.attrs, block).start().line
+                ),
+            }
+        }
+    }
 
-    Ok(delete_chunks(bytes, &expanded_spans))
+    found
 }
 
-/// This actually applies a collection of changes to your filesystem (use with care)
-pub fn commit_changes(
-    changes: impl IntoIterator<Item = Change>,
-) -> Result<(), Vec<std::io::Error>> {
-    let errors = changes
-        .into_iter()
-        .filter_map(|change| std::fs::write(change.file_name, change.proposed_content).err())
-        .collect::<Vec<_>>();
+/// Finds all impl blocks for a type with the given name, descending into
+/// (inline) modules, whichever type of that name they are for
+fn find_impls<'a>(items: &'a [syn::Item], ident: &str) -> Vec<&'a syn::ItemImpl> {
+    items
+        .iter()
+        .flat_map(|item| match item {
+            syn::Item::Impl(block)
+                if self_type_ident(&block.self_ty).is_some_and(|name| name.unraw() == ident) =>
+            {
+                vec![block]
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => find_impls(items, ident),
+            _ => Vec::new(),
+        })
+        .collect()
+}
 
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(errors)
-    }
+/// Whether an impl block implements a trait without any items, such as
+/// `impl Send for Foo {}`; since it compiles, the trait has no required items
+/// (it is a marker trait, or all of its items have a default)
+fn is_marker_impl(block: &syn::ItemImpl) -> bool {
+    block.trait_.is_some() && block.items.is_empty()
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// The name of the type an impl block is for, e.g. `Foo` for `impl<T> Foo<T>`
+/// or `impl Display for &crate::Foo`
+fn self_type_ident(ty: &syn::Type) -> Option<&syn::Ident> {
+    self_type_path(ty)?
+        .segments
+        .last()
+        .map(|segment| &segment.ident)
+}
 
-    fn fun(name: &str) -> (UnusedDiagnosticKind, String) {
-        (UnusedDiagnosticKind::Function, name.to_owned())
+/// The path of the type an impl block is for, e.g. `crate::Foo` for
+/// `impl Display for &crate::Foo`
+fn self_type_path(ty: &syn::Type) -> Option<&syn::Path> {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => Some(&path.path),
+        syn::Type::Reference(reference) => self_type_path(&reference.elem),
+        syn::Type::Paren(paren) => self_type_path(&paren.elem),
+        syn::Type::Group(group) => self_type_path(&group.elem),
+        _ => None,
     }
+}
 
-    fn constant(name: &str) -> (UnusedDiagnosticKind, String) {
-        (UnusedDiagnosticKind::Constant, name.to_owned())
+/// Determines the span of an item, explicitly extended to cover its outer
+/// attributes (doc comments are attributes as well), so removing the item
+/// does not leave these behind
+fn span_with_attrs(attrs: &[syn::Attribute], item: &impl Spanned) -> Span {
+    let span = item.span();
+
+    attrs
+        .iter()
+        .find(|attr| matches!(attr.style, syn::AttrStyle::Outer))
+        .and_then(|attr| attr.span().join(span))
+        .unwrap_or(span)
+}
+
+fn expand_ranges_to_include_whitespace<'a>(
+    src: &'a [u8],
+    iter: impl Iterator<Item = Range<usize>> + 'a,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+    iter.map(|range| {
+        find_prefix_whitespace(&src[..range.start])
+            ..find_suffix_whitespace(&src[range.end..]) + range.end
+    })
+}
+
+/// Deletes the given (possibly overlapping) byte ranges from the source
+pub fn delete_chunks(src: &[u8], chunks_to_delete: &[Range<usize>]) -> Vec<u8> {
+    // the ranges come from the spans of the parsed source, so a bad one is a
+    // bug rather than a problem with the code
+    for chunk in chunks_to_delete {
+        assert!(
+            chunk.start <= chunk.end && chunk.end <= src.len(),
+            "cannot delete {}..{} from a file of {} bytes",
+            chunk.start,
+            chunk.end,
+            src.len()
+        );
     }
 
-    #[test]
-    fn identifier_to_span() {
-        let src = b"fn foo() {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;";
-        //          01234567890123456789012345678901234567890123456789012345678
-        //                    1         2         3         4         5
-        let pos = diagnostics_to_ranges(src, [fun("foo"), fun("foa"), constant("FOO")])
-            .unwrap()
-            .collect::<Vec<_>>();
-        assert_eq!(pos, vec![0..11, 13..38, 39..59]);
+    let mut result = Vec::with_capacity(src.len());
+    let mut position = 0;
+    for chunk in coalesce_ranges(chunks_to_delete.iter().cloned()) {
+        result.extend_from_slice(&src[position..chunk.start]);
+        position = chunk.end;
+    }
+    result.extend_from_slice(&src[position..]);
+
+    result
+}
+
+/// Merges ranges that overlap, such as the range of an impl block and that of
+/// an item in it, or duplicates; the resulting ranges are sorted and disjoint,
+/// provided that none of the given ranges is reversed
+fn coalesce_ranges(ranges: impl IntoIterator<Item = Range<usize>>) -> Vec<Range<usize>> {
+    let mut ranges: Vec<_> = ranges.into_iter().collect();
+    // a reversed range would be kept apart from the ones it overlaps with, so
+    // that deleting the result would duplicate code instead
+    debug_assert!(
+        ranges.iter().all(|range| range.start <= range.end),
+        "reversed ranges: {ranges:?}"
+    );
+    ranges.sort_by_key(|range| range.start);
+
+    let mut coalesced: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => coalesced.push(range),
+        }
+    }
+
+    coalesced
+}
+
+/// Replaces chunks of a bytearray with new content; a chunk that overlaps with
+/// an earlier one (such as a duplicate) is skipped
+fn replace_chunks(src: &[u8], mut replacements: Vec<(Range<usize>, Vec<u8>)>) -> Vec<u8> {
+    replacements.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+
+    let mut result = Vec::with_capacity(src.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        if range.start < position {
+            continue;
+        }
+
+        result.extend_from_slice(&src[position..range.start]);
+        result.extend_from_slice(&replacement);
+        position = range.end;
+    }
+    result.extend_from_slice(&src[position..]);
+
+    result
+}
+
+/// Deletes (or comments out, depending on the mode) a
+/// list-of-positions-of-identifiers from a bytearray that is valid
+/// rust code, the file of the module `module` (its path from the crate root,
+/// which tells which impl blocks are for a removed type) BUGS: if the
+/// position is in the body of a function, it will try to delete identifiers
+/// there ...  probably?
+pub fn rust_remove(
+    src: &[u8],
+    module: &[String],
+    diagnostics: impl IntoIterator<Item = (UnusedDiagnosticKind, String)>,
+    mode: Mode,
+    tombstones: bool,
+    edition: Edition,
+) -> Result<Vec<u8>, syn::Error> {
+    let diagnostics: Vec<_> = diagnostics.into_iter().collect();
+    // a nested range (e.g. of an item in an impl block that is removed as a
+    // whole) would otherwise be edited twice
+    let ranges = coalesce_ranges(diagnostics_to_ranges(
+        src,
+        module,
+        diagnostics.clone(),
+        edition,
+    )?);
+
+    if tombstones && mode != Mode::Comment {
+        let labels = item_labels(src, &diagnostics, edition);
+        return Ok(tombstone_chunks(src, ranges.into_iter(), &labels));
+    }
+
+    Ok(remove_chunks(src, ranges.into_iter(), mode))
+}
+
+/// Describes the items that can be found in a file, like `fn foo`, together
+/// with their location
+fn item_labels(
+    src: &[u8],
+    diagnostics: &[ItemName],
+    edition: Edition,
+) -> Vec<(Range<usize>, String)> {
+    let Ok(ast) = parse_file(src, edition) else {
+        return Vec::new();
+    };
+    let offsets = line_offsets(src);
+
+    diagnostics
+        .iter()
+        .filter_map(|(kind, ident)| {
+            let item = find_item(&ast.items, kind, ident)?;
+            Some((
+                to_range(&offsets, item.span()),
+                format!("{} {ident}", item.keyword()),
+            ))
+        })
+        .collect()
+}
+
+/// Deletes chunks of code, but leaves a one-line marker (a "tombstone") where
+/// the removed items used to be; chunks that do not contain any of the labelled
+/// items (such as the impl blocks of a removed type) are deleted silently
+fn tombstone_chunks(
+    src: &[u8],
+    chunks: impl Iterator<Item = Range<usize>>,
+    labels: &[(Range<usize>, String)],
+) -> Vec<u8> {
+    let replacements = chunks
+        .map(|range| {
+            let mut removed: Vec<&str> = Vec::new();
+            for (item, label) in labels {
+                let contained = range.start <= item.start && item.end <= range.end;
+                if contained && !removed.contains(&label.as_str()) {
+                    removed.push(label);
+                }
+            }
+
+            if removed.is_empty() {
+                let expanded = expand_ranges_to_include_whitespace(src, std::iter::once(range))
+                    .next()
+                    .unwrap();
+                return (expanded, Vec::new());
+            }
+
+            let text = format!("cargo-minify: removed unused {}", removed.join(", "));
+            let ends_line = src[range.end..]
+                .iter()
+                .take_while(|&&c| c != NEWLINE)
+                .all(u8::is_ascii_whitespace);
+            let tombstone = if ends_line {
+                format!("// {text}")
+            } else {
+                format!("/* {text} */")
+            };
+
+            (range, tombstone.into_bytes())
+        })
+        .collect();
+
+    replace_chunks(src, replacements)
+}
+
+/// Removes whole items from the source, either by deleting them (together with
+/// the whitespace that belongs to them) or by commenting them out
+fn remove_chunks(src: &[u8], chunks: impl Iterator<Item = Range<usize>>, mode: Mode) -> Vec<u8> {
+    match mode {
+        Mode::Comment => comment_chunks(src, chunks),
+        Mode::Delete | Mode::Stub | Mode::CfgTest | Mode::BinFeature | Mode::DeprecatePub => {
+            let chunks: Vec<_> = expand_ranges_to_include_whitespace(src, chunks).collect();
+            delete_chunks(src, &chunks)
+        }
+    }
+}
+
+/// Comments out chunks of code: with line comments if a chunk spans whole
+/// lines, with a block comment otherwise; unless the chunk contains `/*` or
+/// `*/` (which would end or nest the block comment), then it is put on lines
+/// of its own, and commented out with line comments
+fn comment_chunks(src: &[u8], chunks: impl Iterator<Item = Range<usize>>) -> Vec<u8> {
+    let newline = line_ending(src);
+    let replacements = chunks
+        .map(|range| {
+            let leading = src[range.clone()]
+                .iter()
+                .take_while(|c| c.is_ascii_whitespace())
+                .count();
+            let start = range.start + leading;
+            let trailing = src[start..range.end]
+                .iter()
+                .rev()
+                .take_while(|c| c.is_ascii_whitespace())
+                .count();
+            let end = range.end - trailing;
+
+            let line_start = src[..start]
+                .iter()
+                .rposition(|&c| c == NEWLINE)
+                .map_or(0, |pos| pos + 1);
+            let line_end = src[end..]
+                .iter()
+                .position(|&c| c == NEWLINE)
+                .map_or(src.len(), |pos| end + pos);
+
+            let whole_lines = src[line_start..start].iter().all(u8::is_ascii_whitespace)
+                && src[end..line_end].iter().all(u8::is_ascii_whitespace);
+            let delimits_comment = src[start..end]
+                .windows(2)
+                .any(|pair| pair == b"/*" || pair == b"*/");
+            if !whole_lines && !delimits_comment {
+                let mut comment = b"/* ".to_vec();
+                comment.extend_from_slice(&src[start..end]);
+                comment.extend_from_slice(b" */");
+
+                return (start..end, comment);
+            }
+            if !whole_lines {
+                let is_space = |c: &&u8| **c == SPACE || **c == b'\t';
+                let indent: Vec<u8> = src[line_start..]
+                    .iter()
+                    .take_while(is_space)
+                    .copied()
+                    .collect();
+                let code_before = !src[line_start..start].iter().all(u8::is_ascii_whitespace);
+                let code_after = !src[end..line_end].iter().all(u8::is_ascii_whitespace);
+                // the spaces around the chunk would end up at the end or start
+                // of a line
+                let start = if code_before {
+                    start - src[..start].iter().rev().take_while(is_space).count()
+                } else {
+                    start
+                };
+                let end = if code_after {
+                    end + src[end..].iter().take_while(is_space).count()
+                } else {
+                    end
+                };
+
+                let mut comment = Vec::new();
+                if code_before {
+                    comment.extend_from_slice(newline);
+                    comment.extend_from_slice(&indent);
+                }
+                for (i, line) in src[start..end]
+                    .trim_ascii()
+                    .split(|&c| c == NEWLINE)
+                    .enumerate()
+                {
+                    // the following lines keep their own indentation
+                    let pos = line.iter().take_while(is_space).count().min(indent.len());
+                    if i > 0 {
+                        comment.push(NEWLINE);
+                        comment.extend_from_slice(&line[..pos]);
+                    }
+                    comment.extend_from_slice(b"// ");
+                    comment.extend_from_slice(&line[pos..]);
+                }
+                if code_after {
+                    comment.extend_from_slice(newline);
+                    comment.extend_from_slice(&indent);
+                }
+
+                return (start..end, comment);
+            }
+
+            // put the `//` of every line at the indentation of the first one
+            let indent = start - line_start;
+            let mut comment = Vec::new();
+            for (i, line) in src[line_start..end].split(|&c| c == NEWLINE).enumerate() {
+                if i > 0 {
+                    comment.push(NEWLINE);
+                }
+                let pos = line
+                    .iter()
+                    .take_while(|&&c| c == SPACE || c == b'\t')
+                    .count()
+                    .min(indent);
+                let is_blank = line[pos..].iter().all(u8::is_ascii_whitespace);
+                comment.extend_from_slice(&line[..pos]);
+                comment.extend_from_slice(if is_blank { b"//" } else { b"// " });
+                comment.extend_from_slice(&line[pos..]);
+            }
+
+            (line_start..end, comment)
+        })
+        .collect();
+
+    replace_chunks(src, replacements)
+}
+
+/// Processes a list of file+list-of-edits into an iterator of
+/// filenames+proposed new contents; the edits are made on top of the suggested
+/// content of a file, if any
+fn process_files<'a>(
+    diagnostics: impl IntoIterator<Item = (PathBuf, Vec<ItemName>)> + 'a,
+    suggested: &'a BTreeMap<PathBuf, Vec<u8>>,
+    options: &'a CauterizeOptions,
+) -> impl Iterator<Item = Result<Change, Skipped>> + 'a {
+    let module_paths = file_modules(options);
+    diagnostics
+        .into_iter()
+        .map(move |(file_name, diagnostics)| {
+            let unused = diagnostics.len();
+            let skip = |reason: String| Skipped {
+                file_name: file_name.clone(),
+                unused,
+                reason,
+            };
+            let syntax_error = |err: syn::Error| {
+                let line = err.span().start().line;
+                skip(format!("could not be parsed (line {line}: {err})"))
+            };
+
+            let original_content = std::fs::read(&file_name)
+                .map_err(|err| skip(format!("could not be read ({err})")))?;
+            if std::str::from_utf8(&original_content).is_err() {
+                return Err(skip("is not valid UTF-8".to_owned()));
+            }
+            let base = suggested.get(&file_name).unwrap_or(&original_content);
+            let edition = edition_of(options, &file_name);
+            let module = module_paths
+                .get(&file_name)
+                .map_or(&[][..], |(_, module)| module.as_slice());
+            report_ambiguous_items(&file_name, base, &diagnostics, edition);
+            if options.mode == Mode::CfgTest {
+                let proposed_content =
+                    gate_items(base, module, &diagnostics, b"#[cfg(test)]", edition)
+                        .map_err(syntax_error)?;
+                return Ok(Change {
+                    file_name,
+                    original_content,
+                    proposed_content: Some(proposed_content),
+                    removed_items: Vec::new(),
+                    unread_fields: Vec::new(),
+                });
+            }
+
+            let (content, diagnostics) = match options.mode {
+                Mode::Delete
+                | Mode::Comment
+                | Mode::CfgTest
+                | Mode::BinFeature
+                | Mode::DeprecatePub => (base.clone(), diagnostics),
+                Mode::Stub => {
+                    let stub = stub_macro(options, &file_name);
+                    stub_functions(base, diagnostics, stub, edition).map_err(syntax_error)?
+                }
+            };
+            let removed_items = located_items(&content, &diagnostics, edition);
+            let removed_unused = rust_remove(
+                &content,
+                module,
+                diagnostics,
+                options.mode,
+                options.tombstones,
+                edition,
+            )
+            .map_err(syntax_error)?;
+            let (proposed_content, libraries) =
+                remove_empty_blocks(&content, &removed_unused, options.mode, edition)
+                    .map_err(syntax_error)?;
+            for library in libraries {
+                info!(
+                    "{} no longer links `{library}`; a build script or `links` key \
+                 that is only there for it may be removed as well",
+                    file_name.display()
+                );
+            }
+
+            Ok(Change {
+                file_name,
+                original_content,
+                proposed_content: Some(proposed_content),
+                removed_items,
+                unread_fields: Vec::new(),
+            })
+        })
+}
+
+/// Puts the given attribute (e.g. `#[cfg(test)]`) on the given items, together
+/// with the impl blocks of types, since these are only used in the same places
+fn gate_items(
+    src: &[u8],
+    module: &[String],
+    diagnostics: &[ItemName],
+    attribute: &[u8],
+    edition: Edition,
+) -> Result<Vec<u8>, syn::Error> {
+    let ast = parse_file(src, edition)?;
+    let offsets = line_offsets(src);
+
+    let mut ranges = Vec::new();
+    for (kind, ident) in diagnostics {
+        let Some(item) = find_item(&ast.items, kind, ident) else {
+            continue;
+        };
+        ranges.push(to_range(&offsets, item.span()));
+
+        if let ItemRef::Item(definition) = item {
+            ranges.extend(
+                find_impl_spans(&ast.items, module, definition)
+                    .into_iter()
+                    .map(|span| to_range(&offsets, span)),
+            );
+        }
+    }
+    // items in an impl block that is gated as a whole need no attribute
+    let attributes = ranges
+        .iter()
+        .filter(|range| {
+            !ranges.iter().any(|outer| {
+                outer != *range && outer.start <= range.start && range.end <= outer.end
+            })
+        })
+        .map(|range| (range.start, vec![attribute]))
+        .collect();
+
+    Ok(insert_attributes(src, attributes))
+}
+
+/// Marks the given items as `#[deprecated]` (unless they are already); as
+/// these items, and the impl blocks of such types, may refer to each other,
+/// they allow the use of deprecated items
+fn deprecate_items(
+    src: &[u8],
+    module: &[String],
+    items: &[ItemName],
+    edition: Edition,
+) -> Result<Vec<u8>, syn::Error> {
+    let ast = parse_file(src, edition)?;
+    let offsets = line_offsets(src);
+
+    let mut attributes = BTreeMap::new();
+    for (kind, ident) in items {
+        let Some(item) = find_item(&ast.items, kind, ident) else {
+            continue;
+        };
+        if deprecation(item.attrs()).is_some() {
+            continue;
+        }
+        let start = to_range(&offsets, item.span()).start;
+        attributes.insert(start, vec![DEPRECATION, ALLOW_DEPRECATED]);
+
+        if let ItemRef::Item(definition) = item {
+            for span in find_impl_spans(&ast.items, module, definition) {
+                attributes.insert(to_range(&offsets, span).start, vec![ALLOW_DEPRECATED]);
+            }
+        }
+    }
+
+    Ok(insert_attributes(src, attributes))
+}
+
+/// Puts attributes in front of the items starting at the given offsets, each
+/// on a line of its own if the item starts a line
+fn insert_attributes(src: &[u8], attributes: BTreeMap<usize, Vec<&[u8]>>) -> Vec<u8> {
+    let replacements = attributes
+        .into_iter()
+        .map(|(start, attributes)| {
+            let line_start = src[..start]
+                .iter()
+                .rposition(|&c| c == NEWLINE)
+                .map_or(0, |pos| pos + 1);
+            let indentation = &src[line_start..start];
+
+            let mut inserted = Vec::new();
+            for attribute in attributes {
+                inserted.extend_from_slice(attribute);
+                if indentation.iter().all(u8::is_ascii_whitespace) {
+                    inserted.extend_from_slice(line_ending(src));
+                    inserted.extend_from_slice(indentation);
+                } else {
+                    inserted.push(SPACE);
+                }
+            }
+
+            (start..start, inserted)
+        })
+        .collect();
+
+    replace_chunks(src, replacements)
+}
+
+/// Warns about diagnostics that match several items (that are not configured
+/// out), these are left alone
+fn report_ambiguous_items(
+    file_name: &Path,
+    src: &[u8],
+    diagnostics: &[ItemName],
+    edition: Edition,
+) {
+    let Ok(ast) = parse_file(src, edition) else {
+        return;
+    };
+
+    for (kind, ident) in diagnostics {
+        if find_items(&ast.items, kind, ident).len() > 1 {
+            warn!(
+                "not removing `{ident}` from {}, since it matches several items",
+                file_name.display()
+            );
+        }
+    }
+}
+
+/// Drops the diagnostics that do not point at the name of the item they refer
+/// to; this happens when a macro (typically an attribute macro) produced the
+/// item, or gave its tokens a different span, in which case the item found by
+/// name may not be the one that is unused
+fn drop_mislocated(
+    file_name: &Path,
+    diagnostics: Vec<UnusedDiagnostic>,
+    edition: Edition,
+) -> Vec<UnusedDiagnostic> {
+    let Some(ast) = std::fs::read(file_name)
+        .ok()
+        .and_then(|src| parse_file(&src, edition).ok())
+    else {
+        return diagnostics;
+    };
+
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            // fields are not items, and invocations are not named
+            if matches!(
+                diagnostic.kind,
+                UnusedDiagnosticKind::Field | UnusedDiagnosticKind::MacroInvocation
+            ) {
+                return true;
+            }
+
+            let position = (diagnostic.span.line_start, diagnostic.span.column_start);
+            let located = points_at_item(&ast.items, &diagnostic.kind, &diagnostic.ident, position);
+            if !located {
+                warn!(
+                    "not removing `{}` from {}, since the compiler points at line {} \
+                     instead of its definition (it may be generated by a macro)",
+                    diagnostic.ident,
+                    file_name.display(),
+                    position.0
+                );
+            }
+
+            located
+        })
+        .collect()
+}
+
+/// The innermost item (or associated function) that spans the given (1-based)
+/// line of a file
+pub fn item_at(file_name: &Path, line: usize, options: &CauterizeOptions) -> Option<ItemName> {
+    fn innermost(items: &[syn::Item], line: usize) -> Option<ItemName> {
+        let item = items.iter().find(|item| {
+            let span = item.span();
+            span.start().line <= line && line <= span.end().line
+        })?;
+        match item {
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => innermost(items, line),
+            syn::Item::Impl(block) => {
+                let function = block.items.iter().find_map(|item| match item {
+                    syn::ImplItem::Fn(function)
+                        if function.span().start().line <= line
+                            && line <= function.span().end().line =>
+                    {
+                        Some(function)
+                    }
+                    _ => None,
+                })?;
+                Some((
+                    UnusedDiagnosticKind::AssociatedFunction,
+                    function.sig.ident.unraw().to_string(),
+                ))
+            }
+            item => item_name(item),
+        }
+    }
+
+    let src = std::fs::read(file_name).ok()?;
+    let ast = parse_file(&src, edition_of(options, file_name)).ok()?;
+    innermost(&ast.items, line)
+}
+
+/// The reason an item that the compiler reports as unused is left alone, if
+/// any (see `cargo minify explain`)
+pub fn why_kept(diagnostic: &UnusedDiagnostic, options: &CauterizeOptions) -> Option<String> {
+    let file_name = Path::new(&diagnostic.span.file_name);
+    if let Some(reason) = skip_reason(file_name, options, &crate_includes(options)) {
+        return Some(format!("its file is skipped, since it {reason}"));
+    }
+    if !in_scope(file_name, options) {
+        return Some("its file lies outside of the given paths".to_owned());
+    }
+
+    let src = match std::fs::read(file_name) {
+        Ok(src) => src,
+        Err(err) => return Some(format!("its file could not be read ({err})")),
+    };
+    let ast = match parse_file(&src, edition_of(options, file_name)) {
+        Ok(ast) => ast,
+        Err(err) => {
+            let line = err.span().start().line;
+            return Some(format!("its file could not be parsed (line {line}: {err})"));
+        }
+    };
+
+    if diagnostic.kind == UnusedDiagnosticKind::Field {
+        return None;
+    }
+    match find_items(&ast.items, &diagnostic.kind, &diagnostic.ident).len() {
+        0 => return Some("no such item was found (it may be configured out)".to_owned()),
+        1 => {}
+        _ => return Some("several items match it (it may depend on the configuration)".to_owned()),
+    }
+
+    let items = find_items(&ast.items, &diagnostic.kind, &diagnostic.ident);
+    let protection = items
+        .iter()
+        .find_map(|item| protection(item.attrs(), &options.keep_attributes));
+    if let Some((attribute, reason)) = protection {
+        return Some(format!("it is marked #[{attribute}] ({reason})"));
+    }
+
+    let position = (diagnostic.span.line_start, diagnostic.span.column_start);
+    let located = diagnostic.kind == UnusedDiagnosticKind::MacroInvocation
+        || points_at_item(&ast.items, &diagnostic.kind, &diagnostic.ident, position);
+    (!located).then(|| {
+        "the compiler does not point at its definition (it may be generated by a macro)".to_owned()
+    })
+}
+
+/// Whether a (1-based) line and column point at the name of one of the items
+/// with the given kind and name; if there are no such items at all, this is
+/// left for the other passes to deal with
+fn points_at_item(
+    items: &[syn::Item],
+    kind: &UnusedDiagnosticKind,
+    ident: &str,
+    (line, column): (usize, usize),
+) -> bool {
+    let candidates = find_items(items, kind, ident);
+
+    candidates.is_empty()
+        || candidates.iter().any(|item| {
+            item.ident().is_some_and(|ident| {
+                let start = ident.span().start();
+                start.line == line && start.column + 1 == column
+            })
+        })
+}
+
+/// Replaces the bodies of unused non-private functions by `stub` (such as
+/// `todo!()`); returns the diagnostics for the private functions, which are to
+/// be removed instead
+fn stub_functions(
+    src: &[u8],
+    diagnostics: Vec<ItemName>,
+    stub: &[u8],
+    edition: Edition,
+) -> Result<(Vec<u8>, Vec<ItemName>), syn::Error> {
+    let ast = parse_file(src, edition)?;
+    let offsets = line_offsets(src);
+
+    let mut stubs = Vec::new();
+    let mut remaining = Vec::new();
+    for (kind, ident) in diagnostics {
+        let Some(item) = find_item(&ast.items, &kind, &ident) else {
+            continue;
+        };
+        let Some(body) = item.body() else {
+            continue;
+        };
+
+        if matches!(item.vis(), Some(syn::Visibility::Inherited)) {
+            remaining.push((kind, ident));
+        } else {
+            let range = to_range(&offsets, body.span());
+            let start = to_range(&offsets, item.span()).start;
+            stubs.push((range.clone(), stub_body(src, start, range, stub)));
+        }
+    }
+
+    Ok((replace_chunks(src, stubs), remaining))
+}
+
+/// A body for a function that only calls `stub`, indented like the original one
+fn stub_body(src: &[u8], item_start: usize, body: Range<usize>, stub: &[u8]) -> Vec<u8> {
+    if !src[body].contains(&NEWLINE) {
+        return [b"{ ", stub, b" }"].concat();
+    }
+
+    let line_start = src[..item_start]
+        .iter()
+        .rposition(|&c| c == NEWLINE)
+        .map_or(0, |pos| pos + 1);
+    let indent: Vec<u8> = src[line_start..item_start]
+        .iter()
+        .copied()
+        .take_while(|c| c.is_ascii_whitespace())
+        .collect();
+
+    let newline = line_ending(src);
+    let mut block = b"{".to_vec();
+    block.extend_from_slice(newline);
+    block.extend_from_slice(&indent);
+    block.extend_from_slice(b"    ");
+    block.extend_from_slice(stub);
+    block.extend_from_slice(newline);
+    block.extend_from_slice(&indent);
+    block.push(b'}');
+
+    block
+}
+
+/// The macro call for stubbed function bodies: `todo!()`, unless the package
+/// of the file supports a Rust version that predates it
+fn stub_macro(options: &CauterizeOptions, file_name: &Path) -> &'static [u8] {
+    match rust_version_of(options, file_name) {
+        Some(version) if (version.major, version.minor) < TODO_MACRO_SINCE => b"unimplemented!()",
+        _ => b"todo!()",
+    }
+}
+
+/// Filters the items that can actually be found in a file
+fn located_items(src: &[u8], diagnostics: &[ItemName], edition: Edition) -> Vec<ItemName> {
+    let Ok(ast) = parse_file(src, edition) else {
+        return Vec::new();
+    };
+
+    diagnostics
+        .iter()
+        .filter(|(kind, ident)| find_item_span(&ast.items, kind, ident).is_some())
+        .cloned()
+        .collect()
+}
+
+/// Process a list of UnusedDiagnostics into the proposed changes, and the files
+/// that were skipped; the fixes that the compiler suggests (see
+/// `--apply-suggestions`) and the removal of unused features (see
+/// `--remove-unused-features`) are applied first
+pub fn process_diagnostics(
+    diagnostics: impl IntoIterator<Item = UnusedDiagnostic>,
+    suggestions: Vec<unused::Suggestion>,
+    features: Vec<unused::UnusedFeature>,
+    options: &CauterizeOptions,
+) -> (Vec<Change>, Vec<Skipped>) {
+    let mut unread_fields = BTreeMap::<(PathBuf, String), BTreeSet<String>>::new();
+    let mut skipped = BTreeMap::<PathBuf, (String, usize)>::new();
+    let includes = crate_includes(options);
+
+    let diagnostics = diagnostics
+        .into_iter()
+        .filter(|diagnostic| in_scope(Path::new(&diagnostic.span.file_name), options))
+        .filter(|diagnostic| {
+            let path = Path::new(&diagnostic.span.file_name);
+            let Some(reason) = skip_reason(path, options, &includes) else {
+                return true;
+            };
+
+            skipped.entry(path.to_path_buf()).or_insert((reason, 0)).1 += 1;
+            false
+        })
+        .filter_map(|diagnostic| {
+            if diagnostic.kind != UnusedDiagnosticKind::Field {
+                return Some(diagnostic);
+            }
+
+            if let Some(parent) = diagnostic.parent {
+                let path = PathBuf::from(&diagnostic.span.file_name);
+                unread_fields
+                    .entry((path, parent))
+                    .or_default()
+                    .insert(diagnostic.ident);
+            }
+
+            None
+        })
+        .map(|diagnostic| {
+            let path = PathBuf::from(&diagnostic.span.file_name);
+            (path, diagnostic)
+        })
+        .collect::<multimap::MultiMap<_, _>>();
+
+    let skipped: Vec<_> = skipped
+        .into_iter()
+        .map(|(file_name, (reason, unused))| Skipped {
+            file_name,
+            unused,
+            reason,
+        })
+        .collect();
+
+    report_foreign_impls(&diagnostics, options);
+
+    let mut items: BTreeMap<PathBuf, Vec<ItemName>> = diagnostics
+        .into_iter()
+        .map(|(file_name, diagnostics)| {
+            let edition = edition_of(options, &file_name);
+            let items = drop_mislocated(&file_name, diagnostics, edition)
+                .into_iter()
+                .map(|diagnostic| (diagnostic.kind, diagnostic.ident))
+                .collect();
+            (file_name, items)
+        })
+        .collect();
+    if options.exported_macros {
+        for (file_name, name) in unused_exported_macros(options) {
+            items
+                .entry(file_name)
+                .or_default()
+                .push((UnusedDiagnosticKind::MacroDefinition, name));
+        }
+    }
+
+    let suggested = apply_suggestions(suggestions, features, &includes, options);
+
+    process_items(
+        items,
+        &suggested,
+        &unread_fields,
+        skipped,
+        &includes,
+        options,
+    )
+}
+
+/// The content of the files that the compiler suggested fixes for, or reported
+/// unused features in, with these fixes applied and these features removed
+fn apply_suggestions(
+    suggestions: Vec<unused::Suggestion>,
+    features: Vec<unused::UnusedFeature>,
+    includes: &[Include],
+    options: &CauterizeOptions,
+) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut edits = BTreeMap::<PathBuf, Vec<_>>::new();
+    let mut unused_features = BTreeMap::<PathBuf, BTreeSet<_>>::new();
+    for feature in features {
+        let file_name = PathBuf::from(&feature.file_name);
+        if !in_scope(&file_name, options) || skip_reason(&file_name, options, includes).is_some() {
+            continue;
+        }
+
+        unused_features
+            .entry(file_name)
+            .or_default()
+            .insert((feature.line, feature.name));
+    }
+    for (file_name, features) in unused_features {
+        let Ok(src) = std::fs::read(&file_name) else {
+            continue;
+        };
+        let edition = edition_of(options, &file_name);
+        match unused_arguments(&src, &features, feature_attribute, edition) {
+            Ok(chunks) => edits
+                .entry(file_name)
+                .or_default()
+                .extend(chunks.into_iter().map(|chunk| (chunk, Vec::new()))),
+            Err(err) => warn!(
+                "could not remove unused features from {}: {err}",
+                file_name.display()
+            ),
+        }
+    }
+    for suggestion in suggestions {
+        let file_name = PathBuf::from(&suggestion.file_name);
+        if !in_scope(&file_name, options) || skip_reason(&file_name, options, includes).is_some() {
+            continue;
+        }
+
+        let replacements = suggestion
+            .edits
+            .into_iter()
+            .map(|(range, replacement)| (range, replacement.into_bytes()));
+        edits.entry(file_name).or_default().extend(replacements);
+    }
+
+    edits
+        .into_iter()
+        .filter_map(|(file_name, replacements)| {
+            let src = std::fs::read(&file_name).ok()?;
+            if replacements.iter().any(|(range, _)| range.end > src.len()) {
+                warn!(
+                    "not applying the suggestions for {}, which changed since it was checked",
+                    file_name.display()
+                );
+                return None;
+            }
+            let content = replace_chunks(&src, replacements);
+            std::str::from_utf8(&content).ok()?;
+            Some((file_name, content))
+        })
+        .collect()
+}
+
+/// Process the items to remove from each file into the proposed changes, and
+/// the files that were skipped; unlike [`process_diagnostics`], the items are
+/// not reported by the compiler, but chosen otherwise (e.g. using `--remove`)
+pub fn process_chosen_items(
+    items: BTreeMap<PathBuf, Vec<ItemName>>,
+    options: &CauterizeOptions,
+) -> (Vec<Change>, Vec<Skipped>) {
+    let includes = crate_includes(options);
+
+    let mut skipped = Vec::new();
+    let items = items
+        .into_iter()
+        .filter(|(file_name, _)| in_scope(file_name, options))
+        .filter(|(file_name, items)| {
+            let Some(reason) = skip_reason(file_name, options, &includes) else {
+                return true;
+            };
+
+            skipped.push(Skipped {
+                file_name: file_name.clone(),
+                unused: items.len(),
+                reason,
+            });
+            false
+        })
+        .collect();
+
+    process_items(
+        items,
+        &BTreeMap::new(),
+        &BTreeMap::new(),
+        skipped,
+        &includes,
+        options,
+    )
+}
+
+/// Keeps the items with a protecting attribute (see [`protection`]), which are
+/// unused by Rust code on purpose
+fn spare_protected(
+    items: BTreeMap<PathBuf, Vec<ItemName>>,
+    options: &CauterizeOptions,
+) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    items
+        .into_iter()
+        .map(|(file_name, items)| {
+            let Some(ast) = std::fs::read(&file_name)
+                .ok()
+                .and_then(|src| parse_file(&src, edition_of(options, &file_name)).ok())
+            else {
+                return (file_name, items);
+            };
+
+            let items = items
+                .into_iter()
+                .filter(|(kind, ident)| {
+                    let protection = find_items(&ast.items, kind, ident)
+                        .iter()
+                        .find_map(|item| protection(item.attrs(), &options.keep_attributes));
+                    if let Some((_, reason)) = &protection {
+                        info!("`{ident}` in {} is kept ({reason})", file_name.display());
+                    }
+                    protection.is_none()
+                })
+                .collect();
+            (file_name, items)
+        })
+        .collect()
+}
+
+/// Warns about the items that doc examples use, since these are not compiled
+/// by `cargo check`; with `--keep-doctested`, these items are kept instead
+fn spare_doctested(
+    items: BTreeMap<PathBuf, Vec<ItemName>>,
+    options: &CauterizeOptions,
+) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    if items.values().all(Vec::is_empty) {
+        return items;
+    }
+
+    let doctests = workspace_doctests(options);
+    items
+        .into_iter()
+        .map(|(file_name, items)| {
+            let items = items
+                .into_iter()
+                .filter(|(_, ident)| {
+                    if !doctests::uses(&doctests, ident) {
+                        return true;
+                    }
+
+                    if options.keep_doctested {
+                        info!(
+                            "keeping `{ident}` in {}, which a doc example uses",
+                            file_name.display()
+                        );
+                    } else {
+                        warn!(
+                            "`{ident}` in {} is used by a doc example, which `cargo check` does \
+                             not compile (use --keep-doctested to keep it)",
+                            file_name.display()
+                        );
+                    }
+                    !options.keep_doctested
+                })
+                .collect();
+            (file_name, items)
+        })
+        .collect()
+}
+
+/// Finds the doc examples in all packages (see [`doctests::doctests`])
+fn workspace_doctests(options: &CauterizeOptions) -> Vec<doctests::Doctest> {
+    let files: Vec<_> = options
+        .editions
+        .keys()
+        .flat_map(|package_root| rust_files(package_root, options))
+        .filter_map(|file_name| {
+            let src = std::fs::read(&file_name).ok()?;
+            let ast = parse_file(&src, edition_of(options, &file_name)).ok()?;
+            Some((relative_path(&file_name), ast))
+        })
+        .collect();
+
+    doctests::doctests(&files)
+}
+
+/// Keeps the `max` items that span the most lines, leaving the others for a
+/// later run
+fn cap_removals(
+    items: BTreeMap<PathBuf, Vec<ItemName>>,
+    max: usize,
+    options: &CauterizeOptions,
+) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    let mut sized = Vec::new();
+    for (file_name, items) in items {
+        let edition = edition_of(options, &file_name);
+        let src = std::fs::read(&file_name).unwrap_or_default();
+        let ast = parse_file(&src, edition).ok();
+        for item in items {
+            let lines = ast.as_ref().map_or(1, |ast| item_lines(ast, &item));
+            sized.push((lines, file_name.clone(), item));
+        }
+    }
+    if sized.len() <= max {
+        return group_by_file(sized);
+    }
+
+    info!(
+        "removing the {max} largest of {} unused items (see --max-removals)",
+        sized.len()
+    );
+    sized.sort_by_key(|(lines, ..)| std::cmp::Reverse(*lines));
+    sized.truncate(max);
+    group_by_file(sized)
+}
+
+fn group_by_file(sized: Vec<(usize, PathBuf, ItemName)>) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    let mut items = BTreeMap::<_, Vec<_>>::new();
+    for (_, file_name, item) in sized {
+        items.entry(file_name).or_default().push(item);
+    }
+    items
+}
+
+/// The number of lines spanned by an item (or by all items of that name); one
+/// if it cannot be found
+fn item_lines(ast: &File, (kind, ident): &ItemName) -> usize {
+    let lines: usize = find_items(&ast.items, kind, ident)
+        .iter()
+        .map(|item| item.span().end().line - item.span().start().line + 1)
+        .sum();
+    lines.max(1)
+}
+
+fn process_items(
+    items: BTreeMap<PathBuf, Vec<ItemName>>,
+    suggested: &BTreeMap<PathBuf, Vec<u8>>,
+    unread_fields: &BTreeMap<(PathBuf, String), BTreeSet<String>>,
+    mut skipped: Vec<Skipped>,
+    includes: &[Include],
+    options: &CauterizeOptions,
+) -> (Vec<Change>, Vec<Skipped>) {
+    let items = spare_protected(items, options);
+    let items = spare_doctested(items, options);
+    let items = match options.max_removals {
+        Some(max) => cap_removals(items, max, options),
+        None => items,
+    };
+
+    let mut changes = BTreeMap::new();
+    for result in process_files(items, suggested, options) {
+        match result {
+            Ok(change) => {
+                changes.insert(change.file_name.clone(), change);
+            }
+            Err(skip) => skipped.push(skip),
+        }
+    }
+    // files with nothing to remove may still have fixes to apply
+    for (file_name, content) in suggested {
+        if changes.contains_key(file_name) {
+            continue;
+        }
+        if let Ok(original_content) = std::fs::read(file_name) {
+            changes.insert(
+                file_name.clone(),
+                Change {
+                    file_name: file_name.clone(),
+                    original_content,
+                    proposed_content: Some(content.clone()),
+                    removed_items: Vec::new(),
+                    unread_fields: Vec::new(),
+                },
+            );
+        }
+    }
+    skipped.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    if options.mode == Mode::BinFeature {
+        gate_single_bin_items(&mut changes, options);
+    }
+    if options.mode == Mode::DeprecatePub {
+        deprecate_unused_pub_items(&mut changes, options);
+    }
+
+    let gates = matches!(options.mode, Mode::CfgTest | Mode::BinFeature);
+    if options.unit_structs && !gates {
+        convert_unit_structs(unread_fields, &mut changes, options);
+    }
+
+    // before the imports of the removed types are, which it resolves through
+    if options.remove_marker_impls && !gates {
+        remove_marker_impls(&mut changes, options);
+    }
+    // gated items are kept, as are their imports
+    if !gates {
+        remove_orphaned_imports(&mut changes, options);
+    }
+    // commented out code leaves a module that looks empty, but is not meant to go
+    if options.mode != Mode::Comment && !gates {
+        remove_empty_modules(&mut changes, options);
+    }
+
+    if options.strip_docs || options.strip_tests || options.remove_ignored_tests {
+        strip_files(&mut changes, options);
+    }
+    // attributes may have become pointless because of all of the above
+    if options.remove_unused_allows {
+        remove_unused_allows(&mut changes, options);
+    }
+
+    // the passes above may have edited other files in the same package
+    changes.retain(|file_name, _| {
+        in_scope(file_name, options) && skip_reason(file_name, options, includes).is_none()
+    });
+    // cargo checks vendored sources against their checksums
+    if !options.vendored.is_empty() {
+        update_checksums(&mut changes, options);
+    }
+
+    for change in changes.values_mut() {
+        if let Some(content) = &change.proposed_content {
+            let normalized =
+                collapse_blank_lines(&change.original_content, content, options.max_blank_lines);
+            change.proposed_content = Some(normalized);
+        }
+    }
+
+    let changes = changes
+        .into_values()
+        .filter(|change| {
+            change.removes_file() || change.proposed_content() != change.original_content()
+        })
+        .collect();
+
+    (changes, skipped)
+}
+
+/// Leaves the files of vendored packages that are changed out of the checksums
+/// of these packages (see [`vendor::update_checksum`])
+fn update_checksums(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    for vendored in &options.vendored {
+        let changed: BTreeSet<_> = changes
+            .values()
+            .filter(|change| {
+                change.removes_file() || change.proposed_content() != change.original_content()
+            })
+            .filter_map(|change| change.file_name.canonicalize().ok())
+            .filter(|file_name| file_name.starts_with(&vendored.dir))
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        let checksum_file = relative_path(&vendored.checksum_file());
+        let Some(checksum) = current_content(changes, &checksum_file)
+            .and_then(|content| String::from_utf8(content).ok())
+        else {
+            continue;
+        };
+        match vendor::update_checksum(&checksum, vendored, &changed) {
+            Ok(checksum) => update_content(changes, &checksum_file, Some(checksum.into_bytes())),
+            Err(err) => warn!("could not update {}: {err}", checksum_file.display()),
+        }
+    }
+}
+
+/// Collapses runs of blank lines at the places where lines were removed to at
+/// most `max` lines; other places are left as they were
+fn collapse_blank_lines(original: &[u8], proposed: &[u8], max: usize) -> Vec<u8> {
+    let original: Vec<&[u8]> = original.split_inclusive(|&c| c == NEWLINE).collect();
+    let proposed: Vec<&[u8]> = proposed.split_inclusive(|&c| c == NEWLINE).collect();
+    let is_blank = |line: &[u8]| line.iter().all(u8::is_ascii_whitespace);
+
+    // positions in `proposed` where lines were removed, without replacement
+    let mut sites = Vec::new();
+    let (mut removed, mut added) = (false, false);
+    let mut position = 0;
+    for result in diff::slice(&original, &proposed) {
+        match result {
+            diff::Result::Left(_) => removed = true,
+            diff::Result::Right(_) => {
+                added = true;
+                position += 1;
+            }
+            diff::Result::Both(..) => {
+                if removed && !added {
+                    sites.push(position);
+                }
+                (removed, added) = (false, false);
+                position += 1;
+            }
+        }
+    }
+    if removed && !added {
+        sites.push(position);
+    }
+
+    let mut result = Vec::new();
+    let mut index = 0;
+    while index < proposed.len() {
+        let run = proposed[index..]
+            .iter()
+            .take_while(|line| is_blank(line))
+            .count();
+        if run == 0 {
+            result.extend_from_slice(proposed[index]);
+            index += 1;
+            continue;
+        }
+
+        let at_site = sites
+            .iter()
+            .any(|&site| index <= site && site <= index + run);
+        let keep = if at_site { run.min(max) } else { run };
+        for line in &proposed[index..index + keep] {
+            result.extend_from_slice(line);
+        }
+        index += run;
+    }
+
+    result
+}
+
+/// Removes the imports of items that were removed, since these would no
+/// longer compile; the paths of imports are resolved from the module they are
+/// in (see [`prune_imports`]), and only the imports of removed items go
+fn remove_orphaned_imports(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let module_paths = file_modules(options);
+
+    // the removed items of each crate, by the path of their module and name
+    let mut removed = BTreeMap::<PathBuf, BTreeSet<ItemPath>>::new();
+    for change in changes.values() {
+        let Some((root, file_module)) = module_paths.get(&change.file_name) else {
+            continue;
+        };
+
+        let edition = edition_of(options, &change.file_name);
+        removed.entry(root.clone()).or_default().extend(
+            removed_paths(change, edition)
+                .into_iter()
+                .map(|(inline_path, ident)| {
+                    ([file_module.as_slice(), &inline_path].concat(), ident)
+                }),
+        );
+    }
+
+    for (root, paths) in removed.iter_mut().filter(|(_, paths)| !paths.is_empty()) {
+        let files: Vec<_> = module_paths
+            .iter()
+            .filter(|(_, (file_root, _))| file_root == root)
+            .map(|(file_name, (_, module))| (file_name.clone(), module.clone()))
+            .collect();
+
+        // Removing a re-export removes the (possibly renamed) name from the
+        // module that exported it, so repeat until no more names disappear
+        loop {
+            let mut reexported = BTreeSet::new();
+            for (file_name, module) in &files {
+                let Some(content) = current_content(changes, file_name) else {
+                    continue;
+                };
+                let edition = edition_of(options, file_name);
+                let Some((proposed_content, names)) =
+                    prune_imports(&content, module, paths, options.mode, edition)
+                else {
+                    continue;
+                };
+
+                update_content(changes, file_name, Some(proposed_content));
+                reexported.extend(names);
+            }
+
+            let count = paths.len();
+            paths.extend(reexported);
+            if paths.len() == count {
+                break;
+            }
+        }
+    }
+}
+
+/// The crate root that each file of the crates (as they are on disk) belongs
+/// to, and the path of its module in that crate (see [`modules::module_paths`])
+fn file_modules(options: &CauterizeOptions) -> BTreeMap<PathBuf, (PathBuf, Vec<String>)> {
+    let declarations = modules::declarations(
+        &options.crate_roots,
+        |file| {
+            let content = std::fs::read(file).ok()?;
+            parse_file(&content, edition_of(options, file)).ok()
+        },
+        |file| file.exists(),
+    );
+
+    modules::module_paths(&options.crate_roots, &declarations)
+        .into_iter()
+        .map(|(file, path)| (relative_path(&file), path))
+        .collect()
+}
+
+/// The importable items that a change removes, by the path of their inline
+/// module within the file and their name; an item counts as removed from the
+/// modules in which it is defined before the change, but not after it
+fn removed_paths(change: &Change, edition: Edition) -> Vec<(Vec<String>, String)> {
+    let Ok(original) = parse_file(&change.original_content, edition) else {
+        return Vec::new();
+    };
+    let proposed = change
+        .proposed_content
+        .as_ref()
+        .and_then(|content| parse_file(content, edition).ok());
+    let remaining = proposed
+        .as_ref()
+        .map(|ast| inline_modules(&ast.items))
+        .unwrap_or_default();
+    let defines = |items: &[syn::Item], name: &ItemName| {
+        items
+            .iter()
+            .any(|item| item_name(item).as_ref() == Some(name))
+    };
+
+    let mut paths = Vec::new();
+    for name in change
+        .removed_items
+        .iter()
+        .filter(|(kind, _)| kind.is_importable())
+    {
+        for (inline_path, items) in inline_modules(&original.items) {
+            let kept = remaining
+                .iter()
+                .any(|(path, items)| *path == inline_path && defines(items, name));
+            if defines(items, name) && !kept {
+                paths.push((inline_path, name.1.clone()));
+            }
+        }
+    }
+
+    paths
+}
+
+/// Removes the empty trait impls (see [`is_marker_impl`]) of the types that
+/// are removed, from the other files of their crate; the impls in the file of
+/// a type are removed along with it already. Only the impls whose self type
+/// resolves to a removed type are removed (see [`names_type`]), and types that
+/// are defined more than once in their file are skipped
+fn remove_marker_impls(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let declarations = modules::declarations(
+        &options.crate_roots,
+        |file| {
+            let content = current_content(changes, &relative_path(file))?;
+            parse_file(&content, edition_of(options, file)).ok()
+        },
+        |file| current_content(changes, &relative_path(file)).is_some(),
+    );
+    let module_paths: BTreeMap<_, _> = modules::module_paths(&options.crate_roots, &declarations)
+        .into_iter()
+        .map(|(file, path)| (relative_path(&file), path))
+        .collect();
+
+    // the crate root and module of each removed type, and its name
+    let mut removed = Vec::new();
+    for change in changes.values() {
+        let Some((root, file_module)) = module_paths.get(&change.file_name) else {
+            continue;
+        };
+        let edition = edition_of(options, &change.file_name);
+        let Ok(ast) = parse_file(&change.original_content, edition) else {
+            continue;
+        };
+
+        for (_, ident) in change
+            .removed_items
+            .iter()
+            .filter(|(kind, _)| kind.is_type_definition())
+        {
+            let defined_in: Vec<_> = inline_modules(&ast.items)
+                .into_iter()
+                .filter(|(_, items)| {
+                    items
+                        .iter()
+                        .any(|item| defined_type(item).is_some_and(|name| &name == ident))
+                })
+                .collect();
+            if let [(inline_path, _)] = defined_in.as_slice() {
+                let module = [file_module.as_slice(), inline_path].concat();
+                removed.push((root.clone(), module, ident.clone()));
+            }
+        }
+    }
+
+    let package_roots: BTreeSet<_> = changes
+        .values()
+        .filter(|change| {
+            change
+                .removed_items
+                .iter()
+                .any(|(kind, _)| kind.is_type_definition())
+        })
+        .filter_map(|change| package_root(&change.file_name))
+        .collect();
+    for package_root in &package_roots {
+        for file_name in rust_files(package_root, options) {
+            let file_name = relative_path(&file_name);
+            let Some((root, file_module)) = module_paths.get(&file_name) else {
+                continue;
+            };
+            let Some(content) = current_content(changes, &file_name) else {
+                continue;
+            };
+            let edition = edition_of(options, &file_name);
+            let Ok(ast) = parse_file(&content, edition) else {
+                continue;
+            };
+
+            let offsets = line_offsets(&content);
+            let mut ranges = Vec::new();
+            for (inline_path, items) in inline_modules(&ast.items) {
+                let module = [file_module.as_slice(), &inline_path].concat();
+                let blocks = items.iter().filter_map(|item| match item {
+                    syn::Item::Impl(block) if is_marker_impl(block) => Some(block),
+                    _ => None,
+                });
+
+                for block in blocks {
+                    let removed_types = removed
+                        .iter()
+                        .filter(|(type_root, ..)| type_root == root)
+                        .map(|(_, defined_in, ident)| (defined_in.as_slice(), ident.as_str()));
+                    if impl_of_removed_type(block, &file_name, &module, items, removed_types) {
+                        let span = span_with_attrs(&block.attrs, block);
+                        ranges.push(to_range(&offsets, span));
+                    }
+                }
+            }
+            if ranges.is_empty() {
+                continue;
+            }
+
+            let proposed_content = remove_chunks(&content, ranges.into_iter(), options.mode);
+            update_content(changes, &file_name, Some(proposed_content));
+        }
+    }
+}
+
+/// Whether the empty `impl` block `block`, in the module `module` of the file
+/// `file_name`, is for one of the `removed` types (given by the module they
+/// were defined in and their name); warns about the blocks for which that
+/// cannot be told
+fn impl_of_removed_type<'a>(
+    block: &syn::ItemImpl,
+    file_name: &Path,
+    module: &[String],
+    items: &[syn::Item],
+    removed: impl IntoIterator<Item = (&'a [String], &'a str)>,
+) -> bool {
+    let Some(path) = self_type_path(&block.self_ty) else {
+        return false;
+    };
+    for (defined_in, ident) in removed {
+        if self_type_ident(&block.self_ty).is_none_or(|name| name.unraw() != ident) {
+            continue;
+        }
+
+        match names_type(path, module, items, defined_in, ident) {
+            Some(true) => {
+                info!(
+                    "removing an empty `impl` block for the removed type `{ident}` from {}",
+                    file_name.display()
+                );
+                return true;
+            }
+            Some(false) => {}
+            None => warn!(
+                "`impl` block at {}:{} may be for the removed type `{ident}`, \
+                 but it was not removed, since it is unclear which type it is for",
+                file_name.display(),
+                span_with_attrs(&block.attrs, block).start().line
+            ),
+        }
+    }
+
+    false
+}
+
+/// The items of a file and of the inline modules in it, each with the path of
+/// its module within the file
+fn inline_modules(items: &[syn::Item]) -> Vec<(Vec<String>, &[syn::Item])> {
+    let mut modules = vec![(Vec::new(), items)];
+    for item in items {
+        if let syn::Item::Mod(syn::ItemMod {
+            ident,
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            for (mut path, items) in inline_modules(items) {
+                path.insert(0, ident.unraw().to_string());
+                modules.push((path, items));
+            }
+        }
+    }
+
+    modules
+}
+
+/// The name of the type an item defines, if it is a struct, enum, union or
+/// type alias that may be compiled (see [`cfg::is_possibly_active`])
+fn defined_type(item: &syn::Item) -> Option<String> {
+    let ident = match item {
+        syn::Item::Enum(obj) => &obj.ident,
+        syn::Item::Struct(obj) => &obj.ident,
+        syn::Item::Type(obj) => &obj.ident,
+        syn::Item::Union(obj) => &obj.ident,
+        _ => return None,
+    };
+
+    cfg::is_possibly_active(item_attrs(item)).then(|| ident.unraw().to_string())
+}
+
+/// Whether a path, written in the module `module` (which has the given items),
+/// names the type `ident` of the module `defined_in`, both modules given by
+/// their path from the crate root; `None` if that cannot be told. A single name
+/// refers to that type if it is defined in the same module, or otherwise if it
+/// is imported by a `use` declaration (or a glob import, if there is only one),
+/// and no other type of that name is defined in the module; a longer path is
+/// followed through `crate`, `self`, `super` and the modules of the crate
+fn names_type(
+    path: &syn::Path,
+    module: &[String],
+    items: &[syn::Item],
+    defined_in: &[String],
+    ident: &str,
+) -> Option<bool> {
+    let segments: Vec<_> = path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.unraw().to_string())
+        .collect();
+    let (name, prefix) = segments.split_last()?;
+    if name != ident || path.leading_colon.is_some() {
+        return Some(false);
+    }
+    if !prefix.is_empty() {
+        return Some(resolve_module(module, prefix).is_some_and(|resolved| resolved == defined_in));
+    }
+
+    if module == defined_in {
+        return Some(true);
+    }
+    // another type of the same name shadows the removed one
+    if items
+        .iter()
+        .any(|item| defined_type(item).is_some_and(|defined| defined == ident))
+    {
+        return Some(false);
+    }
+
+    let target = [defined_in, &[ident.to_owned()]].concat();
+    let imports = module_imports(module, items);
+    let named: Vec<_> = imports
+        .iter()
+        .filter(|(imported, _)| imported == ident)
+        .collect();
+    let globs: Vec<_> = imports
+        .iter()
+        .filter(|(imported, _)| imported == "*")
+        .collect();
+    match (named.as_slice(), globs.as_slice()) {
+        ([(_, path)], _) => Some(*path == target),
+        ([], [(_, path)]) => Some(path == defined_in),
+        ([], globs) if globs.iter().all(|(_, path)| path != defined_in) => Some(false),
+        _ => None,
+    }
+}
+
+/// The path from the crate root of the module that the given path (of module
+/// names, `crate`, `self` and `super`), written in `module`, leads to; `None`
+/// if it leaves the crate
+fn resolve_module(module: &[String], path: &[String]) -> Option<Vec<String>> {
+    let mut resolved = module.to_vec();
+    for (i, segment) in path.iter().enumerate() {
+        match segment.as_str() {
+            "crate" if i == 0 => resolved.clear(),
+            "self" if i == 0 => {}
+            "super" => {
+                resolved.pop()?;
+            }
+            "crate" | "self" => return None,
+            name => resolved.push(name.to_owned()),
+        }
+    }
+
+    Some(resolved)
+}
+
+/// The names that the `use` declarations of a module bring into scope, with
+/// the path from the crate root of what they refer to; a glob import is named
+/// `*`, and refers to the module it imports from
+fn module_imports(module: &[String], items: &[syn::Item]) -> Vec<(String, Vec<String>)> {
+    fn walk(
+        tree: &syn::UseTree,
+        prefix: &mut Vec<String>,
+        module: &[String],
+        imports: &mut Vec<(String, Vec<String>)>,
+    ) {
+        let mut import = |name: String, target: &[String]| {
+            if let Some(resolved) = resolve_module(module, target) {
+                imports.push((name, resolved));
+            }
+        };
+
+        match tree {
+            syn::UseTree::Path(path) => {
+                prefix.push(path.ident.unraw().to_string());
+                walk(&path.tree, prefix, module, imports);
+                prefix.pop();
+            }
+            syn::UseTree::Name(name) => {
+                let name = name.ident.unraw().to_string();
+                if name == "self" {
+                    if let Some(last) = prefix.last() {
+                        import(last.clone(), prefix);
+                    }
+                } else {
+                    import(name.clone(), &[prefix.as_slice(), &[name]].concat());
+                }
+            }
+            syn::UseTree::Rename(rename) => {
+                let target = [prefix.as_slice(), &[rename.ident.unraw().to_string()]].concat();
+                import(rename.rename.unraw().to_string(), &target);
+            }
+            syn::UseTree::Glob(_) => import("*".to_owned(), prefix),
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    walk(tree, prefix, module, imports);
+                }
+            }
+        }
+    }
+
+    let mut imports = Vec::new();
+    for item in items {
+        if let syn::Item::Use(declaration) = item {
+            if declaration.leading_colon.is_none() {
+                walk(&declaration.tree, &mut Vec::new(), module, &mut imports);
+            }
+        }
+    }
+
+    imports
+}
+
+/// The result of removing names from a `use` tree
+enum Pruned {
+    Keep,
+    All,
+    Partial(Vec<Range<usize>>),
+}
+
+/// Removes the imports of the given items (by the path of their module from
+/// the crate root, and their name) from the `use` declarations (including
+/// re-exports) of a file of the module `module`, removing the declaration as a
+/// whole if nothing remains; also returns the items that are no longer
+/// re-exported because of this. The path of an import is resolved from the
+/// module that it is in, so an import of another item with the same name stays
+fn prune_imports(
+    src: &[u8],
+    module: &[String],
+    removed: &BTreeSet<ItemPath>,
+    mode: Mode,
+    edition: Edition,
+) -> Option<(Vec<u8>, BTreeSet<ItemPath>)> {
+    struct Visitor<'a> {
+        src: &'a [u8],
+        removed: &'a BTreeSet<ItemPath>,
+        module: Vec<String>,
+        edition: Edition,
+        offsets: LineOffsets<'a>,
+        items: Vec<Range<usize>>,
+        parts: Vec<Range<usize>>,
+        exporting: bool,
+        reexported: BTreeSet<ItemPath>,
+    }
+
+    impl Visitor<'_> {
+        /// Whether the name `ident`, imported from the path `prefix`, is a
+        /// removed item
+        fn is_removed(&self, prefix: &[String], ident: &syn::Ident) -> bool {
+            // paths of the 2015 edition start at the crate root
+            let is_relative = prefix
+                .first()
+                .is_some_and(|first| matches!(first.as_str(), "crate" | "self" | "super"));
+            let prefix = match self.edition {
+                Edition::E2015 if !is_relative => [&["crate".to_owned()], prefix].concat(),
+                _ => prefix.to_vec(),
+            };
+            if prefix.is_empty() {
+                return false;
+            }
+
+            resolve_module(&self.module, &prefix).is_some_and(|resolved| {
+                self.removed
+                    .contains(&(resolved, ident.unraw().to_string()))
+            })
+        }
+
+        fn prune(&mut self, tree: &syn::UseTree, prefix: &mut Vec<String>) -> Pruned {
+            use syn::UseTree;
+
+            match tree {
+                UseTree::Path(path) => {
+                    prefix.push(path.ident.unraw().to_string());
+                    let pruned = self.prune(&path.tree, prefix);
+                    prefix.pop();
+                    pruned
+                }
+                UseTree::Name(name) if self.is_removed(prefix, &name.ident) => {
+                    if self.exporting {
+                        let name = name.ident.unraw().to_string();
+                        self.reexported.insert((self.module.clone(), name));
+                    }
+                    Pruned::All
+                }
+                UseTree::Rename(rename) if self.is_removed(prefix, &rename.ident) => {
+                    if self.exporting {
+                        let name = rename.rename.unraw().to_string();
+                        self.reexported.insert((self.module.clone(), name));
+                    }
+                    Pruned::All
+                }
+                UseTree::Group(group) => {
+                    let pruned: Vec<_> = group
+                        .items
+                        .iter()
+                        .map(|item| self.prune(item, prefix))
+                        .collect();
+
+                    if pruned.iter().all(|pruned| matches!(pruned, Pruned::All)) {
+                        return Pruned::All;
+                    }
+
+                    let mut ranges = Vec::new();
+                    let mut previous_end = None;
+                    for (pair, pruned) in group.items.pairs().zip(pruned) {
+                        let range = to_range(&self.offsets, pair.value().span());
+                        match (pruned, pair.punct()) {
+                            (Pruned::All, Some(punct)) => {
+                                let end = to_range(&self.offsets, punct.span()).end;
+                                ranges.extend(expand_ranges_to_include_whitespace(
+                                    self.src,
+                                    std::iter::once(range.start..end),
+                                ));
+                            }
+                            (Pruned::All, None) => {
+                                ranges.push(previous_end.unwrap_or(range.start)..range.end)
+                            }
+                            (Pruned::Partial(partial), _) => ranges.extend(partial),
+                            (Pruned::Keep, _) => {}
+                        }
+                        previous_end = Some(range.end);
+                    }
+
+                    Pruned::Partial(ranges)
+                }
+                _ => Pruned::Keep,
+            }
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Visitor<'_> {
+        fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+            if node.content.is_some() {
+                self.module.push(node.ident.unraw().to_string());
+                syn::visit::visit_item_mod(self, node);
+                self.module.pop();
+            }
+        }
+
+        fn visit_item_use(&mut self, node: &'ast syn::ItemUse) {
+            // paths with a leading `::` are of other crates
+            if node.leading_colon.is_some() {
+                return;
+            }
+            self.exporting = !matches!(node.vis, syn::Visibility::Inherited);
+
+            match self.prune(&node.tree, &mut Vec::new()) {
+                Pruned::Keep => {}
+                Pruned::All => self
+                    .items
+                    .push(to_range(&self.offsets, span_with_attrs(&node.attrs, node))),
+                Pruned::Partial(ranges) => self.parts.extend(ranges),
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition).ok()?;
+
+    let mut visitor = Visitor {
+        src,
+        removed,
+        module: module.to_vec(),
+        edition,
+        offsets: line_offsets(src),
+        items: Vec::new(),
+        parts: Vec::new(),
+        exporting: false,
+        reexported: BTreeSet::new(),
+    };
+    visitor.visit_file(&ast);
+
+    if visitor.items.is_empty() && visitor.parts.is_empty() {
+        return None;
+    }
+
+    if mode == Mode::Comment {
+        let chunks = visitor.items.into_iter().chain(visitor.parts);
+        return Some((comment_chunks(src, chunks), visitor.reexported));
+    }
+
+    let mut chunks: Vec<_> =
+        expand_ranges_to_include_whitespace(src, visitor.items.into_iter()).collect();
+    chunks.extend(visitor.parts);
+
+    Some((delete_chunks(src, &chunks), visitor.reexported))
+}
+
+/// The content of a file, taking into account the changes made so far; `None`
+/// if it is to be removed or could not be read
+fn current_content(changes: &BTreeMap<PathBuf, Change>, file_name: &Path) -> Option<Vec<u8>> {
+    match changes.get(file_name) {
+        Some(change) => change.proposed_content.clone(),
+        None => std::fs::read(file_name).ok(),
+    }
+}
+
+/// Puts the library items that only a single binary uses behind a feature
+/// named after that binary, and makes the binary require it (see
+/// [`features::single_bin_items`])
+fn gate_single_bin_items(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let parse = |file: &Path| {
+        let src = std::fs::read(file).ok()?;
+        parse_file(&src, edition_of(options, file)).ok()
+    };
+    let module_paths = file_modules(options);
+
+    for targets in &options.packages {
+        let gates = features::single_bin_items(targets, parse);
+        if gates.is_empty() {
+            continue;
+        }
+        // the gated items are public, so users of a published library that do
+        // not enable the features would no longer build
+        if targets.published && !options.force {
+            warn!(
+                "not gating items used by a single binary, since {} may be published, and its \
+                 users would lose them (set `publish = false`, or use --force to override)",
+                relative_path(&targets.manifest_path).display()
+            );
+            continue;
+        }
+
+        // without the feature, the binaries would not build anymore
+        let manifest_path = relative_path(&targets.manifest_path);
+        let bins = gates.iter().map(|gate| gate.bin.as_str()).collect();
+        let Some(manifest) = current_content(changes, &manifest_path)
+            .and_then(|manifest| String::from_utf8(manifest).ok())
+            .and_then(|manifest| features::update_manifest(&manifest, targets, &bins))
+        else {
+            warn!(
+                "not gating items used by a single binary, since {} could not be updated \
+                 (a binary may have required features already)",
+                manifest_path.display()
+            );
+            continue;
+        };
+
+        let mut items = BTreeMap::<_, Vec<ItemName>>::new();
+        for gate in &gates {
+            items
+                .entry((relative_path(&gate.file), &gate.bin))
+                .or_default()
+                .push((gate.kind.clone(), gate.ident.clone()));
+        }
+        for ((file_name, bin), items) in items {
+            let Some(content) = current_content(changes, &file_name) else {
+                continue;
+            };
+            let attribute = format!("#[cfg(feature = \"{bin}\")]");
+            let edition = edition_of(options, &file_name);
+            let module = module_paths
+                .get(&file_name)
+                .map_or(&[][..], |(_, module)| module.as_slice());
+            match gate_items(&content, module, &items, attribute.as_bytes(), edition) {
+                Ok(content) => update_content(changes, &file_name, Some(content)),
+                Err(err) => warn!("could not gate items in {}: {err}", file_name.display()),
+            }
+        }
+        update_content(changes, &manifest_path, Some(manifest.into_bytes()));
+    }
+}
+
+/// Marks the public items of the libraries that nothing in the workspace uses
+/// as `#[deprecated]` (see [`unused_pub_items`])
+fn deprecate_unused_pub_items(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let module_paths = file_modules(options);
+    for (file_name, items) in spare_doctested(unused_pub_items(options), options) {
+        let Some(content) = current_content(changes, &file_name) else {
+            continue;
+        };
+        let module = module_paths
+            .get(&file_name)
+            .map_or(&[][..], |(_, module)| module.as_slice());
+        match deprecate_items(&content, module, &items, edition_of(options, &file_name)) {
+            Ok(content) => update_content(changes, &file_name, Some(content)),
+            Err(err) => warn!(
+                "could not deprecate items in {}: {err}",
+                file_name.display()
+            ),
+        }
+    }
+}
+
+/// Strips documentation and/or (ignored) tests from every file of the crates,
+/// as these are dead weight in bundled code; the files of test modules are
+/// removed
+fn strip_files(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let files = reachable_files(changes, options);
+    for file_name in &files {
+        let Some(content) = current_content(changes, file_name) else {
+            continue;
+        };
+
+        let edition = edition_of(options, file_name);
+        let mut stripped = Ok(content.clone());
+        if options.remove_ignored_tests {
+            // without a minimum age, every ignored test goes
+            let is_old = |line| match options.ignored_for_days {
+                Some(days) => vcs::line_age_days(file_name, &content, line) >= Some(days),
+                None => true,
+            };
+            stripped = stripped.and_then(|content| strip_ignored_tests(&content, edition, is_old));
+        }
+        if options.strip_tests {
+            stripped = stripped.and_then(|content| strip_tests(&content, edition));
+        }
+        if options.strip_docs {
+            stripped = stripped.and_then(|content| strip_docs(&content, edition));
+        }
+
+        match stripped {
+            Ok(stripped) if stripped != content => {
+                update_content(changes, file_name, Some(stripped))
+            }
+            Ok(_) => {}
+            Err(err) => warn!("could not strip {}: {err}", file_name.display()),
+        }
+    }
+
+    for file_name in files.difference(&reachable_files(changes, options)) {
+        update_content(changes, file_name, None);
+    }
+}
+
+/// The files in the module trees of all crates, according to the proposed
+/// changes
+fn reachable_files(
+    changes: &BTreeMap<PathBuf, Change>,
+    options: &CauterizeOptions,
+) -> BTreeSet<PathBuf> {
+    let declarations = modules::declarations(
+        &options.crate_roots,
+        |file| {
+            let content = current_content(changes, &relative_path(file))?;
+            parse_file(&content, edition_of(options, file)).ok()
+        },
+        |file| current_content(changes, &relative_path(file)).is_some(),
+    );
+
+    options
+        .crate_roots
+        .iter()
+        .chain(declarations.iter().map(|declaration| &declaration.file))
+        .map(|file| relative_path(file))
+        .collect()
+}
+
+/// Removes the items that are only compiled for tests (such as `#[cfg(test)]`
+/// modules), as well as `#[test]` functions and `#[bench]` items
+fn strip_tests(src: &[u8], edition: Edition) -> Result<Vec<u8>, syn::Error> {
+    fn is_test(attrs: &[syn::Attribute]) -> bool {
+        cfg::is_test_only(attrs) || is_test_function(attrs)
+    }
+
+    fn test_spans(items: &[syn::Item], spans: &mut Vec<Span>) {
+        for item in items {
+            let attrs = item_attrs(item);
+            if is_test(attrs) {
+                spans.push(span_with_attrs(attrs, item));
+                continue;
+            }
+
+            match item {
+                syn::Item::Mod(syn::ItemMod {
+                    content: Some((_, items)),
+                    ..
+                }) => test_spans(items, spans),
+                syn::Item::Impl(block) => spans.extend(
+                    block
+                        .items
+                        .iter()
+                        .filter(|item| is_test(impl_item_attrs(item)))
+                        .map(|item| span_with_attrs(impl_item_attrs(item), item)),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition)?;
+    let mut spans = Vec::new();
+    test_spans(&ast.items, &mut spans);
+
+    let offsets = line_offsets(src);
+    let ranges = spans.into_iter().map(|span| to_range(&offsets, span));
+    let ranges: Vec<_> = expand_ranges_to_include_whitespace(src, ranges).collect();
+    Ok(delete_chunks(src, &ranges))
+}
+
+/// Removes the lints from `#[allow(...)]` and `#[expect(...)]` attributes that
+/// do not suppress anything once the changes are applied; these are found by
+/// checking a copy of the workspace, with the changes applied and every `allow`
+/// turned into an `expect`
+fn remove_unused_allows(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let scratch = options.target_directory.join("cargo-minify").join("allows");
+    let unfulfilled = match check_expectations(changes, options, &scratch) {
+        Ok(unfulfilled) => unfulfilled,
+        Err(err) => {
+            warn!("could not check for unused #[allow] attributes: {err}");
+            return;
+        }
+    };
+
+    let mut lints = BTreeMap::<PathBuf, BTreeSet<(usize, String)>>::new();
+    for (file_name, line, lint) in unfulfilled {
+        let file_name = relative_path(&options.workspace_root.join(file_name));
+        lints.entry(file_name).or_default().insert((line, lint));
+    }
+    for (file_name, lints) in lints {
+        let Some(content) = current_content(changes, &file_name) else {
+            continue;
+        };
+        let edition = edition_of(options, &file_name);
+        match unused_arguments(&content, &lints, lint_attribute, edition) {
+            Ok(chunks) => {
+                let proposed_content = delete_chunks(&content, &chunks);
+                update_content(changes, &file_name, Some(proposed_content));
+            }
+            Err(err) => warn!(
+                "could not remove unused lints from {}: {err}",
+                file_name.display()
+            ),
+        }
+    }
+}
+
+/// Copies the workspace into `scratch`, applies the changes there, turns
+/// `allow` attributes into `expect` attributes, and checks which of these are
+/// unfulfilled
+fn check_expectations(
+    changes: &BTreeMap<PathBuf, Change>,
+    options: &CauterizeOptions,
+    scratch: &Path,
+) -> MinifyResult<Vec<(String, usize, String)>> {
+    copy_workspace(changes.values(), options, scratch)?;
+    for file_name in reachable_files(changes, options) {
+        let (Some(content), Some(copy)) = (
+            current_content(changes, &file_name),
+            in_workspace_copy(&file_name, options, scratch),
+        ) else {
+            continue;
+        };
+        if let Ok(expecting) = allows_to_expects(&content, edition_of(options, &file_name)) {
+            std::fs::write(copy, expecting)?;
+        }
+    }
+
+    unused::unfulfilled_expectations(&scratch.join("Cargo.toml"), &options.target_directory)
+}
+
+/// Copies the workspace into `scratch` (leaving out build output and hidden
+/// directories, such as `.git`), and applies the changes to the copy; path
+/// dependencies outside of the workspace are made absolute in the copied
+/// manifests, so they still resolve
+pub fn copy_workspace<'a>(
+    changes: impl IntoIterator<Item = &'a Change>,
+    options: &CauterizeOptions,
+    scratch: &Path,
+) -> std::io::Result<()> {
+    if scratch.exists() {
+        std::fs::remove_dir_all(scratch)?;
+    }
+    copy_dir(&options.workspace_root, scratch, &options.target_directory)?;
+
+    for change in changes {
+        let Some(copy) = in_workspace_copy(&change.file_name, options, scratch) else {
+            continue;
+        };
+        match &change.proposed_content {
+            Some(content) => std::fs::write(copy, content)?,
+            None => std::fs::remove_file(copy)?,
+        }
+    }
+
+    let manifests = options
+        .packages
+        .iter()
+        .map(|targets| targets.manifest_path.clone())
+        .chain([options.workspace_root.join("Cargo.toml")]);
+    for manifest_path in manifests.filter_map(|path| path.canonicalize().ok()) {
+        let (Some(dir), Some(copy)) = (
+            manifest_path.parent(),
+            in_workspace_copy(&manifest_path, options, scratch),
+        ) else {
+            continue;
+        };
+        let Ok(mut manifest) = std::fs::read_to_string(&copy)?.parse::<Manifest>() else {
+            continue;
+        };
+        if manifest.rebase_path_dependencies(dir, &options.workspace_root) {
+            std::fs::write(copy, manifest.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The path of a file of the workspace in a copy of it
+fn in_workspace_copy(
+    file_name: &Path,
+    options: &CauterizeOptions,
+    scratch: &Path,
+) -> Option<PathBuf> {
+    let file_name = file_name.canonicalize().ok()?;
+    let relative = file_name.strip_prefix(&options.workspace_root).ok()?;
+    Some(scratch.join(relative))
+}
+
+/// Recursively copies a directory, except for hidden directories and `skip`
+fn copy_dir(from: &Path, to: &Path, skip: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for path in std::fs::read_dir(from)?.flatten().map(|entry| entry.path()) {
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        if hidden || path == skip {
+            continue;
+        }
+
+        let target = to.join(path.file_name().unwrap_or_default());
+        if path.is_dir() {
+            copy_dir(&path, &target, skip)?;
+        } else {
+            std::fs::copy(&path, &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The lints of an `#[allow(...)]` or `#[expect(...)]` attribute, if it is one
+fn lint_attribute(attr: &syn::Attribute) -> Option<Punctuated<syn::Meta, Token![,]>> {
+    let path = attr.path();
+    if !path.is_ident("allow") && !path.is_ident("expect") {
+        return None;
+    }
+
+    attr.parse_args_with(Punctuated::parse_terminated).ok()
+}
+
+/// The features of an inner `#![feature(...)]` attribute, if it is one
+fn feature_attribute(attr: &syn::Attribute) -> Option<Punctuated<syn::Meta, Token![,]>> {
+    if !matches!(attr.style, syn::AttrStyle::Inner(_)) || !attr.path().is_ident("feature") {
+        return None;
+    }
+
+    attr.parse_args_with(Punctuated::parse_terminated).ok()
+}
+
+/// Turns `#[allow(...)]` attributes into `#[expect(...)]`, unless they refer to
+/// tool lints (such as `clippy::...`), which `cargo check` does not check
+fn allows_to_expects(src: &[u8], edition: Edition) -> Result<Vec<u8>, syn::Error> {
+    struct Visitor<'a> {
+        offsets: LineOffsets<'a>,
+        replacements: Vec<(Range<usize>, Vec<u8>)>,
+    }
+
+    impl<'ast> Visit<'ast> for Visitor<'_> {
+        fn visit_attribute(&mut self, node: &'ast syn::Attribute) {
+            if !node.path().is_ident("allow") {
+                return;
+            }
+            let Some(lints) = lint_attribute(node) else {
+                return;
+            };
+            let only_rustc = lints.iter().all(|lint| match lint {
+                syn::Meta::Path(path) => path.get_ident().is_some(),
+                meta => meta.path().is_ident("reason"),
+            });
+            if only_rustc {
+                let range = to_range(&self.offsets, node.path().span());
+                self.replacements.push((range, b"expect".to_vec()));
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition)?;
+    let mut visitor = Visitor {
+        offsets: line_offsets(src),
+        replacements: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+
+    Ok(replace_chunks(src, visitor.replacements))
+}
+
+/// The chunks to delete to remove the given arguments (by line and name) from
+/// the attributes that `arguments` takes apart (such as the lints of
+/// `#[allow(...)]` attributes), and the attributes of which no argument
+/// remains; the chunks are sorted and do not overlap
+fn unused_arguments(
+    src: &[u8],
+    lints: &BTreeSet<(usize, String)>,
+    arguments: fn(&syn::Attribute) -> Option<Punctuated<syn::Meta, Token![,]>>,
+    edition: Edition,
+) -> Result<Vec<Range<usize>>, syn::Error> {
+    struct Visitor<'a> {
+        lints: &'a BTreeSet<(usize, String)>,
+        arguments: fn(&syn::Attribute) -> Option<Punctuated<syn::Meta, Token![,]>>,
+        offsets: LineOffsets<'a>,
+        attributes: Vec<Range<usize>>,
+        chunks: Vec<Range<usize>>,
+    }
+
+    impl<'ast> Visit<'ast> for Visitor<'_> {
+        fn visit_attribute(&mut self, node: &'ast syn::Attribute) {
+            let Some(lints) = (self.arguments)(node) else {
+                return;
+            };
+            let is_unused = |lint: &syn::Meta| {
+                let syn::Meta::Path(path) = lint else {
+                    return false;
+                };
+                let name = quote::ToTokens::to_token_stream(path)
+                    .to_string()
+                    .replace(' ', "");
+                self.lints.contains(&(path.span().start().line, name))
+            };
+
+            let pairs: Vec<_> = lints.pairs().collect();
+            let remaining = pairs
+                .iter()
+                .filter(|pair| !is_unused(pair.value()) && !pair.value().path().is_ident("reason"))
+                .count();
+            if remaining == 0 {
+                self.attributes.push(to_range(&self.offsets, node.span()));
+                return;
+            }
+
+            // remove a lint with the comma after it, or before it if it is last
+            for (index, pair) in pairs.iter().enumerate() {
+                if !is_unused(pair.value()) {
+                    continue;
+                }
+                let lint = to_range(&self.offsets, pair.value().span());
+                let chunk = match (pairs.get(index + 1), index.checked_sub(1)) {
+                    (Some(next), _) => {
+                        lint.start..to_range(&self.offsets, next.value().span()).start
+                    }
+                    (None, Some(previous)) => {
+                        let Some(comma) = pairs[previous].punct() else {
+                            continue;
+                        };
+                        to_range(&self.offsets, comma.span()).start..lint.end
+                    }
+                    (None, None) => lint,
+                };
+                self.chunks.push(chunk);
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition)?;
+    let mut visitor = Visitor {
+        lints,
+        arguments,
+        offsets: line_offsets(src),
+        attributes: Vec::new(),
+        chunks: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+
+    let attributes = expand_ranges_to_include_whitespace(src, visitor.attributes.into_iter());
+    Ok(coalesce_ranges(attributes.chain(visitor.chunks)))
+}
+
+/// Whether the attributes mark a test (including e.g. `#[tokio::test]`) or a
+/// benchmark
+fn is_test_function(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "test" || segment.ident == "bench")
+    })
+}
+
+/// Removes the tests marked as `#[ignore]` for which `is_old` holds, given the
+/// line of that attribute
+fn strip_ignored_tests(
+    src: &[u8],
+    edition: Edition,
+    is_old: impl Fn(usize) -> bool,
+) -> Result<Vec<u8>, syn::Error> {
+    fn ignored_spans(items: &[syn::Item], is_old: &dyn Fn(usize) -> bool, spans: &mut Vec<Span>) {
+        for item in items {
+            match item {
+                syn::Item::Fn(function) if is_test_function(&function.attrs) => {
+                    let ignore = function
+                        .attrs
+                        .iter()
+                        .find(|attr| attr.path().is_ident("ignore"));
+                    if ignore.is_some_and(|attr| is_old(attr.span().start().line)) {
+                        spans.push(span_with_attrs(&function.attrs, item));
+                    }
+                }
+                syn::Item::Mod(syn::ItemMod {
+                    content: Some((_, items)),
+                    ..
+                }) => ignored_spans(items, is_old, spans),
+                _ => {}
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition)?;
+    let mut spans = Vec::new();
+    ignored_spans(&ast.items, &is_old, &mut spans);
+
+    let offsets = line_offsets(src);
+    let ranges = spans.into_iter().map(|span| to_range(&offsets, span));
+    let ranges: Vec<_> = expand_ranges_to_include_whitespace(src, ranges).collect();
+    Ok(delete_chunks(src, &ranges))
+}
+
+/// Removes all doc comments (`///`, `//!`, `/** */` and `/*! */`) and
+/// `#[doc = "..."]` attributes; the attributes that configure the docs, such as
+/// `#[doc(hidden)]`, are kept
+fn strip_docs(src: &[u8], edition: Edition) -> Result<Vec<u8>, syn::Error> {
+    struct Visitor<'a> {
+        offsets: LineOffsets<'a>,
+        ranges: Vec<Range<usize>>,
+    }
+
+    impl<'ast> Visit<'ast> for Visitor<'_> {
+        fn visit_attribute(&mut self, node: &'ast syn::Attribute) {
+            if node.path().is_ident("doc") && matches!(node.meta, syn::Meta::NameValue(_)) {
+                self.ranges.push(to_range(&self.offsets, node.span()));
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition)?;
+    let mut visitor = Visitor {
+        offsets: line_offsets(src),
+        ranges: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+
+    let ranges: Vec<_> =
+        expand_ranges_to_include_whitespace(src, visitor.ranges.into_iter()).collect();
+    Ok(delete_chunks(src, &ranges))
+}
+
+/// Records new proposed content for a file, which may not have had any
+/// changes yet
+fn update_content(
+    changes: &mut BTreeMap<PathBuf, Change>,
+    file_name: &Path,
+    proposed_content: Option<Vec<u8>>,
+) {
+    if let Some(change) = changes.get_mut(file_name) {
+        change.proposed_content = proposed_content;
+    } else if let Ok(original_content) = std::fs::read(file_name) {
+        changes.insert(
+            file_name.to_path_buf(),
+            Change {
+                file_name: file_name.to_path_buf(),
+                original_content,
+                proposed_content,
+                removed_items: Vec::new(),
+                unread_fields: Vec::new(),
+            },
+        );
+    }
+}
+
+/// Turns structs of which every field is never read into unit structs, and
+/// rewrites the places in the package where they are constructed or
+/// destructured (these are matched by name)
+fn convert_unit_structs(
+    unread_fields: &BTreeMap<(PathBuf, String), BTreeSet<String>>,
+    changes: &mut BTreeMap<PathBuf, Change>,
+    options: &CauterizeOptions,
+) {
+    let mut converted = BTreeMap::<PathBuf, BTreeSet<String>>::new();
+
+    for ((file_name, ident), fields) in unread_fields {
+        let Some(content) = current_content(changes, file_name) else {
+            continue;
+        };
+        let edition = edition_of(options, file_name);
+        let Some(proposed_content) = to_unit_struct(&content, ident, fields, edition) else {
+            continue;
+        };
+
+        update_content(changes, file_name, Some(proposed_content));
+        if let Some(change) = changes.get_mut(file_name) {
+            let unread = fields.iter().map(|field| format!("{ident}::{field}"));
+            change
+                .unread_fields
+                .extend(unread.map(|field| (UnusedDiagnosticKind::Field, field)));
+        }
+
+        if let Some(package_root) = package_root(file_name) {
+            converted
+                .entry(package_root)
+                .or_default()
+                .insert(ident.clone());
+        }
+    }
+
+    for (package_root, idents) in converted {
+        for file_name in rust_files(&package_root, options) {
+            let file_name = relative_path(&file_name);
+            let Some(content) = current_content(changes, &file_name) else {
+                continue;
+            };
+            let edition = edition_of(options, &file_name);
+            let Some(proposed_content) = rewrite_unit_struct_uses(&content, &idents, edition)
+            else {
+                continue;
+            };
+
+            update_content(changes, &file_name, Some(proposed_content));
+        }
+    }
+}
+
+/// Turns `struct Foo { a: u8 }` or `struct Foo(u8);` into `struct Foo;`, if the
+/// given fields are all of its fields; generic structs are left alone, since
+/// their parameters would become unused
+fn to_unit_struct(
+    src: &[u8],
+    ident: &str,
+    fields: &BTreeSet<String>,
+    edition: Edition,
+) -> Option<Vec<u8>> {
+    fn find<'a>(items: &'a [syn::Item], ident: &str) -> Option<&'a syn::ItemStruct> {
+        items.iter().find_map(|item| match item {
+            syn::Item::Struct(obj) if obj.ident.unraw() == ident => Some(obj),
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => find(items, ident),
+            _ => None,
+        })
+    }
+
+    let ast = parse_file(src, edition).ok()?;
+    let item = find(&ast.items, ident)?;
+
+    let names = item
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(name) => name.unraw().to_string(),
+            None => index.to_string(),
+        })
+        .collect::<BTreeSet<_>>();
+
+    if names.is_empty()
+        || names != *fields
+        || !item.generics.params.is_empty()
+        || item.generics.where_clause.is_some()
+    {
+        return None;
+    }
+
+    let offsets = line_offsets(src);
+    let range = to_range(&offsets, item.ident.span()).end..to_range(&offsets, item.span()).end;
+
+    Some(replace_chunks(src, vec![(range, b";".to_vec())]))
+}
+
+/// Rewrites expressions and patterns such as `Foo { a: 1 }`, `Foo(1)` or
+/// `Self { .. }` (in an impl block of `Foo`) into the unit struct `Foo`
+fn rewrite_unit_struct_uses(
+    src: &[u8],
+    idents: &BTreeSet<String>,
+    edition: Edition,
+) -> Option<Vec<u8>> {
+    struct Visitor<'a> {
+        idents: &'a BTreeSet<String>,
+        offsets: LineOffsets<'a>,
+        in_impl: Vec<bool>,
+        edits: Vec<(Range<usize>, Span)>,
+    }
+
+    impl Visitor<'_> {
+        /// Records an edit if the path refers to one of the structs, returning
+        /// whether it did
+        fn rewrite(&mut self, path: &syn::Path, node: Span) -> bool {
+            let Some(last) = path.segments.last() else {
+                return false;
+            };
+            let matches = if path.is_ident("Self") {
+                self.in_impl.last().copied().unwrap_or(false)
+            } else {
+                self.idents.contains(&last.ident.unraw().to_string())
+            };
+
+            if matches {
+                self.edits
+                    .push((to_range(&self.offsets, node), path.span()));
+            }
+
+            matches
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Visitor<'_> {
+        fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+            let is_target = self_type_ident(&node.self_ty)
+                .is_some_and(|ident| self.idents.contains(&ident.unraw().to_string()));
+            self.in_impl.push(is_target);
+            syn::visit::visit_item_impl(self, node);
+            self.in_impl.pop();
+        }
+
+        fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+            if !self.rewrite(&node.path, node.span()) {
+                syn::visit::visit_expr_struct(self, node);
+            }
+        }
+
+        fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+            let rewritten = match &*node.func {
+                syn::Expr::Path(func) if func.qself.is_none() => {
+                    self.rewrite(&func.path, node.span())
+                }
+                _ => false,
+            };
+            if !rewritten {
+                syn::visit::visit_expr_call(self, node);
+            }
+        }
+
+        fn visit_pat_struct(&mut self, node: &'ast syn::PatStruct) {
+            if !self.rewrite(&node.path, node.span()) {
+                syn::visit::visit_pat_struct(self, node);
+            }
+        }
+
+        fn visit_pat_tuple_struct(&mut self, node: &'ast syn::PatTupleStruct) {
+            if !self.rewrite(&node.path, node.span()) {
+                syn::visit::visit_pat_tuple_struct(self, node);
+            }
+        }
+    }
+
+    let ast = parse_file(src, edition).ok()?;
+    let mut visitor = Visitor {
+        idents,
+        offsets: line_offsets(src),
+        in_impl: Vec::new(),
+        edits: Vec::new(),
+    };
+    visitor.visit_file(&ast);
+
+    if visitor.edits.is_empty() {
+        return None;
+    }
+
+    let edits = visitor
+        .edits
+        .into_iter()
+        .map(|(range, path)| {
+            let path = to_range(&visitor.offsets, path);
+            (range, src[path].to_vec())
+        })
+        .collect();
+
+    Some(replace_chunks(src, edits))
+}
+
+/// Makes a path relative to the current directory (if it lies within it), in
+/// line with the file names reported by cargo
+pub fn relative_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// The graph of the items of each package (see [`graph::Graph`])
+pub fn package_graphs(options: &CauterizeOptions) -> Vec<Graph> {
+    options
+        .packages
+        .iter()
+        .map(|targets| crates_graph(&package_crates(targets), options))
+        .collect()
+}
+
+/// The graph of the items of all packages together, which also has the
+/// references from one package to another
+fn workspace_graph(options: &CauterizeOptions) -> Graph {
+    let crates: Vec<_> = options.packages.iter().flat_map(package_crates).collect();
+    crates_graph(&crates, options)
+}
+
+fn crates_graph(crates: &[graph::Crate], options: &CauterizeOptions) -> Graph {
+    let parse = |file: &Path| {
+        let src = std::fs::read(file).ok()?;
+        parse_file(&src, edition_of(options, file)).ok()
+    };
+
+    let mut graph = Graph::build(crates, &options.keep_attributes, parse);
+    for node in &mut graph.nodes {
+        node.file = relative_path(&node.file);
+    }
+    graph
+}
+
+/// The crates of a package, to add to a graph
+fn package_crates(targets: &features::Targets) -> Vec<graph::Crate<'_>> {
+    let lib = targets.lib.iter().map(|(name, root)| graph::Crate {
+        name,
+        root,
+        is_lib: true,
+        has_main: false,
+    });
+    let others = targets
+        .bins
+        .iter()
+        .chain(&targets.others)
+        .map(|(name, root)| graph::Crate {
+            name,
+            root,
+            is_lib: false,
+            has_main: true,
+        });
+
+    lib.chain(others).collect()
+}
+
+/// Resolves the paths of the items to remove explicitly (see `--remove`), and
+/// adds the items that are no longer used once these are gone
+pub fn explicit_removals(
+    paths: &[String],
+    options: &CauterizeOptions,
+) -> MinifyResult<BTreeMap<PathBuf, Vec<ItemName>>> {
+    let graphs = package_graphs(options);
+    let removed =
+        find_nodes(&graphs, paths)?
+            .into_iter()
+            .zip(&graphs)
+            .map(|(mut removed, graph)| {
+                removed.extend(graph.dead_without(&removed));
+                removed
+            });
+
+    Ok(removed_items(&graphs, removed))
+}
+
+/// Resolves the paths of the only items to keep (see `--keep-only`), and finds
+/// all items that cannot be reached from these; the packages of the workspace
+/// are in one graph, since the kept items may use other packages
+pub fn keep_only(
+    paths: &[String],
+    options: &CauterizeOptions,
+) -> MinifyResult<BTreeMap<PathBuf, Vec<ItemName>>> {
+    let graphs = [workspace_graph(options)];
+    let removed = find_nodes(&graphs, paths)?
+        .into_iter()
+        .zip(&graphs)
+        .map(|(mut kept, graph)| {
+            // items that an attribute keeps stay, along with what they use
+            kept.extend((0..graph.nodes.len()).filter(|&node| graph.nodes[node].kept));
+            let reachable = graph.reachable(&kept, &BTreeSet::new());
+            (0..graph.nodes.len())
+                .filter(|node| !reachable.contains(node))
+                .collect()
+        });
+
+    Ok(removed_items(&graphs, removed))
+}
+
+/// Finds the deprecated items (see `--remove-deprecated`), deprecated in or
+/// before version `since` if given, of which no item that stays in use refers
+/// to them; the items that are no longer used once these are gone are added
+pub fn deprecated_removals(
+    since: Option<&str>,
+    options: &CauterizeOptions,
+) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    let graphs = package_graphs(options);
+    let mut files = BTreeMap::new();
+    let mut is_deprecated = |node: &graph::Node| {
+        let ast = files.entry(node.file.clone()).or_insert_with(|| {
+            let src = std::fs::read(&node.file).ok()?;
+            parse_file(&src, edition_of(options, &node.file)).ok()
+        });
+        let Some(item) = ast
+            .as_ref()
+            .and_then(|ast| find_item(&ast.items, &node.kind, &node.ident))
+        else {
+            return false;
+        };
+
+        match (deprecation(item.attrs()), since) {
+            (Some(_), None) => true,
+            (Some(Some(deprecated)), Some(since)) => version_at_most(&deprecated, since),
+            _ => false,
+        }
+    };
+
+    let removed: Vec<_> = graphs
+        .iter()
+        .map(|graph| {
+            let deprecated: BTreeSet<_> = (0..graph.nodes.len())
+                .filter(|&node| is_deprecated(&graph.nodes[node]))
+                .collect();
+            let mut removed = graph.with_members(&deprecated);
+
+            // keeping an item that is in use can keep other deprecated items in
+            // use, so repeat until nothing changes anymore
+            let live = graph.live();
+            loop {
+                let dead = graph.dead_without(&removed);
+                let still_used: Vec<_> = removed
+                    .iter()
+                    .map(|&node| {
+                        let users: Vec<_> = live
+                            .iter()
+                            .filter(|user| !removed.contains(user) && !dead.contains(user))
+                            .filter(|&&user| graph.edges[user].contains(&node))
+                            .map(|&user| graph.nodes[user].path.as_str())
+                            .collect();
+                        (node, users)
+                    })
+                    .filter(|(_, users)| !users.is_empty())
+                    .collect();
+                if still_used.is_empty() {
+                    removed.extend(dead);
+                    break removed;
+                }
+
+                for (node, users) in still_used {
+                    removed.remove(&node);
+                    if deprecated.contains(&node) {
+                        warn!(
+                            "not removing the deprecated `{}`, since it is still used by {}",
+                            graph.nodes[node].path,
+                            users.join(", ")
+                        );
+                    }
+                }
+            }
+        })
+        .collect();
+
+    removed_items(&graphs, removed)
+}
+
+/// Whether the attributes mark an item as `#[deprecated]`, and if so, the
+/// version since which it is (if given)
+fn deprecation(attrs: &[syn::Attribute]) -> Option<Option<String>> {
+    let attr = attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("deprecated"))?;
+    let syn::Meta::List(list) = &attr.meta else {
+        return Some(None);
+    };
+
+    let mut since = None;
+    let _ = list.parse_nested_meta(|meta| {
+        if meta.path.is_ident("since") {
+            since = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+        } else if meta.input.peek(syn::Token![=]) {
+            meta.value()?.parse::<syn::Expr>()?;
+        }
+        Ok(())
+    });
+    Some(since)
+}
+
+/// Compares versions such as `1.2.0` by their numeric components; any other
+/// suffix (such as `-beta`) is ignored
+fn version_at_most(version: &str, max: &str) -> bool {
+    let components = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|component| {
+                let digits = component.trim_start_matches('v');
+                let end = digits
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(digits.len());
+                digits[..end].parse().unwrap_or(0)
+            })
+            .collect()
+    };
+    let (mut version, mut max) = (components(version), components(max));
+    let len = version.len().max(max.len());
+    version.resize(len, 0);
+    max.resize(len, 0);
+
+    version <= max
+}
+
+/// Finds the items of the vendored crates (see `--vendor-dir`) that are not
+/// used by the workspace, directly or through other vendored crates. Public
+/// items of vendored crates are not in use by definition, except for those that
+/// are `#[doc(hidden)]`, or in such a module (like the `__private` module of
+/// serde): these are typically used by the code that the macros of the crate
+/// expand to
+pub fn vendored_removals(options: &CauterizeOptions) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    let parse = |file: &Path| {
+        let src = std::fs::read(file).ok()?;
+        parse_file(&src, edition_of(options, file)).ok()
+    };
+
+    let mut crates: Vec<_> = options.packages.iter().flat_map(package_crates).collect();
+    crates.extend(options.vendored.iter().map(|vendored| graph::Crate {
+        name: &vendored.name,
+        root: &vendored.root,
+        is_lib: false,
+        has_main: false,
+    }));
+    let mut graph = Graph::build(&crates, &options.keep_attributes, parse);
+
+    let is_vendored = |node: &graph::Node| {
+        options
+            .vendored
+            .iter()
+            .any(|vendored| node.file.starts_with(&vendored.dir))
+    };
+    let vendored: BTreeSet<_> = (0..graph.nodes.len())
+        .filter(|&node| is_vendored(&graph.nodes[node]))
+        .collect();
+    let roots = (0..graph.nodes.len())
+        .filter(|&node| {
+            let node = &graph.nodes[node];
+            node.root || (is_vendored(node) && node.hidden)
+        })
+        .collect();
+    let used = graph.reachable(&roots, &BTreeSet::new());
+    let removed = vendored.difference(&used).copied().collect();
+
+    for node in &mut graph.nodes {
+        node.file = relative_path(&node.file);
+    }
+    removed_items(&[graph], [removed])
+}
+
+/// Finds the public items of the libraries in the workspace that no other code
+/// in the workspace uses (see `--mode deprecate-pub`); items that are
+/// `#[doc(hidden)]` (or in such a module) are left out, since these are
+/// typically used by the code that the macros of the library expand to
+pub fn unused_pub_items(options: &CauterizeOptions) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    let parse = |file: &Path| {
+        let src = std::fs::read(file).ok()?;
+        parse_file(&src, edition_of(options, file)).ok()
+    };
+
+    let crates: Vec<_> = options.packages.iter().flat_map(package_crates).collect();
+    let exported = Graph::build(&crates, &options.keep_attributes, parse);
+    // within the workspace, the public items of the libraries are not used by
+    // definition
+    let internal: Vec<_> = crates
+        .iter()
+        .map(|krate| graph::Crate {
+            is_lib: false,
+            ..*krate
+        })
+        .collect();
+    let mut graph = Graph::build(&internal, &options.keep_attributes, parse);
+
+    let used = graph.live();
+    let unused = (0..graph.nodes.len())
+        .filter(|&node| {
+            exported.nodes[node].root && !graph.nodes[node].root && !used.contains(&node)
+        })
+        .filter(|&node| !graph.nodes[node].hidden)
+        .collect();
+
+    for node in &mut graph.nodes {
+        node.file = relative_path(&node.file);
+    }
+    removed_items(&[graph], [unused])
+}
+
+/// The items affected by removing some others (see `cargo minify what-if`)
+pub struct WhatIf {
+    /// The items that would no longer be used
+    pub dead: Vec<graph::Node>,
+    /// The items that would remain, but refer to the removed ones
+    pub users: Vec<graph::Node>,
+}
+
+/// Finds which items would be affected by removing the items with the given
+/// paths, without changing anything
+pub fn what_if(paths: &[String], options: &CauterizeOptions) -> MinifyResult<WhatIf> {
+    let graphs = package_graphs(options);
+    let mut what_if = WhatIf {
+        dead: Vec::new(),
+        users: Vec::new(),
+    };
+
+    for (removed, graph) in find_nodes(&graphs, paths)?.into_iter().zip(&graphs) {
+        let removed = graph.with_members(&removed);
+        let dead = graph.dead_without(&removed);
+        let users = (0..graph.nodes.len()).filter(|node| {
+            !removed.contains(node)
+                && !dead.contains(node)
+                && !graph.edges[*node].is_disjoint(&removed)
+        });
+
+        what_if
+            .dead
+            .extend(dead.iter().map(|&node| graph.nodes[node].clone()));
+        what_if
+            .users
+            .extend(users.map(|node| graph.nodes[node].clone()));
+    }
+
+    Ok(what_if)
+}
+
+/// The nodes of each graph with the given paths; each path has to refer to an
+/// item in at least one of the graphs
+fn find_nodes(graphs: &[Graph], paths: &[String]) -> MinifyResult<Vec<BTreeSet<usize>>> {
+    let mut found = vec![BTreeSet::new(); graphs.len()];
+    for path in paths {
+        let mut any = false;
+        for (graph, found) in graphs.iter().zip(&mut found) {
+            let nodes = graph.find(path);
+            any |= !nodes.is_empty();
+            found.extend(nodes);
+        }
+        if !any {
+            return Err(Error::UnknownItem(path.clone()));
+        }
+    }
+
+    Ok(found)
+}
+
+/// The items to remove from each file for the given nodes of each graph
+fn removed_items(
+    graphs: &[Graph],
+    removed: impl IntoIterator<Item = BTreeSet<usize>>,
+) -> BTreeMap<PathBuf, Vec<ItemName>> {
+    let mut items = BTreeMap::<PathBuf, Vec<ItemName>>::new();
+    for (graph, removed) in graphs.iter().zip(removed) {
+        for (file_name, item) in removed_nodes(graph, &removed) {
+            let items = items.entry(file_name).or_default();
+            if !items.contains(&item) {
+                items.push(item);
+            }
+        }
+    }
+
+    items
+}
+
+/// The items to remove for the given nodes of a graph; associated functions of
+/// types that are removed go together with the type
+fn removed_nodes(graph: &Graph, removed: &BTreeSet<usize>) -> Vec<(PathBuf, ItemName)> {
+    let removed_types: BTreeSet<_> = removed
+        .iter()
+        .filter(|&&node| graph.nodes[node].kind.is_type_definition())
+        .map(|&node| graph.nodes[node].path.as_str())
+        .collect();
+
+    removed
+        .iter()
+        .filter(|&&node| {
+            graph.nodes[node].kind != UnusedDiagnosticKind::AssociatedFunction
+                || !removed_types.contains(graph.owner(node))
+        })
+        .map(|&node| {
+            let node = &graph.nodes[node];
+            (node.file.clone(), (node.kind.clone(), node.ident.clone()))
+        })
+        .collect()
+}
+
+/// Finds the exported macros that are not invoked anywhere in the workspace
+/// (see [`macros::unused_exported_macros`])
+fn unused_exported_macros(options: &CauterizeOptions) -> Vec<(PathBuf, String)> {
+    let files: Vec<_> = options
+        .editions
+        .keys()
+        .flat_map(|package_root| rust_files(package_root, options))
+        .filter_map(|file_name| {
+            let src = std::fs::read(&file_name).ok()?;
+            let ast = parse_file(&src, edition_of(options, &file_name)).ok()?;
+            Some((relative_path(&file_name), ast))
+        })
+        .collect();
+
+    macros::unused_exported_macros(&files)
+}
+
+/// Impl blocks for a removed type are only removed if they live in the same
+/// file as the type itself; warn about the ones found elsewhere in the package
+fn report_foreign_impls(
+    diagnostics: &multimap::MultiMap<PathBuf, UnusedDiagnostic>,
+    options: &CauterizeOptions,
+) {
+    let mut removed_types = BTreeMap::<PathBuf, BTreeSet<(&Path, &str)>>::new();
+    for (file_name, diagnostics) in diagnostics.iter_all() {
+        let Some(package_root) = package_root(file_name) else {
+            continue;
+        };
+
+        removed_types.entry(package_root).or_default().extend(
+            diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.kind.is_type_definition())
+                .map(|diagnostic| (file_name.as_path(), diagnostic.ident.as_str())),
+        );
+    }
+
+    for (package_root, types) in removed_types.iter().filter(|(_, types)| !types.is_empty()) {
+        for file_name in rust_files(package_root, options) {
+            let Some(ast) = std::fs::read(&file_name)
+                .ok()
+                .and_then(|src| parse_file(&src, edition_of(options, &file_name)).ok())
+            else {
+                continue;
+            };
+
+            for (type_file, ident) in types {
+                if same_file(type_file, &file_name) {
+                    continue;
+                }
+
+                for block in find_impls(&ast.items, ident) {
+                    // these are removed later on
+                    if options.remove_marker_impls && is_marker_impl(block) {
+                        continue;
+                    }
+
+                    warn!(
+                        "`impl` block at {}:{} is probably for the removed type `{}`, \
+                         but it was not removed",
+                        file_name.display(),
+                        span_with_attrs(&block.attrs, block).start().line,
+                        ident
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Why a file should not be modified, if that is the case: files outside of
+/// the workspace, generated files (or files that version control ignores) and
+/// included files are left alone, unless explicitly allowed; build output is
+/// never modified
+fn skip_reason(
+    file_name: &Path,
+    options: &CauterizeOptions,
+    includes: &[Include],
+) -> Option<String> {
+    let include = includes.iter().find(|include| include.refers_to(file_name));
+    let included_by = |include: &Include| relative_path(&include.includer).display().to_string();
+
+    let canonical = file_name.canonicalize();
+    if canonical
+        .as_ref()
+        .is_ok_and(|path| path.starts_with(&options.target_directory))
+    {
+        return Some(match include {
+            Some(include) => format!(
+                "is generated by a build script (included by {})",
+                included_by(include)
+            ),
+            None => "is generated by a build script".to_owned(),
+        });
+    }
+
+    let in_workspace = canonical
+        .as_ref()
+        .is_ok_and(|path| path.starts_with(&options.workspace_root));
+    if !(in_workspace || options.allow_external) {
+        return Some("lies outside of the workspace (use --allow-external to override)".to_owned());
+    }
+
+    if let Some(include) = include.filter(|_| !options.allow_included) {
+        return Some(format!(
+            "is included by {} (use --allow-included to override)",
+            included_by(include)
+        ));
+    }
+
+    let generated = std::fs::read(file_name).is_ok_and(|src| is_generated(&src));
+    if generated && !options.include_generated {
+        return Some("is marked as @generated (use --include-generated to override)".to_owned());
+    }
+
+    if canonical.is_ok_and(|path| options.ignored.contains(&path)) {
+        return Some(
+            "is ignored by version control (use --include-ignored to override)".to_owned(),
+        );
+    }
+
+    if dead_code_level_of(options, file_name) == Some(Level::Allow) && !options.force {
+        return Some(
+            "is in a package that allows dead_code in its manifest (use --force to override)"
+                .to_owned(),
+        );
+    }
+
+    None
+}
+
+/// Whether a file lies within the files and directories that the run is
+/// limited to; files outside of them are left alone without further notice
+fn in_scope(file_name: &Path, options: &CauterizeOptions) -> bool {
+    options.scope.is_empty()
+        || file_name
+            .canonicalize()
+            .is_ok_and(|path| options.scope.iter().any(|scope| path.starts_with(scope)))
+}
+
+/// Finds the files that are included with `include!` in the crates
+fn crate_includes(options: &CauterizeOptions) -> Vec<Include> {
+    let files: Vec<_> = reachable_files(&BTreeMap::new(), options)
+        .into_iter()
+        .filter_map(|file| {
+            let src = std::fs::read(&file).ok()?;
+            let ast = parse_file(&src, edition_of(options, &file)).ok()?;
+            Some((file, ast))
+        })
+        .collect();
+
+    includes::includes(&files)
+}
+
+/// Generated files are marked as such with `@generated` in their header
+fn is_generated(src: &[u8]) -> bool {
+    src.split(|&c| c == NEWLINE).take(5).any(|line| {
+        line.windows(b"@generated".len())
+            .any(|w| w == b"@generated")
+    })
+}
+
+/// Finds the root directory of the package a file belongs to
+fn package_root(file_name: &Path) -> Option<PathBuf> {
+    let file_name = file_name.canonicalize().ok()?;
+
+    file_name
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Recursively lists all Rust source files in a directory, skipping build
+/// output (also if `CARGO_TARGET_DIR` moved it) and hidden directories
+fn rust_files(dir: &Path, options: &CauterizeOptions) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.') || name == "target");
+
+        if hidden
+            || path
+                .canonicalize()
+                .is_ok_and(|path| path == options.target_directory)
+        {
+            continue;
+        } else if path.is_dir() {
+            files.extend(rust_files(&path, options));
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+pub fn same_file(left: &Path, right: &Path) -> bool {
+    match (left.canonicalize(), right.canonicalize()) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+/// Schedules files that no longer contain any items for removal, together with
+/// the `mod` declaration that refers to them (following the module tree, see
+/// [`modules::declarations`]); since this can leave the parent
+/// module empty as well, this repeats until nothing changes anymore
+fn remove_empty_modules(changes: &mut BTreeMap<PathBuf, Change>, options: &CauterizeOptions) {
+    let declarations = modules::declarations(
+        &options.crate_roots,
+        |file| {
+            let content = current_content(changes, &relative_path(file))?;
+            parse_file(&content, edition_of(options, file)).ok()
+        },
+        |file| current_content(changes, &relative_path(file)).is_some(),
+    );
+
+    let mut pending: Vec<PathBuf> = changes.keys().cloned().collect();
+
+    while let Some(file_name) = pending.pop() {
+        let edition = edition_of(options, &file_name);
+        let is_emptied = changes
+            .get(&file_name)
+            .and_then(|change| change.proposed_content.as_deref())
+            .is_some_and(|content| is_empty_module(content, edition));
+        if !is_emptied {
+            continue;
+        }
+
+        let Some(declaration) = declarations
+            .iter()
+            .find(|declaration| same_file(&declaration.file, &file_name))
+        else {
+            continue;
+        };
+
+        let parent = relative_path(&declaration.parent);
+        let Some(content) = current_content(changes, &parent) else {
+            continue;
+        };
+        let Some(range) = find_mod_declaration(
+            &content,
+            &declaration.inline_path,
+            &declaration.name,
+            edition_of(options, &parent),
+        ) else {
+            continue;
+        };
+
+        let chunks: Vec<_> =
+            expand_ranges_to_include_whitespace(&content, std::iter::once(range)).collect();
+        let proposed_content = delete_chunks(&content, &chunks);
+
+        update_content(changes, &file_name, None);
+        update_content(changes, &parent, Some(proposed_content));
+
+        pending.push(parent);
+    }
+}
+
+/// A module is considered empty if it parses, but does not define anything
+fn is_empty_module(src: &[u8], edition: Edition) -> bool {
+    parse_file(src, edition).is_ok_and(|ast| ast.items.is_empty())
+}
+
+/// Finds the location of an out-of-line `mod name;` declaration, nested in the
+/// given inline modules
+fn find_mod_declaration(
+    src: &[u8],
+    inline_path: &[String],
+    name: &str,
+    edition: Edition,
+) -> Option<Range<usize>> {
+    fn find<'a>(
+        items: &'a [syn::Item],
+        inline_path: &[String],
+        name: &str,
+    ) -> Option<&'a syn::Item> {
+        items.iter().find_map(|item| match (item, inline_path) {
+            (syn::Item::Mod(module), [])
+                if module.content.is_none() && module.ident.unraw() == name =>
+            {
+                Some(item)
+            }
+            (syn::Item::Mod(module), [inline, rest @ ..]) if module.ident.unraw() == inline => {
+                let (_, items) = module.content.as_ref()?;
+                find(items, rest, name)
+            }
+            _ => None,
+        })
+    }
+
+    let ast = parse_file(src, edition).ok()?;
+    let item = find(&ast.items, inline_path, name)?;
+
+    Some(to_range(
+        &line_offsets(src),
+        span_with_attrs(item_attrs(item), item),
+    ))
+}
+
+/// Parses a source file; a byte order mark or shebang line at its start is
+/// blanked out first (keeping all columns where they are), since these are not
+/// valid tokens
+pub fn parse_file(src: &[u8], edition: Edition) -> Result<File, syn::Error> {
+    // a lossy conversion would shift the offsets of everything after an
+    // invalid sequence, so such files are not touched at all
+    let Ok(s) = std::str::from_utf8(src) else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "file is not valid UTF-8",
+        ));
+    };
+    let mut s = s.to_owned();
+
+    if s.starts_with('\u{feff}') {
+        s.replace_range(..'\u{feff}'.len_utf8(), " ");
+    }
+
+    let start = s.len() - s.trim_start_matches(' ').len();
+    let is_shebang = s[start..].starts_with("#!") && !s[start + 2..].trim_start().starts_with('[');
+    if is_shebang {
+        let end = s[start..].find('\n').map_or(s.len(), |pos| start + pos);
+        let blank = " ".repeat(s[start..end].chars().count());
+        s.replace_range(start..end, &blank);
+    }
+
+    if edition == Edition::E2015 {
+        let tokens: TokenStream = s
+            .parse()
+            .map_err(|err| syn::Error::new(Span::call_site(), err))?;
+        let mut ast: File = syn::parse2(raw_2015_identifiers(tokens))?;
+        block_macros::flatten(&mut ast.items);
+        return Ok(ast);
+    }
+
+    let mut ast: File = syn::parse_str(&s)?;
+    block_macros::flatten(&mut ast.items);
+    Ok(ast)
+}
+
+/// In the 2015 edition, `async`, `await` and `try` are ordinary identifiers,
+/// which syn would not accept; these are turned into raw identifiers (keeping
+/// their spans)
+fn raw_2015_identifiers(tokens: TokenStream) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Ident(ident)
+                if matches!(ident.to_string().as_str(), "async" | "await" | "try") =>
+            {
+                TokenTree::Ident(Ident::new_raw(&ident.to_string(), ident.span()))
+            }
+            TokenTree::Group(group) => {
+                let mut raw = Group::new(group.delimiter(), raw_2015_identifiers(group.stream()));
+                raw.set_span(group.span());
+                TokenTree::Group(raw)
+            }
+            tree => tree,
+        })
+        .collect()
+}
+
+/// The edition of the package a file belongs to; files outside of the known
+/// packages are assumed to use a recent edition
+fn edition_of(options: &CauterizeOptions, file_name: &Path) -> Edition {
+    package_root(file_name)
+        .and_then(|root| options.editions.get(&root).copied())
+        .unwrap_or(Edition::E2021)
+}
+
+/// The minimum supported Rust version (`package.rust-version`) of the package
+/// a file belongs to, if it declares one
+fn rust_version_of<'a>(options: &'a CauterizeOptions, file_name: &Path) -> Option<&'a Version> {
+    package_root(file_name).and_then(|root| options.rust_versions.get(&root))
+}
+
+/// The level of the `dead_code` lint that the package a file belongs to
+/// configures in its manifest, if any
+fn dead_code_level_of(options: &CauterizeOptions, file_name: &Path) -> Option<Level> {
+    package_root(file_name).and_then(|root| options.dead_code_levels.get(&root).copied())
+}
+
+/// Whether the package a file belongs to denies (or forbids) unused code, so
+/// that its unused items fail the build
+pub fn denies_dead_code(options: &CauterizeOptions, file_name: &Path) -> bool {
+    dead_code_level_of(options, file_name) >= Some(Level::Deny)
+}
+
+/// A table of byte locations of newline symbols, together with the source
+/// itself, to translate LineColumn's into exact offsets
+struct LineOffsets<'a> {
+    src: &'a [u8],
+    starts: Vec<usize>,
+}
+
+/// Create a table of byte locations of newline symbols,
+/// to translate LineColumn's into exact offsets
+fn line_offsets(bytes: &[u8]) -> LineOffsets<'_> {
+    let mut offsets: Vec<usize> = bytes
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, b)| match b {
+            // a `\r` before it is simply counted as the last column of a line
+            b'\n' => Some(pos + 1),
+            _ => None,
+        })
+        .collect();
+    // First line has no offset
+    offsets.insert(0, 0);
+
+    LineOffsets {
+        src: bytes,
+        starts: offsets,
+    }
+}
+
+fn to_range(offsets: &LineOffsets, span: Span) -> Range<usize> {
+    // columns count characters rather than bytes, so walk the characters of
+    // the line to find the byte offset
+    let byte_offset = |pos: proc_macro2::LineColumn| {
+        let start = offsets.starts[pos.line - 1];
+        let rest = &offsets.src[start..];
+        let column = rest
+            .iter()
+            .enumerate()
+            .filter(|&(_, &b)| !is_utf8_continuation(b))
+            .map(|(i, _)| i)
+            .nth(pos.column)
+            .unwrap_or(rest.len());
+
+        start + column
+    };
+
+    byte_offset(span.start())..byte_offset(span.end())
+}
+
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+/// Removes the impl and extern blocks that are empty. An extern block with
+/// attributes, such as `#[link(name = "foo")]`, is only removed if it still had
+/// foreign items in `before`, since a block may exist just to link a library;
+/// returns the libraries that the removed blocks linked
+fn remove_empty_blocks(
+    before: &[u8],
+    bytes: &[u8],
+    mode: Mode,
+    edition: Edition,
+) -> Result<(Vec<u8>, Vec<String>), syn::Error> {
+    let ast = parse_file(bytes, edition)?;
+
+    let cumulative_lengths = line_offsets(bytes);
+
+    // removing items leaves the extern blocks themselves in place, so they can
+    // be paired up in order
+    let emptied = {
+        let extern_blocks = |items: &[syn::Item]| -> Vec<bool> {
+            items
+                .iter()
+                .filter_map(|item| match item {
+                    syn::Item::ForeignMod(block) => Some(block.items.is_empty()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let before = parse_file(before, edition).map(|ast| extern_blocks(&ast.items));
+        let after = extern_blocks(&ast.items);
+        match before {
+            Ok(before) if before.len() == after.len() => before
+                .iter()
+                .zip(after)
+                .map(|(&was, is)| !was && is)
+                .collect(),
+            _ => vec![false; after.len()],
+        }
+    };
+
+    let mut libraries = Vec::new();
+    let mut extern_blocks = emptied.into_iter();
+    let spans: Vec<_> = ast
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::ForeignMod(block) => {
+                let emptied = extern_blocks.next().unwrap_or(false);
+                if emptied {
+                    libraries.extend(block.attrs.iter().filter_map(linked_library));
+                }
+                (block.items.is_empty() && (block.attrs.is_empty() || emptied))
+                    .then(|| span_with_attrs(&block.attrs, block))
+            }
+            syn::Item::Impl(block) => {
+                (block.items.is_empty() && block.attrs.is_empty() && block.trait_.is_none())
+                    .then(|| span_with_attrs(&block.attrs, block))
+            }
+            _ => None,
+        })
+        .collect();
+    let ranges = spans
+        .into_iter()
+        .map(|span| to_range(&cumulative_lengths, span));
+
+    Ok((remove_chunks(bytes, ranges, mode), libraries))
+}
+
+/// The name of the library of a `#[link(name = "...")]` attribute
+fn linked_library(attr: &syn::Attribute) -> Option<String> {
+    if !attr.path().is_ident("link") {
+        return None;
+    }
+
+    let arguments = attr
+        .parse_args_with(Punctuated::<syn::Meta, Token![,]>::parse_terminated)
+        .ok()?;
+    arguments.into_iter().find_map(|meta| match meta {
+        syn::Meta::NameValue(syn::MetaNameValue {
+            path,
+            value:
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(name),
+                    ..
+                }),
+            ..
+        }) if path.is_ident("name") => Some(name.value()),
+        _ => None,
+    })
+}
+
+/// Writes (or removes) the files of the given changes; returns the result for
+/// every file, one failing does not stop the others from being written, but
+/// an interrupt does
+pub fn commit_changes(
+    changes: impl IntoIterator<Item = Change>,
+) -> Vec<(Change, std::io::Result<()>)> {
+    changes
+        .into_iter()
+        .take_while(|_| !interrupt::requested())
+        .map(|change| {
+            let result = match &change.proposed_content {
+                Some(content) => write_atomically(&change.file_name, content),
+                None => std::fs::remove_file(&change.file_name),
+            };
+            (change, result)
+        })
+        .collect()
+}
+
+/// Writes a file by way of a temporary file in the same directory, which is
+/// renamed over the original, so the file is never left half-written
+pub fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.minify-{}.tmp", std::process::id()));
+
+    let write = || {
+        let permissions = std::fs::metadata(path)?.permissions();
+        let mut temp = std::fs::File::create(&temp_path)?;
+        temp.write_all(content)?;
+        temp.set_permissions(permissions)?;
+        temp.sync_all()?;
+        std::fs::rename(&temp_path, path)
+    };
+
+    write().inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp_path);
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rust_delete(
+        src: &[u8],
+        diagnostics: impl IntoIterator<Item = (UnusedDiagnosticKind, String)>,
+    ) -> Result<Vec<u8>, syn::Error> {
+        rust_remove(src, &[], diagnostics, Mode::Delete, false, Edition::E2021)
+    }
+
+    fn fun(name: &str) -> (UnusedDiagnosticKind, String) {
+        (UnusedDiagnosticKind::Function, name.to_owned())
+    }
+
+    fn constant(name: &str) -> (UnusedDiagnosticKind, String) {
+        (UnusedDiagnosticKind::Constant, name.to_owned())
+    }
+
+    #[test]
+    fn identifier_to_span() {
+        let src = b"fn foo() {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;";
+        //          01234567890123456789012345678901234567890123456789012345678
+        //                    1         2         3         4         5
+        let pos = diagnostics_to_ranges(
+            src,
+            &[],
+            [fun("foo"), fun("foa"), constant("FOO")],
+            Edition::E2021,
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+        assert_eq!(pos, vec![0..11, 13..38, 39..59]);
     }
 
     #[allow(clippy::single_range_in_vec_init)]
     #[test]
-    fn chunk_deletion() {
-        let src = b"fn foo() {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;";
-        //          012345678901234567890123456789012345678901234567890123456
+    fn chunk_deletion() {
+        let src = b"fn foo() {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;";
+        //          012345678901234567890123456789012345678901234567890123456
+        assert_eq!(
+            delete_chunks(src, &[5..8]),
+            b"fn fo {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;"
+        );
+
+        assert_eq!(
+            delete_chunks(src, &[0..3, 1..2, 2..6, 30..59, 59..59]),
+            b"() {}  fn foa() -> i32 {"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot delete 50..60 from a file of 59 bytes")]
+    fn chunk_deletion_out_of_bounds() {
+        let src = b"fn foo() {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;";
+        delete_chunks(src, &[0..3, 50..60]);
+    }
+
+    #[test]
+    fn deletion() {
+        let src = b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;";
+        assert_eq!(
+            rust_delete(src, [fun("foo")]).unwrap(),
+            b"fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+        );
+        assert_eq!(
+            rust_delete(src, [fun("foa")]).unwrap(),
+            b"fn foo() { }const FOO: i32 = 42;"
+        );
+        assert_eq!(
+            rust_delete(src, [constant("FOO")]).unwrap(),
+            b"fn foo() { }fn foa() -> i32 { barf; }"
+        );
+    }
+
+    #[test]
+    fn type_check() {
+        let src = b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;";
+        assert_eq!(
+            rust_delete(src, [constant("foo")]).unwrap(),
+            b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+        );
+        assert_eq!(
+            rust_delete(src, [constant("foa")]).unwrap(),
+            b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+        );
+        assert_eq!(
+            rust_delete(src, [fun("FOO")]).unwrap(),
+            b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+        );
+    }
+
+    #[test]
+    fn formatting_preserval() {
+        let src = b" fn foo(){}  fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  fn bar(){ } ";
+        assert_eq!(
+            rust_delete(src, [fun("foo")]).unwrap(),
+            b" fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  fn bar(){ } "
+        );
+        assert_eq!(
+            rust_delete(src, [fun("foa")]).unwrap(),
+            b" fn foo(){}  const FOO: i32 = 42;  fn bar(){ } "
+        );
+        assert_eq!(
+            rust_delete(src, [constant("FOO")]).unwrap(),
+            b" fn foo(){}  fn foa()  -> huk {  barf; }   fn bar(){ } "
+        );
+        assert_eq!(
+            rust_delete(src, [fun("bar")]).unwrap(),
+            b" fn foo(){}  fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  "
+        );
+
+        assert_eq!(
+            rust_delete(src, [fun("foa"), fun("foo")]).unwrap(),
+            b" const FOO: i32 = 42;  fn bar(){ } "
+        );
+        assert_eq!(
+            rust_delete(src, [fun("foa"), constant("FOO")]).unwrap(),
+            b" fn foo(){}  fn bar(){ } "
+        );
+    }
+
+    #[test]
+    fn emptied_extern_blocks() {
+        let src = b"#[link(name = \"foo\", kind = \"static\")]
+extern \"C\" {
+    fn foo();
+}
+#[link(name = \"m\")]
+extern \"C\" {}
+extern \"C\" {
+    fn bar();
+}
+";
+        let removed = rust_delete(src, [fun("foo"), fun("bar")]).unwrap();
+        let (content, libraries) =
+            remove_empty_blocks(src, &removed, Mode::Delete, Edition::E2021).unwrap();
+        // the block that only links `m` was empty to begin with
+        assert_eq!(content, b"#[link(name = \"m\")]\nextern \"C\" {}\n");
+        assert_eq!(libraries, ["foo"]);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn whitespace_semi_preserval() {
+        let src = b" fn foo() {} fn fixme() {} fn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {} fn main() {}"
+        );
+        let src = b" fn foo() {} fn fixme() {}fn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {} fn main() {}"
+        );
+        let src = b" fn foo() {}fn fixme() {} fn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {}fn main() {}"
+        );
+        let src = b" fn foo() {}\nfn fixme() {}\nfn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {}\nfn main() {}"
+        );
+        let src = b" fn foo() {}\n\nfn fixme() {}\nfn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {}\n\nfn main() {}"
+        );
+        let src = b" fn foo() {}\nfn fixme() {}\n\nfn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {}\n\nfn main() {}"
+        );
+        let src = b" fn foo() {}\n\nfn fixme() {}\n\nfn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b" fn foo() {}\n\n\nfn main() {}"
+        );
+
+        let src = b"fn foo() {}\n          fn fixme() {}\n   fn main() {}";
+        assert_eq!(
+            rust_delete(src, [fun("fixme")]).unwrap(),
+            b"fn foo() {}\n   fn main() {}"
+        );
+    }
+
+    #[test]
+    fn empty_module() {
+        assert!(is_empty_module(b"", Edition::E2021));
+        assert!(is_empty_module(
+            b"//! Nothing to see here\n",
+            Edition::E2021
+        ));
+        assert!(!is_empty_module(b"fn foo() {}", Edition::E2021));
+        assert!(!is_empty_module(b"use std::io;", Edition::E2021));
+        assert!(!is_empty_module(b"fn foo( {}", Edition::E2021));
+    }
+
+    #[test]
+    fn mod_declaration() {
+        let src = b"mod foo;\nmod bar { }\n#[path = \"x.rs\"]\nmod baz;\npub mod bar;\nmod qux { mod quux; }";
+        let find = |inline_path: &[&str], name| {
+            let inline_path: Vec<_> = inline_path.iter().map(|name| name.to_string()).collect();
+            find_mod_declaration(src, &inline_path, name, Edition::E2021)
+        };
+        assert_eq!(find(&[], "foo"), Some(0..8));
+        assert_eq!(find(&[], "bar"), Some(47..59));
+        assert_eq!(find(&[], "baz"), Some(21..46));
+        assert_eq!(find(&[], "quux"), None);
+        assert_eq!(find(&["qux"], "quux"), Some(70..79));
+    }
+
+    #[test]
+    fn attribute_removal() {
+        let src =
+            b"fn foo() {}\n/// Docs\n#[derive(Debug)]\n#[allow(unused)]\nstruct Foo;\nfn bar() {}";
+        assert_eq!(
+            rust_delete(src, [(UnusedDiagnosticKind::Struct, "Foo".to_owned())]).unwrap(),
+            b"fn foo() {}\nfn bar() {}"
+        );
+
+        let src = b"impl Foo {\n    /// Docs\n    #[inline]\n    fn new() {}\n    fn bar() {}\n}";
+        assert_eq!(
+            rust_delete(
+                src,
+                [(UnusedDiagnosticKind::AssociatedFunction, "new".to_owned())]
+            )
+            .unwrap(),
+            b"impl Foo {\n    fn bar() {}\n}"
+        );
+
+        let src =
+            b"mod inner {\n    #[doc = \"Docs\"]\n    #[cfg(unix)]\n    const FOO: u8 = 0;\n}";
+        assert_eq!(
+            rust_delete(src, [constant("FOO")]).unwrap(),
+            b"mod inner {\n}"
+        );
+    }
+
+    #[test]
+    fn impl_removal() {
+        let src = b"struct Foo<T>(T);\nimpl<T> Foo<T> {\n    fn new() {}\n}\nimpl Display for &Foo<u8> {}\nimpl Bar {}\nmod inner {\n    impl super::Foo<u16> {}\n}\n";
+        assert_eq!(
+            rust_delete(src, [(UnusedDiagnosticKind::Struct, "Foo".to_owned())]).unwrap(),
+            b"impl Bar {}\nmod inner {\n}\n"
+        );
+
+        let src = b"impl Foo {}\nfn bar() {}";
+        assert_eq!(
+            rust_delete(src, [(UnusedDiagnosticKind::Enum, "Foo".to_owned())]).unwrap(),
+            b"impl Foo {}\nfn bar() {}"
+        );
+
+        // impls of other types of the same name stay
+        let src = b"mod other;\nstruct Foo;\nmod inner {\n    impl crate::other::Foo {}\n    impl crate::Foo {}\n    impl super::Foo {}\n    impl Foo {}\n}\n";
+        let removed = [(UnusedDiagnosticKind::Struct, "Foo".to_owned())];
+        assert_eq!(
+            rust_delete(src, removed.clone()).unwrap(),
+            b"mod other;\nmod inner {\n    impl crate::other::Foo {}\n    impl Foo {}\n}\n"
+        );
+        // paths from the crate root depend on the module of the file
+        let src = b"struct Foo;\nimpl crate::other::Foo {}\nimpl crate::Foo {}\n";
+        assert_eq!(
+            rust_remove(
+                src,
+                &["other".to_owned()],
+                removed,
+                Mode::Delete,
+                false,
+                Edition::E2021
+            )
+            .unwrap(),
+            b"impl crate::Foo {}\n"
+        );
+    }
+
+    #[test]
+    fn whole_impl_removal() {
+        let assoc = |name: &str| (UnusedDiagnosticKind::AssociatedItem, name.to_owned());
+
+        let src = b"fn foo() {}\n#[cfg(unix)]\nimpl Foo {\n    fn a() {}\n    const B: u8 = 0;\n}\nfn bar() {}";
+        let ranges = diagnostics_to_ranges(src, &[], [assoc("a"), assoc("B")], Edition::E2021)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![12..72]);
+        assert_eq!(
+            rust_delete(src, [assoc("a"), assoc("B")]).unwrap(),
+            b"fn foo() {}\nfn bar() {}"
+        );
+
+        assert_eq!(
+            rust_delete(src, [assoc("B")]).unwrap(),
+            b"fn foo() {}\n#[cfg(unix)]\nimpl Foo {\n    fn a() {}\n}\nfn bar() {}"
+        );
+    }
+
+    #[test]
+    fn unit_struct() {
+        let fields = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        let src = b"#[derive(Debug)]\nstruct Foo {\n    a: u8,\n    b: u8,\n}\nstruct Bar(u8, u16);\nstruct Baz<T>(T);";
+        assert_eq!(
+            to_unit_struct(src, "Foo", &fields(&["a", "b"]), Edition::E2021).unwrap(),
+            b"#[derive(Debug)]\nstruct Foo;\nstruct Bar(u8, u16);\nstruct Baz<T>(T);"
+        );
+        assert_eq!(
+            to_unit_struct(src, "Bar", &fields(&["0", "1"]), Edition::E2021).unwrap(),
+            b"#[derive(Debug)]\nstruct Foo {\n    a: u8,\n    b: u8,\n}\nstruct Bar;\nstruct Baz<T>(T);"
+        );
+        assert!(to_unit_struct(src, "Foo", &fields(&["a"]), Edition::E2021).is_none());
+        assert!(to_unit_struct(src, "Baz", &fields(&["0"]), Edition::E2021).is_none());
+    }
+
+    #[test]
+    fn unit_struct_uses() {
+        let idents = ["Foo".to_owned(), "Bar".to_owned()].into();
+
+        let src = b"impl Foo { fn new() -> Self { Self { a: 1 } } }\nfn f(Bar(x): Bar) { let _ = (crate::Foo { a: Bar(2).0 }, Baz(3)); }";
+        assert_eq!(
+            rewrite_unit_struct_uses(src, &idents, Edition::E2021).unwrap(),
+            b"impl Foo { fn new() -> Self { Self } }\nfn f(Bar: Bar) { let _ = (crate::Foo, Baz(3)); }"
+        );
+        assert!(
+            rewrite_unit_struct_uses(b"fn f() { Baz { a: 1 }; }", &idents, Edition::E2021)
+                .is_none()
+        );
+    }
+
+    /// Items to remove, by module path and name
+    fn removed(items: &[(&[&str], &str)]) -> BTreeSet<ItemPath> {
+        items
+            .iter()
+            .map(|(module, ident)| {
+                let module = module.iter().map(|name| name.to_string()).collect();
+                (module, ident.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn import_pruning() {
+        let paths = removed(&[
+            (&["utils"], "helper"),
+            (&["inner"], "Dead"),
+            (&["b"], "Dead"),
+        ]);
+        let prune = |src: &[u8]| prune_imports(src, &[], &paths, Mode::Delete, Edition::E2021);
+
+        let src = b"use crate::utils::helper;\nuse std::fmt::Debug;\nuse self::inner::{Dead, Alive};\nmod inner;\n";
+        assert_eq!(
+            prune(src).unwrap().0,
+            b"use std::fmt::Debug;\nuse self::inner::{Alive};\nmod inner;\n"
+        );
+
+        let src = b"use crate::{\n    utils::{helper, x},\n    b::Dead,\n    c,\n};\n";
+        assert_eq!(
+            prune(src).unwrap().0,
+            b"use crate::{\n    utils::{x},\n    c,\n};\n"
+        );
+
+        let src = b"use crate::{utils::helper as h, b::Dead};\nfn foo() {}";
+        assert_eq!(prune(src).unwrap().0, b"fn foo() {}");
+
+        let src = b"use other::helper;\nuse crate::*;\nuse crate::a::helper;\nuse ::utils::helper;";
+        assert!(prune(src).is_none());
+
+        // paths are resolved from the module of the import
+        let src = b"mod inner {\n    use super::utils::helper;\n    use crate::inner::Dead;\n}\n";
+        assert_eq!(prune(src).unwrap().0, b"mod inner {\n}\n");
+        let src = b"use super::helper;\nuse crate::b::Dead;\n";
+        assert_eq!(
+            prune_imports(
+                src,
+                &["utils".to_owned(), "x".to_owned()],
+                &paths,
+                Mode::Delete,
+                Edition::E2021
+            )
+            .unwrap()
+            .0,
+            b""
+        );
+
+        // paths of the 2015 edition start at the crate root
+        let src = b"use utils::helper;\n";
+        assert!(
+            prune_imports(src, &["b".to_owned()], &paths, Mode::Delete, Edition::E2021).is_none()
+        );
+        assert_eq!(
+            prune_imports(src, &["b".to_owned()], &paths, Mode::Delete, Edition::E2015)
+                .unwrap()
+                .0,
+            b""
+        );
+    }
+
+    #[test]
+    fn reexport_pruning() {
+        let paths = removed(&[(&["inner"], "helper"), (&["inner"], "Dead")]);
+
+        let src = b"pub use inner::{helper, Alive};\npub(crate) use self::inner::Dead as Other;\nmod inner;\n";
+        let (proposed, reexported) =
+            prune_imports(src, &[], &paths, Mode::Delete, Edition::E2021).unwrap();
+        assert_eq!(proposed, b"pub use inner::{Alive};\nmod inner;\n");
+        assert_eq!(reexported, removed(&[(&[], "helper"), (&[], "Other")]));
+    }
+
+    #[test]
+    fn stubs() {
+        let src = b"pub fn foo(x: u8) -> u8 {\n    helper(x)\n}\nfn helper(x: u8) -> u8 { x }\nimpl Foo {\n    pub(crate) fn new() -> Self { Foo }\n}\nstruct Bar;";
+        let (stubbed, remaining) = stub_functions(
+            src,
+            vec![
+                fun("foo"),
+                fun("helper"),
+                (UnusedDiagnosticKind::AssociatedFunction, "new".to_owned()),
+                (UnusedDiagnosticKind::Struct, "Bar".to_owned()),
+            ],
+            b"todo!()",
+            Edition::E2021,
+        )
+        .unwrap();
+
+        assert_eq!(
+            stubbed,
+            b"pub fn foo(x: u8) -> u8 {\n    todo!()\n}\nfn helper(x: u8) -> u8 { x }\nimpl Foo {\n    pub(crate) fn new() -> Self { todo!() }\n}\nstruct Bar;"
+        );
+        assert_eq!(remaining, vec![fun("helper")]);
+
+        let (stubbed, _) =
+            stub_functions(src, vec![fun("foo")], b"unimplemented!()", Edition::E2021).unwrap();
+        assert!(stubbed.starts_with(b"pub fn foo(x: u8) -> u8 {\n    unimplemented!()\n}\n"));
+    }
+
+    #[test]
+    fn nested_ranges() {
+        assert_eq!(
+            coalesce_ranges([5..9, 0..4, 1..3, 3..6, 12..14, 12..14]),
+            [0..9, 12..14]
+        );
+
+        let src = b"struct Foo;\nimpl Foo {\n    fn a() {}\n}\nimpl Foo { fn b() {} } fn c() {}\n";
+        let items = vec![
+            (UnusedDiagnosticKind::Struct, "Foo".to_owned()),
+            (UnusedDiagnosticKind::AssociatedFunction, "a".to_owned()),
+            (UnusedDiagnosticKind::AssociatedFunction, "b".to_owned()),
+            (UnusedDiagnosticKind::AssociatedFunction, "b".to_owned()),
+        ];
+        assert_eq!(
+            rust_remove(src, &[], items.clone(), Mode::Delete, false, Edition::E2021).unwrap(),
+            b"fn c() {}\n"
+        );
+        assert_eq!(
+            rust_remove(src, &[], items.clone(), Mode::Comment, false, Edition::E2021).unwrap(),
+            b"// struct Foo;\n// impl Foo {\n//     fn a() {}\n// }\n/* impl Foo { fn b() {} } */ fn c() {}\n"
+        );
+        assert_eq!(
+            rust_remove(src, &[], items, Mode::Delete, true, Edition::E2021).unwrap(),
+            b"// cargo-minify: removed unused struct Foo\n// cargo-minify: removed unused fn a\n/* cargo-minify: removed unused fn b */ fn c() {}\n"
+        );
+    }
+
+    #[test]
+    fn block_macros() {
+        let src = b"cfg_if::cfg_if! {
+    if #[cfg(feature = \"fast\")] {
+        fn imp() {}
+    } else {
+        fn imp() {}
+        fn unused() {}
+    }
+}
+";
+        assert_eq!(
+            std::str::from_utf8(
+                &rust_remove(
+                    src,
+                    &[],
+                    [fun("unused")],
+                    Mode::Delete,
+                    false,
+                    Edition::E2021
+                )
+                .unwrap()
+            )
+            .unwrap(),
+            "cfg_if::cfg_if! {
+    if #[cfg(feature = \"fast\")] {
+        fn imp() {}
+    } else {
+        fn imp() {}
+    }
+}
+"
+        );
+        // which `imp` is meant depends on the configuration
+        assert_eq!(
+            rust_remove(src, &[], [fun("imp")], Mode::Delete, false, Edition::E2021).unwrap(),
+            src
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let src = b"fn foo() {\n    bar();\n\n    baz();\n}\nconst X: u8 = 1; const Y: u8 = 2;\n";
+        assert_eq!(
+            rust_remove(src, &[], vec![fun("foo"), constant("Y")], Mode::Comment, false, Edition::E2021).unwrap(),
+            b"// fn foo() {\n//     bar();\n//\n//     baz();\n// }\nconst X: u8 = 1; /* const Y: u8 = 2; */\n"
+        );
+
+        let src = b"impl Foo {\n    fn a() {}\n    fn b() {}\n}\n";
+        assert_eq!(
+            rust_remove(
+                src,
+                &[],
+                vec![(UnusedDiagnosticKind::AssociatedFunction, "a".to_owned())],
+                Mode::Comment,
+                false,
+                Edition::E2021
+            )
+            .unwrap(),
+            b"impl Foo {\n    // fn a() {}\n    fn b() {}\n}\n"
+        );
+
+        // a block comment would end at the `*/` in the string
+        let src = b"fn a() -> &'static str { \"*/\" } fn main() {}\n";
+        assert_eq!(
+            rust_remove(
+                src,
+                &[],
+                vec![fun("a")],
+                Mode::Comment,
+                false,
+                Edition::E2021
+            )
+            .unwrap(),
+            b"// fn a() -> &'static str { \"*/\" }\nfn main() {}\n"
+        );
+
+        let src = b"mod m {\n    fn main() {} fn a() {\n        /* a */\n    }\n}\n";
+        assert_eq!(
+            rust_remove(
+                src,
+                &[],
+                vec![fun("a")],
+                Mode::Comment,
+                false,
+                Edition::E2021
+            )
+            .unwrap(),
+            b"mod m {\n    fn main() {}\n    // fn a() {\n    //     /* a */\n    // }\n}\n"
+        );
+    }
+
+    #[test]
+    fn tombstones() {
+        let src = b"const X: u8 = 1;\n\n/// Docs\nfn foo() {\n    bar();\n}\n\nfn bar() {} fn baz() {}\nstruct Foo;\nimpl Foo {}\n";
+        assert_eq!(
+            rust_remove(src, &[], vec![fun("foo"), fun("bar"), (UnusedDiagnosticKind::Struct, "Foo".to_owned())], Mode::Delete, true, Edition::E2021).unwrap(),
+            b"const X: u8 = 1;\n\n// cargo-minify: removed unused fn foo\n\n/* cargo-minify: removed unused fn bar */ fn baz() {}\n// cargo-minify: removed unused struct Foo\n"
+        );
+
+        let src = b"impl Foo {\n    fn a() {}\n    const B: u8 = 0;\n}\n";
+        let assoc = |name: &str| (UnusedDiagnosticKind::AssociatedItem, name.to_owned());
+        assert_eq!(
+            rust_remove(
+                src,
+                &[],
+                vec![assoc("a"), assoc("B")],
+                Mode::Delete,
+                true,
+                Edition::E2021
+            )
+            .unwrap(),
+            b"// cargo-minify: removed unused fn a, const B\n"
+        );
+    }
+
+    #[test]
+    fn blank_lines() {
+        let original = b"fn a() {}\n\nfn b() {}\n\nfn c() {}\n\n\n\nfn d() {}\n";
+        let proposed = b"fn a() {}\n\n\nfn c() {}\n\n\n\nfn d() {}\n";
+        assert_eq!(
+            collapse_blank_lines(original, proposed, 1),
+            b"fn a() {}\n\nfn c() {}\n\n\n\nfn d() {}\n"
+        );
+        assert_eq!(collapse_blank_lines(original, proposed, 2), proposed);
+
+        let proposed = b"fn a() {}\n\nfn b() {}\n\n\n\n";
+        assert_eq!(
+            collapse_blank_lines(original, proposed, 0),
+            b"fn a() {}\n\nfn b() {}\n"
+        );
+    }
+
+    #[test]
+    fn crlf() {
+        let src = b"fn foo() {}\r\n\r\nfn bar() {\r\n    baz();\r\n}\r\nfn baz() {}\r\n";
         assert_eq!(
-            delete_chunks(src, &[5..8]),
-            b"fn fo {}  fn foa() -> i32 { barf; } const FOO: i32 = 42;"
+            rust_delete(src, [fun("bar")]).unwrap(),
+            b"fn foo() {}\r\n\r\nfn baz() {}\r\n"
+        );
+        assert_eq!(
+            rust_remove(src, &[], [fun("bar")], Mode::Comment, false, Edition::E2021).unwrap(),
+            b"fn foo() {}\r\n\r\n// fn bar() {\r\n//     baz();\r\n// }\r\nfn baz() {}\r\n"
+        );
+
+        let src = b"pub fn bar() {\r\n    baz();\r\n}\r\n";
+        assert_eq!(
+            stub_functions(src, vec![fun("bar")], b"todo!()", Edition::E2021)
+                .unwrap()
+                .0,
+            b"pub fn bar() {\r\n    todo!()\r\n}\r\n"
         );
     }
 
     #[test]
-    fn deletion() {
-        let src = b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;";
+    fn multibyte() {
+        let src =
+            "/// Größe 😀\nconst S: &str = \"ünïcödé\"; fn foo() {}\nfn bar() {}\n".as_bytes();
         assert_eq!(
             rust_delete(src, [fun("foo")]).unwrap(),
-            b"fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+            "/// Größe 😀\nconst S: &str = \"ünïcödé\"; fn bar() {}\n".as_bytes()
         );
         assert_eq!(
-            rust_delete(src, [fun("foa")]).unwrap(),
-            b"fn foo() { }const FOO: i32 = 42;"
+            rust_delete(src, [constant("S")]).unwrap(),
+            "fn foo() {}\nfn bar() {}\n".as_bytes()
         );
+    }
+
+    #[test]
+    fn prologues() {
+        let src = "\u{feff}fn foo() {}\nfn bar() {}\n".as_bytes();
         assert_eq!(
-            rust_delete(src, [constant("FOO")]).unwrap(),
-            b"fn foo() { }fn foa() -> i32 { barf; }"
+            rust_delete(src, [fun("foo")]).unwrap(),
+            "\u{feff}fn bar() {}\n".as_bytes()
+        );
+
+        let src = b"#!/usr/bin/env cargo\n#![allow(unused)]\n\nfn foo() {}\nfn bar() {}\n";
+        assert_eq!(
+            rust_delete(src, [fun("foo")]).unwrap(),
+            b"#!/usr/bin/env cargo\n#![allow(unused)]\n\nfn bar() {}\n"
+        );
+
+        let src = b"#![allow(unused)]\nfn foo() {}\n";
+        assert_eq!(
+            rust_delete(src, [fun("foo")]).unwrap(),
+            b"#![allow(unused)]\n"
         );
     }
 
     #[test]
-    fn type_check() {
-        let src = b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;";
+    fn invalid_utf8() {
+        let src = b"// \xff\nfn foo() {}\n";
+        assert!(rust_delete(src, [fun("foo")]).is_err());
+        let paths = removed(&[(&[], "foo")]);
+        assert!(prune_imports(src, &[], &paths, Mode::Delete, Edition::E2021).is_none());
+    }
+
+    #[test]
+    fn raw_identifiers() {
+        let src = b"fn r#type() {}\nstruct r#async;\nimpl r#async {}\nfn bar() {}\n";
         assert_eq!(
-            rust_delete(src, [constant("foo")]).unwrap(),
-            b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+            rust_delete(
+                src,
+                [
+                    fun("type"),
+                    (UnusedDiagnosticKind::Struct, "async".to_owned())
+                ]
+            )
+            .unwrap(),
+            b"fn bar() {}\n"
         );
+
+        let paths = removed(&[(&[], "type")]);
+        let src = b"use crate::{r#type, bar};\n";
         assert_eq!(
-            rust_delete(src, [constant("foa")]).unwrap(),
-            b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+            prune_imports(src, &[], &paths, Mode::Delete, Edition::E2021)
+                .unwrap()
+                .0,
+            b"use crate::{bar};\n"
         );
+    }
+
+    #[test]
+    fn editions() {
+        let src = b"fn async() {}\nfn try(x: u8) -> u8 { x }\nfn foo() { async(); }\n";
+        assert!(rust_remove(
+            src,
+            &[],
+            [fun("async")],
+            Mode::Delete,
+            false,
+            Edition::E2021
+        )
+        .is_err());
         assert_eq!(
-            rust_delete(src, [fun("FOO")]).unwrap(),
-            b"fn foo() { }fn foa() -> i32 { barf; }const FOO: i32 = 42;"
+            rust_remove(
+                src,
+                &[],
+                [fun("async"), fun("try")],
+                Mode::Delete,
+                false,
+                Edition::E2015
+            )
+            .unwrap(),
+            b"fn foo() { async(); }\n"
         );
     }
 
     #[test]
-    fn formatting_preserval() {
-        let src = b" fn foo(){}  fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  fn bar(){ } ";
+    fn cfg_resolution() {
+        let src = b"#[cfg(unix)]\nfn foo() {}\n#[cfg(windows)]\nfn foo() {}\n";
+        let expected: &[u8] = if cfg!(unix) {
+            b"#[cfg(windows)]\nfn foo() {}\n"
+        } else {
+            b"#[cfg(unix)]\nfn foo() {}\n"
+        };
+        assert_eq!(rust_delete(src, [fun("foo")]).unwrap(), expected);
+
+        let src =
+            b"#[cfg(feature = \"a\")]\nfn foo() {}\n#[cfg(not(feature = \"a\"))]\nfn foo() {}\n";
+        assert_eq!(rust_delete(src, [fun("foo")]).unwrap(), src);
+    }
+
+    #[test]
+    fn generated() {
+        assert!(is_generated(
+            b"// This file is @generated by prost-build.\nfn foo() {}"
+        ));
+        assert!(is_generated(
+            b"//! Schema\n\n// @generated automatically by Diesel CLI.\n"
+        ));
+        assert!(!is_generated(b"fn foo() {}\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("minify-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("script.rs");
+        std::fs::write(&path, b"fn foo() {}\nfn bar() {}\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        write_atomically(&path, b"fn bar() {}\n").unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"fn bar() {}\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partial_apply() {
+        let dir = std::env::temp_dir().join(format!("minify-apply-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let written = dir.join("written.rs");
+        let removed = dir.join("removed.rs");
+        std::fs::write(&written, b"fn foo() {}\nfn bar() {}\n").unwrap();
+        std::fs::write(&removed, b"fn baz() {}\n").unwrap();
+        let change = |file_name: PathBuf, proposed_content: Option<&[u8]>| Change {
+            original_content: std::fs::read(&file_name).unwrap_or_default(),
+            file_name,
+            proposed_content: proposed_content.map(<[u8]>::to_vec),
+            removed_items: Vec::new(),
+            unread_fields: Vec::new(),
+        };
+
+        // a failure does not keep the other files from being changed
+        let results = commit_changes([
+            change(dir.join("missing/gone.rs"), Some(b"")),
+            change(written.clone(), Some(b"fn bar() {}\n")),
+            change(dir.join("missing.rs"), None),
+            change(removed.clone(), None),
+        ]);
+        let failed: Vec<_> = results
+            .iter()
+            .map(|(change, result)| (change.file_name(), result.is_err()))
+            .collect();
         assert_eq!(
-            rust_delete(src, [fun("foo")]).unwrap(),
-            b" fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  fn bar(){ } "
+            failed,
+            [
+                (dir.join("missing/gone.rs").as_path(), true),
+                (written.as_path(), false),
+                (dir.join("missing.rs").as_path(), true),
+                (removed.as_path(), false),
+            ]
         );
+        assert_eq!(std::fs::read(&written).unwrap(), b"fn bar() {}\n");
+        assert!(!removed.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn attribute_macros() {
+        let src = "#[tracing::instrument(skip_all)]
+async fn traced() {}
+
+#[async_trait::async_trait]
+impl Service for Handler {
+    async fn call(&self) {}
+}
+
+impl Handler {
+    #[tracing::instrument]
+    fn helper(&self) {}
+}
+
+#[tokio::main]
+async fn main() {}
+";
+        let ast = parse_file(src.as_bytes(), Edition::E2021).unwrap();
+        let at = |kind, ident, position| points_at_item(&ast.items, &kind, ident, position);
+
+        assert!(at(UnusedDiagnosticKind::Function, "traced", (2, 10)));
+        assert!(at(
+            UnusedDiagnosticKind::AssociatedFunction,
+            "helper",
+            (11, 8)
+        ));
+        // macros that re-span their output make the compiler point elsewhere
+        assert!(!at(UnusedDiagnosticKind::Function, "traced", (1, 1)));
+        assert!(!at(
+            UnusedDiagnosticKind::AssociatedFunction,
+            "helper",
+            (10, 5)
+        ));
+        assert!(!at(UnusedDiagnosticKind::Function, "main", (14, 1)));
+    }
+
+    #[test]
+    fn cfg_test() {
+        let src = b"fn helper() {}
+struct Fixture;
+impl Fixture {
+    fn new() -> Self { Fixture }
+}
+impl Other {
+    fn for_tests() {}
+}
+";
+        let diagnostics = [
+            fun("helper"),
+            (UnusedDiagnosticKind::Struct, "Fixture".to_owned()),
+            (UnusedDiagnosticKind::AssociatedFunction, "new".to_owned()),
+            (
+                UnusedDiagnosticKind::AssociatedFunction,
+                "for_tests".to_owned(),
+            ),
+        ];
+
         assert_eq!(
-            rust_delete(src, [fun("foa")]).unwrap(),
-            b" fn foo(){}  const FOO: i32 = 42;  fn bar(){ } "
+            std::str::from_utf8(
+                &gate_items(src, &[], &diagnostics, b"#[cfg(test)]", Edition::E2021).unwrap()
+            )
+            .unwrap(),
+            "#[cfg(test)]
+fn helper() {}
+#[cfg(test)]
+struct Fixture;
+#[cfg(test)]
+impl Fixture {
+    fn new() -> Self { Fixture }
+}
+impl Other {
+    #[cfg(test)]
+    fn for_tests() {}
+}
+"
+        );
+    }
+
+    #[test]
+    fn signatures() {
+        let src = b"/// Docs
+#[inline]
+pub fn run<T: Into<u8>>(x: T)
+    -> u8 { x.into() }
+pub const TABLE: [u8; 4] = [0; 4];
+pub struct Wrapper<T = u8>(pub T);
+";
+        let ast = parse_file(src, Edition::E2021).unwrap();
+        let offsets = line_offsets(src);
+        let signature_of = |kind, ident| {
+            let item = find_item(&ast.items, &kind, ident).unwrap();
+            signature(src, &offsets, &item)
+        };
+
+        assert_eq!(
+            signature_of(UnusedDiagnosticKind::Function, "run"),
+            "pub fn run<T: Into<u8>>(x: T) -> u8"
         );
         assert_eq!(
-            rust_delete(src, [constant("FOO")]).unwrap(),
-            b" fn foo(){}  fn foa()  -> huk {  barf; }   fn bar(){ } "
+            signature_of(UnusedDiagnosticKind::Constant, "TABLE"),
+            "pub const TABLE: [u8; 4]"
         );
         assert_eq!(
-            rust_delete(src, [fun("bar")]).unwrap(),
-            b" fn foo(){}  fn foa()  -> huk {  barf; }   const FOO: i32 = 42;  "
+            signature_of(UnusedDiagnosticKind::Struct, "Wrapper"),
+            "pub struct Wrapper<T = u8>(pub T)"
         );
+    }
+
+    #[test]
+    fn deprecations() {
+        let src = b"pub struct Config;
+impl Config {
+    pub fn new() -> Self { Config }
+}
+#[deprecated]
+pub fn old() {}
+";
+        let items = [
+            (UnusedDiagnosticKind::Struct, "Config".to_owned()),
+            fun("old"),
+        ];
 
         assert_eq!(
-            rust_delete(src, [fun("foa"), fun("foo")]).unwrap(),
-            b" const FOO: i32 = 42;  fn bar(){ } "
+            std::str::from_utf8(&deprecate_items(src, &[], &items, Edition::E2021).unwrap())
+                .unwrap(),
+            "#[deprecated(note = \"unused internally, scheduled for removal\")]
+#[allow(deprecated)]
+pub struct Config;
+#[allow(deprecated)]
+impl Config {
+    pub fn new() -> Self { Config }
+}
+#[deprecated]
+pub fn old() {}
+"
         );
+    }
+
+    #[test]
+    fn impact() {
+        let change = |file_name: &str, original: &[u8], proposed: Option<&[u8]>| Change {
+            file_name: file_name.into(),
+            original_content: original.to_vec(),
+            proposed_content: proposed.map(<[u8]>::to_vec),
+            removed_items: Vec::new(),
+            unread_fields: Vec::new(),
+        };
+        let mut changes = vec![
+            change("a.rs", b"fn a() {}\nfn b() {}\n", Some(b"fn b() {}\n")),
+            change("b.rs", b"fn a() {}\nfn b() {}\n", None),
+            change("c.rs", b"fn a() {}\n", Some(b"// fn a() {}\n")),
+        ];
+        assert_eq!(changes[1].lines_removed(), 2);
+
+        rank_by_impact(&mut changes);
+        let order: Vec<_> = changes.iter().map(|change| change.file_name()).collect();
         assert_eq!(
-            rust_delete(src, [fun("foa"), constant("FOO")]).unwrap(),
-            b" fn foo(){}  fn bar(){ } "
+            order,
+            [Path::new("b.rs"), Path::new("a.rs"), Path::new("c.rs")]
         );
     }
 
     #[test]
-    #[rustfmt::skip]
-    fn whitespace_semi_preserval() {
-        let src = b" fn foo() {} fn fixme() {} fn main() {}";
+    fn lines_of_items() {
+        let src = b"fn small() {}
+
+fn large() {
+    let x = 1;
+    println!(\"{x}\");
+}
+
+fn a() {}
+mod inner {
+    fn a() {
+    }
+}
+";
+        let ast = parse_file(src, Edition::E2021).unwrap();
+        let fun = |ident: &str| (UnusedDiagnosticKind::Function, ident.to_owned());
+        assert_eq!(item_lines(&ast, &fun("small")), 1);
+        assert_eq!(item_lines(&ast, &fun("large")), 4);
+        assert_eq!(item_lines(&ast, &fun("a")), 3);
+        assert_eq!(item_lines(&ast, &fun("missing")), 1);
+    }
+
+    #[test]
+    fn docs() {
+        let src = b"//! The crate
+#![doc(html_root_url = \"https://docs.rs/foo\")]
+
+/// A function
+///
+/// with more lines
+#[inline]
+fn documented() {}
+
+/** A block */
+#[doc = \"an attribute\"] #[doc(hidden)] struct Foo {
+    /// A field
+    field: u8,
+}
+
+impl Foo {
+    #[doc = \"a method\"]
+    fn method() {
+        // a regular comment
+        let x = \"/// not a doc comment\";
+    }
+}
+";
+
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {} fn main() {}"
+            std::str::from_utf8(&strip_docs(src, Edition::E2021).unwrap()).unwrap(),
+            "#![doc(html_root_url = \"https://docs.rs/foo\")]
+
+#[inline]
+fn documented() {}
+
+#[doc(hidden)] struct Foo {
+    field: u8,
+}
+
+impl Foo {
+    fn method() {
+        // a regular comment
+        let x = \"/// not a doc comment\";
+    }
+}
+"
         );
-        let src = b" fn foo() {} fn fixme() {}fn main() {}";
+    }
+
+    #[test]
+    fn test_items() {
+        let src = b"fn main() {}
+
+#[test]
+fn top_level() {}
+
+struct Foo;
+
+impl Foo {
+    fn new() -> Foo { Foo }
+
+    #[cfg(all(test, unix))]
+    fn fixture() -> Foo { Foo }
+}
+
+#[cfg(test)]
+mod tests;
+
+mod inner {
+    pub fn used() {}
+
+    #[tokio::test]
+    async fn runtime() {}
+
+    #[bench]
+    fn bench(b: &mut Bencher) {}
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn works() {}
+}
+";
+
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {} fn main() {}"
+            std::str::from_utf8(&strip_tests(src, Edition::E2021).unwrap()).unwrap(),
+            "fn main() {}
+
+
+struct Foo;
+
+impl Foo {
+    fn new() -> Foo { Foo }
+
+}
+
+
+mod inner {
+    pub fn used() {}
+
+
+}
+
+"
         );
-        let src = b" fn foo() {}fn fixme() {} fn main() {}";
+    }
+
+    #[test]
+    fn ignored_tests() {
+        let src = b"#[test]
+fn runs() {}
+
+#[test]
+#[ignore]
+fn old() {}
+
+#[ignore = \"flaky\"]
+#[test]
+fn recent() {}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore]
+    fn nested() {}
+}
+";
+
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {}fn main() {}"
+            std::str::from_utf8(
+                &strip_ignored_tests(src, Edition::E2021, |line| line < 8).unwrap()
+            )
+            .unwrap(),
+            "#[test]
+fn runs() {}
+
+
+#[ignore = \"flaky\"]
+#[test]
+fn recent() {}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[ignore]
+    fn nested() {}
+}
+"
         );
-        let src = b" fn foo() {}\nfn fixme() {}\nfn main() {}";
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {}\nfn main() {}"
+            std::str::from_utf8(&strip_ignored_tests(src, Edition::E2021, |_| true).unwrap())
+                .unwrap(),
+            "#[test]
+fn runs() {}
+
+
+
+#[cfg(test)]
+mod tests {
+}
+"
         );
-        let src = b" fn foo() {}\n\nfn fixme() {}\nfn main() {}";
+    }
+
+    #[test]
+    fn deprecated() {
+        let attrs = |src: &str| match syn::parse_str::<syn::Item>(src).unwrap() {
+            syn::Item::Fn(function) => deprecation(&function.attrs),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(attrs("fn f() {}"), None);
+        assert_eq!(attrs("#[deprecated] fn f() {}"), Some(None));
+        assert_eq!(attrs("#[deprecated = \"use g\"] fn f() {}"), Some(None));
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {}\n\nfn main() {}"
+            attrs("#[deprecated(note = \"use g\", since = \"1.2.0\")] fn f() {}"),
+            Some(Some("1.2.0".to_owned()))
         );
-        let src = b" fn foo() {}\nfn fixme() {}\n\nfn main() {}";
+
+        assert!(version_at_most("1.2.0", "1.2"));
+        assert!(version_at_most("0.9.1", "1.0.0"));
+        assert!(version_at_most("v1.10", "1.10.0-beta"));
+        assert!(!version_at_most("1.10.0", "1.9.3"));
+    }
+
+    #[test]
+    fn unused_allows() {
+        let src = b"#![allow(unused_imports)]
+
+#[allow(dead_code)]
+fn a() {}
+
+#[allow(clippy::all, dead_code)]
+#[expect(unused_variables, dead_code, reason = \"for now\")]
+fn b() {}
+";
+
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {}\n\nfn main() {}"
+            std::str::from_utf8(&allows_to_expects(src, Edition::E2021).unwrap()).unwrap(),
+            "#![expect(unused_imports)]
+
+#[expect(dead_code)]
+fn a() {}
+
+#[allow(clippy::all, dead_code)]
+#[expect(unused_variables, dead_code, reason = \"for now\")]
+fn b() {}
+"
         );
-        let src = b" fn foo() {}\n\nfn fixme() {}\n\nfn main() {}";
+
+        let lints = [
+            (1, "unused_imports".to_owned()),
+            (3, "dead_code".to_owned()),
+            (6, "dead_code".to_owned()),
+            (7, "unused_variables".to_owned()),
+        ]
+        .into();
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b" fn foo() {}\n\n\nfn main() {}"
+            std::str::from_utf8(&delete_chunks(
+                src,
+                &unused_arguments(src, &lints, lint_attribute, Edition::E2021).unwrap()
+            ))
+            .unwrap(),
+            "
+fn a() {}
+
+#[allow(clippy::all)]
+#[expect(dead_code, reason = \"for now\")]
+fn b() {}
+"
         );
+    }
 
-        let src = b"fn foo() {}\n          fn fixme() {}\n   fn main() {}";
+    #[test]
+    fn protected() {
+        let ast = syn::parse_file(
+            "#[used] static A: u8 = 0;
+#[link_section = \".vectors\"] static B: u8 = 0;
+#[panic_handler] fn c(_: &PanicInfo) -> ! { loop {} }
+#[cortex_m_rt::exception] fn SysTick() {}
+#[entry] fn e() -> ! { loop {} }
+#[no_mangle] static F: u8 = 0;",
+        )
+        .unwrap();
+        let protections: Vec<_> = ast
+            .items
+            .iter()
+            .map(|item| protection(item_attrs(item), &["entry".to_owned()]))
+            .collect();
         assert_eq!(
-            rust_delete(src, [fun("fixme")]).unwrap(),
-            b"fn foo() {}\n   fn main() {}"
+            protections,
+            [
+                Some(("used".to_owned(), "linker-referenced")),
+                Some(("link_section".to_owned(), "linker-referenced")),
+                Some(("panic_handler".to_owned(), "runtime-referenced")),
+                Some(("exception".to_owned(), "runtime-referenced")),
+                Some(("entry".to_owned(), "--keep-attribute")),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_features() {
+        let src = b"#![feature(never_type, let_chains)]
+#![feature(
+    try_blocks,
+)]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+#[feature(never_type)]
+fn a() {}
+";
+        let features = [
+            (1, "let_chains".to_owned()),
+            (3, "try_blocks".to_owned()),
+            (5, "doc_cfg".to_owned()),
+            (7, "never_type".to_owned()),
+        ]
+        .into();
+        let chunks = unused_arguments(src, &features, feature_attribute, Edition::E2021).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&delete_chunks(src, &chunks)).unwrap(),
+            "#![feature(never_type)]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+#[feature(never_type)]
+fn a() {}
+"
+        );
+    }
+
+    #[test]
+    fn marker_impls() {
+        let ast = syn::parse_file(
+            "unsafe impl Send for Foo {}
+impl Foo {}
+impl fmt::Display for Foo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+}
+impl Send for Bar {}
+mod error {
+    #[cfg(feature = \"std\")]
+    impl std::error::Error for super::Foo {}
+}",
+        )
+        .unwrap();
+        let lines: Vec<_> = find_impls(&ast.items, "Foo")
+            .into_iter()
+            .filter(|block| is_marker_impl(block))
+            .map(|block| span_with_attrs(&block.attrs, block).start().line)
+            .collect();
+        assert_eq!(lines, [1, 8]);
+    }
+
+    #[test]
+    fn marker_impl_types() {
+        let ast = syn::parse_file(
+            "mod a { pub struct Foo; }
+mod b { pub struct Foo; }
+mod c {
+    use super::a::Foo;
+    impl Send for Foo {}
+}
+mod d {
+    use crate::b::Foo;
+    impl Send for Foo {}
+}
+mod e {
+    impl Send for super::a::Foo {}
+    impl Sync for crate::b::Foo {}
+}
+mod f {
+    use crate::{a::*, b::*};
+    impl Send for Foo {}
+}
+mod g {
+    use crate::a::*;
+    struct Foo;
+    impl Send for Foo {}
+}",
+        )
+        .unwrap();
+
+        // `a::Foo` is removed, `b::Foo` is not
+        let defined_in = ["a".to_owned()];
+        let mut found = Vec::new();
+        for (module, items) in inline_modules(&ast.items) {
+            for item in items {
+                let syn::Item::Impl(block) = item else {
+                    continue;
+                };
+                let path = self_type_path(&block.self_ty).unwrap();
+                let names = names_type(path, &module, items, &defined_in, "Foo");
+                found.push((block.span().start().line, names));
+            }
+        }
+        assert_eq!(
+            found,
+            [
+                (5, Some(true)),
+                (9, Some(false)),
+                (12, Some(true)),
+                (13, Some(false)),
+                (17, None),
+                (22, Some(false)),
+            ]
         );
     }
 }
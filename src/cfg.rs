@@ -0,0 +1,486 @@
+//! This module evaluates `#[cfg(...)]` attributes against the configuration
+//! that `cargo check` analyzed: the target of the workspace (`build.target` in
+//! its `.cargo/config.toml`, or the host if none is set) and the default
+//! features of its packages. The configuration is set per workspace with
+//! [`configure`], for the thread that analyzes it.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use cargo_metadata::{Metadata, Package};
+use syn::{punctuated::Punctuated, Meta, Token};
+use toml_edit::DocumentMut;
+use tracing::warn;
+
+/// The configuration that `cargo check` builds a workspace for
+#[derive(Clone, Debug, Default)]
+pub struct Configuration {
+    platform: Platform,
+    /// The features of every package of the workspace
+    packages: Vec<Features>,
+}
+
+#[derive(Clone, Debug, Default)]
+enum Platform {
+    /// No target is configured, so the host is built for
+    #[default]
+    Host,
+    /// The options (`name` or `key="value"`) that `rustc --print cfg` gives
+    /// for the configured target
+    Target(BTreeSet<(String, Option<String>)>),
+    /// A target is configured, but its options could not be determined
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+struct Features {
+    /// The features (and optional dependencies) that the package declares
+    declared: BTreeSet<String>,
+    /// The features that its default features enable
+    enabled: BTreeSet<String>,
+}
+
+thread_local! {
+    // every workspace is analyzed on a single thread, and tests analyze their
+    // fixtures in parallel
+    static CONFIGURATION: RefCell<Configuration> = RefCell::new(Configuration::default());
+}
+
+/// Evaluates the attributes against the given configuration from now on, on
+/// the current thread
+pub fn configure(configuration: Configuration) {
+    CONFIGURATION.with(|current| *current.borrow_mut() = configuration);
+}
+
+impl Configuration {
+    /// The configuration of the workspace, as given by its metadata and the
+    /// cargo configuration that applies in its root
+    pub fn of(metadata: &Metadata) -> Configuration {
+        let root = metadata.workspace_root.as_std_path();
+        let platform = match build_targets(root).as_deref() {
+            None => Platform::Host,
+            Some([target]) => match target_options(root, target) {
+                Some(options) => Platform::Target(options),
+                None => {
+                    warn!(
+                        "could not determine the configuration of target {target}, \
+                         so target-specific items are left alone"
+                    );
+                    Platform::Unknown
+                }
+            },
+            // an item may be used for one of the targets only
+            Some(_) => Platform::Unknown,
+        };
+
+        Configuration {
+            platform,
+            packages: metadata
+                .workspace_packages()
+                .into_iter()
+                .map(Features::of)
+                .collect(),
+        }
+    }
+
+    /// Whether a feature is enabled; `None` if it is declared but not enabled
+    /// by default, as another package may still enable it, or if the packages
+    /// of the workspace disagree (as it is not known which one the item is in)
+    fn feature(&self, name: &str) -> Option<bool> {
+        let mut results = self.packages.iter().map(|features| {
+            if features.enabled.contains(name) {
+                Some(true)
+            } else if features.declared.contains(name) {
+                None
+            } else {
+                Some(false)
+            }
+        });
+        let first = results.next()??;
+
+        results.all(|result| result == Some(first)).then_some(first)
+    }
+
+    /// Whether the target has the given option; `None` if this is not known,
+    /// or if it is not one of the options that are evaluated (others, such as
+    /// those given by `--cfg`, do not depend on the target alone)
+    fn has_option(&self, name: &str, value: Option<&str>) -> Option<bool> {
+        let host = host_has_option(name, value)?;
+        match &self.platform {
+            Platform::Host => Some(host),
+            Platform::Target(options) => {
+                Some(options.contains(&(name.to_owned(), value.map(str::to_owned))))
+            }
+            Platform::Unknown => None,
+        }
+    }
+}
+
+impl Features {
+    fn of(package: &Package) -> Features {
+        let mut enabled = BTreeSet::new();
+        let mut pending = vec!["default"];
+        while let Some(feature) = pending.pop() {
+            let Some(values) = package.features.get(feature) else {
+                continue;
+            };
+            if !enabled.insert(feature.to_owned()) {
+                continue;
+            }
+            for value in values {
+                // `dep:name` and `name?/feature` do not enable a feature
+                // named after the dependency, `name/feature` does
+                if value.starts_with("dep:") {
+                    continue;
+                }
+                let name = value.split('/').next().unwrap_or(value);
+                if !name.ends_with('?') {
+                    pending.push(name);
+                }
+            }
+        }
+
+        let mut declared: BTreeSet<String> = package.features.keys().cloned().collect();
+        declared.extend(
+            package
+                .dependencies
+                .iter()
+                .filter(|dependency| dependency.optional)
+                .map(|dependency| dependency.rename.as_ref().unwrap_or(&dependency.name))
+                .cloned(),
+        );
+
+        Features { declared, enabled }
+    }
+}
+
+/// The targets that cargo builds for in the given directory: the one given by
+/// `CARGO_BUILD_TARGET`, or those of `build.target` in the nearest cargo
+/// configuration file that sets it; `None` if it builds for the host
+fn build_targets(dir: &Path) -> Option<Vec<String>> {
+    if let Some(target) = std::env::var_os("CARGO_BUILD_TARGET") {
+        return Some(vec![target.to_string_lossy().into_owned()]);
+    }
+
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")));
+    let dirs = dir
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(cargo_home);
+    for dir in dirs {
+        // cargo prefers `config` over `config.toml` if both exist
+        let Some(src) = [dir.join("config"), dir.join("config.toml")]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+        else {
+            continue;
+        };
+        let Ok(document) = src.parse::<DocumentMut>() else {
+            continue;
+        };
+        let Some(target) = document.get("build").and_then(|build| build.get("target")) else {
+            continue;
+        };
+        let targets: Vec<&str> = match target.as_array() {
+            Some(targets) => targets
+                .iter()
+                .filter_map(|target| target.as_str())
+                .collect(),
+            None => vec![target.as_str()?],
+        };
+        // the path of a target specification is relative to the directory
+        // that contains `.cargo`
+        return Some(
+            targets
+                .into_iter()
+                .map(|target| match dir.parent() {
+                    Some(parent) if target.ends_with(".json") => {
+                        parent.join(target).to_string_lossy().into_owned()
+                    }
+                    _ => target.to_owned(),
+                })
+                .collect(),
+        );
+    }
+
+    None
+}
+
+/// The cfg options of the target, as printed by `rustc --print cfg`; `None`
+/// if they cannot be determined
+fn target_options(dir: &Path, target: &str) -> Option<BTreeSet<(String, Option<String>)>> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    // in the workspace, so its toolchain file applies
+    let output = Command::new(rustc)
+        .args(["--print", "cfg", "--target", target])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(parse_options(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The options of the output of `rustc --print cfg`, e.g. `unix` or
+/// `target_os="linux"`
+fn parse_options(output: &str) -> BTreeSet<(String, Option<String>)> {
+    output
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((name, value)) => (name.to_owned(), Some(value.trim_matches('"').to_owned())),
+            None => (line.to_owned(), None),
+        })
+        .collect()
+}
+
+/// Whether the `#[cfg(...)]` attributes of an item could be active; `false`
+/// only if one of them is known to be inactive, predicates that cannot be
+/// evaluated (such as features that are not enabled by default) are assumed
+/// to be possibly active
+pub fn is_possibly_active(attrs: &[syn::Attribute]) -> bool {
+    CONFIGURATION.with(|configuration| {
+        let configuration = configuration.borrow();
+        attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("cfg"))
+            .filter_map(|attr| attr.parse_args::<Meta>().ok())
+            .all(|predicate| evaluate(&predicate, &configuration) != Some(false))
+    })
+}
+
+/// Whether an item is only compiled for tests, i.e. one of its `#[cfg(...)]`
+/// attributes requires `test`, such as `#[cfg(test)]` or `#[cfg(all(test, unix))]`
+pub fn is_test_only(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .filter_map(|attr| attr.parse_args::<Meta>().ok())
+        .any(|predicate| requires_test(&predicate))
+}
+
+fn requires_test(predicate: &Meta) -> bool {
+    match predicate {
+        Meta::Path(path) => path.is_ident("test"),
+        Meta::List(list) if list.path.is_ident("all") => list
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .is_ok_and(|nested| nested.iter().any(requires_test)),
+        _ => false,
+    }
+}
+
+/// Evaluates a cfg predicate; `None` if this cannot be determined
+fn evaluate(predicate: &Meta, configuration: &Configuration) -> Option<bool> {
+    match predicate {
+        Meta::Path(path) => {
+            let name = path.get_ident()?.to_string();
+            match name.as_str() {
+                "unix" | "windows" => configuration.has_option(&name, None),
+                "debug_assertions" => Some(true),
+                "doc" | "doctest" | "miri" => Some(false),
+                _ => None,
+            }
+        }
+        Meta::NameValue(pair) => {
+            let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(value),
+                ..
+            }) = &pair.value
+            else {
+                return None;
+            };
+            match pair.path.get_ident()?.to_string().as_str() {
+                "feature" => configuration.feature(&value.value()),
+                name => configuration.has_option(name, Some(&value.value())),
+            }
+        }
+        Meta::List(list) => {
+            let nested = list
+                .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .ok()?;
+            let results: Vec<_> = nested
+                .iter()
+                .map(|predicate| evaluate(predicate, configuration))
+                .collect();
+
+            match list.path.get_ident()?.to_string().as_str() {
+                "not" if results.len() == 1 => results[0].map(|result| !result),
+                "all" if results.contains(&Some(false)) => Some(false),
+                "all" => results.iter().all(Option::is_some).then_some(true),
+                "any" if results.contains(&Some(true)) => Some(true),
+                "any" => results.iter().all(Option::is_some).then_some(false),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Whether the host has the given option; `None` for the options that are not
+/// evaluated
+fn host_has_option(name: &str, value: Option<&str>) -> Option<bool> {
+    let actual = match (name, value) {
+        ("unix", None) => return Some(cfg!(unix)),
+        ("windows", None) => return Some(cfg!(windows)),
+        ("target_os", Some(_)) => std::env::consts::OS,
+        ("target_family", Some(_)) => std::env::consts::FAMILY,
+        ("target_arch", Some(_)) => std::env::consts::ARCH,
+        ("target_pointer_width", Some(_)) => pointer_width(),
+        ("target_endian", Some(_)) => endian(),
+        _ => return None,
+    };
+
+    Some(value == Some(actual))
+}
+
+fn pointer_width() -> &'static str {
+    if cfg!(target_pointer_width = "64") {
+        "64"
+    } else if cfg!(target_pointer_width = "32") {
+        "32"
+    } else {
+        "16"
+    }
+}
+
+fn endian() -> &'static str {
+    if cfg!(target_endian = "little") {
+        "little"
+    } else {
+        "big"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn is_active(configuration: Configuration, item: &str) -> bool {
+        let item: syn::ItemFn = syn::parse_str(item).unwrap();
+        configure(configuration);
+        let active = is_possibly_active(&item.attrs);
+        configure(Configuration::default());
+
+        active
+    }
+
+    #[test]
+    fn configured_target() {
+        let target = || Configuration {
+            platform: Platform::Target(parse_options(
+                "debug_assertions\npanic=\"abort\"\ntarget_arch=\"arm\"\n\
+                 target_endian=\"little\"\ntarget_os=\"none\"\ntarget_pointer_width=\"32\"\n",
+            )),
+            packages: Vec::new(),
+        };
+        assert!(is_active(
+            target(),
+            "#[cfg(target_arch = \"arm\")] fn f() {}"
+        ));
+        assert!(!is_active(
+            target(),
+            "#[cfg(not(target_arch = \"arm\"))] fn f() {}"
+        ));
+        assert!(!is_active(target(), "#[cfg(any(unix, windows))] fn f() {}"));
+        assert!(is_active(
+            target(),
+            "#[cfg(target_pointer_width = \"32\")] fn f() {}"
+        ));
+        // not evaluated, as `--cfg` may set it
+        assert!(is_active(target(), "#[cfg(panic = \"unwind\")] fn f() {}"));
+
+        let unknown = || Configuration {
+            platform: Platform::Unknown,
+            packages: Vec::new(),
+        };
+        assert!(is_active(
+            unknown(),
+            "#[cfg(target_arch = \"arm\")] fn f() {}"
+        ));
+        assert!(is_active(
+            unknown(),
+            "#[cfg(not(target_arch = \"arm\"))] fn f() {}"
+        ));
+    }
+
+    #[test]
+    fn configured_targets() {
+        if std::env::var_os("CARGO_BUILD_TARGET").is_some() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("minify-cfg-{}", std::process::id()));
+        let crate_dir = dir.join("crates/app");
+        std::fs::create_dir_all(dir.join(".cargo")).unwrap();
+        std::fs::create_dir_all(crate_dir.join(".cargo")).unwrap();
+
+        std::fs::write(
+            dir.join(".cargo/config.toml"),
+            "[build]\ntarget = \"thumbv7em-none-eabihf\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            build_targets(&crate_dir),
+            Some(vec!["thumbv7em-none-eabihf".to_owned()])
+        );
+
+        // the nearest configuration that sets it applies, with a specification
+        // relative to it
+        std::fs::write(
+            crate_dir.join(".cargo/config.toml"),
+            "[build]\ntarget = [\"board.json\", \"wasm32-unknown-unknown\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            build_targets(&crate_dir),
+            Some(vec![
+                crate_dir.join("board.json").to_string_lossy().into_owned(),
+                "wasm32-unknown-unknown".to_owned()
+            ])
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn features() {
+        let features = |declared: &[&str], enabled: &[&str]| Features {
+            declared: declared.iter().map(|name| name.to_string()).collect(),
+            enabled: enabled.iter().map(|name| name.to_string()).collect(),
+        };
+        let single = || Configuration {
+            platform: Platform::Host,
+            packages: vec![features(&["default", "std", "alloc"], &["default", "std"])],
+        };
+        assert!(is_active(single(), "#[cfg(feature = \"std\")] fn f() {}"));
+        assert!(!is_active(
+            single(),
+            "#[cfg(not(feature = \"std\"))] fn f() {}"
+        ));
+        assert!(!is_active(
+            single(),
+            "#[cfg(feature = \"serde\")] fn f() {}"
+        ));
+        // another package may enable it
+        assert!(is_active(single(), "#[cfg(feature = \"alloc\")] fn f() {}"));
+        assert!(is_active(
+            single(),
+            "#[cfg(not(feature = \"alloc\"))] fn f() {}"
+        ));
+
+        // the packages disagree, and the package of the item is not known
+        let several = Configuration {
+            platform: Platform::Host,
+            packages: vec![features(&["std"], &["std"]), features(&[], &[])],
+        };
+        assert!(is_active(
+            several,
+            "#[cfg(not(feature = \"std\"))] fn f() {}"
+        ));
+    }
+}
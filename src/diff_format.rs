@@ -1,15 +1,25 @@
-use std::str::FromStr;
+use std::{io::IsTerminal, ops::RangeInclusive, str::FromStr};
 
 use nu_ansi_term::Color;
 use thiserror::Error;
+use tracing::warn;
 
-use crate::cauterize::Change;
+use crate::cauterize::{Change, ListedItem};
 
 const BEFORE_CONTEXT: isize = 3;
 const AFTER_CONTEXT: isize = 3;
+/// The width of the tab that separates the symbol of a line of a diff from
+/// the line
+const TAB_WIDTH: usize = 8;
+/// Marks where a long line is cut off
+const ELLIPSIS: char = '…';
 
-pub fn println(change: &Change, color_mode: ColorMode) {
-    let text = format!("#\n#\tshowing diff for {:?}:\n#", change.file_name());
+pub fn println(change: &Change, color_mode: ColorMode, long_lines: LongLines) {
+    let text = if change.removes_file() {
+        format!("#\n#\tremoving {:?}:\n#", change.file_name())
+    } else {
+        format!("#\n#\tshowing diff for {:?}:\n#", change.file_name())
+    };
     if color_mode.enabled() {
         println!("{}", Color::DarkGray.paint(text));
     } else {
@@ -45,6 +55,12 @@ pub fn println(change: &Change, color_mode: ColorMode) {
         included.push(DiffLine::Ellipsis);
     }
 
+    // output that is piped elsewhere is left as it is
+    let width = match long_lines {
+        LongLines::Keep => None,
+        LongLines::Truncate | LongLines::Wrap => terminal_width(),
+    };
+
     for line in included {
         let (symbol, color, line) = match line {
             DiffLine::Diff(diff::Result::Left(line)) => ('-', Color::LightRed, line),
@@ -54,13 +70,280 @@ pub fn println(change: &Change, color_mode: ColorMode) {
             DiffLine::Ellipsis => ('#', Color::DarkGray, "..."),
         };
 
-        let format = format!("{symbol}\t{line}");
+        let pieces = match width {
+            Some(width) => fit(line, width.saturating_sub(TAB_WIDTH), long_lines),
+            None => vec![line.to_owned()],
+        };
+        for piece in pieces {
+            let format = format!("{symbol}\t{piece}");
+
+            if color_mode.enabled() {
+                println!("{}", color.paint(format));
+            } else {
+                println!("{format}");
+            }
+        }
+    }
+}
+
+/// The number of columns of the terminal that stdout is written to; `None` if
+/// it is not written to a terminal, or if its size is not known
+fn terminal_width() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+    {
+        return Some(columns);
+    }
+
+    #[cfg(unix)]
+    {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        // SAFETY: TIOCGWINSZ only writes a winsize to the given pointer
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if result == 0 && size.ws_col > 0 {
+            return Some(size.ws_col.into());
+        }
+    }
+
+    None
+}
+
+/// Fits a line in the given number of columns: cut off, or split into pieces,
+/// with an ellipsis where it does not fit; tabs count up to the next multiple
+/// of the tab width
+fn fit(line: &str, width: usize, long_lines: LongLines) -> Vec<String> {
+    let columns = |text: &str| {
+        text.chars().fold(0, |column, c| match c {
+            '\t' => (column / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => column + 1,
+        })
+    };
+    // too narrow to show anything but ellipses
+    if columns(line) <= width || width < 2 {
+        return vec![line.to_owned()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    for c in line.chars() {
+        piece.push(c);
+        if columns(&piece) >= width {
+            let c = piece.pop();
+            piece.push(ELLIPSIS);
+            pieces.push(std::mem::take(&mut piece));
+            if long_lines == LongLines::Truncate {
+                return pieces;
+            }
+            piece.extend(c);
+        }
+    }
+    pieces.push(piece);
+
+    pieces
+}
+
+/// Prints a change as a JSON object on a single line, with the items it
+/// removes (see `--message-format json`)
+pub fn println_json(change: &Change, package: Option<&str>) {
+    println!("{}", to_json(change, package));
+}
+
+/// The JSON object of a change, see [`println_json`]
+pub fn to_json(change: &Change, package: Option<&str>) -> serde_json::Value {
+    let items: Vec<_> = change
+        .severities()
+        .into_iter()
+        .map(|(severity, (kind, ident))| {
+            serde_json::json!({ "kind": kind.name(), "name": ident, "severity": severity.name() })
+        })
+        .collect();
+    serde_json::json!({
+        "reason": "change",
+        "file": change.file_name(),
+        "package": package,
+        "removes_file": change.removes_file(),
+        "lines_removed": change.lines_removed(),
+        "items": items,
+    })
+}
+
+/// Prints every hunk of a change as a GitHub suggested change, in the shape of a
+/// review comment of the GitHub API (see `--message-format github`), as JSON
+/// on a single line; removing a file cannot be suggested. The comment names
+/// the (listed) items that a hunk removes, and how unused they are
+pub fn println_suggestions(change: &Change, listed: &[ListedItem]) {
+    if change.removes_file() {
+        warn!(
+            "cannot suggest removing {}, as a suggestion keeps the file",
+            change.file_name().display()
+        );
+        return;
+    }
+
+    for comment in to_suggestions(change, listed) {
+        println!("{comment}");
+    }
+}
+
+/// The review comments of a change, see [`println_suggestions`]
+pub fn to_suggestions(change: &Change, listed: &[ListedItem]) -> Vec<serde_json::Value> {
+    let mut comments = Vec::new();
+    let left = String::from_utf8_lossy(change.original_content());
+    let right = String::from_utf8_lossy(change.proposed_content());
+    for suggestion in suggestions(&left, &right) {
+        let removes: Vec<_> = listed
+            .iter()
+            .filter(|item| item.file_name == change.file_name())
+            .filter(|item| {
+                item.line
+                    .is_some_and(|line| suggestion.lines.contains(&line))
+            })
+            .map(|item| {
+                let kind = item.kind.name().replace('_', " ");
+                format!("the {} {kind} `{}`", item.severity.name(), item.ident)
+            })
+            .collect();
+        let body = match removes.is_empty() {
+            true => suggestion.body(),
+            false => format!("Removes {}.\n\n{}", removes.join(", "), suggestion.body()),
+        };
+        let mut comment = serde_json::json!({
+            "reason": "suggestion",
+            "path": change.file_name(),
+            "line": suggestion.lines.end(),
+            "side": "RIGHT",
+            "body": body,
+        });
+        // GitHub wants a start line only for comments that span several lines
+        if suggestion.lines.start() != suggestion.lines.end() {
+            comment["start_line"] = (*suggestion.lines.start()).into();
+            comment["start_side"] = "RIGHT".into();
+        }
+        comments.push(comment);
+    }
+
+    comments
+}
+
+/// The replacement of a range of lines of the original (numbered from 1)
+#[derive(Debug, PartialEq)]
+struct Suggestion {
+    lines: RangeInclusive<usize>,
+    replacement: Vec<String>,
+}
+
+impl Suggestion {
+    /// The body of the review comment, with a fence that is longer than any
+    /// run of backticks in the replacement
+    fn body(&self) -> String {
+        let longest = self
+            .replacement
+            .iter()
+            .flat_map(|line| line.split(|c| c != '`'))
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
 
-        if color_mode.enabled() {
-            println!("{}", color.paint(format));
-        } else {
-            println!("{format}");
+        let mut body = format!("{fence}suggestion\n");
+        for line in &self.replacement {
+            body.push_str(line);
+            body.push('\n');
         }
+        body.push_str(&fence);
+        body
+    }
+}
+
+/// Splits the difference between two texts into suggestions, one for each
+/// hunk; lines that are only inserted are suggested together with the line
+/// after them (or before them, at the end of the file), since a suggestion
+/// replaces at least one line
+fn suggestions(left: &str, right: &str) -> Vec<Suggestion> {
+    // the final newline does not start a line that can be suggested on
+    let diff = diff::lines(
+        left.strip_suffix('\n').unwrap_or(left),
+        right.strip_suffix('\n').unwrap_or(right),
+    );
+
+    let mut suggestions = Vec::new();
+    let mut line = 0;
+    let mut index = 0;
+    while index < diff.len() {
+        if !has_changed(&diff[index]) {
+            line += 1;
+            index += 1;
+            continue;
+        }
+
+        let start = line + 1;
+        let mut replacement = Vec::new();
+        while index < diff.len() && has_changed(&diff[index]) {
+            match diff[index] {
+                diff::Result::Left(_) => line += 1,
+                diff::Result::Right(inserted) => replacement.push(inserted.to_owned()),
+                diff::Result::Both(_, _) => unreachable!(),
+            }
+            index += 1;
+        }
+
+        if line >= start {
+            suggestions.push(Suggestion {
+                lines: start..=line,
+                replacement,
+            });
+        } else if let Some(diff::Result::Both(after, _)) = diff.get(index) {
+            replacement.push(after.to_string());
+            line += 1;
+            index += 1;
+            suggestions.push(Suggestion {
+                lines: line..=line,
+                replacement,
+            });
+        } else if let Some(diff::Result::Both(before, _)) = index
+            .checked_sub(replacement.len() + 1)
+            .map(|before| &diff[before])
+        {
+            replacement.insert(0, before.to_string());
+            suggestions.push(Suggestion {
+                lines: line..=line,
+                replacement,
+            });
+        }
+    }
+
+    suggestions
+}
+
+/// Prints the header above the changes to the files of a package, with the
+/// totals of these changes
+pub fn println_package(
+    package: Option<&str>,
+    files: usize,
+    lines_removed: usize,
+    color_mode: ColorMode,
+) {
+    let package = match package {
+        Some(name) => format!("package `{name}`"),
+        None => "files outside of any package".to_owned(),
+    };
+    let plural = if files == 1 { "" } else { "s" };
+    let text = format!(
+        "##\n##\t{package}: {files} changed file{plural}, {lines_removed} lines removed\n##"
+    );
+    if color_mode.enabled() {
+        println!("{}", Color::Cyan.bold().paint(text));
+    } else {
+        println!("{text}")
     }
 }
 
@@ -84,6 +367,35 @@ enum DiffLine<T> {
     Ellipsis,
 }
 
+/// What to do with lines of a diff that are too long for the terminal
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LongLines {
+    /// Cut them off
+    #[default]
+    Truncate,
+    /// Continue them on the next lines
+    Wrap,
+    /// Leave them to the terminal
+    Keep,
+}
+
+impl FromStr for LongLines {
+    type Err = UnsupportedLongLines;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(LongLines::Truncate),
+            "wrap" => Ok(LongLines::Wrap),
+            "keep" => Ok(LongLines::Keep),
+            _ => Err(UnsupportedLongLines),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported handling of long lines, pick any of: truncate, wrap, keep")]
+pub struct UnsupportedLongLines;
+
 #[derive(Copy, Clone, Debug, Default)]
 pub enum ColorMode {
     #[default]
@@ -119,3 +431,96 @@ impl FromStr for ColorMode {
 #[derive(Debug, Error)]
 #[error("unsupported color mode, pick any of: auto, always, never")]
 pub struct UnsupportedPrintColor;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum MessageFormat {
+    /// Diffs, for people to read
+    #[default]
+    Human,
+    /// A JSON object per line, for other tools
+    Json,
+    /// A GitHub suggested change per hunk, as a JSON object per line, for a
+    /// review bot to post
+    Github,
+    /// A line per removed item, in the format of compiler errors, for the
+    /// quickfix list of Vim or the compilation buffer of Emacs
+    Quickfix,
+}
+
+impl FromStr for MessageFormat {
+    type Err = UnsupportedMessageFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            "github" => Ok(MessageFormat::Github),
+            "quickfix" => Ok(MessageFormat::Quickfix),
+            _ => Err(UnsupportedMessageFormat),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported message format, pick any of: human, json, github, quickfix")]
+pub struct UnsupportedMessageFormat;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn suggestion(lines: RangeInclusive<usize>, replacement: &[&str]) -> Suggestion {
+        Suggestion {
+            lines,
+            replacement: replacement.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn hunks() {
+        let left = "fn foo() {}\n\nfn bar() {\n    baz();\n}\nfn baz() {}\n";
+        let right = "fn foo() {}\n\nfn baz() {}\n";
+        assert_eq!(suggestions(left, right), [suggestion(3..=5, &[])]);
+
+        let left = "fn foo() {}\n";
+        let right = "fn foo() {}\n// cargo-minify: removed unused bar\n";
+        assert_eq!(
+            suggestions(left, right),
+            [suggestion(
+                1..=1,
+                &["fn foo() {}", "// cargo-minify: removed unused bar"]
+            )]
+        );
+
+        let left = "struct Foo;\nimpl Foo {}\n";
+        let right = "#[cfg(test)]\nstruct Foo;\n#[cfg(test)]\nimpl Foo {}\n";
+        assert_eq!(
+            suggestions(left, right),
+            [
+                suggestion(1..=1, &["#[cfg(test)]", "struct Foo;"]),
+                suggestion(2..=2, &["#[cfg(test)]", "impl Foo {}"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_lines() {
+        let line = "let x = 1234567890;";
+        assert_eq!(fit(line, 40, LongLines::Truncate), [line]);
+        assert_eq!(fit(line, 10, LongLines::Truncate), ["let x = 1…"]);
+        assert_eq!(
+            fit(line, 10, LongLines::Wrap),
+            ["let x = 1…", "234567890…", ";"]
+        );
+        assert_eq!(fit("\tx = 1;", 10, LongLines::Truncate), ["\tx…"]);
+    }
+
+    #[test]
+    fn fences() {
+        assert_eq!(suggestion(1..=1, &[]).body(), "```suggestion\n```");
+        assert_eq!(
+            suggestion(1..=1, &["/// ```", "fn foo() {}"]).body(),
+            "````suggestion\n/// ```\nfn foo() {}\n````"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! This module finds the names that the doc examples (doctests) in the
+//! workspace use. `cargo check` does not compile these, so items that only
+//! doctests use look unused; rather than running `cargo test --doc`, the code
+//! blocks of doc comments are read, and the identifiers in them collected.
+
+use std::{collections::BTreeSet, path::PathBuf, str::FromStr};
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::visit::{self, Visit};
+
+/// The names used by a single doc comment
+#[derive(Debug, PartialEq)]
+pub struct Doctest {
+    /// The item that the doc comment belongs to, if it is a named item (and
+    /// not, for instance, a module docstring)
+    pub documented: Option<String>,
+    pub names: BTreeSet<String>,
+}
+
+/// Whether a doctest uses a name, other than the examples of the item with
+/// that name itself, which go together with the item
+pub fn uses(doctests: &[Doctest], name: &str) -> bool {
+    doctests
+        .iter()
+        .any(|doctest| doctest.documented.as_deref() != Some(name) && doctest.names.contains(name))
+}
+
+/// Collects the doctests in the given files that use any names
+pub fn doctests(files: &[(PathBuf, syn::File)]) -> Vec<Doctest> {
+    let mut collector = Collector {
+        doctests: Vec::new(),
+    };
+    for (_, ast) in files {
+        collector.add(None, &ast.attrs);
+        collector.visit_file(ast);
+    }
+
+    collector.doctests
+}
+
+struct Collector {
+    doctests: Vec<Doctest>,
+}
+
+impl Collector {
+    fn add(&mut self, documented: Option<String>, attrs: &[syn::Attribute]) {
+        let docs: Vec<_> = attrs.iter().filter_map(doc_line).collect();
+        let names: BTreeSet<_> = code_blocks(&docs.join("\n"))
+            .iter()
+            .flat_map(|code| names(code))
+            .collect();
+        if !names.is_empty() {
+            self.doctests.push(Doctest { documented, names });
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector {
+    fn visit_item(&mut self, node: &'ast syn::Item) {
+        let (attrs, ident) = match node {
+            syn::Item::Const(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Enum(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Fn(item) => (&item.attrs, Some(&item.sig.ident)),
+            syn::Item::Impl(item) => (&item.attrs, None),
+            syn::Item::Macro(item) => (&item.attrs, item.ident.as_ref()),
+            syn::Item::Mod(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Static(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Struct(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Trait(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Type(item) => (&item.attrs, Some(&item.ident)),
+            syn::Item::Union(item) => (&item.attrs, Some(&item.ident)),
+            _ => return visit::visit_item(self, node),
+        };
+        self.add(ident.map(ToString::to_string), attrs);
+        visit::visit_item(self, node);
+    }
+
+    fn visit_impl_item(&mut self, node: &'ast syn::ImplItem) {
+        let (attrs, ident) = match node {
+            syn::ImplItem::Const(item) => (&item.attrs, &item.ident),
+            syn::ImplItem::Fn(item) => (&item.attrs, &item.sig.ident),
+            syn::ImplItem::Type(item) => (&item.attrs, &item.ident),
+            _ => return visit::visit_impl_item(self, node),
+        };
+        self.add(Some(ident.to_string()), attrs);
+        visit::visit_impl_item(self, node);
+    }
+
+    fn visit_trait_item(&mut self, node: &'ast syn::TraitItem) {
+        let (attrs, ident) = match node {
+            syn::TraitItem::Const(item) => (&item.attrs, &item.ident),
+            syn::TraitItem::Fn(item) => (&item.attrs, &item.sig.ident),
+            syn::TraitItem::Type(item) => (&item.attrs, &item.ident),
+            _ => return visit::visit_trait_item(self, node),
+        };
+        self.add(Some(ident.to_string()), attrs);
+        visit::visit_trait_item(self, node);
+    }
+
+    fn visit_field(&mut self, node: &'ast syn::Field) {
+        self.add(node.ident.as_ref().map(ToString::to_string), &node.attrs);
+        visit::visit_field(self, node);
+    }
+
+    fn visit_variant(&mut self, node: &'ast syn::Variant) {
+        self.add(Some(node.ident.to_string()), &node.attrs);
+        visit::visit_variant(self, node);
+    }
+}
+
+/// The text of a `///` or `//!` line (or of a `#[doc = "..."]` attribute)
+fn doc_line(attr: &syn::Attribute) -> Option<String> {
+    let syn::Meta::NameValue(meta) = &attr.meta else {
+        return None;
+    };
+    if !meta.path.is_ident("doc") {
+        return None;
+    }
+    match &meta.value {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(text),
+            ..
+        }) => Some(text.value()),
+        _ => None,
+    }
+}
+
+/// The code of the fenced code blocks in a doc comment that rustdoc compiles
+/// as Rust, with the lines it hides (starting with `#`) shown again
+fn code_blocks(doc: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut block: Option<(String, bool)> = None;
+    for line in doc.lines() {
+        let trimmed = line.trim();
+        let fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+        match &mut block {
+            None if fence => block = Some((String::new(), is_compiled(&trimmed[3..]))),
+            Some((code, compiled)) if fence => {
+                if *compiled {
+                    blocks.push(std::mem::take(code));
+                }
+                block = None;
+            }
+            Some((code, _)) => {
+                let line = match trimmed.strip_prefix('#') {
+                    Some(hidden) if !hidden.starts_with(['#', '[', '!']) => hidden,
+                    _ => line,
+                };
+                code.push_str(line);
+                code.push('\n');
+            }
+            None => {}
+        }
+    }
+
+    blocks
+}
+
+/// Whether rustdoc compiles a code block with the given info string (e.g.
+/// `rust,no_run`); blocks that are ignored or in another language are not
+fn is_compiled(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|attribute| !attribute.is_empty())
+        .all(|attribute| {
+            matches!(
+                attribute,
+                "rust" | "no_run" | "should_panic" | "test_harness"
+            ) || attribute.starts_with("edition")
+        })
+}
+
+/// The identifiers in a piece of code
+fn names(code: &str) -> BTreeSet<String> {
+    fn collect(tokens: TokenStream, found: &mut BTreeSet<String>) {
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    let ident = ident.to_string();
+                    found.insert(ident.strip_prefix("r#").unwrap_or(&ident).to_owned());
+                }
+                TokenTree::Group(group) => collect(group.stream(), found),
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = BTreeSet::new();
+    match TokenStream::from_str(code) {
+        Ok(tokens) => collect(tokens, &mut found),
+        // examples need not be complete, e.g. when their braces are hidden
+        Err(_) => found.extend(
+            code.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|word| !word.is_empty())
+                .map(str::to_owned),
+        ),
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn examples() {
+        let src = r#"
+            //! ```
+            //! let config = mycrate::Config::load();
+            //! ```
+
+            /// Makes a widget
+            ///
+            /// ```rust,no_run
+            /// # use mycrate::helper;
+            /// let widget = make_widget(helper());
+            /// ```
+            ///
+            /// ```ignore
+            /// ignored();
+            /// ```
+            ///
+            /// ```text
+            /// not_rust
+            /// ```
+            pub fn make_widget(x: u8) {}
+
+            impl Widget {
+                /// ```
+                /// Widget::spin();
+                /// ```
+                fn spin() {}
+            }
+        "#;
+        let files = [(PathBuf::from("src/lib.rs"), syn::parse_file(src).unwrap())];
+        let doctests = doctests(&files);
+
+        assert_eq!(doctests.len(), 3);
+        assert!(uses(&doctests, "Config"));
+        assert!(uses(&doctests, "helper"));
+        assert!(!uses(&doctests, "ignored"));
+        assert!(!uses(&doctests, "not_rust"));
+        // only used by their own examples
+        assert!(!uses(&doctests, "make_widget"));
+        assert!(!uses(&doctests, "spin"));
+    }
+}
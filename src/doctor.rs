@@ -0,0 +1,411 @@
+//! This module checks the environment that cargo-minify runs in (see `cargo
+//! minify doctor`): the versions of cargo and rustc, the flags that are passed
+//! to the compiler, the version control system and the target directory. Every
+//! check that does not pass comes with a way to fix it, since a run that finds
+//! nothing to remove usually has one of these causes.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use cargo_metadata::semver::Version;
+use nu_ansi_term::Color;
+use toml_edit::{DocumentMut, Item};
+
+use crate::{diff_format::ColorMode, error::Error, lock, vcs};
+
+/// The version of cargo that stabilized `--keep-going` and the `[lints]` table
+const KEEP_GOING_SINCE: (u64, u64) = (1, 74);
+
+/// How a check turned out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// Something that may keep runs from finding or applying changes
+    Warning,
+    /// Something that keeps cargo-minify from running at all
+    Problem,
+}
+
+pub struct Check {
+    pub outcome: Outcome,
+    pub what: String,
+    /// How to fix the problem, for checks that do not pass
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub fn passed(what: impl Into<String>) -> Self {
+        Check {
+            outcome: Outcome::Passed,
+            what: what.into(),
+            fix: None,
+        }
+    }
+
+    pub fn warning(what: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            outcome: Outcome::Warning,
+            what: what.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    pub fn problem(what: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            outcome: Outcome::Problem,
+            what: what.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// The first line of the output of `<tool> --version`, and the version in it,
+/// e.g. `cargo 1.95.0 (f2d3ce0bd 2026-03-21)`
+fn version(mut command: Command) -> Option<(String, Version)> {
+    let output = command
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let line = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()?
+        .to_owned();
+    let version = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some((line, version))
+}
+
+/// Checks that cargo and rustc can be run, that their versions match, and that
+/// they are recent enough
+pub fn toolchain() -> Vec<Check> {
+    let mut checks = Vec::new();
+    // the cargo that runs this tool, like `cargo::command`
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let cargo = version(Command::new(cargo));
+    match &cargo {
+        Some((line, version)) => {
+            checks.push(Check::passed(line.clone()));
+            if (version.major, version.minor) < KEEP_GOING_SINCE {
+                checks.push(Check::warning(
+                    format!(
+                        "cargo {version} does not support --keep-going, nor the [lints] table of \
+                         manifests"
+                    ),
+                    "update the toolchain, e.g. using `rustup update`",
+                ));
+            }
+        }
+        None => checks.push(Check::problem(
+            "cargo could not be run",
+            "install Rust using rustup (https://rustup.rs), or point $CARGO to cargo",
+        )),
+    }
+
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    match version(Command::new(&rustc)) {
+        Some((line, version)) => {
+            checks.push(Check::passed(line));
+            let mismatch = cargo.as_ref().is_some_and(|(_, cargo)| {
+                (cargo.major, cargo.minor) != (version.major, version.minor)
+            });
+            if mismatch {
+                checks.push(Check::warning(
+                    "cargo and rustc are of different versions",
+                    "make sure both come from the same toolchain (see $RUSTC and $PATH)",
+                ));
+            }
+        }
+        None => checks.push(Check::problem(
+            format!("rustc could not be run (as {rustc:?})"),
+            "install Rust using rustup (https://rustup.rs), or point $RUSTC to rustc",
+        )),
+    }
+
+    checks
+}
+
+/// Checks that the flags passed to rustc do not silence the warnings about
+/// unused code; these come from the environment or, if it sets none, from the
+/// `rustflags` keys of the cargo configuration files
+pub fn rustflags() -> Vec<Check> {
+    let encoded = std::env::var("CARGO_ENCODED_RUSTFLAGS").ok();
+    let (variable, flags): (_, Vec<String>) = match encoded {
+        Some(flags) => (
+            "CARGO_ENCODED_RUSTFLAGS",
+            flags.split('\x1f').map(str::to_owned).collect(),
+        ),
+        None => match std::env::var("RUSTFLAGS") {
+            Ok(flags) => (
+                "RUSTFLAGS",
+                flags.split_whitespace().map(str::to_owned).collect(),
+            ),
+            Err(_) => return config_rustflags(),
+        },
+    };
+
+    let silencing = silencing_flags(&flags);
+    if silencing.is_empty() {
+        return vec![Check::passed(format!(
+            "{variable} does not silence warnings about unused code"
+        ))];
+    }
+
+    vec![Check::warning(
+        format!(
+            "{variable} contains {}, so the compiler does not report unused code",
+            silencing.join(" ")
+        ),
+        format!("remove these flags from {variable}"),
+    )]
+}
+
+/// Checks the `rustflags` keys of the cargo configuration files that apply in
+/// the current directory
+fn config_rustflags() -> Vec<Check> {
+    let mut checks = Vec::new();
+    for path in config_files() {
+        let Ok(src) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let Some(keys) = config_flags(&src) else {
+            checks.push(Check::warning(
+                format!(
+                    "{} could not be parsed, so its rustflags were not checked",
+                    path.display()
+                ),
+                "check it using `cargo build`",
+            ));
+            continue;
+        };
+
+        for (key, flags) in keys {
+            let silencing = silencing_flags(&flags);
+            if !silencing.is_empty() {
+                checks.push(Check::warning(
+                    format!(
+                        "{key} in {} contains {}, so the compiler does not report unused code",
+                        path.display(),
+                        silencing.join(" ")
+                    ),
+                    format!("remove these flags from {key}"),
+                ));
+            }
+        }
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::passed(
+            "the rustflags of the environment and the cargo configuration do not silence \
+             warnings about unused code",
+        ));
+    }
+    checks
+}
+
+/// The cargo configuration files that apply in the current directory: those in
+/// the `.cargo` directories of it and its ancestors, and the one in the cargo
+/// home directory
+fn config_files() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::current_dir()
+        .map(|dir| dir.ancestors().map(|dir| dir.join(".cargo")).collect())
+        .unwrap_or_default();
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")));
+    if let Some(cargo_home) = cargo_home.filter(|home| !dirs.contains(home)) {
+        dirs.push(cargo_home);
+    }
+
+    // cargo prefers `config` over `config.toml` if both exist
+    dirs.into_iter()
+        .filter_map(|dir| {
+            [dir.join("config"), dir.join("config.toml")]
+                .into_iter()
+                .find(|path| path.is_file())
+        })
+        .collect()
+}
+
+/// The flags of the `build.rustflags` and `target.<triple or cfg>.rustflags`
+/// keys of a cargo configuration file, which are given as a string or an array
+/// of strings; `None` if the file cannot be parsed
+fn config_flags(src: &str) -> Option<Vec<(String, Vec<String>)>> {
+    fn flags(item: &Item) -> Option<Vec<String>> {
+        match item.as_str() {
+            Some(flags) => Some(flags.split_whitespace().map(str::to_owned).collect()),
+            None => item
+                .as_array()?
+                .iter()
+                .map(|flag| flag.as_str().map(str::to_owned))
+                .collect(),
+        }
+    }
+
+    let document: DocumentMut = src.parse().ok()?;
+    let mut keys = Vec::new();
+    if let Some(build) = document
+        .get("build")
+        .and_then(|build| build.get("rustflags"))
+    {
+        keys.extend(flags(build).map(|flags| ("build.rustflags".to_owned(), flags)));
+    }
+    if let Some(targets) = document.get("target").and_then(Item::as_table_like) {
+        for (target, table) in targets.iter() {
+            if let Some(item) = table.get("rustflags") {
+                let key = format!("target.{target}.rustflags");
+                keys.extend(flags(item).map(|flags| (key, flags)));
+            }
+        }
+    }
+
+    Some(keys)
+}
+
+/// The flags of rustc that silence the `dead_code` lint, with their values
+fn silencing_flags(flags: &[String]) -> Vec<String> {
+    let mut silencing = Vec::new();
+    let mut flags = flags.iter().map(String::as_str);
+    while let Some(flag) = flags.next() {
+        let (flag, value) = match flag.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => match flag.strip_prefix("-A").filter(|lint| !lint.is_empty()) {
+                Some(lint) => ("-A", Some(lint)),
+                None => (flag, None),
+            },
+        };
+        let value = match value {
+            Some(value) => value,
+            None if matches!(flag, "-A" | "--allow" | "--cap-lints") => match flags.next() {
+                Some(value) => value,
+                None => break,
+            },
+            None => continue,
+        };
+
+        let silences = match flag {
+            "-A" | "--allow" => matches!(value, "dead_code" | "unused" | "warnings"),
+            "--cap-lints" => value == "allow",
+            _ => false,
+        };
+        if silences {
+            let separator = if flag == "-A" { "" } else { " " };
+            silencing.push(format!("{flag}{separator}{value}"));
+        }
+    }
+
+    silencing
+}
+
+/// Checks the version control system of the workspace, which `--apply` needs
+pub fn version_control(workspace_root: &Path) -> Check {
+    match vcs::status(workspace_root) {
+        vcs::Status::Clean => Check::passed("the working directory is clean"),
+        vcs::Status::Unclean { dirty, staged } => Check::warning(
+            format!(
+                "the working directory has {} dirty and {} staged files, so --apply refuses to \
+                 change it",
+                dirty.len(),
+                staged.len()
+            ),
+            "commit or stash the changes, or pass --allow-dirty and/or --allow-staged",
+        ),
+        vcs::Status::NoVCS => Check::warning(
+            "no version control system was found, so --apply refuses to change the workspace",
+            "run `git init` and commit, or pass --allow-no-vcs",
+        ),
+        vcs::Status::Error(err) => Check::problem(
+            format!("the git repository could not be read: {err}"),
+            "check the repository using `git status`",
+        ),
+    }
+}
+
+/// Checks that the target directory is writable (for the lock, the history of
+/// runs and snapshots), and that no other run is active
+pub fn target_directory(target_directory: &Path) -> Check {
+    match lock::acquire(target_directory) {
+        Ok(_) => Check::passed(format!(
+            "the target directory {} is writable",
+            target_directory.display()
+        )),
+        Err(Error::Locked(path)) => Check::warning(
+            format!(
+                "another run of cargo minify is active (holding {})",
+                path.display()
+            ),
+            "wait for it to finish",
+        ),
+        Err(err) => Check::problem(
+            format!(
+                "the target directory {} is not writable: {err}",
+                target_directory.display()
+            ),
+            "fix its permissions, or choose another one using $CARGO_TARGET_DIR",
+        ),
+    }
+}
+
+/// Prints the checks, with the fix of those that do not pass
+pub fn print(checks: &[Check], color_mode: ColorMode) {
+    for check in checks {
+        let (label, color) = match check.outcome {
+            Outcome::Passed => ("ok", Color::LightGreen),
+            Outcome::Warning => ("warning", Color::Yellow),
+            Outcome::Problem => ("problem", Color::LightRed),
+        };
+        let label = format!("{label:<8}");
+        if color_mode.enabled() {
+            println!("{}{}", color.paint(label), check.what);
+        } else {
+            println!("{label}{}", check.what);
+        }
+        if let Some(fix) = &check.fix {
+            println!("{:<8}fix: {fix}", "");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn silenced() {
+        let flags = |flags: &str| -> Vec<String> {
+            let flags: Vec<_> = flags.split_whitespace().map(str::to_owned).collect();
+            silencing_flags(&flags)
+        };
+        assert!(flags("-C target-cpu=native -Dwarnings").is_empty());
+        assert_eq!(flags("-Adead_code"), ["-Adead_code"]);
+        assert_eq!(
+            flags("-A warnings --cap-lints allow"),
+            ["-Awarnings", "--cap-lints allow"]
+        );
+        assert_eq!(flags("--allow=unused -A clippy::all"), ["--allow unused"]);
+    }
+
+    #[test]
+    fn config() {
+        let src = "[build]\nrustflags = \"-A dead_code\"\n\n\
+                   [target.'cfg(unix)']\nrustflags = [\"-C\", \"target-cpu=native\"]\n\n\
+                   [target.x86_64-unknown-linux-gnu]\nlinker = \"clang\"\n";
+        let flags = |flags: &[&str]| flags.iter().map(|&flag| flag.to_owned()).collect();
+        assert_eq!(
+            config_flags(src),
+            Some(vec![
+                ("build.rustflags".to_owned(), flags(&["-A", "dead_code"])),
+                (
+                    "target.cfg(unix).rustflags".to_owned(),
+                    flags(&["-C", "target-cpu=native"])
+                ),
+            ])
+        );
+        assert_eq!(config_flags("[build]\njobs = 4\n"), Some(Vec::new()));
+        assert_eq!(config_flags("[build"), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! This module is the part of cargo-minify that works on code in memory: given
+//! the source of a file and the diagnostics of the compiler (as JSON), it
+//! removes the unused items, without running cargo or touching the file system.
+//! Unlike the rest of the crate, it also works when compiled to
+//! `wasm32-unknown-unknown`, for instance for a playground in the browser.
+
+use std::{collections::BTreeSet, path::Path};
+
+use cargo_metadata::diagnostic::Diagnostic;
+pub use cargo_metadata::Edition;
+
+use crate::{
+    cauterize::{self, Mode},
+    error::Result,
+    unused::UnusedDiagnostic,
+};
+pub use crate::{error::Error, unused::UnusedDiagnosticKind};
+
+/// How to minify a file
+#[derive(Debug)]
+pub struct Options {
+    pub edition: Edition,
+    /// The kinds of items to remove (all if empty)
+    pub kinds: Vec<UnusedDiagnosticKind>,
+    /// Comment out unused items, instead of removing them
+    pub comment: bool,
+    /// Leave a one-line comment where each item was removed
+    pub tombstones: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            edition: Edition::E2021,
+            kinds: Vec::new(),
+            comment: false,
+            tombstones: false,
+        }
+    }
+}
+
+/// The unused items that the diagnostics report, given as a JSON object per
+/// line: either the messages of `cargo check --message-format json` or the
+/// diagnostics of `rustc --error-format json`; other lines are skipped. These
+/// are the items of all files that the diagnostics are about
+pub fn unused_items(diagnostics: &str) -> Result<Vec<(UnusedDiagnosticKind, String)>> {
+    let items: BTreeSet<_> = parse_diagnostics(diagnostics)?
+        .into_iter()
+        .map(|diagnostic| (diagnostic.kind, diagnostic.ident))
+        .collect();
+
+    Ok(items.into_iter().collect())
+}
+
+/// Removes the unused items that the diagnostics (see [`unused_items`]) report
+/// from the source of a single file, and returns the new source. Only the
+/// diagnostics about that file are used: those of which the path ends with
+/// `file_name`, so `src/lib.rs` also matches the `crates/app/src/lib.rs` that
+/// cargo reports in a workspace
+pub fn minify(src: &str, file_name: &str, diagnostics: &str, options: &Options) -> Result<String> {
+    let items: BTreeSet<_> = parse_diagnostics(diagnostics)?
+        .into_iter()
+        .filter(|diagnostic| Path::new(&diagnostic.span.file_name).ends_with(file_name))
+        .map(|diagnostic| (diagnostic.kind, diagnostic.ident))
+        .filter(|(kind, _)| options.kinds.is_empty() || options.kinds.contains(kind))
+        .collect();
+    let mode = if options.comment {
+        Mode::Comment
+    } else {
+        Mode::Delete
+    };
+    // without the rest of the crate, the file is taken to be its root
+    let minified = cauterize::rust_remove(
+        src.as_bytes(),
+        &[],
+        items,
+        mode,
+        options.tombstones,
+        options.edition,
+    )?;
+
+    Ok(String::from_utf8_lossy(&minified).into_owned())
+}
+
+fn parse_diagnostics(diagnostics: &str) -> Result<Vec<UnusedDiagnostic>> {
+    let mut found = Vec::new();
+    for line in diagnostics.lines().filter(|line| line.starts_with('{')) {
+        let mut message: serde_json::Value = serde_json::from_str(line)?;
+        // cargo wraps the diagnostics of rustc
+        if message["reason"] == "compiler-message" {
+            message = message["message"].take();
+        }
+        let Ok(diagnostic) = serde_json::from_value::<Diagnostic>(message) else {
+            continue;
+        };
+        found.extend(UnusedDiagnostic::from_diagnostic(diagnostic));
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn corpus() {
+        let src = include_str!("../tests/rustc/corpus.rs");
+        let diagnostics = include_str!("../tests/rustc/1.95.0.jsonl");
+
+        let minified = minify(src, "corpus.rs", diagnostics, &Options::default()).unwrap();
+        assert!(!minified.contains("fn unused_function"));
+        assert!(!minified.contains("macro_rules! unused_macro"));
+        assert!(minified.contains("struct Partly"));
+
+        // the diagnostics are about another file
+        let minified = minify(src, "src/lib.rs", diagnostics, &Options::default()).unwrap();
+        assert_eq!(minified, src);
+
+        // as cargo reports them, relative to the root of the workspace
+        let messages: String = diagnostics
+            .lines()
+            .map(|line| {
+                let line = line.replace("\"corpus.rs\"", "\"tests/rustc/corpus.rs\"");
+                format!("{{\"reason\":\"compiler-message\",\"message\":{line}}}\n")
+            })
+            .collect();
+        let options = Options {
+            kinds: vec![UnusedDiagnosticKind::Function],
+            ..Options::default()
+        };
+        let minified = minify(src, "corpus.rs", &messages, &options).unwrap();
+        assert!(!minified.contains("fn unused_function"));
+        assert!(minified.contains("macro_rules! unused_macro"));
+
+        let error = minify(src, "corpus.rs", "{", &options).unwrap_err();
+        assert!(matches!(error, Error::Json(_)));
+    }
+}
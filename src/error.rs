@@ -12,6 +12,45 @@ pub enum Error {
     #[error("{0}")]
     CommandLine(#[from] gumdrop::Error),
 
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("the code could not be parsed: {0}")]
+    Parse(#[from] syn::Error),
+
     #[error("invalid command line arguments: {0}")]
     Args(&'static str),
+
+    #[error("{failed} of {total} files could not be written, the changes were applied partially")]
+    PartialApply { failed: usize, total: usize },
+
+    #[error("`{0}` failed")]
+    CommandFailed(&'static str),
+
+    #[error("{0} of the checks found a problem")]
+    ChecksFailed(usize),
+
+    #[error("interrupted")]
+    Interrupted,
+
+    #[error("cargo check did not finish within {0} seconds, so the findings are partial")]
+    CheckTimedOut(u64),
+
+    #[error("invalid cargo bloat output: {0}")]
+    Bloat(&'static str),
+
+    #[error("no item `{0}` was found")]
+    UnknownItem(String),
+
+    #[error("no run `{0}` was recorded")]
+    UnknownRun(String),
+
+    #[error("no snapshot `{0}` was taken")]
+    UnknownSnapshot(String),
+
+    #[error("no such file or directory: {}", .0.display())]
+    UnknownPath(std::path::PathBuf),
+
+    #[error("another run of cargo minify is active in this workspace (holding {})", .0.display())]
+    Locked(std::path::PathBuf),
 }
@@ -0,0 +1,273 @@
+//! This module maps diagnostics about items that were produced by a macro back
+//! to the invocation of that macro; an invocation can be removed if all of the
+//! items it expands to are unused.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    str::FromStr,
+};
+
+use cargo_metadata::{
+    diagnostic::{DiagnosticSpan, DiagnosticSpanMacroExpansion},
+    Edition,
+};
+use proc_macro2::{Group, Ident, TokenStream, TokenTree};
+use quote::ToTokens;
+
+use crate::{
+    cauterize,
+    unused::{UnusedDiagnostic, UnusedDiagnosticKind},
+};
+
+/// Turns diagnostics about expanded items into diagnostics about the macro
+/// invocations that are entirely unused
+pub fn unused_invocations(diagnostics: Vec<UnusedDiagnostic>) -> Vec<UnusedDiagnostic> {
+    let mut invocations = BTreeMap::<_, (&DiagnosticSpanMacroExpansion, BTreeSet<_>)>::new();
+    for diagnostic in &diagnostics {
+        // unread fields do not make an item unused
+        if diagnostic.kind == UnusedDiagnosticKind::Field {
+            continue;
+        }
+        let Some(expansion) = outermost_expansion(&diagnostic.span) else {
+            continue;
+        };
+
+        let span = &expansion.span;
+        let key = (&span.file_name, span.byte_start, span.byte_end);
+        invocations
+            .entry(key)
+            .or_insert_with(|| (expansion, BTreeSet::new()))
+            .1
+            .insert((&diagnostic.kind, &diagnostic.ident));
+    }
+
+    let mut expanded_items = BTreeMap::new();
+    invocations
+        .into_values()
+        .filter_map(|(expansion, unused)| {
+            let macro_name = expansion.macro_decl_name.strip_suffix('!')?;
+            let definition = expansion.def_site_span.as_ref()?;
+            let count = *expanded_items
+                .entry((&definition.file_name, macro_name))
+                .or_insert_with(|| {
+                    count_expanded_items(Path::new(&definition.file_name), macro_name)
+                });
+            if count != Some(unused.len()) {
+                return None;
+            }
+
+            Some(UnusedDiagnostic {
+                kind: UnusedDiagnosticKind::MacroInvocation,
+                ident: invocation_text(&expansion.span)?,
+                span: expansion.span.clone(),
+                parent: None,
+                message: format!(
+                    "all items expanded from this {macro_name}! invocation are unused"
+                ),
+            })
+        })
+        .collect()
+}
+
+/// The expansion of the macro invocation written by the user that (possibly
+/// through other macros) produced the code of a span
+pub fn outermost_expansion(span: &DiagnosticSpan) -> Option<&DiagnosticSpanMacroExpansion> {
+    let mut expansion = span.expansion.as_deref()?;
+    while let Some(outer) = expansion.span.expansion.as_deref() {
+        expansion = outer;
+    }
+
+    Some(expansion)
+}
+
+/// The number of items each invocation of a `macro_rules!` macro expands to;
+/// `None` if this cannot be determined, e.g. because it depends on the input,
+/// or because the macro expands to items that are not reported when unused
+fn count_expanded_items(file: &Path, macro_name: &str) -> Option<usize> {
+    let src = std::fs::read(file).ok()?;
+    let ast = cauterize::parse_file(&src, Edition::E2021).ok()?;
+
+    let mut definitions = Vec::new();
+    find_macro_rules(&ast.items, macro_name, &mut definitions);
+    let [definition] = definitions.as_slice() else {
+        return None;
+    };
+
+    let counts = rules(definition.mac.tokens.clone())?
+        .into_iter()
+        .map(count_items)
+        .collect::<Option<BTreeSet<_>>>()?;
+
+    match counts.into_iter().collect::<Vec<_>>().as_slice() {
+        [count] => Some(*count),
+        _ => None,
+    }
+}
+
+fn find_macro_rules<'a>(items: &'a [syn::Item], name: &str, found: &mut Vec<&'a syn::ItemMacro>) {
+    for item in items {
+        match item {
+            syn::Item::Macro(definition)
+                if definition.mac.path.is_ident("macro_rules")
+                    && definition.ident.as_ref().is_some_and(|ident| ident == name) =>
+            {
+                found.push(definition)
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => find_macro_rules(items, name, found),
+            _ => {}
+        }
+    }
+}
+
+/// The transcribers of the rules of a `macro_rules!` definition
+fn rules(tokens: TokenStream) -> Option<Vec<TokenStream>> {
+    let mut transcribers = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+
+    while tokens.peek().is_some() {
+        let (Some(TokenTree::Group(_)), Some(TokenTree::Punct(eq)), Some(TokenTree::Punct(gt))) =
+            (tokens.next(), tokens.next(), tokens.next())
+        else {
+            return None;
+        };
+        if eq.as_char() != '=' || gt.as_char() != '>' {
+            return None;
+        }
+        let Some(TokenTree::Group(transcriber)) = tokens.next() else {
+            return None;
+        };
+        transcribers.push(transcriber.stream());
+
+        if let Some(TokenTree::Punct(semi)) = tokens.peek() {
+            if semi.as_char() == ';' {
+                tokens.next();
+            }
+        }
+    }
+
+    Some(transcribers)
+}
+
+/// The number of items a transcriber produces, if all of them are of a kind
+/// that is reported when unused
+fn count_items(transcriber: TokenStream) -> Option<usize> {
+    let file: syn::File = syn::parse2(substitute_metavariables(transcriber)?).ok()?;
+
+    file.items
+        .iter()
+        .all(|item| {
+            matches!(
+                item,
+                syn::Item::Const(_)
+                    | syn::Item::Enum(_)
+                    | syn::Item::Fn(_)
+                    | syn::Item::Static(_)
+                    | syn::Item::Struct(_)
+                    | syn::Item::Type(_)
+                    | syn::Item::Union(_)
+            )
+        })
+        .then_some(file.items.len())
+}
+
+/// Replaces metavariables (`$name`) by a placeholder identifier, so the
+/// transcriber can be parsed; repetitions (`$(...)*`) cannot be counted
+fn substitute_metavariables(tokens: TokenStream) -> Option<TokenStream> {
+    let mut result = Vec::new();
+    let mut tokens = tokens.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '$' => match tokens.next()? {
+                TokenTree::Ident(ident) if ident == "crate" => result.push(TokenTree::Ident(ident)),
+                TokenTree::Ident(ident) => result.push(TokenTree::Ident(Ident::new(
+                    "__minify_metavariable",
+                    ident.span(),
+                ))),
+                _ => return None,
+            },
+            TokenTree::Group(group) => {
+                let stream = substitute_metavariables(group.stream())?;
+                result.push(TokenTree::Group(Group::new(group.delimiter(), stream)));
+            }
+            token => result.push(token),
+        }
+    }
+
+    Some(result.into_iter().collect())
+}
+
+/// Whether a macro invocation is the one with the given normalized text
+pub fn is_invocation(mac: &syn::Macro, text: &str) -> bool {
+    mac.to_token_stream().to_string() == text
+}
+
+/// Normalizes the text of a macro invocation (by which it is identified), so
+/// that it does not depend on whitespace or comments
+fn normalize(text: &str) -> Option<String> {
+    let tokens = TokenStream::from_str(text).ok()?;
+    Some(tokens.to_string())
+}
+
+/// The normalized text of the invocation a span points at
+fn invocation_text(span: &DiagnosticSpan) -> Option<String> {
+    let src = std::fs::read(&span.file_name).ok()?;
+    let text = src.get(span.byte_start as usize..span.byte_end as usize)?;
+    normalize(std::str::from_utf8(text).ok()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn counts(definition: &str) -> Option<Vec<usize>> {
+        let definition: syn::ItemMacro = syn::parse_str(definition).unwrap();
+        rules(definition.mac.tokens)?
+            .into_iter()
+            .map(count_items)
+            .collect()
+    }
+
+    #[test]
+    fn expanded_items() {
+        assert_eq!(
+            counts("macro_rules! huk { () => { fn huk() {} }; }"),
+            Some(vec![1])
+        );
+        assert_eq!(
+            counts(
+                "macro_rules! named {
+                    ($name:ident, $ty:ident) => { fn $name() -> $crate::X {} struct $ty; };
+                    ($name:ident) => { const $name: u8 = 0; };
+                }"
+            ),
+            Some(vec![2, 1])
+        );
+        // repetitions and impls cannot be accounted for
+        assert_eq!(
+            counts("macro_rules! many { ($($name:ident),*) => { $(fn $name() {})* }; }"),
+            None
+        );
+        assert_eq!(
+            counts("macro_rules! imp { ($ty:ty) => { impl X for $ty {} }; }"),
+            None
+        );
+    }
+
+    #[test]
+    fn invocation_text() {
+        let item: syn::ItemMacro = syn::parse_str("named!(foo,\n    Foo);").unwrap();
+        assert!(is_invocation(
+            &item.mac,
+            &normalize("named!(foo, /* a */ Foo)").unwrap()
+        ));
+        assert!(!is_invocation(
+            &item.mac,
+            &normalize("named!(bar, Bar)").unwrap()
+        ));
+    }
+}
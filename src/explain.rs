@@ -0,0 +1,304 @@
+//! This module explains why a single item is, or is not, removed (see
+//! `cargo minify explain`), following the same steps as a regular run.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    cauterize::{self, Mode},
+    error::{Error, Result},
+    expansion,
+    graph::Graph,
+    unused::{Severity, UnusedDiagnostic, UnusedDiagnosticKind},
+    CauterizeOptions, FileResolutionOptions,
+};
+
+/// The item to explain
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    /// The item at a line of a file, e.g. `src/lib.rs:12`
+    Location(PathBuf, usize),
+    /// The path of an item, e.g. `mycrate::cli::run`
+    Path(String),
+}
+
+impl FromStr for Target {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.contains("::") {
+            return Ok(Target::Path(s.to_owned()));
+        }
+
+        match s.rsplit_once(':') {
+            Some((file, line)) => match line.parse() {
+                Ok(line) => Ok(Target::Location(file.into(), line)),
+                Err(_) => Err(Error::Args("expected a line number after the file name")),
+            },
+            None => Err(Error::Args(
+                "expected FILE:LINE, or the path of an item such as mycrate::cli::run",
+            )),
+        }
+    }
+}
+
+/// The filters of a regular run that apply to the diagnostics
+pub struct Filters<'a> {
+    pub kinds: &'a [UnusedDiagnosticKind],
+    pub excluded_kinds: &'a [UnusedDiagnosticKind],
+    pub files: &'a FileResolutionOptions,
+    pub severity: Option<Severity>,
+    pub expand_macros: bool,
+    pub test_only: bool,
+}
+
+/// Prints why the target is, or is not, changed by a run with the given
+/// options, based on the diagnostics of the compiler
+pub fn explain(
+    target: &Target,
+    diagnostics: Vec<UnusedDiagnostic>,
+    filters: &Filters,
+    options: &CauterizeOptions,
+) -> Result<()> {
+    let graphs = cauterize::package_graphs(options);
+    let (file_name, line, item) = match target {
+        Target::Location(file_name, line) => (
+            file_name.clone(),
+            Some(*line),
+            cauterize::item_at(file_name, *line, options),
+        ),
+        Target::Path(path) => {
+            let Some(node) = graphs
+                .iter()
+                .find_map(|graph| Some(&graph.nodes[*graph.find(path).first()?]))
+            else {
+                return Err(Error::UnknownItem(path.clone()));
+            };
+            let item = (node.kind.clone(), node.ident.clone());
+            (node.file.clone(), None, Some(item))
+        }
+    };
+
+    let invocations = if filters.expand_macros {
+        let expanded = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.span.expansion.is_some())
+            .cloned()
+            .collect();
+        expansion::unused_invocations(expanded)
+    } else {
+        Vec::new()
+    };
+
+    // the compiler reports items in every target they are part of
+    let mut matching: Vec<UnusedDiagnostic> = Vec::new();
+    for diagnostic in diagnostics.into_iter().chain(invocations) {
+        let same_item = item
+            .as_ref()
+            .is_some_and(|(_, ident)| diagnostic.ident == *ident);
+        let invocation = match expansion::outermost_expansion(&diagnostic.span) {
+            Some(expansion) => Some(expansion.span.line_start) == line,
+            None => {
+                diagnostic.kind == UnusedDiagnosticKind::MacroInvocation
+                    && Some(diagnostic.span.line_start) == line
+            }
+        };
+        if (same_item || invocation)
+            && cauterize::same_file(diagnostic.span.file_name.as_ref(), &file_name)
+            && !matching.iter().any(|other| other.same_item(&diagnostic))
+        {
+            matching.push(diagnostic);
+        }
+    }
+
+    match &item {
+        Some((kind, ident)) => println!(
+            "{} `{ident}` in {}",
+            kind.name().replace('_', " "),
+            file_name.display()
+        ),
+        None if !matching.is_empty() => {
+            println!("items generated by a macro in {}", file_name.display())
+        }
+        None => {
+            let line = line.unwrap_or_default();
+            return Err(Error::UnknownItem(format!(
+                "{}:{line}",
+                file_name.display()
+            )));
+        }
+    }
+
+    if filters.test_only {
+        println!(
+            "checked using `cargo check` and `cargo check --tests`, for items only used by tests"
+        );
+    } else {
+        println!("checked using `cargo check --all-targets`");
+    }
+
+    if matching.is_empty() {
+        println!("the compiler does not report it as unused");
+        if let Some((_, ident)) = &item {
+            explain_uses(&graphs, &file_name, ident);
+        }
+        return Ok(());
+    }
+
+    let unused_invocations: Vec<_> = matching
+        .iter()
+        .filter(|diagnostic| diagnostic.kind == UnusedDiagnosticKind::MacroInvocation)
+        .map(|diagnostic| {
+            (
+                diagnostic.span.file_name.clone(),
+                diagnostic.span.byte_start,
+            )
+        })
+        .collect();
+
+    for diagnostic in matching {
+        println!(
+            "the compiler reports: {} ({}:{}:{}, {})",
+            diagnostic.message,
+            diagnostic.span.file_name,
+            diagnostic.span.line_start,
+            diagnostic.span.column_start,
+            diagnostic.severity().name()
+        );
+
+        if let Some(expansion) = expansion::outermost_expansion(&diagnostic.span) {
+            let span = &expansion.span;
+            println!(
+                "it is produced by `{}` (at {}:{})",
+                expansion.macro_decl_name, span.file_name, span.line_start
+            );
+            if unused_invocations.contains(&(span.file_name.clone(), span.byte_start)) {
+                println!("all items of that invocation are unused, so it is handled as a whole");
+            } else if filters.expand_macros {
+                println!(
+                    "it is kept, since not all items of that invocation are known to be unused"
+                );
+            } else {
+                println!(
+                    "it is kept, since macro invocations are only removed with --expand-macros"
+                );
+            }
+            continue;
+        }
+
+        match reason_to_keep(&diagnostic, filters, options) {
+            Some(reason) => println!("it is kept, since {reason}"),
+            None => explain_result(diagnostic, options),
+        }
+    }
+
+    Ok(())
+}
+
+/// The reason a diagnostic is filtered out, before or while computing changes
+fn reason_to_keep(
+    diagnostic: &UnusedDiagnostic,
+    filters: &Filters,
+    options: &CauterizeOptions,
+) -> Option<String> {
+    if !filters.kinds.is_empty() && !filters.kinds.contains(&diagnostic.kind) {
+        return Some(format!(
+            "--kinds does not include {}",
+            diagnostic.kind.name()
+        ));
+    }
+    if filters.excluded_kinds.contains(&diagnostic.kind) {
+        return Some(format!(
+            "--exclude-kinds includes {}",
+            diagnostic.kind.name()
+        ));
+    }
+    if let Some(severity) = filters
+        .severity
+        .filter(|&severity| severity != diagnostic.severity())
+    {
+        return Some(format!(
+            "--severity is {}, but it is {}",
+            severity.name(),
+            diagnostic.severity().name()
+        ));
+    }
+    if let Some(reason) = filters.files.why_excluded(&diagnostic.span.file_name) {
+        return Some(format!("its file is left alone, since it {reason}"));
+    }
+
+    cauterize::why_kept(diagnostic, options)
+}
+
+/// Reports what a run would do with an item that passes all filters
+fn explain_result(diagnostic: UnusedDiagnostic, options: &CauterizeOptions) {
+    let file_name = PathBuf::from(&diagnostic.span.file_name);
+    let (changes, _) =
+        cauterize::process_diagnostics([diagnostic], Vec::new(), Vec::new(), options);
+    if !changes
+        .iter()
+        .any(|change| cauterize::same_file(change.file_name(), &file_name))
+    {
+        println!("it is kept, since the mode does not change it");
+        return;
+    }
+
+    let action = match options.mode {
+        Mode::Delete => "removed",
+        Mode::Stub => "stubbed out, or removed if it is private",
+        Mode::Comment => "commented out",
+        Mode::CfgTest => "marked as #[cfg(test)]",
+        Mode::BinFeature => "gated",
+        Mode::DeprecatePub => "removed",
+    };
+    println!("it is {action}");
+}
+
+/// Reports which items that are in use refer to an item, according to the
+/// item graph
+fn explain_uses(graphs: &[Graph], file_name: &Path, ident: &str) {
+    for graph in graphs {
+        let live = graph.live();
+        let Some(node) = (0..graph.nodes.len()).find(|&node| {
+            graph.nodes[node].ident == ident
+                && cauterize::same_file(&graph.nodes[node].file, file_name)
+        }) else {
+            continue;
+        };
+
+        if graph.nodes[node].root {
+            println!("it is exported by a library, or an entry point");
+        }
+        let users: Vec<_> = (0..graph.nodes.len())
+            .filter(|user| live.contains(user) && graph.edges[*user].contains(&node))
+            .map(|user| &graph.nodes[user].path)
+            .collect();
+        if !users.is_empty() {
+            println!("it is referred to by:");
+            for user in users {
+                println!("    {user}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn targets() {
+        assert_eq!(
+            "src/lib.rs:12".parse::<Target>().unwrap(),
+            Target::Location("src/lib.rs".into(), 12)
+        );
+        assert_eq!(
+            "mycrate::cli::run".parse::<Target>().unwrap(),
+            Target::Path("mycrate::cli::run".to_owned())
+        );
+        assert!("src/lib.rs".parse::<Target>().is_err());
+        assert!("src/lib.rs:x".parse::<Target>().is_err());
+    }
+}
@@ -0,0 +1,423 @@
+//! This module finds library items that are used by a single binary target
+//! only; these can be put behind a feature named after that binary (which the
+//! binary then requires), so the other binaries do not have to build them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+
+use crate::{
+    items::{idents, item_name, self_type_name},
+    manifest::Manifest,
+    modules,
+    unused::UnusedDiagnosticKind,
+};
+
+/// The targets of a package
+pub struct Targets {
+    pub manifest_path: PathBuf,
+    /// The name of the library crate (as used in paths), and its root file
+    pub lib: Option<(String, PathBuf)>,
+    /// The name and root file of every binary target
+    pub bins: Vec<(String, PathBuf)>,
+    /// The name and root file of all other targets, e.g. examples and tests
+    pub others: Vec<(String, PathBuf)>,
+    pub features: BTreeSet<String>,
+    /// The binaries that already require a feature named after themselves
+    pub gated_bins: BTreeSet<String>,
+    /// Whether the package may be published (it does not set `publish = false`)
+    pub published: bool,
+}
+
+/// A library item that is only used by the given binary
+#[derive(Debug, PartialEq)]
+pub struct Gate {
+    pub bin: String,
+    pub file: PathBuf,
+    pub kind: UnusedDiagnosticKind,
+    pub ident: String,
+}
+
+/// Finds the library items that only a single binary uses, directly or
+/// through other such items, while no other target uses them; `parse`
+/// provides the syntax tree of a file
+pub fn single_bin_items(
+    targets: &Targets,
+    parse: impl Fn(&Path) -> Option<syn::File>,
+) -> Vec<Gate> {
+    let Some((lib_name, lib_root)) = &targets.lib else {
+        return Vec::new();
+    };
+    if targets.bins.len() < 2 {
+        return Vec::new();
+    }
+
+    let files_of = |root: &Path| -> Vec<(PathBuf, syn::File)> {
+        crate_files(root, &parse)
+            .into_iter()
+            .filter_map(|file| Some((file.clone(), parse(&file)?)))
+            .collect()
+    };
+
+    let lib_files = files_of(lib_root);
+    let mut lib_items = Vec::new();
+    for (file, ast) in &lib_files {
+        collect_items(file, &ast.items, &mut lib_items);
+    }
+    let mentions = mentions(&lib_files);
+
+    let bin_uses: Vec<(&str, Uses)> = targets
+        .bins
+        .iter()
+        .map(|(name, root)| (name.as_str(), uses(lib_name, &files_of(root))))
+        .collect();
+    let other_uses = targets
+        .others
+        .iter()
+        .map(|(_, root)| uses(lib_name, &files_of(root)))
+        .fold(Uses::default(), Uses::merge);
+
+    // an item can be gated if all of the binaries that use it, and the bins of
+    // the gated items that refer to it, are the same one
+    let mut gated = BTreeMap::<&str, &str>::new();
+    loop {
+        let mut changed = false;
+        for (_, _, ident) in &lib_items {
+            if gated.contains_key(ident.as_str()) || other_uses.contains(ident) {
+                continue;
+            }
+
+            let mut bins = BTreeSet::new();
+            bins.extend(
+                bin_uses
+                    .iter()
+                    .filter(|(_, uses)| uses.contains(ident))
+                    .map(|(bin, _)| *bin),
+            );
+            let mut gatable = true;
+            for user in mentions.iter().filter_map(|(user, mentioned)| {
+                (user.as_deref() != Some(ident.as_str()) && mentioned.contains(ident))
+                    .then_some(user)
+            }) {
+                match user.as_deref().and_then(|user| gated.get(user)) {
+                    Some(bin) => {
+                        bins.insert(bin);
+                    }
+                    None => gatable = false,
+                }
+            }
+
+            if let (true, [bin]) = (gatable, Vec::from_iter(bins).as_slice()) {
+                gated.insert(ident, bin);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    lib_items
+        .iter()
+        .filter_map(|(file, kind, ident)| {
+            Some(Gate {
+                bin: gated.get(ident.as_str())?.to_string(),
+                file: file.clone(),
+                kind: kind.clone(),
+                ident: ident.clone(),
+            })
+        })
+        .collect()
+}
+
+/// All files in the module tree of a crate
+fn crate_files(root: &Path, parse: &impl Fn(&Path) -> Option<syn::File>) -> BTreeSet<PathBuf> {
+    let roots = [root.to_path_buf()];
+    let declarations = modules::declarations(&roots, parse, Path::exists);
+
+    roots
+        .into_iter()
+        .chain(declarations.into_iter().map(|declaration| declaration.file))
+        .collect()
+}
+
+/// The items of a library that can be gated, i.e. those of a kind that the
+/// compiler reports when unused
+fn collect_items(
+    file: &Path,
+    items: &[syn::Item],
+    found: &mut Vec<(PathBuf, UnusedDiagnosticKind, String)>,
+) {
+    for item in items {
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            collect_items(file, items, found);
+        } else if let Some((kind, ident)) = gated_name(item) {
+            found.push((file.to_path_buf(), kind, ident));
+        }
+    }
+}
+
+/// The kind and name of an item that can be gated; macros are not, since
+/// their uses are not found by name
+fn gated_name(item: &syn::Item) -> Option<(UnusedDiagnosticKind, String)> {
+    item_name(item).filter(|(kind, _)| *kind != UnusedDiagnosticKind::MacroDefinition)
+}
+
+/// The names each item of a library mentions; impl blocks count as part of
+/// the type they are for (since they are gated together with it), and items
+/// that cannot be gated (e.g. traits and imports) have no name
+fn mentions(files: &[(PathBuf, syn::File)]) -> Vec<(Option<String>, BTreeSet<String>)> {
+    files
+        .iter()
+        .flat_map(|(_, ast)| flatten(&ast.items))
+        .map(|item| {
+            let name = match item {
+                syn::Item::Impl(block) => self_type_name(&block.self_ty),
+                item => gated_name(item).map(|(_, ident)| ident),
+            };
+            let mut mentioned = BTreeSet::new();
+            idents(item.to_token_stream(), &mut mentioned);
+
+            (name, mentioned)
+        })
+        .collect()
+}
+
+/// The items of a file, with the items of inline modules instead of the modules
+fn flatten(items: &[syn::Item]) -> Vec<&syn::Item> {
+    items
+        .iter()
+        .flat_map(|item| match item {
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => flatten(items),
+            item => vec![item],
+        })
+        .collect()
+}
+
+/// The names a target refers to through paths starting with the library
+#[derive(Default)]
+struct Uses {
+    names: BTreeSet<String>,
+    /// Whether a glob import of the library was found, which could use anything
+    glob: bool,
+}
+
+impl Uses {
+    fn contains(&self, ident: &str) -> bool {
+        self.glob || self.names.contains(ident)
+    }
+
+    fn merge(mut self, other: Uses) -> Uses {
+        self.names.extend(other.names);
+        self.glob |= other.glob;
+        self
+    }
+}
+
+fn uses(lib_name: &str, files: &[(PathBuf, syn::File)]) -> Uses {
+    let mut uses = Uses::default();
+    for (_, ast) in files {
+        library_paths(lib_name, ast.to_token_stream(), &mut uses);
+    }
+
+    uses
+}
+
+/// Collects the names in paths such as `lib::a::B` and `lib::{a::B, C}`
+fn library_paths(lib_name: &str, tokens: TokenStream, uses: &mut Uses) {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Ident(name)
+                if name == lib_name && is_path_separator(&tokens[index + 1..]) =>
+            {
+                path_names(&tokens[index + 1..], uses);
+            }
+            TokenTree::Group(group) => library_paths(lib_name, group.stream(), uses),
+            _ => {}
+        }
+    }
+}
+
+fn is_path_separator(tokens: &[TokenTree]) -> bool {
+    matches!(
+        tokens,
+        [TokenTree::Punct(first), TokenTree::Punct(second), ..]
+            if first.as_char() == ':' && second.as_char() == ':'
+    )
+}
+
+/// Collects the names of a path (or use tree) following a `::`
+fn path_names(mut tokens: &[TokenTree], uses: &mut Uses) {
+    while is_path_separator(tokens) {
+        match &tokens[2..] {
+            [TokenTree::Ident(name), rest @ ..] => {
+                uses.names.insert(name.unraw().to_string());
+                tokens = rest;
+            }
+            [TokenTree::Punct(star), ..] if star.as_char() == '*' => {
+                uses.glob = true;
+                return;
+            }
+            [TokenTree::Group(group), ..] if group.delimiter() == Delimiter::Brace => {
+                use_tree(group.stream(), uses);
+                return;
+            }
+            _ => return,
+        }
+    }
+}
+
+/// Collects the names in a braced use tree, e.g. `{a::B, C, d::*}`
+fn use_tree(tokens: TokenStream, uses: &mut Uses) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(name) => {
+                uses.names.insert(name.unraw().to_string());
+            }
+            TokenTree::Punct(star) if star.as_char() == '*' => uses.glob = true,
+            TokenTree::Group(group) => use_tree(group.stream(), uses),
+            _ => {}
+        }
+    }
+}
+
+/// Adds a feature for every binary (if it does not exist yet), and makes the
+/// binaries require it; returns `None` if the manifest cannot be updated
+/// automatically, i.e. when a binary is declared with `required-features`
+/// already
+pub fn update_manifest(manifest: &str, targets: &Targets, bins: &BTreeSet<&str>) -> Option<String> {
+    let mut manifest: Manifest = manifest.parse().ok()?;
+
+    for bin in bins.iter().filter(|bin| !targets.features.contains(**bin)) {
+        manifest.add_feature(bin)?;
+    }
+
+    for bin in bins
+        .iter()
+        .filter(|bin| !targets.gated_bins.contains(**bin))
+    {
+        let table = manifest.bin_mut(bin)?;
+        if table.contains_key("required-features") {
+            return None;
+        }
+        table.insert(
+            "required-features",
+            toml_edit::value(toml_edit::Array::from_iter([*bin])),
+        );
+    }
+
+    Some(manifest.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn library_uses() {
+        let src = "use mylib::{net::Client, util::*};
+            fn main() { mylib::run(); let x = mylib::Thing; }";
+        let files = [(PathBuf::from("src/main.rs"), syn::parse_str(src).unwrap())];
+        let found = uses("mylib", &files);
+
+        assert!(found.glob);
+        assert_eq!(
+            found.names,
+            BTreeSet::from(["Client", "Thing", "net", "run", "util"].map(str::to_owned))
+        );
+    }
+
+    #[test]
+    fn single_bin() {
+        let sources = BTreeMap::from([
+            (
+                "src/lib.rs",
+                "pub fn shared() {}
+                pub fn only_a() { helper() }
+                fn helper() {}
+                pub struct OnlyB;
+                impl OnlyB { pub fn new() -> OnlyB { OnlyB } }
+                pub fn tested() {}
+                pub fn both() -> OnlyB { OnlyB }",
+            ),
+            (
+                "src/bin/a.rs",
+                "use app::{only_a, shared}; fn main() { only_a(); app::tested(); }",
+            ),
+            (
+                "src/bin/b.rs",
+                "fn main() { app::OnlyB::new(); app::both(); app::shared(); }",
+            ),
+            ("tests/t.rs", "#[test] fn t() { app::tested(); }"),
+        ]);
+        let targets = Targets {
+            manifest_path: PathBuf::from("Cargo.toml"),
+            lib: Some(("app".to_owned(), PathBuf::from("src/lib.rs"))),
+            bins: ["a", "b"]
+                .map(|bin| (bin.to_owned(), PathBuf::from(format!("src/bin/{bin}.rs"))))
+                .to_vec(),
+            others: vec![("t".to_owned(), PathBuf::from("tests/t.rs"))],
+            features: BTreeSet::new(),
+            gated_bins: BTreeSet::new(),
+            published: false,
+        };
+
+        let gates = single_bin_items(&targets, |file| {
+            syn::parse_str(sources.get(file.to_str()?)?).ok()
+        });
+        let gates: Vec<_> = gates
+            .iter()
+            .map(|gate| (gate.bin.as_str(), gate.ident.as_str()))
+            .collect();
+        // `OnlyB` is used by `both`, which is gated for the same binary
+        assert_eq!(
+            gates,
+            [
+                ("a", "only_a"),
+                ("a", "helper"),
+                ("b", "OnlyB"),
+                ("b", "both")
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest() {
+        let targets = Targets {
+            manifest_path: PathBuf::from("Cargo.toml"),
+            lib: None,
+            bins: Vec::new(),
+            others: Vec::new(),
+            features: BTreeSet::from(["server".to_owned()]),
+            gated_bins: BTreeSet::new(),
+            published: false,
+        };
+        let manifest = "[package]\nname = \"app\"\n\n[features]\nserver = []\n\n[[bin]]\nname = \"server\"\npath = \"src/server.rs\"\n";
+
+        assert_eq!(
+            update_manifest(manifest, &targets, &BTreeSet::from(["client", "server"])).unwrap(),
+            "[package]\nname = \"app\"\n\n[features]\nserver = []\nclient = []\n\n[[bin]]\n\
+             name = \"server\"\npath = \"src/server.rs\"\nrequired-features = [\"server\"]\n\n\
+             [[bin]]\nname = \"client\"\nrequired-features = [\"client\"]\n"
+        );
+
+        // binaries that require features already are left to the user
+        let manifest = "[[bin]]\nname = \"server\"\nrequired-features = [\"tls\"]\n";
+        assert!(update_manifest(manifest, &targets, &BTreeSet::from(["server"])).is_none());
+    }
+}
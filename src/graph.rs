@@ -0,0 +1,648 @@
+//! This module builds a graph of the items of the crates in a package, and
+//! which items refer to which; references are found by name, so an item is
+//! considered used if any item that is in use mentions its name.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::ext::IdentExt;
+
+use crate::{
+    cauterize,
+    items::{idents, is_doc_hidden, item_attrs, item_name, item_vis, self_type_name},
+    modules,
+    unused::UnusedDiagnosticKind,
+};
+
+/// An item that can be removed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    /// The path of the item, starting with the name of its crate, e.g.
+    /// `mycrate::cli::run` or `mycrate::Config::new`
+    pub path: String,
+    pub file: PathBuf,
+    pub kind: UnusedDiagnosticKind,
+    pub ident: String,
+    /// Whether the item is used from outside of the package by definition,
+    /// i.e. it is exported by a library, or an entry point such as `main` or
+    /// a test
+    pub root: bool,
+    /// Whether an attribute keeps the item (see [`cauterize::protection`]),
+    /// including those given with `--keep-attribute`; these are roots too
+    pub kept: bool,
+    /// Whether the item is `#[doc(hidden)]`, or in a module or impl block that
+    /// is
+    pub hidden: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    /// The nodes each node refers to
+    pub edges: Vec<BTreeSet<usize>>,
+    /// The nodes that are referred to by code that is not part of any node,
+    /// e.g. trait definitions or impls for foreign types; these are kept
+    pub anchored: BTreeSet<usize>,
+}
+
+/// A crate to add to the graph
+pub struct Crate<'a> {
+    /// The name of the crate, as used in paths
+    pub name: &'a str,
+    pub root: &'a Path,
+    pub is_lib: bool,
+    /// Whether the crate root has a `main` function that is called on startup
+    pub has_main: bool,
+}
+
+impl Graph {
+    /// Builds the graph of the given crates (typically all of those in a
+    /// package); `parse` provides the syntax tree of a file, and the items
+    /// with one of the `keep_attributes` (see `--keep-attribute`) are roots
+    pub fn build(
+        crates: &[Crate],
+        keep_attributes: &[String],
+        parse: impl Fn(&Path) -> Option<syn::File>,
+    ) -> Graph {
+        let mut builder = Builder {
+            nodes: Vec::new(),
+            mentions: Vec::new(),
+            anchor_mentions: BTreeSet::new(),
+            impl_mentions: Vec::new(),
+        };
+
+        for krate in crates {
+            let roots = [krate.root.to_path_buf()];
+            let declarations = modules::declarations(&roots, &parse, Path::exists);
+            let Some(ast) = parse(krate.root) else {
+                continue;
+            };
+
+            let mut walker = Walker {
+                krate,
+                keep_attributes,
+                parse: &parse,
+                declarations: &declarations,
+                builder: &mut builder,
+            };
+            let mut module = Module {
+                file: krate.root,
+                inline_path: Vec::new(),
+                path: vec![krate.name.to_owned()],
+                exported: krate.is_lib,
+                hidden: ast.attrs.iter().any(is_doc_hidden),
+            };
+            walker.walk(&ast.items, &mut module);
+        }
+
+        builder.finish()
+    }
+
+    /// The nodes with the given path; a path ending in `::*` matches every item
+    /// in that module (or type), recursively
+    pub fn find(&self, path: &str) -> Vec<usize> {
+        let prefix = path.strip_suffix('*');
+        (0..self.nodes.len())
+            .filter(|&node| match prefix {
+                Some(prefix) => self.nodes[node].path.starts_with(prefix),
+                None => self.nodes[node].path == path,
+            })
+            .collect()
+    }
+
+    /// The nodes that can be reached from the given ones (including these), or
+    /// from any anchored node; the nodes in `removed` are left out
+    pub fn reachable(&self, roots: &BTreeSet<usize>, removed: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut reached = BTreeSet::new();
+        let mut pending: Vec<usize> = roots.iter().chain(&self.anchored).copied().collect();
+
+        while let Some(node) = pending.pop() {
+            if removed.contains(&node) || !reached.insert(node) {
+                continue;
+            }
+            pending.extend(&self.edges[node]);
+        }
+
+        reached
+    }
+
+    /// The given nodes, together with the associated functions of the types
+    /// among them (which go together with their type)
+    pub fn with_members(&self, nodes: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let types: BTreeSet<_> = nodes
+            .iter()
+            .map(|&node| &self.nodes[node])
+            .filter(|node| node.kind.is_type_definition())
+            .map(|node| node.path.as_str())
+            .collect();
+
+        (0..self.nodes.len())
+            .filter(|node| nodes.contains(node) || types.contains(self.owner(*node)))
+            .collect()
+    }
+
+    /// The path of the type an associated function belongs to, or of the module
+    /// any other item is in
+    pub fn owner(&self, node: usize) -> &str {
+        let path = &self.nodes[node].path;
+        path.rsplit_once("::").map_or("", |(owner, _)| owner)
+    }
+
+    /// The nodes that are in use, i.e. reachable from a root
+    pub fn live(&self) -> BTreeSet<usize> {
+        self.reachable(&self.roots(), &BTreeSet::new())
+    }
+
+    /// The nodes that would no longer be in use if the given nodes were
+    /// removed, not counting those that are not in use anyway
+    pub fn dead_without(&self, removed: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let still_live = self.reachable(&self.roots(), removed);
+
+        self.live()
+            .into_iter()
+            .filter(|node| !removed.contains(node) && !still_live.contains(node))
+            .collect()
+    }
+
+    fn roots(&self) -> BTreeSet<usize> {
+        (0..self.nodes.len())
+            .filter(|&node| self.nodes[node].root)
+            .collect()
+    }
+}
+
+/// Writes the graphs in the DOT format of Graphviz; items that are not in use
+/// are drawn dashed, and roots in bold
+pub fn to_dot(graphs: &[Graph]) -> String {
+    let mut dot = String::from("digraph items {\n    node [shape=box];\n");
+
+    let mut offset = 0;
+    for graph in graphs {
+        let live = graph.live();
+        for (index, node) in graph.nodes.iter().enumerate() {
+            let style = if node.root {
+                "bold"
+            } else if live.contains(&index) {
+                "solid"
+            } else {
+                "dashed"
+            };
+            dot.push_str(&format!(
+                "    n{} [label={:?}, tooltip={:?}, style={style}];\n",
+                offset + index,
+                node.path,
+                format!("{} in {}", node.kind.name(), node.file.display()),
+            ));
+        }
+        for (from, targets) in graph.edges.iter().enumerate() {
+            for to in targets {
+                dot.push_str(&format!("    n{} -> n{};\n", offset + from, offset + to));
+            }
+        }
+        offset += graph.nodes.len();
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Writes the graphs as JSON: a list of nodes (identified by their position in
+/// the list), and a list of edges between them
+pub fn to_json(graphs: &[Graph]) -> String {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+
+    for graph in graphs {
+        let offset = nodes.len();
+        let live = graph.live();
+        for (index, node) in graph.nodes.iter().enumerate() {
+            nodes.push(serde_json::json!({
+                "id": offset + index,
+                "path": node.path,
+                "kind": node.kind.name(),
+                "file": node.file,
+                "root": node.root,
+                "dead": !live.contains(&index),
+            }));
+        }
+        for (from, targets) in graph.edges.iter().enumerate() {
+            edges.extend(
+                targets
+                    .iter()
+                    .map(|to| serde_json::json!({ "from": offset + from, "to": offset + to })),
+            );
+        }
+    }
+
+    serde_json::json!({ "nodes": nodes, "edges": edges }).to_string()
+}
+
+struct Builder {
+    nodes: Vec<Node>,
+    /// The names mentioned by each node
+    mentions: Vec<BTreeSet<String>>,
+    /// The names mentioned by code that is not part of any node
+    anchor_mentions: BTreeSet<String>,
+    /// The names mentioned by impl blocks, by the name of their self type
+    impl_mentions: Vec<(String, BTreeSet<String>)>,
+}
+
+impl Builder {
+    fn add(&mut self, node: Node, tokens: TokenStream) {
+        let mut mentioned = BTreeSet::new();
+        idents(tokens, &mut mentioned);
+        mentioned.remove(&node.ident);
+
+        self.nodes.push(node);
+        self.mentions.push(mentioned);
+    }
+
+    fn finish(mut self) -> Graph {
+        let mut by_ident = BTreeMap::<&str, Vec<usize>>::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            by_ident.entry(&node.ident).or_default().push(index);
+        }
+
+        // impl blocks belong to their self type; if that is not a node in the
+        // graph, the impl is kept regardless
+        for (self_type, mentioned) in self.impl_mentions {
+            match by_ident.get(self_type.as_str()) {
+                Some(types) => {
+                    for &node in types {
+                        self.mentions[node].extend(mentioned.iter().cloned());
+                    }
+                }
+                None => self.anchor_mentions.extend(mentioned),
+            }
+        }
+
+        let resolve = |names: &BTreeSet<String>| -> BTreeSet<usize> {
+            names
+                .iter()
+                .filter_map(|name| by_ident.get(name.as_str()))
+                .flatten()
+                .copied()
+                .collect()
+        };
+
+        let edges = self.mentions.iter().map(resolve).collect();
+        let anchored = resolve(&self.anchor_mentions);
+
+        Graph {
+            edges,
+            anchored,
+            nodes: self.nodes,
+        }
+    }
+}
+
+struct Module<'a> {
+    file: &'a Path,
+    /// The inline modules within the file, outermost first
+    inline_path: Vec<String>,
+    /// The path of the module, starting with the crate name
+    path: Vec<String>,
+    /// Whether the public items of this module can be used by other crates
+    exported: bool,
+    /// Whether this module, or one of the modules it is in, is `#[doc(hidden)]`
+    hidden: bool,
+}
+
+struct Walker<'a, P> {
+    krate: &'a Crate<'a>,
+    keep_attributes: &'a [String],
+    parse: &'a P,
+    declarations: &'a [modules::Declaration],
+    builder: &'a mut Builder,
+}
+
+impl<P: Fn(&Path) -> Option<syn::File>> Walker<'_, P> {
+    fn walk(&mut self, items: &[syn::Item], module: &mut Module) {
+        for item in items {
+            match item {
+                syn::Item::Mod(inner) => self.walk_module(inner, module),
+                syn::Item::Impl(block) => self.walk_impl(block, module),
+                // imports do not keep an item in use, unless they re-export it
+                syn::Item::Use(import) if !is_pub(&import.vis) => {}
+                item => {
+                    let Some((kind, ident, public)) = node_name(item) else {
+                        idents(item.to_token_stream(), &mut self.builder.anchor_mentions);
+                        continue;
+                    };
+
+                    let kept = self.is_kept(item_attrs(item));
+                    let root = (module.exported && public)
+                        || self.is_entry_point(item, &ident, module)
+                        || kept;
+                    let node = Node {
+                        path: path_of(&module.path, &ident),
+                        file: module.file.to_path_buf(),
+                        kind,
+                        ident,
+                        root,
+                        kept,
+                        hidden: module.hidden || item_attrs(item).iter().any(is_doc_hidden),
+                    };
+                    self.builder.add(node, item.to_token_stream());
+                }
+            }
+        }
+    }
+
+    fn walk_module(&mut self, inner: &syn::ItemMod, module: &mut Module) {
+        let name = inner.ident.unraw().to_string();
+        let exported = module.exported && is_pub(&inner.vis);
+        // inner attributes of an inline module are among its attributes
+        let hidden = module.hidden || inner.attrs.iter().any(is_doc_hidden);
+
+        match &inner.content {
+            Some((_, items)) => {
+                module.inline_path.push(name.clone());
+                module.path.push(name);
+                let outer_exported = std::mem::replace(&mut module.exported, exported);
+                let outer_hidden = std::mem::replace(&mut module.hidden, hidden);
+
+                self.walk(items, module);
+
+                module.hidden = outer_hidden;
+                module.exported = outer_exported;
+                module.path.pop();
+                module.inline_path.pop();
+            }
+            None => {
+                let Some(declaration) = self.declarations.iter().find(|declaration| {
+                    declaration.parent == module.file
+                        && declaration.inline_path == module.inline_path
+                        && declaration.name == name
+                }) else {
+                    return;
+                };
+                let Some(ast) = (self.parse)(&declaration.file) else {
+                    return;
+                };
+
+                let mut path = module.path.clone();
+                path.push(name);
+                let mut inner = Module {
+                    file: &declaration.file,
+                    inline_path: Vec::new(),
+                    path,
+                    exported,
+                    hidden: hidden || ast.attrs.iter().any(is_doc_hidden),
+                };
+                self.walk(&ast.items, &mut inner);
+            }
+        }
+    }
+
+    /// Inherent associated functions are nodes themselves; everything else in
+    /// an impl block belongs to its self type
+    fn walk_impl(&mut self, block: &syn::ItemImpl, module: &Module) {
+        let Some(self_type) = self_type_name(&block.self_ty) else {
+            idents(block.to_token_stream(), &mut self.builder.anchor_mentions);
+            return;
+        };
+
+        let mut rest = BTreeSet::new();
+        for item in &block.items {
+            match item {
+                syn::ImplItem::Fn(function) if block.trait_.is_none() => {
+                    let ident = function.sig.ident.unraw().to_string();
+                    let kept = self.is_kept(&function.attrs);
+                    let root = (module.exported && is_pub(&function.vis)) || kept;
+                    let node = Node {
+                        path: path_of(&module.path, &format!("{self_type}::{ident}")),
+                        file: module.file.to_path_buf(),
+                        kind: UnusedDiagnosticKind::AssociatedFunction,
+                        ident,
+                        root,
+                        kept,
+                        hidden: module.hidden
+                            || block.attrs.iter().chain(&function.attrs).any(is_doc_hidden),
+                    };
+                    // a method refers to its type, even if only through `Self`
+                    let mut tokens = function.to_token_stream();
+                    tokens.extend(block.self_ty.to_token_stream());
+                    self.builder.add(node, tokens);
+                }
+                item => idents(item.to_token_stream(), &mut rest),
+            }
+        }
+        idents(block.generics.to_token_stream(), &mut rest);
+        if let Some((_, trait_, _)) = &block.trait_ {
+            idents(trait_.to_token_stream(), &mut rest);
+        }
+        idents(block.self_ty.to_token_stream(), &mut rest);
+        rest.remove(&self_type);
+
+        self.builder.impl_mentions.push((self_type, rest));
+    }
+
+    /// Whether an item is called by the compiler or the test harness, or
+    /// exported as a symbol
+    fn is_entry_point(&self, item: &syn::Item, ident: &str, module: &Module) -> bool {
+        let is_main = self.krate.has_main
+            && ident == "main"
+            && module.path.len() == 1
+            && matches!(item, syn::Item::Fn(_));
+
+        is_main
+            || item_attrs(item).iter().any(|attr| {
+                let path = attr.path();
+                path.segments
+                    .last()
+                    .is_some_and(|last| last.ident == "test" || last.ident == "bench")
+                    || path.is_ident("no_mangle")
+                    || path.is_ident("export_name")
+                    || path.is_ident("proc_macro")
+                    || path.is_ident("proc_macro_derive")
+                    || path.is_ident("proc_macro_attribute")
+            })
+    }
+
+    fn is_kept(&self, attrs: &[syn::Attribute]) -> bool {
+        cauterize::protection(attrs, self.keep_attributes).is_some()
+    }
+}
+
+fn path_of(module: &[String], ident: &str) -> String {
+    format!("{}::{ident}", module.join("::"))
+}
+
+fn is_pub(vis: &syn::Visibility) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+}
+
+/// The kind and name of the items that can be nodes, and whether they are
+/// public
+fn node_name(item: &syn::Item) -> Option<(UnusedDiagnosticKind, String, bool)> {
+    let (kind, ident) = item_name(item)?;
+    let public = match item {
+        // exported macros can be used by other crates
+        syn::Item::Macro(obj) => obj
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("macro_export")),
+        item => item_vis(item).is_some_and(is_pub),
+    };
+
+    Some((kind, ident, public))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dead_items() {
+        let lib = "pub fn api() -> u8 { helper() + Inner::new().0 }
+            fn helper() -> u8 { CONST }
+            const CONST: u8 = 1;
+            struct Inner(u8);
+            impl Inner { fn new() -> Self { Inner(0) } }
+            impl Default for Inner { fn default() -> Self { Inner(DEFAULT) } }
+            const DEFAULT: u8 = 2;
+            fn unused() {}
+            mod private { pub fn hidden() {} }";
+        let bin = "fn main() { mylib::api(); } #[test] fn check() { tested() } fn tested() {}";
+        let crates = [
+            Crate {
+                name: "mylib",
+                root: Path::new("src/lib.rs"),
+                is_lib: true,
+                has_main: false,
+            },
+            Crate {
+                name: "app",
+                root: Path::new("src/main.rs"),
+                is_lib: false,
+                has_main: true,
+            },
+        ];
+        let graph = Graph::build(&crates, &[], |file| match file.to_str()? {
+            "src/lib.rs" => syn::parse_str(lib).ok(),
+            "src/main.rs" => syn::parse_str(bin).ok(),
+            _ => None,
+        });
+        let paths = |nodes: BTreeSet<usize>| -> Vec<&str> {
+            nodes
+                .into_iter()
+                .map(|node| graph.nodes[node].path.as_str())
+                .collect()
+        };
+
+        let all = (0..graph.nodes.len()).collect::<BTreeSet<_>>();
+        let live = graph.live();
+        assert_eq!(
+            paths(all.difference(&live).copied().collect()),
+            ["mylib::unused", "mylib::private::hidden"]
+        );
+
+        let api = graph.find("mylib::api").into_iter().collect();
+        assert_eq!(
+            paths(graph.dead_without(&api)),
+            [
+                "mylib::helper",
+                "mylib::CONST",
+                "mylib::Inner",
+                "mylib::Inner::new",
+                "mylib::DEFAULT"
+            ]
+        );
+        assert_eq!(graph.find("app::*").len(), 3);
+
+        let inner = graph.find("mylib::Inner").into_iter().collect();
+        assert_eq!(
+            paths(graph.with_members(&inner)),
+            ["mylib::Inner", "mylib::Inner::new"]
+        );
+    }
+
+    #[test]
+    fn proc_macro_crate() {
+        let src = "#[proc_macro_derive(Thing)]
+            pub fn derive(input: TokenStream) -> TokenStream { expand(input) }
+            fn expand(input: TokenStream) -> TokenStream { input }
+            pub fn unused() {}";
+        let crates = [Crate {
+            name: "mymacros",
+            root: Path::new("src/lib.rs"),
+            is_lib: false,
+            has_main: false,
+        }];
+        let graph = Graph::build(&crates, &[], |_| syn::parse_str(src).ok());
+
+        let live = graph.live();
+        let dead: Vec<_> = (0..graph.nodes.len())
+            .filter(|node| !live.contains(node))
+            .map(|node| graph.nodes[node].path.as_str())
+            .collect();
+        assert_eq!(dead, ["mymacros::unused"]);
+    }
+
+    #[test]
+    fn hidden_items() {
+        let src = "pub fn visible() {}
+            #[doc(hidden)] pub fn hidden() {}
+            #[doc(hidden)] pub mod __private { pub fn helper() {} pub mod de { pub fn inner() {} } }
+            pub struct Foo;
+            #[doc(hidden)] impl Foo { pub fn method() {} }";
+        let crates = [Crate {
+            name: "serde",
+            root: Path::new("src/lib.rs"),
+            is_lib: true,
+            has_main: false,
+        }];
+        let graph = Graph::build(&crates, &[], |_| syn::parse_str(src).ok());
+
+        let hidden: Vec<_> = graph
+            .nodes
+            .iter()
+            .filter(|node| node.hidden)
+            .map(|node| node.path.as_str())
+            .collect();
+        assert_eq!(
+            hidden,
+            [
+                "serde::hidden",
+                "serde::__private::helper",
+                "serde::__private::de::inner",
+                "serde::Foo::method"
+            ]
+        );
+    }
+
+    #[test]
+    fn formats() {
+        let crates = [Crate {
+            name: "app",
+            root: Path::new("src/main.rs"),
+            is_lib: false,
+            has_main: true,
+        }];
+        let graphs = [Graph::build(&crates, &[], |_| {
+            syn::parse_str("fn main() { used() } fn used() {} fn unused() {}").ok()
+        })];
+
+        assert_eq!(
+            to_dot(&graphs),
+            "digraph items {
+    node [shape=box];
+    n0 [label=\"app::main\", tooltip=\"function in src/main.rs\", style=bold];
+    n1 [label=\"app::used\", tooltip=\"function in src/main.rs\", style=solid];
+    n2 [label=\"app::unused\", tooltip=\"function in src/main.rs\", style=dashed];
+    n0 -> n1;
+}
+"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&graphs)).unwrap();
+        assert_eq!(json["nodes"][2]["path"], "app::unused");
+        assert_eq!(json["nodes"][2]["dead"], true);
+        assert_eq!(json["edges"], serde_json::json!([{ "from": 0, "to": 1 }]));
+    }
+}
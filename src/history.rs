@@ -0,0 +1,203 @@
+//! This module keeps the findings of every run in the target directory (under
+//! `minify/history`), so `cargo minify diff-report` can show how these changed
+//! between two runs, to track the progress of a cleanup over time.
+
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    records,
+};
+
+/// An unused item that a run reported
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    /// The file of the item, relative to the workspace root
+    pub file: PathBuf,
+    /// The kind of the item, as accepted by `--kinds`
+    pub kind: String,
+    pub name: String,
+}
+
+/// How a finding changed between two runs
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Status {
+    /// Reported by the new run only
+    Appeared,
+    /// Reported by the old run only, while the item is still there (it may be
+    /// in use again, or left out of the analysis)
+    Disappeared,
+    /// Reported by the old run only, and the item is gone
+    Fixed,
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Appeared => "appeared",
+            Status::Disappeared => "disappeared",
+            Status::Fixed => "fixed",
+        }
+    }
+}
+
+fn history_directory(target_directory: &Path) -> PathBuf {
+    target_directory.join("minify").join("history")
+}
+
+/// Stores the findings of a run of the workspace at the given root, and
+/// returns the id of the run (the time at which it was recorded, in seconds
+/// since the Unix epoch)
+pub fn record(
+    target_directory: &Path,
+    workspace_root: &Path,
+    findings: &BTreeSet<Finding>,
+) -> io::Result<String> {
+    let directory = history_directory(target_directory);
+    std::fs::create_dir_all(&directory)?;
+
+    let time = records::now();
+    let findings: Vec<_> = findings
+        .iter()
+        .map(|finding| {
+            serde_json::json!({
+                "file": finding.file,
+                "kind": finding.kind,
+                "name": finding.name,
+            })
+        })
+        .collect();
+    let content = serde_json::json!({
+        "time": time,
+        "workspace": workspace_root,
+        "findings": findings,
+    });
+
+    records::write(&directory, time, &content)
+}
+
+/// The ids of the recorded runs of the workspace at the given root, oldest
+/// first; several workspaces may share a target directory, and thereby its
+/// history
+pub fn runs(target_directory: &Path, workspace_root: &Path) -> io::Result<Vec<String>> {
+    let directory = history_directory(target_directory);
+    let workspace_of = |id: &str| -> Option<PathBuf> {
+        let content = std::fs::read_to_string(directory.join(format!("{id}.json"))).ok()?;
+        let record: serde_json::Value = serde_json::from_str(&content).ok()?;
+        Some(PathBuf::from(record["workspace"].as_str()?))
+    };
+
+    Ok(records::ids(&directory)?
+        .into_iter()
+        .filter(|id| workspace_of(id).is_some_and(|workspace| workspace == workspace_root))
+        .collect())
+}
+
+/// Reads the findings of a run, given by its id or by the path of its file
+pub fn load(target_directory: &Path, run: &str) -> Result<BTreeSet<Finding>> {
+    let path = match Path::new(run) {
+        path if path.is_file() => path.to_path_buf(),
+        _ => history_directory(target_directory).join(format!("{run}.json")),
+    };
+    let content = std::fs::read_to_string(&path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Error::UnknownRun(run.to_owned()),
+        _ => err.into(),
+    })?;
+    let record: serde_json::Value = serde_json::from_str(&content)?;
+
+    Ok(record["findings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|finding| {
+            Some(Finding {
+                file: PathBuf::from(finding["file"].as_str()?),
+                kind: finding["kind"].as_str()?.to_owned(),
+                name: finding["name"].as_str()?.to_owned(),
+            })
+        })
+        .collect())
+}
+
+/// Compares the findings of two runs; `exists` tells whether the item of a
+/// finding is still in the code, to tell fixed findings from those that
+/// disappeared otherwise
+pub fn compare<'a>(
+    old: &'a BTreeSet<Finding>,
+    new: &'a BTreeSet<Finding>,
+    exists: impl Fn(&Finding) -> bool,
+) -> Vec<(Status, &'a Finding)> {
+    let appeared = new
+        .difference(old)
+        .map(|finding| (Status::Appeared, finding));
+    let gone = old.difference(new).map(|finding| {
+        let status = if exists(finding) {
+            Status::Disappeared
+        } else {
+            Status::Fixed
+        };
+        (status, finding)
+    });
+
+    appeared.chain(gone).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn finding(name: &str) -> Finding {
+        Finding {
+            file: PathBuf::from("src/lib.rs"),
+            kind: "function".to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn comparison() {
+        let old = [finding("kept"), finding("removed"), finding("used")].into();
+        let new = [finding("kept"), finding("new")].into();
+
+        let changes = compare(&old, &new, |finding| finding.name != "removed");
+        assert_eq!(
+            changes,
+            [
+                (Status::Appeared, &finding("new")),
+                (Status::Fixed, &finding("removed")),
+                (Status::Disappeared, &finding("used")),
+            ]
+        );
+    }
+
+    #[test]
+    fn recording() {
+        let target_directory =
+            std::env::temp_dir().join(format!("minify-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&target_directory);
+
+        let workspace = Path::new("/workspace");
+        let other = Path::new("/other");
+        let findings = [finding("foo")].into();
+        let first = record(&target_directory, workspace, &findings).unwrap();
+        let unrelated = record(&target_directory, other, &findings).unwrap();
+        let second = record(&target_directory, workspace, &BTreeSet::new()).unwrap();
+
+        assert_eq!(
+            runs(&target_directory, workspace).unwrap(),
+            [first.clone(), second]
+        );
+        assert_eq!(runs(&target_directory, other).unwrap(), [unrelated]);
+        assert_eq!(load(&target_directory, &first).unwrap(), findings);
+        assert!(matches!(
+            load(&target_directory, "0"),
+            Err(Error::UnknownRun(_))
+        ));
+
+        std::fs::remove_dir_all(&target_directory).unwrap();
+    }
+}
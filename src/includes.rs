@@ -0,0 +1,155 @@
+//! This module finds the files that are pulled into a crate using `include!`,
+//! rather than being part of the module tree; changes to these may affect
+//! several places, and files generated by build scripts are overwritten on the
+//! next build anyway.
+
+use std::path::{Path, PathBuf};
+
+use syn::{punctuated::Punctuated, visit::Visit, Expr, Lit, Macro, Token};
+
+/// A file that is included using `include!`
+#[derive(Debug, PartialEq)]
+pub struct Include {
+    /// The file that contains the `include!`
+    pub includer: PathBuf,
+    pub file: Included,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Included {
+    /// A file relative to the includer, e.g. `include!("bindings.rs")`
+    Path(PathBuf),
+    /// A file in the output directory of the build script, e.g.
+    /// `include!(concat!(env!("OUT_DIR"), "/bindings.rs"))`
+    OutDir(PathBuf),
+}
+
+impl Include {
+    /// Whether this refers to the given file; files in the output directory
+    /// are matched by their path relative to it
+    pub fn refers_to(&self, file_name: &Path) -> bool {
+        match &self.file {
+            Included::Path(path) => match (path.canonicalize(), file_name.canonicalize()) {
+                (Ok(path), Ok(file_name)) => path == file_name,
+                _ => path == file_name,
+            },
+            Included::OutDir(path) => {
+                let Some(out_dir) = file_name
+                    .ancestors()
+                    .find(|dir| dir.file_name() == Some("out".as_ref()))
+                else {
+                    return false;
+                };
+                file_name
+                    .strip_prefix(out_dir)
+                    .is_ok_and(|rest| rest == path)
+            }
+        }
+    }
+}
+
+/// Finds the `include!`s in the given files
+pub fn includes(files: &[(PathBuf, syn::File)]) -> Vec<Include> {
+    let mut includes = Vec::new();
+    for (file_name, ast) in files {
+        let mut visitor = IncludeVisitor { found: Vec::new() };
+        visitor.visit_file(ast);
+
+        let dir = file_name.parent().unwrap_or(Path::new(""));
+        includes.extend(visitor.found.into_iter().map(|file| Include {
+            includer: file_name.clone(),
+            file: match file {
+                Included::Path(path) => Included::Path(dir.join(path)),
+                out_dir => out_dir,
+            },
+        }));
+    }
+
+    includes
+}
+
+struct IncludeVisitor {
+    found: Vec<Included>,
+}
+
+impl<'ast> Visit<'ast> for IncludeVisitor {
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        if mac.path.is_ident("include") {
+            if let Some(file) = mac.parse_body::<Expr>().ok().as_ref().and_then(included) {
+                self.found.push(file);
+            }
+        }
+    }
+}
+
+/// Interprets the argument of an `include!`
+fn included(expr: &Expr) -> Option<Included> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(path) => Some(Included::Path(path.value().into())),
+            _ => None,
+        },
+        Expr::Macro(concat) if concat.mac.path.is_ident("concat") => {
+            let args = concat
+                .mac
+                .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                .ok()?;
+            let mut args = args.iter();
+
+            let Some(Expr::Macro(env)) = args.next() else {
+                return None;
+            };
+            let var: syn::LitStr = env.mac.parse_body().ok()?;
+            if !env.mac.path.is_ident("env") || var.value() != "OUT_DIR" {
+                return None;
+            }
+
+            let mut path = String::new();
+            for arg in args {
+                let Expr::Lit(syn::ExprLit {
+                    lit: Lit::Str(part),
+                    ..
+                }) = arg
+                else {
+                    return None;
+                };
+                path.push_str(&part.value());
+            }
+
+            Some(Included::OutDir(path.trim_start_matches('/').into()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn include_targets() {
+        let src = "include!(concat!(env!(\"OUT_DIR\"), \"/bindings.rs\"));
+            mod a { include!(\"inc/a.rs\"); }
+            fn f() { include!(concat!(env!(\"HOME\"), \"/x.rs\")); }";
+        let files = [(PathBuf::from("src/lib.rs"), syn::parse_str(src).unwrap())];
+
+        let includes = includes(&files);
+        assert_eq!(
+            includes,
+            vec![
+                Include {
+                    includer: "src/lib.rs".into(),
+                    file: Included::OutDir("bindings.rs".into()),
+                },
+                Include {
+                    includer: "src/lib.rs".into(),
+                    file: Included::Path("src/inc/a.rs".into()),
+                },
+            ]
+        );
+
+        assert!(includes[0].refers_to(Path::new("/ws/target/debug/build/x-0123/out/bindings.rs")));
+        assert!(!includes[0].refers_to(Path::new("/ws/target/debug/build/x-0123/out/other.rs")));
+        assert!(includes[1].refers_to(Path::new("src/inc/a.rs")));
+    }
+}
@@ -0,0 +1,119 @@
+//! This module handles interrupts (Ctrl-C): instead of exiting right away,
+//! which could leave a file half-written, the request is recorded, `cargo
+//! check` is stopped, and the program stops at the next safe point.
+
+use std::{
+    process::{Child, ChildStdout, Command},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// The running child process, if any
+static CHILD: Mutex<Option<Child>> = Mutex::new(None);
+
+/// Installs the interrupt handler; a second interrupt exits immediately
+#[cfg(not(target_arch = "wasm32"))]
+pub fn install() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("interrupted, stopping...");
+        kill_child();
+    });
+
+    if let Err(err) = result {
+        tracing::warn!("could not install interrupt handler: {err}");
+    }
+}
+
+/// There are no interrupts in the browser, where only the engine is used
+#[cfg(target_arch = "wasm32")]
+pub fn install() {}
+
+/// Whether an interrupt was received
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Spawns a child process in its own process group, so that it (and anything
+/// it starts) can be stopped as a whole when an interrupt is received; returns
+/// its output, which must be piped
+pub fn spawn(command: &mut Command) -> std::io::Result<ChildStdout> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+
+    let mut child = command.spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| std::io::Error::other("the output of the child process is not piped"))?;
+    *CHILD.lock().unwrap_or_else(PoisonError::into_inner) = Some(child);
+    if requested() {
+        kill_child();
+    }
+
+    Ok(stdout)
+}
+
+/// Waits for the running child process to exit, once its output has been read
+pub fn wait_child() {
+    // the lock is not held while waiting, which would keep an interrupt (or
+    // the timeout) from going through until the child exits
+    let child = CHILD.lock().unwrap_or_else(PoisonError::into_inner).take();
+    if let Some(mut child) = child {
+        let _ = child.wait();
+    }
+}
+
+/// Stops the running child process, if any; on unix, anything it started is
+/// stopped as well, elsewhere that stops once the child is gone
+pub fn kill_child() {
+    let Some(mut child) = CHILD.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+        return;
+    };
+
+    // the child is in its own process group, so it does not receive the
+    // interrupt from the terminal; on other platforms it does
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGTERM);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    // reap the child, so it does not linger
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_process_group() {
+        use std::{io::Read, process::Stdio, time::Instant};
+
+        // the other tests that run checks would lose their child process
+        let _checks = crate::unused::CHECKS.write();
+
+        // the shell starts `sleep`, which would keep the output open
+        let mut stdout = spawn(
+            Command::new("sh")
+                .args(["-c", "sleep 30; echo done"])
+                .stdout(Stdio::piped()),
+        )
+        .unwrap();
+        let start = Instant::now();
+        kill_child();
+
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "");
+        assert!(start.elapsed().as_secs() < 10);
+        assert!(CHILD.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,110 @@
+//! This module names the items of a syntax tree, and finds the names that
+//! they mention; it is shared by the modules that look items up by the name
+//! that the compiler reports, and those that find references between them.
+
+use std::collections::BTreeSet;
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::ext::IdentExt;
+
+use crate::unused::UnusedDiagnosticKind;
+
+/// The kind and name of an item that the compiler can report as unused
+pub(crate) fn item_name(item: &syn::Item) -> Option<(UnusedDiagnosticKind, String)> {
+    use UnusedDiagnosticKind::*;
+
+    let (kind, ident) = match item {
+        syn::Item::Const(obj) => (Constant, &obj.ident),
+        syn::Item::Enum(obj) => (Enum, &obj.ident),
+        syn::Item::Fn(obj) => (Function, &obj.sig.ident),
+        syn::Item::Macro(syn::ItemMacro {
+            ident: Some(ident), ..
+        }) => (MacroDefinition, ident),
+        syn::Item::Static(obj) => (Static, &obj.ident),
+        syn::Item::Struct(obj) => (Struct, &obj.ident),
+        syn::Item::Type(obj) => (TypeAlias, &obj.ident),
+        syn::Item::Union(obj) => (Union, &obj.ident),
+        _ => return None,
+    };
+
+    Some((kind, ident.unraw().to_string()))
+}
+
+pub(crate) fn item_attrs(item: &syn::Item) -> &[syn::Attribute] {
+    use syn::Item;
+
+    match item {
+        Item::Const(obj) => &obj.attrs,
+        Item::Enum(obj) => &obj.attrs,
+        Item::ExternCrate(obj) => &obj.attrs,
+        Item::Fn(obj) => &obj.attrs,
+        Item::ForeignMod(obj) => &obj.attrs,
+        Item::Impl(obj) => &obj.attrs,
+        Item::Macro(obj) => &obj.attrs,
+        Item::Mod(obj) => &obj.attrs,
+        Item::Static(obj) => &obj.attrs,
+        Item::Struct(obj) => &obj.attrs,
+        Item::Trait(obj) => &obj.attrs,
+        Item::TraitAlias(obj) => &obj.attrs,
+        Item::Type(obj) => &obj.attrs,
+        Item::Union(obj) => &obj.attrs,
+        Item::Use(obj) => &obj.attrs,
+        _ => &[],
+    }
+}
+
+/// The visibility of an item, if it has one (macros by example do not)
+pub(crate) fn item_vis(item: &syn::Item) -> Option<&syn::Visibility> {
+    use syn::Item;
+
+    match item {
+        Item::Const(obj) => Some(&obj.vis),
+        Item::Enum(obj) => Some(&obj.vis),
+        Item::ExternCrate(obj) => Some(&obj.vis),
+        Item::Fn(obj) => Some(&obj.vis),
+        Item::Mod(obj) => Some(&obj.vis),
+        Item::Static(obj) => Some(&obj.vis),
+        Item::Struct(obj) => Some(&obj.vis),
+        Item::Trait(obj) => Some(&obj.vis),
+        Item::TraitAlias(obj) => Some(&obj.vis),
+        Item::Type(obj) => Some(&obj.vis),
+        Item::Union(obj) => Some(&obj.vis),
+        Item::Use(obj) => Some(&obj.vis),
+        _ => None,
+    }
+}
+
+/// Whether an attribute is `#[doc(hidden)]` (or `#![doc(hidden)]`)
+pub(crate) fn is_doc_hidden(attr: &syn::Attribute) -> bool {
+    let mut hidden = false;
+    if attr.path().is_ident("doc") {
+        let _ = attr.parse_nested_meta(|meta| {
+            hidden |= meta.path.is_ident("hidden");
+            Ok(())
+        });
+    }
+    hidden
+}
+
+/// The name of the type an impl block is for, e.g. `Foo` for `impl Foo` or
+/// `impl Display for &crate::Foo`
+pub(crate) fn self_type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => Some(path.path.segments.last()?.ident.unraw().to_string()),
+        syn::Type::Reference(reference) => self_type_name(&reference.elem),
+        _ => None,
+    }
+}
+
+/// Collects every identifier in the given tokens, including those in groups
+pub(crate) fn idents(tokens: TokenStream, found: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(name) => {
+                found.insert(name.unraw().to_string());
+            }
+            TokenTree::Group(group) => idents(group.stream(), found),
+            _ => {}
+        }
+    }
+}
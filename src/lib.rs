@@ -0,0 +1,246 @@
+//! The library of `cargo minify`: the [`engine`], which removes the unused
+//! items from code in memory, and the modules that it needs; these are also
+//! used by the command line interface, but are not part of the API.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::{semver::Version, Edition};
+
+use crate::cauterize::Mode;
+
+#[doc(hidden)]
+pub mod backend;
+#[doc(hidden)]
+pub mod bloat;
+#[doc(hidden)]
+pub mod block_macros;
+#[doc(hidden)]
+pub mod cargo;
+#[doc(hidden)]
+pub mod cauterize;
+#[doc(hidden)]
+pub mod cfg;
+#[doc(hidden)]
+pub mod doctests;
+pub mod engine;
+#[doc(hidden)]
+pub mod error;
+#[doc(hidden)]
+pub mod expansion;
+#[doc(hidden)]
+pub mod features;
+#[doc(hidden)]
+pub mod graph;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod includes;
+#[doc(hidden)]
+pub mod interrupt;
+mod items;
+#[doc(hidden)]
+pub mod lints;
+#[doc(hidden)]
+pub mod macros;
+#[doc(hidden)]
+pub mod manifest;
+#[doc(hidden)]
+pub mod modules;
+#[doc(hidden)]
+pub mod progress;
+#[doc(hidden)]
+pub mod records;
+#[doc(hidden)]
+pub mod resolver;
+#[doc(hidden)]
+pub mod sizes;
+#[doc(hidden)]
+pub mod unused;
+#[doc(hidden)]
+pub mod vcs;
+#[doc(hidden)]
+pub mod vendor;
+
+#[doc(hidden)]
+pub enum CrateResolutionOptions<'a> {
+    Root,
+    Workspace {
+        exclude: &'a [String],
+        /// Whether to include path dependencies outside of the workspace
+        follow_path_deps: bool,
+    },
+    Package {
+        packages: &'a [String],
+    },
+}
+
+#[doc(hidden)]
+/// Which of the files of the selected packages may be minified, from the
+/// command line and the `[workspace.metadata.minify]` table of the manifest
+pub struct FileResolutionOptions {
+    /// Globs of the files to minify (all files if there are none)
+    pub files: Vec<String>,
+    /// Globs of the files to leave alone
+    pub ignored: Vec<String>,
+    /// The (canonical) directories of which no files are minified
+    pub excluded_dirs: Vec<PathBuf>,
+    /// The extensions of the files to minify (all extensions if there are none)
+    pub extensions: Vec<String>,
+    /// The (canonical) target directory, in which build scripts write their
+    /// output
+    pub target_directory: PathBuf,
+}
+
+impl FileResolutionOptions {
+    pub fn is_included(&self, file_name: &str) -> bool {
+        self.why_excluded(file_name).is_none()
+    }
+
+    /// Completes the sentence "the file is left alone, since it ...", if it is
+    pub fn why_excluded(&self, file_name: &str) -> Option<&'static str> {
+        let path = Path::new(file_name);
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if is_build_output(&canonical, &self.target_directory) {
+            return Some("is written by a build script (in its OUT_DIR)");
+        }
+        // with only negated globs, all other files are selected
+        let only_negated = self.files.iter().all(|glob| glob.starts_with('!'));
+        if !last_match(&self.files, file_name).unwrap_or(only_negated) {
+            return Some("is not selected by --file");
+        }
+        if last_match(&self.ignored, file_name).unwrap_or(false) {
+            return Some("is excluded by --ignore");
+        }
+        if self
+            .excluded_dirs
+            .iter()
+            .any(|dir| canonical.starts_with(dir))
+        {
+            return Some("lies in a directory excluded by --exclude-dir");
+        }
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        if !self.extensions.is_empty()
+            && !extension.is_some_and(|extension| self.extensions.iter().any(|e| e == extension))
+        {
+            return Some("does not have an extension selected by --only-ext");
+        }
+
+        None
+    }
+}
+
+/// Whether the last glob that matches a file is a regular one (`Some(true)`)
+/// or one that is negated with a leading `!` (`Some(false)`), if any matches
+fn last_match(globs: &[String], file_name: &str) -> Option<bool> {
+    globs
+        .iter()
+        .rev()
+        .find_map(|glob| match glob.strip_prefix('!') {
+            Some(glob) => glob_match::glob_match(glob, file_name).then_some(false),
+            None => glob_match::glob_match(glob, file_name).then_some(true),
+        })
+}
+
+/// Whether a (canonical) file lies in the `OUT_DIR` of a build script, i.e. in
+/// `build/<package>-<hash>/out` somewhere in the target directory
+fn is_build_output(file: &Path, target_directory: &Path) -> bool {
+    let Ok(path) = file.strip_prefix(target_directory) else {
+        return false;
+    };
+    let components: Vec<_> = path.components().map(|c| c.as_os_str()).collect();
+    components
+        .windows(3)
+        .any(|window| window[0] == "build" && window[2] == "out")
+}
+
+#[doc(hidden)]
+pub struct CauterizeOptions {
+    pub mode: Mode,
+    pub tombstones: bool,
+    pub max_blank_lines: usize,
+    /// The maximum number of items to remove in a single run
+    pub max_removals: Option<usize>,
+    pub unit_structs: bool,
+    /// Whether to remove the empty trait impls of removed types that live in
+    /// other files than these types (see `--remove-marker-impls`)
+    pub remove_marker_impls: bool,
+    pub strip_docs: bool,
+    pub strip_tests: bool,
+    pub remove_ignored_tests: bool,
+    pub remove_unused_allows: bool,
+    /// The minimum age of the `#[ignore]` of tests to remove
+    pub ignored_for_days: Option<u64>,
+    /// Whether to remove `#[macro_export]` macros without invocations in the
+    /// workspace, which other crates may still use
+    pub exported_macros: bool,
+    /// Whether to keep the items that doctests use, which `cargo check` does
+    /// not compile
+    pub keep_doctested: bool,
+    /// The names of the attributes that protect the items they are on, besides
+    /// the default ones (see [`cauterize::protection`])
+    pub keep_attributes: Vec<String>,
+    /// The edition of each package, by the (canonical) directory of its manifest
+    pub editions: BTreeMap<PathBuf, Edition>,
+    /// The minimum supported Rust version of each package that declares one,
+    /// by the (canonical) directory of its manifest
+    pub rust_versions: BTreeMap<PathBuf, Version>,
+    /// The level of the `dead_code` lint of each package that configures it
+    /// in its manifest, by the (canonical) directory of its manifest
+    pub dead_code_levels: BTreeMap<PathBuf, lints::Level>,
+    /// The root source files of all targets, from which the module tree is
+    /// resolved
+    pub crate_roots: Vec<PathBuf>,
+    /// The (canonical) workspace root, outside of which no files are modified
+    pub workspace_root: PathBuf,
+    /// The (canonical) target directory, of which files are never modified
+    pub target_directory: PathBuf,
+    pub allow_external: bool,
+    pub include_generated: bool,
+    /// The (canonical) source files in the workspace that version control
+    /// ignores, which are usually generated (see `--include-ignored`)
+    pub ignored: BTreeSet<PathBuf>,
+    pub allow_included: bool,
+    /// Whether to modify packages that allow `dead_code`
+    pub force: bool,
+    /// The (canonical) files and directories to which changes are limited, if
+    /// any are given
+    pub scope: Vec<PathBuf>,
+    /// The targets of every package, to find items used by a single binary
+    pub packages: Vec<features::Targets>,
+    /// The libraries in the vendor directory (see `--vendor-dir`), which are
+    /// not part of the workspace
+    pub vendored: Vec<vendor::Vendored>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn negated_globs() {
+        let globs = ["src/**".to_owned(), "!src/gen/**".to_owned()];
+        assert_eq!(last_match(&globs, "src/lib.rs"), Some(true));
+        assert_eq!(last_match(&globs, "src/gen/parser.rs"), Some(false));
+        assert_eq!(last_match(&globs, "build.rs"), None);
+
+        // later globs take precedence
+        let globs = ["!src/gen/**".to_owned(), "src/gen/keep.rs".to_owned()];
+        assert_eq!(last_match(&globs, "src/gen/keep.rs"), Some(true));
+    }
+
+    #[test]
+    fn build_output() {
+        let target = Path::new("/work/target");
+        let out = "/work/target/debug/build/parser-0123abcd/out/grammar.rs";
+        assert!(is_build_output(Path::new(out), target));
+        let script = "/work/target/debug/build/parser-0123abcd/build-script-build";
+        assert!(!is_build_output(Path::new(script), target));
+        assert!(!is_build_output(
+            Path::new("/work/build/x/out/a.rs"),
+            target
+        ));
+    }
+}
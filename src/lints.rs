@@ -0,0 +1,116 @@
+//! This module reads the level of the `dead_code` lint that packages configure
+//! in the `[lints]` table of their manifest (or inherit from the
+//! `[workspace.lints]` table of the workspace), since `cargo metadata` does not
+//! report it. The manifest is read with toml_edit, like [`crate::manifest`]
+//! edits it.
+
+use std::str::FromStr;
+
+use toml_edit::{DocumentMut, Item};
+
+/// The level of a lint
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+    Forbid,
+}
+
+impl FromStr for Level {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Level::Allow),
+            "warn" => Ok(Level::Warn),
+            "deny" => Ok(Level::Deny),
+            "forbid" => Ok(Level::Forbid),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The lints that a `[lints]` table configures
+#[derive(Debug, Default, PartialEq)]
+struct Lints {
+    dead_code: Option<Level>,
+    /// The level of the `unused` group, which includes `dead_code`
+    unused: Option<Level>,
+    /// Whether the table is `workspace = true`
+    inherited: bool,
+}
+
+/// The level of `dead_code` that a package configures, given its manifest and
+/// that of the workspace; `None` if it is left at the default
+pub fn dead_code_level(manifest: &str, workspace_manifest: &str) -> Option<Level> {
+    let lints = match table_lints(manifest, &["lints"]) {
+        lints if lints.inherited => table_lints(workspace_manifest, &["workspace", "lints"]),
+        lints => lints,
+    };
+
+    lints.dead_code.or(lints.unused)
+}
+
+/// Reads the rustc lints from the table at the given path (`lints` or
+/// `workspace.lints`); a manifest that cannot be parsed configures none
+fn table_lints(manifest: &str, table: &[&str]) -> Lints {
+    let Ok(document) = manifest.parse::<DocumentMut>() else {
+        return Lints::default();
+    };
+    let Some(lints) = table
+        .iter()
+        .try_fold(document.as_item(), |item, key| item.get(key))
+    else {
+        return Lints::default();
+    };
+
+    let rust = lints.get("rust");
+    Lints {
+        dead_code: rust.and_then(|rust| rust.get("dead_code")).and_then(level),
+        unused: rust.and_then(|rust| rust.get("unused")).and_then(level),
+        inherited: lints.get("workspace").and_then(Item::as_bool) == Some(true),
+    }
+}
+
+/// The level of a lint, given as `"deny"` or as `{ level = "deny", ... }`
+fn level(item: &Item) -> Option<Level> {
+    let level = match item.as_str() {
+        Some(level) => level,
+        None => item.get("level")?.as_str()?,
+    };
+
+    level.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let workspace = "[workspace]\nmembers = [\"a\"]\n\n[workspace.lints.rust]\n\
+                         unused = { level = \"warn\", priority = -1 }\ndead_code = \"deny\"\n";
+
+        let own = "[package]\nname = \"a\"\n\n[lints.rust]\ndead_code = \"allow\" # for now\n";
+        assert_eq!(dead_code_level(own, workspace), Some(Level::Allow));
+
+        let inherited = "[package]\nname = \"a\"\n\n[lints]\nworkspace = true\n";
+        assert_eq!(dead_code_level(inherited, workspace), Some(Level::Deny));
+
+        let dotted = "[lints]\nrust.dead_code = { level = \"forbid\" }\n";
+        assert_eq!(dead_code_level(dotted, ""), Some(Level::Forbid));
+
+        let inline = "[lints]\nrust = { unused = \"allow\", unused_dead_code = \"warn\" }\n";
+        assert_eq!(dead_code_level(inline, ""), Some(Level::Allow));
+
+        let table = "[lints.rust.dead_code]\nlevel = \"deny\"\npriority = 1\n";
+        assert_eq!(dead_code_level(table, ""), Some(Level::Deny));
+
+        let quoted = "[lints]\n\"rust\" . 'dead_code' = \"warn\"\n";
+        assert_eq!(dead_code_level(quoted, ""), Some(Level::Warn));
+
+        let clippy = "[lints.clippy]\ndead_code = \"allow\"\n";
+        assert_eq!(dead_code_level(clippy, ""), None);
+    }
+}
@@ -0,0 +1,46 @@
+//! This module prevents concurrent runs in the same workspace from
+//! interleaving their writes, using an advisory lock on a file in the target
+//! directory.
+
+use std::{
+    fs::{File, TryLockError},
+    path::Path,
+};
+
+use crate::error::{Error, Result};
+
+/// Takes the lock for the given target directory; it is held until the
+/// returned file is dropped
+pub fn acquire(target_directory: &Path) -> Result<File> {
+    let directory = target_directory.join("minify");
+    std::fs::create_dir_all(&directory)?;
+
+    let path = directory.join("lock");
+    let file = File::create(&path)?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(Error::Locked(path)),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn concurrent_runs() {
+        let target = std::env::temp_dir().join(format!("minify-lock-{}", std::process::id()));
+
+        let lock = acquire(&target).unwrap();
+        assert!(
+            matches!(acquire(&target), Err(Error::Locked(path)) if path == target.join("minify/lock"))
+        );
+
+        // the next run can go ahead once the first one is done
+        drop(lock);
+        assert!(acquire(&target).is_ok());
+
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+//! This module sets up logging with `tracing`: warnings and notes are printed
+//! on stderr (in the style of cargo), filtered by `--log-level` or, if that is
+//! not given, by `RUST_LOG`. For bug reports, everything down to the debug level
+//! can be written to a log file as well.
+
+use std::{fmt, fs::File, path::Path, sync::Mutex};
+
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{format, FmtContext, FormatEvent, FormatFields},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+use crate::error::{Error, Result};
+
+/// The level of the messages that are printed if neither `--log-level` nor
+/// `RUST_LOG` is given
+const DEFAULT_LEVEL: &str = "info";
+
+/// Installs the logger; `level` is a level (such as `debug`) or any other
+/// filter that `RUST_LOG` accepts
+pub fn init(level: Option<&str>, file: Option<&Path>) -> Result<()> {
+    let filter = match level {
+        Some(level) => EnvFilter::try_new(level).map_err(|_| {
+            Error::Args("invalid --log-level, pick any of: off, error, warn, info, debug, trace")
+        })?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LEVEL)),
+    };
+    let stderr = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .event_format(Cargo)
+        .with_filter(filter);
+
+    let file = match file {
+        Some(file) => {
+            let file = File::create(file)?;
+            let layer = tracing_subscriber::fmt::layer()
+                .with_writer(Mutex::new(file))
+                .with_ansi(false)
+                .with_filter(LevelFilter::DEBUG);
+            Some(layer)
+        }
+        None => None,
+    };
+
+    // this only fails if a logger was installed already
+    let _ = tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .try_init();
+
+    Ok(())
+}
+
+/// Formats messages like cargo does, e.g. `warning: skipped src/gen.rs`
+struct Cargo;
+
+impl<S, N> FormatEvent<S, N> for Cargo
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => "error",
+            Level::WARN => "warning",
+            Level::INFO => "note",
+            Level::DEBUG => "debug",
+            Level::TRACE => "trace",
+        };
+        write!(writer, "{prefix}: ")?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, sync::Arc};
+
+    use super::*;
+
+    /// Collects what is logged
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn cargo_style() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .event_format(Cargo)
+            .with_max_level(Level::INFO)
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("skipped src/gen.rs");
+            tracing::info!("{} allows dead_code", "Cargo.toml");
+            tracing::debug!("running cargo check");
+        });
+
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            "warning: skipped src/gen.rs\nnote: Cargo.toml allows dead_code\n"
+        );
+    }
+
+    #[test]
+    fn invalid_level() {
+        assert!(matches!(
+            init(Some("cargo_minify=loud"), None),
+            Err(Error::Args(_))
+        ));
+    }
+}
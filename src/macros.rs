@@ -0,0 +1,130 @@
+//! This module finds `#[macro_export]`ed macros that are not invoked anywhere
+//! in the workspace; rustc never reports these, since other crates may use
+//! them.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use proc_macro2::{
+    Spacing, TokenStream,
+    TokenTree::{Group, Ident, Punct},
+};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+
+/// Lists the exported macros that are not invoked in any of the given files,
+/// together with the file they are defined in
+pub fn unused_exported_macros(files: &[(PathBuf, syn::File)]) -> Vec<(PathBuf, String)> {
+    let mut invoked = BTreeSet::new();
+    for (_, ast) in files {
+        invocations(ast.to_token_stream(), None, &mut invoked);
+    }
+
+    let mut unused = Vec::new();
+    for (file_name, ast) in files {
+        let mut definitions = Vec::new();
+        exported_macros(&ast.items, &mut definitions);
+
+        unused.extend(
+            definitions
+                .into_iter()
+                .filter(|name| !invoked.contains(name))
+                .map(|name| (file_name.clone(), name)),
+        );
+    }
+
+    unused
+}
+
+fn exported_macros(items: &[syn::Item], found: &mut Vec<String>) {
+    for item in items {
+        match item {
+            syn::Item::Macro(syn::ItemMacro {
+                attrs,
+                ident: Some(ident),
+                mac,
+                ..
+            }) if mac.path.is_ident("macro_rules")
+                && attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("macro_export")) =>
+            {
+                found.push(ident.unraw().to_string())
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => exported_macros(items, found),
+            _ => {}
+        }
+    }
+}
+
+/// Collects the names of all macros invoked (`name!`) in a token stream,
+/// including in the bodies of other macros; invocations of a macro in its own
+/// definition (i.e. recursion) do not count
+fn invocations(tokens: TokenStream, definition: Option<&str>, found: &mut BTreeSet<String>) {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+
+    let mut index = 0;
+    while index < tokens.len() {
+        match &tokens[index..] {
+            [Ident(keyword), Punct(bang), Ident(name), Group(body), ..]
+                if keyword == "macro_rules" && bang.as_char() == '!' =>
+            {
+                let name = name.unraw().to_string();
+                invocations(body.stream(), Some(&name), found);
+                index += 4;
+            }
+            // `a != b` is not an invocation, `!` is joint with `=` there
+            [Ident(name), Punct(bang), ..]
+                if bang.as_char() == '!' && bang.spacing() == Spacing::Alone =>
+            {
+                let name = name.unraw().to_string();
+                if definition != Some(name.as_str()) {
+                    found.insert(name);
+                }
+                index += 2;
+            }
+            [Group(group), ..] => {
+                invocations(group.stream(), definition, found);
+                index += 1;
+            }
+            _ => index += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unused_exported() {
+        let files = [
+            (
+                "src/lib.rs",
+                "#[macro_export]
+                macro_rules! used { () => {} }
+                #[macro_export]
+                macro_rules! unused { () => { unused!() } }
+                #[macro_export]
+                macro_rules! nested { () => {} }
+                macro_rules! private { () => {} }
+                pub fn foo() -> bool { used!(); vec![nested!()]; a != b }",
+            ),
+            (
+                "tests/test.rs",
+                "mod m { #[macro_export] macro_rules! other { () => {} } }",
+            ),
+        ]
+        .map(|(file_name, src)| (PathBuf::from(file_name), syn::parse_str(src).unwrap()));
+
+        assert_eq!(
+            unused_exported_macros(&files),
+            vec![
+                (PathBuf::from("src/lib.rs"), "unused".to_owned()),
+                (PathBuf::from("tests/test.rs"), "other".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,57 +1,451 @@
-use std::{env, io, io::Write, path::PathBuf};
+use std::{
+    collections::{btree_map, BTreeMap, BTreeSet},
+    env, io,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
+use cargo_metadata::{Metadata, Package};
+use cargo_minify::{
+    backend, bloat, cargo, cauterize, cfg, error, expansion, features, graph, history, interrupt,
+    lints, progress, resolver, unused, vcs, vendor, CauterizeOptions, CrateResolutionOptions,
+    FileResolutionOptions,
+};
 use gumdrop::Options;
+use tracing::{debug, info, warn};
 
 use crate::{
-    diff_format::ColorMode,
+    cauterize::Mode,
+    diff_format::{ColorMode, LongLines, MessageFormat},
     error::{Error, Result},
+    preset::Preset,
+    progress::ProgressFormat,
     unused::UnusedDiagnosticKind,
 };
 
-mod cauterize;
 mod diff_format;
-mod error;
-mod resolver;
-mod unused;
-mod vcs;
+mod doctor;
+mod explain;
+mod lock;
+mod logging;
+mod metrics;
+mod package;
+mod preset;
+mod rustfmt;
+mod snapshot;
 
 const SUBCOMMAND_NAME: &str = "minify";
 
 #[derive(Debug, Options)]
 struct MinifyOptions {
+    #[options(
+        free,
+        help = "Only modify the files in these directories, or these files (the analysis still \
+                covers the selected packages)"
+    )]
+    paths: Vec<String>,
+
     #[options(help = "No output printed to stdout")]
     quiet: bool,
 
-    #[options(help = "Package to minify", meta = "SPEC")]
+    #[options(
+        help = "Package to minify (may be a glob, e.g. my-org-*)",
+        meta = "SPEC"
+    )]
     package: Vec<String>,
     #[options(no_short, help = "Minify all packages in the workspace")]
     workspace: bool,
     #[options(no_short, help = "Exclude packages from the minify", meta = "SPEC")]
     exclude: Vec<String>,
+    #[options(
+        no_short,
+        help = "With --workspace, also minify path dependencies outside of the workspace"
+    )]
+    follow_path_deps: bool,
 
-    #[options(help = "File to minify", meta = "SPEC")]
+    #[options(
+        help = "File to minify; a glob starting with ! leaves files out",
+        meta = "SPEC"
+    )]
     file: Vec<String>,
-    #[options(help = "Ignore files from the minify", meta = "SPEC")]
+    #[options(
+        help = "Ignore files from the minify; a glob starting with ! brings files back",
+        meta = "SPEC"
+    )]
     ignore: Vec<String>,
+    #[options(
+        no_short,
+        help = "Leave the files in this directory alone",
+        meta = "DIR"
+    )]
+    exclude_dir: Vec<String>,
+    #[options(
+        no_short,
+        help = "Only minify files with these extensions (comma-separated, e.g. rs)",
+        meta = "EXTS"
+    )]
+    only_ext: Vec<String>,
 
     #[options(
         help = "specify which kinds of diagnostics to apply (all by default)",
         meta = "< FUNCTION | CONST | STATIC | STRUCT | ENUM | UNION | TYPE_ALIAS | \
-                ASSOCIATED_FUNCTION | MACRO_DEFINITION >"
+                ASSOCIATED_FUNCTION | ASSOCIATED_ITEM | MACRO_DEFINITION | MACRO_INVOCATION | \
+                FIELD >"
     )]
     kinds: Vec<UnusedDiagnosticKind>,
+    #[options(
+        no_short,
+        help = "Kinds of diagnostics not to apply (comma-separated, e.g. type-alias,macro-definition)",
+        meta = "KINDS"
+    )]
+    exclude_kinds: Vec<String>,
+    #[options(
+        no_short,
+        help = "Only apply the diagnostics of this severity: unused (nothing refers to the item) or \
+                unread (the item is written, but never read, such as a field that is only set)",
+        meta = "SEVERITY"
+    )]
+    severity: Option<unused::Severity>,
+
+    #[options(
+        no_short,
+        help = "Read the output of `cargo check --all-targets --message-format json` from this \
+                file, instead of running cargo check",
+        meta = "FILE"
+    )]
+    from_json: Option<String>,
+    #[options(
+        no_short,
+        help = "What reports the unused code: check (cargo check, the default), clippy (cargo \
+                clippy) or json (the file given to --from-json)",
+        meta = "BACKEND"
+    )]
+    backend: Option<backend::BackendKind>,
+    #[options(
+        no_short,
+        help = "Check all crates that compile, even if some do not (passed on to cargo check)"
+    )]
+    keep_going: bool,
+    #[options(
+        no_short,
+        help = "Stop cargo check after this many seconds, and use what it reported until then (the \
+                findings are partial, so they are not applied)",
+        meta = "SECS"
+    )]
+    check_timeout: Option<u64>,
+    #[options(
+        no_short,
+        help = "Do not count what the examples use as used, and leave the examples themselves alone \
+                (the public items that only the examples use are only found by the analyses of \
+                the whole workspace, such as --mode deprecate-pub)"
+    )]
+    ignore_examples: bool,
+    #[options(
+        no_short,
+        help = "Do not count what the benchmarks use as used, and leave the benchmarks themselves \
+                alone (see --ignore-examples)"
+    )]
+    ignore_benches: bool,
+    #[options(
+        no_short,
+        help = "Also apply the fixes that the compiler marks as machine-applicable (e.g. removing \
+                unused imports or `mut`)"
+    )]
+    apply_suggestions: bool,
+
+    #[options(
+        no_short,
+        help = "Remove the item with this path (e.g. mycrate::cli::run), and whatever is no \
+                longer used without it, instead of what the compiler reports",
+        meta = "PATH"
+    )]
+    remove: Vec<String>,
+    #[options(
+        no_short,
+        help = "Remove the items listed in this file, one path per line (see --remove)",
+        meta = "FILE"
+    )]
+    remove_from: Option<String>,
+    #[options(
+        no_short,
+        help = "Keep only the items with these paths (comma-separated, e.g. mycrate::run,mycrate::cli::*), \
+                and what they use; remove everything else",
+        meta = "PATHS"
+    )]
+    keep_only: Vec<String>,
+
+    #[options(
+        no_short,
+        help = "Remove #[deprecated] items that are no longer used within the package, and what \
+                they use"
+    )]
+    remove_deprecated: bool,
+    #[options(
+        no_short,
+        help = "Only remove items deprecated in or before this version; implies --remove-deprecated",
+        meta = "VERSION"
+    )]
+    deprecated_since: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Remove the items of the vendored dependencies in this directory that the \
+                workspace does not use, instead of what the compiler reports",
+        meta = "DIR"
+    )]
+    vendor_dir: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Write the graph of items and the references between them to a .dot or .json file",
+        meta = "FILE"
+    )]
+    emit_graph: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Write the public items that are removed (with their signatures) to a markdown file, \
+                for the changelog",
+        meta = "FILE"
+    )]
+    emit_changelog: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Append the number of unused items and lines (in total, by kind and by package) to a \
+                .csv or .json file",
+        meta = "FILE"
+    )]
+    metrics_out: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Turn on a combination of flags: conservative, standard, aggressive, bundle (see \
+                the README), to which the other flags are added",
+        meta = "PRESET"
+    )]
+    preset: Option<Preset>,
+
+    #[options(
+        no_short,
+        help = "What to do with unused code: delete, stub, comment, cfg-test, bin-feature, deprecate-pub",
+        meta = "MODE"
+    )]
+    mode: Mode,
+
+    #[options(
+        no_short,
+        help = "Leave a one-line comment where each item was removed"
+    )]
+    tombstones: bool,
+
+    #[options(
+        no_short,
+        default = "1",
+        help = "Maximum number of consecutive blank lines to leave where code was removed",
+        meta = "N"
+    )]
+    max_blank_lines: usize,
+
+    #[options(
+        no_short,
+        help = "Turn structs of which no field is ever read into unit structs"
+    )]
+    unit_structs: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove empty trait impls (such as `impl Send for Foo {}`) in other files for \
+                the types that are removed"
+    )]
+    remove_marker_impls: bool,
+
+    #[options(
+        no_short,
+        help = "Remove macro invocations of which all expanded items are unused"
+    )]
+    expand_macros: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove exported macros that are not invoked anywhere in the workspace"
+    )]
+    exported_macros: bool,
+    #[options(
+        no_short,
+        help = "Keep the items that doc examples use, instead of warning about them"
+    )]
+    keep_doctested: bool,
+    #[options(
+        no_short,
+        help = "Keep the items with this attribute (e.g. entry), besides those marked #[used], \
+                #[link_section], #[panic_handler], #[global_allocator], #[interrupt] and the like",
+        meta = "ATTR"
+    )]
+    keep_attribute: Vec<String>,
+
+    #[options(
+        no_short,
+        help = "Remove at most N items in this run, starting with the largest ones",
+        meta = "N"
+    )]
+    max_removals: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Only show the N changes that remove the most lines",
+        meta = "N"
+    )]
+    top: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Instead of diffs, print a line per removed item: its file and line, kind, name, \
+                severity and the number of lines it takes"
+    )]
+    list: bool,
+
+    #[options(
+        no_short,
+        help = "Print the estimated data size of the removed statics and constants"
+    )]
+    stats: bool,
+
+    #[options(
+        no_short,
+        help = "Show the code size of removed functions, and rank changes by it, using the output \
+                of `cargo bloat --message-format json`",
+        meta = "FILE"
+    )]
+    bloat: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Also remove all documentation comments and #[doc = \"...\"] attributes"
+    )]
+    strip_docs: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove #[cfg(test)] items (including test modules), #[test] functions and \
+                #[bench] items"
+    )]
+    strip_tests: bool,
+
+    #[options(no_short, help = "Remove tests that are marked as #[ignore]")]
+    remove_ignored_tests: bool,
+
+    #[options(
+        no_short,
+        help = "Only remove ignored tests of which the #[ignore] is at least this many days old \
+                (according to git blame); implies --remove-ignored-tests",
+        meta = "DAYS"
+    )]
+    ignored_for: Option<u64>,
+
+    #[options(
+        no_short,
+        help = "Also remove the lints of #[allow] and #[expect] attributes that no longer \
+                suppress anything"
+    )]
+    remove_unused_allows: bool,
+
+    #[options(
+        no_short,
+        help = "Also remove the features of #![feature] attributes that the (nightly) compiler \
+                reports as unused"
+    )]
+    remove_unused_features: bool,
+
+    #[options(
+        no_short,
+        help = "Directory to write the .crate files to, for `cargo minify package` \
+                (target/minified by default)",
+        meta = "DIR"
+    )]
+    output: Option<String>,
 
     #[options(no_short, help = "Apply changes instead of outputting a diff")]
     apply: bool,
 
+    #[options(
+        no_short,
+        help = "Run rustfmt on the modified files after applying changes"
+    )]
+    fmt: bool,
+
     #[options(help = "Print help message")]
     help: bool,
 
     #[options(no_short, help = "Coloring: auto, always, never", meta = "WHEN")]
     color: ColorMode,
 
-    #[options(no_short, help = "Path to Cargo.toml", meta = "PATH")]
-    manifest_path: Option<String>,
+    #[options(
+        no_short,
+        help = "What to do with lines of diffs that are wider than the terminal: truncate, wrap, \
+                or keep (lines are never changed when the output is piped)",
+        meta = "HOW"
+    )]
+    long_lines: LongLines,
+
+    #[options(
+        no_short,
+        help = "How to print the changes: human (diffs), json (an object per line), github \
+                (a suggested change per hunk, as an object per line), or quickfix (a line per \
+                removed item, for the quickfix list of editors)",
+        meta = "FORMAT"
+    )]
+    message_format: MessageFormat,
+
+    #[options(
+        no_short,
+        help = "Which messages to print: off, error, warn, info (default), debug, trace, or any \
+                filter that RUST_LOG accepts",
+        meta = "LEVEL"
+    )]
+    log_level: Option<String>,
+
+    #[options(
+        no_short,
+        help = "Also write a debug log to this file, e.g. for bug reports",
+        meta = "FILE"
+    )]
+    log_file: Option<String>,
+
+    #[options(
+        no_short,
+        help = "How to report progress: human, or json (newline-delimited events on stderr)",
+        meta = "FORMAT"
+    )]
+    progress_format: ProgressFormat,
+
+    #[options(
+        no_short,
+        help = "Path to Cargo.toml; given more than once, each of the workspaces is minified",
+        meta = "PATH"
+    )]
+    manifest_path: Vec<String>,
+    #[options(
+        no_short,
+        help = "Also minify the workspaces of the manifests listed in this file, one path per line \
+                (relative to the file)",
+        meta = "FILE"
+    )]
+    manifest_path_from: Option<String>,
+
+    #[options(no_short, help = "Run cargo without accessing the network")]
+    offline: bool,
+    #[options(
+        no_short,
+        help = "Require Cargo.lock to be up to date (passed on to cargo)"
+    )]
+    locked: bool,
+    #[options(
+        no_short,
+        help = "Require Cargo.lock to be up to date, without accessing the network"
+    )]
+    frozen: bool,
 
     #[options(no_short, help = "Fix code even if the working directory is dirty")]
     allow_dirty: bool,
@@ -61,6 +455,41 @@ struct MinifyOptions {
 
     #[options(no_short, help = "Also operate if no version control system was found")]
     allow_no_vcs: bool,
+
+    #[options(no_short, help = "Also modify files outside of the workspace")]
+    allow_external: bool,
+
+    #[options(no_short, help = "Also modify files that are marked as @generated")]
+    include_generated: bool,
+
+    #[options(no_short, help = "Also modify files that version control ignores")]
+    include_ignored: bool,
+
+    #[options(no_short, help = "Also modify files that are included using include!")]
+    allow_included: bool,
+
+    #[options(
+        no_short,
+        help = "Also modify packages that allow dead_code in the [lints] table of their manifest, \
+                and gate the items of packages that may be published (see --mode bin-feature)"
+    )]
+    force: bool,
+}
+
+/// Reports which items would no longer be used if the given items were removed
+#[derive(Debug, Options)]
+struct WhatIfOptions {
+    #[options(
+        free,
+        help = "Paths of the items to remove, e.g. mycrate::old_api::Thing"
+    )]
+    items: Vec<String>,
+
+    #[options(help = "Print help message")]
+    help: bool,
+
+    #[options(no_short, help = "Path to Cargo.toml", meta = "PATH")]
+    manifest_path: Option<String>,
 }
 
 fn main() {
@@ -97,6 +526,35 @@ fn main() {
             mini_help();
             1
         }
+        Err(err @ Error::PartialApply { .. }) => {
+            eprintln!("error: {}", err);
+            4
+        }
+        Err(Error::Interrupted) => 130,
+        Err(
+            err @ (Error::Json(_)
+            | Error::Parse(_)
+            | Error::Bloat(_)
+            | Error::CommandFailed(_)
+            | Error::ChecksFailed(_)
+            | Error::CheckTimedOut(_)),
+        ) => {
+            eprintln!("error: {}", err);
+            1
+        }
+        Err(
+            err @ (Error::UnknownItem(_)
+            | Error::UnknownPath(_)
+            | Error::UnknownRun(_)
+            | Error::UnknownSnapshot(_)),
+        ) => {
+            eprintln!("error: {}", err);
+            1
+        }
+        Err(err @ Error::Locked(_)) => {
+            eprintln!("error: {}", err);
+            5
+        }
         _ => 0,
     };
 
@@ -106,131 +564,2976 @@ fn main() {
 }
 
 fn execute(args: &[String]) -> Result<()> {
-    let opts = MinifyOptions::parse_args_default(args)?;
-    let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
-    let crate_resolution = CrateResolutionOptions::from_options(&opts)?;
-    let file_resolution = FileResolutionOptions::from_options(&opts)?;
+    match args.first().map(String::as_str) {
+        Some("what-if") => return what_if(&args[1..]),
+        Some("explain") => return explain(&args[1..]),
+        Some("package") => return package(&args[1..]),
+        Some("targets") => return targets(&args[1..]),
+        Some("diff-report") => return diff_report(&args[1..]),
+        Some("snapshot") => return take_snapshot(&args[1..]),
+        Some("restore") => return restore_snapshot(&args[1..]),
+        Some("doctor") => return doctor(&args[1..]),
+        _ => {}
+    }
+
+    let opts = parse_options(args)?;
+    let crate_resolution = crate_resolution_from(&opts)?;
 
     if opts.help {
         println!("{}", MinifyOptions::usage());
+        println!();
+        println!("Subcommands:");
+        println!("  what-if ITEM...       Report what would no longer be used without these items");
+        println!("  explain FILE:LINE|ITEM  Explain why an item is, or is not, removed");
+        println!(
+            "  package               Package a minified copy, without changing the working tree"
+        );
+        println!("  targets               List the packages and targets that would be analyzed");
+        println!("  diff-report [OLD NEW] Compare the findings of two recorded runs");
+        println!("  snapshot              Save the source files of the workspace");
+        println!("  restore [ID]          Put back the source files of a snapshot (the last one)");
+        println!("  doctor                Check the environment for what keeps runs from working");
     } else {
-        let unused = unused::get_unused(
-            manifest_path.as_deref(),
-            &crate_resolution,
-            &file_resolution,
-            &opts.kinds,
+        logging::init(
+            opts.log_level.as_deref(),
+            opts.log_file.as_deref().map(Path::new),
         )?;
-        let changes: Vec<_> = cauterize::process_diagnostics(unused).collect();
+        cargo::init(opts.offline, opts.locked, opts.frozen);
+        interrupt::install();
+        progress::init(opts.progress_format);
+
+        let bloat = match &opts.bloat {
+            Some(file) => Some(bloat::Bloat::read(Path::new(file))?),
+            None => None,
+        };
+
+        // the time given by --check-timeout is for the run as a whole
+        let deadline = check_deadline(&opts);
+        // every workspace is analyzed on its own, but the results are merged
+        let mut workspaces = Vec::new();
+        let mut locks = BTreeMap::new();
+        let mut changes = Vec::new();
+        let mut skipped = Vec::new();
+        let mut function_sizes = Vec::new();
+        let mut data_sizes = Vec::new();
+        let mut listed = Vec::new();
+        let mut removed_public = Vec::new();
+        for manifest_path in manifest_paths(&opts)? {
+            let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+            // workspaces may share a target directory
+            let target_directory = metadata.target_directory.clone().into_std_path_buf();
+            if let btree_map::Entry::Vacant(entry) = locks.entry(target_directory) {
+                let lock = lock::acquire(entry.key())?;
+                entry.insert(lock);
+            }
+
+            let file_resolution = file_resolution_from(&opts, &metadata)?;
+            let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+            let (found, skips) = find_changes(
+                &opts,
+                manifest_path.as_deref(),
+                &crate_resolution,
+                &file_resolution,
+                &cauterize_options,
+                deadline,
+            )?;
+            // the analysis is incomplete, so its results cannot be trusted
+            if interrupt::requested() {
+                return Err(Error::Interrupted);
+            }
+
+            // partial findings would distort the history
+            if !unused::timed_out() {
+                let findings = cauterize::findings(&found, &cauterize_options);
+                let recorded = history::record(
+                    &cauterize_options.target_directory,
+                    &cauterize_options.workspace_root,
+                    &findings,
+                );
+                match recorded {
+                    Ok(run) => debug!("recorded the findings as run {run}"),
+                    Err(err) => warn!("could not record the findings of this run: {err}"),
+                }
+            }
+
+            if let Some(bloat) = &bloat {
+                function_sizes.extend(cauterize::function_sizes(&found, bloat, &cauterize_options));
+            }
+            if opts.stats {
+                data_sizes.extend(cauterize::data_sizes(&found, &cauterize_options));
+            }
+            if opts.list
+                || matches!(
+                    opts.message_format,
+                    MessageFormat::Quickfix | MessageFormat::Github
+                )
+            {
+                listed.extend(cauterize::listed_items(&found, &cauterize_options));
+            }
+            if opts.emit_changelog.is_some() {
+                removed_public.extend(cauterize::removed_public_items(&found, &cauterize_options));
+            }
+            changes.extend(found);
+            skipped.extend(skips);
+            workspaces.push(Workspace {
+                metadata,
+                options: cauterize_options,
+            });
+        }
+        function_sizes.sort_by_key(|size| std::cmp::Reverse(size.bytes));
+        data_sizes.sort_by_key(|size| std::cmp::Reverse(size.bytes));
+
+        if let Some(file) = &opts.emit_graph {
+            emit_graph(Path::new(file), &workspaces)?;
+        }
+        if let Some(file) = &opts.emit_changelog {
+            removed_public.sort_by(|a, b| a.path.cmp(&b.path));
+            std::fs::write(file, changelog(&removed_public))?;
+        }
+        if let Some(file) = &opts.metrics_out {
+            metrics::append(Path::new(file), &collect_metrics(&changes, &workspaces))?;
+        }
+
+        // show the biggest wins first
+        if bloat.is_some() {
+            cauterize::rank_by_size(&mut changes, &function_sizes);
+        } else {
+            cauterize::rank_by_impact(&mut changes);
+        }
+        // unused items that fail the build come first
+        changes.sort_by_key(|change| {
+            !workspaces.iter().any(|workspace| {
+                cauterize::denies_dead_code(&workspace.options, change.file_name())
+            })
+        });
 
         if !opts.quiet {
             if changes.is_empty() {
                 eprintln!("no unused code that can be minified")
             } else {
-                for change in &changes {
-                    diff_format::println(change, opts.color);
+                let shown = opts.top.unwrap_or(changes.len()).min(changes.len());
+                let packages = group_by_package(&changes, &workspaces);
+                if opts.message_format == MessageFormat::Quickfix {
+                    print_quickfix(&listed, &changes[..shown]);
+                } else if opts.list {
+                    print_list(&listed, &changes[..shown]);
+                } else if opts.message_format == MessageFormat::Github {
+                    for change in &changes[..shown] {
+                        diff_format::println_suggestions(change, &listed);
+                    }
+                } else if opts.message_format == MessageFormat::Json {
+                    for (index, change) in changes[..shown].iter().enumerate() {
+                        let package = packages
+                            .iter()
+                            .find(|(_, indices)| indices.contains(&index))
+                            .and_then(|(package, _)| package.as_deref());
+                        diff_format::println_json(change, package);
+                    }
+                } else if packages.len() > 1 {
+                    for (package, indices) in &packages {
+                        if indices.iter().all(|&index| index >= shown) {
+                            continue;
+                        }
+                        let lines = indices
+                            .iter()
+                            .map(|&index| changes[index].lines_removed())
+                            .sum();
+                        diff_format::println_package(
+                            package.as_deref(),
+                            indices.len(),
+                            lines,
+                            opts.color,
+                        );
+                        for &index in indices.iter().filter(|&&index| index < shown) {
+                            diff_format::println(&changes[index], opts.color, opts.long_lines);
+                        }
+                    }
+                } else {
+                    for change in &changes[..shown] {
+                        diff_format::println(change, opts.color, opts.long_lines);
+                    }
+                }
+                if shown < changes.len() {
+                    eprintln!(
+                        "{} more changed files not shown (see --top)",
+                        changes.len() - shown
+                    );
                 }
             }
         }
 
-        let cargo_root = resolver::get_cargo_metadata(manifest_path.as_deref())?.workspace_root;
+        if opts.stats && !opts.quiet {
+            print_sizes(
+                "statics and constants removed",
+                "of data",
+                &data_sizes,
+                opts.message_format,
+            );
+        }
+        if bloat.is_some() && !opts.quiet {
+            print_sizes(
+                "functions removed",
+                "of code according to cargo bloat",
+                &function_sizes,
+                opts.message_format,
+            );
+        }
+
+        report_skipped(&skipped);
+
+        // the items that the unchecked targets use may look unused
+        if unused::timed_out() {
+            if opts.apply {
+                eprintln!("not applying the changes, since they are based on partial findings");
+            }
+            return Err(Error::CheckTimedOut(opts.check_timeout.unwrap_or_default()));
+        }
 
         if opts.apply {
-            use vcs::Status;
-            match vcs::status(&cargo_root) {
-                Status::Error(e) => {
-                    eprintln!("git problem: {}", e)
+            let cargo_roots: BTreeSet<_> = workspaces
+                .iter()
+                .map(|workspace| &workspace.metadata.workspace_root)
+                .collect();
+            // the workspaces may share a repository, so stop at the first complaint
+            let allowed = cargo_roots
+                .into_iter()
+                .all(|cargo_root| vcs_allows_apply(cargo_root.as_std_path(), &opts));
+            if allowed {
+                let total = changes.len();
+                let mut modified = Vec::new();
+                let mut failed = 0;
+                let mut processed = 0;
+
+                for (change, result) in cauterize::commit_changes(changes) {
+                    processed += 1;
+                    let file_name = change.file_name().display();
+                    let (action, done) = if change.removes_file() {
+                        ("remove", "removed")
+                    } else {
+                        ("write", "wrote")
+                    };
+                    match result {
+                        Ok(()) => {
+                            if !opts.quiet {
+                                eprintln!("{done} {file_name}");
+                            }
+                            let is_rust = change.file_name().extension() == Some("rs".as_ref());
+                            if !change.removes_file() && is_rust {
+                                modified.push(change.file_name().to_path_buf());
+                            }
+                        }
+                        Err(err) => {
+                            eprintln!("error: could not {action} {file_name}: {err}");
+                            failed += 1;
+                        }
+                    }
                 }
-                Status::NoVCS if !opts.allow_no_vcs => {
+
+                progress::report(
+                    "apply-done",
+                    serde_json::json!({
+                        "applied": processed - failed,
+                        "failed": failed,
+                        "unchanged": total - processed,
+                    }),
+                );
+
+                if processed < total {
                     eprintln!(
-                        "no VCS found for this package and `cargo minify` can potentially perform \
-                         destructive changes; if you'd like to suppress this error pass \
-                         `--allow-no-vcs`"
+                        "interrupted, {} of {total} files were left unchanged",
+                        total - processed
                     );
+                    return Err(Error::Interrupted);
                 }
-                Status::Unclean { dirty, staged }
-                    if !(dirty.is_empty() || opts.allow_dirty)
-                        || !(staged.is_empty() || opts.allow_staged) =>
-                {
-                    eprintln!("working directory contains dirty/staged files:");
-                    for file in dirty {
-                        eprintln!("\t{} (dirty)", file)
-                    }
-                    for file in staged {
-                        eprintln!("\t{} (staged)", file)
-                    }
-                    eprintln!(
-                        "please fix this or ignore this warning with --allow-dirty and/or \
-                         --allow-staged"
-                    );
+
+                if opts.fmt && !modified.is_empty() {
+                    let packages: Vec<Package> = workspaces
+                        .iter()
+                        .flat_map(|workspace| workspace.metadata.packages.iter().cloned())
+                        .collect();
+                    rustfmt::format_files(&modified, &packages)?;
                 }
-                _ => {
-                    // TODO: Remove unwrap
-                    cauterize::commit_changes(changes).unwrap();
+
+                if failed > 0 {
+                    return Err(Error::PartialApply { failed, total });
                 }
             }
         } else if !changes.is_empty() {
-            println!("run with --apply to apply these changes")
+            eprintln!("run with --apply to apply these changes")
         }
     }
 
     Ok(())
 }
 
-pub enum CrateResolutionOptions<'a> {
-    Root,
-    Workspace { exclude: &'a [String] },
-    Package { packages: &'a [String] },
-}
+/// The (indices of the) changes to the files of each package, in the order in
+/// which the packages first appear in the changes; vendored crates count as
+/// packages as well, while files outside of any package are grouped under
+/// `None`
+fn group_by_package(
+    changes: &[cauterize::Change],
+    workspaces: &[Workspace],
+) -> Vec<(Option<String>, Vec<usize>)> {
+    let packages: Vec<Package> = workspaces
+        .iter()
+        .flat_map(|workspace| workspace.metadata.packages.iter().cloned())
+        .collect();
+    let package_name = |file: &Path| {
+        let canonical = file.canonicalize().ok()?;
+        // vendored crates may lie within the directory of another package
+        let vendored = workspaces
+            .iter()
+            .flat_map(|workspace| &workspace.options.vendored)
+            .find(|vendored| canonical.starts_with(&vendored.dir));
+        match vendored {
+            Some(vendored) => Some(vendored.name.clone()),
+            None => resolver::package_of(file, &packages).map(|package| package.name.clone()),
+        }
+    };
 
-impl<'a> CrateResolutionOptions<'a> {
-    fn from_options(opts: &'a MinifyOptions) -> Result<Self> {
-        match (
-            opts.workspace,
-            !opts.package.is_empty(),
-            !opts.exclude.is_empty(),
-        ) {
-            (true, false, true) | (true, false, false) => Ok(CrateResolutionOptions::Workspace {
-                exclude: &opts.exclude,
-            }),
-            (false, true, false) => Ok(CrateResolutionOptions::Package {
-                packages: &opts.package,
-            }),
-            (false, false, false) => Ok(CrateResolutionOptions::Root),
-            (true, true, false) | (false, true, true) | (true, true, true) => Err(Error::Args(
-                "either specify --workspace and optionally --exclude specific targets, or specify \
-                 specific targets with --package",
-            )),
-            (false, false, true) => Err(Error::Args(
-                "--exclude can only be used in conjunction with --workspace",
-            )),
+    let mut packages: Vec<(Option<String>, Vec<usize>)> = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        let name = package_name(change.file_name());
+        match packages.iter_mut().find(|(package, _)| *package == name) {
+            Some((_, indices)) => indices.push(index),
+            None => packages.push((name, vec![index])),
         }
     }
-}
 
-pub enum FileResolutionOptions<'a> {
-    Only(&'a [String]),
-    AllBut(&'a [String]),
+    packages
 }
 
-impl<'a> FileResolutionOptions<'a> {
-    fn from_options(opts: &'a MinifyOptions) -> Result<Self> {
-        match (!opts.file.is_empty(), !opts.ignore.is_empty()) {
-            (false, false) | (false, true) => Ok(FileResolutionOptions::AllBut(&opts.ignore)),
-            (true, false) => Ok(FileResolutionOptions::Only(&opts.file)),
-            (true, true) => Err(Error::Args(
-                "either specify --ignore to minify all files except",
-            )),
+/// Counts the unused items (by kind and by package) and lines of the changes
+fn collect_metrics(changes: &[cauterize::Change], workspaces: &[Workspace]) -> metrics::Metrics {
+    let mut metrics = metrics::Metrics::default();
+    for (package, indices) in group_by_package(changes, workspaces) {
+        for change in indices.iter().map(|&index| &changes[index]) {
+            let items = change.removed_items();
+            for (kind, _) in &items {
+                *metrics.kinds.entry(kind.clone()).or_default() += 1;
+            }
+            *metrics
+                .packages
+                .entry(package.clone().unwrap_or_default())
+                .or_default() += items.len();
+            metrics.items += items.len();
+            metrics.lines += change.lines_removed();
         }
     }
 
-    pub fn is_included(&self, file_name: &str) -> bool {
-        match self {
-            FileResolutionOptions::Only(files) => files
-                .iter()
-                .any(|file| glob_match::glob_match(file, file_name)),
-            FileResolutionOptions::AllBut(ignored) => ignored
+    metrics
+}
+
+/// Prints the removed items of the given changes a line per item, in the format
+/// of compiler errors (`file:line:column: message`) that editors understand
+fn print_quickfix(listed: &[cauterize::ListedItem], changes: &[cauterize::Change]) {
+    let items = listed.iter().filter(|item| {
+        changes
+            .iter()
+            .any(|change| change.file_name() == item.file_name)
+    });
+    for item in items {
+        println!("{}", quickfix_line(item));
+    }
+}
+
+/// The line of an item in the quickfix list, e.g.
+/// `src/lib.rs:5:1: function unused is unused (would remove 3 lines)`
+fn quickfix_line(item: &cauterize::ListedItem) -> String {
+    let location = match (item.line, item.column) {
+        (Some(line), Some(column)) => format!("{}:{line}:{column}", item.file_name.display()),
+        _ => item.file_name.display().to_string(),
+    };
+    let removes = match item.lines {
+        Some(1) => " (would remove 1 line)".to_owned(),
+        Some(lines) => format!(" (would remove {lines} lines)"),
+        None => String::new(),
+    };
+    format!(
+        "{location}: {} {} is {}{removes}",
+        item.kind.name(),
+        item.ident,
+        item.severity.name()
+    )
+}
+
+/// Prints the removed items of the given changes as a table, a line per item,
+/// without a header so it can be sorted or filtered (see `--list`)
+fn print_list(listed: &[cauterize::ListedItem], changes: &[cauterize::Change]) {
+    let rows: Vec<_> = listed
+        .iter()
+        .filter(|item| {
+            changes
                 .iter()
-                .all(|ignore| !glob_match::glob_match(ignore, file_name)),
+                .any(|change| change.file_name() == item.file_name)
+        })
+        .map(|item| {
+            let location = match item.line {
+                Some(line) => format!("{}:{line}", item.file_name.display()),
+                None => item.file_name.display().to_string(),
+            };
+            let lines = item.lines.map_or("-".to_owned(), |lines| lines.to_string());
+            [
+                location,
+                item.kind.name().to_owned(),
+                item.ident.clone(),
+                item.severity.name().to_owned(),
+                lines,
+            ]
+        })
+        .collect();
+
+    let widths: Vec<_> = (0..4)
+        .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+        .collect();
+    for [location, kind, name, severity, lines] in &rows {
+        println!(
+            "{location:<0$}  {kind:<1$}  {name:<2$}  {severity:<3$}  {lines}",
+            widths[0], widths[1], widths[2], widths[3]
+        );
+    }
+}
+
+/// Prints the removed items (of which the sizes are given largest first), with
+/// the total of the sizes that are known; as JSON, every item is an object with
+/// its size in bytes (`null` if it is not known)
+fn print_sizes(title: &str, what: &str, sizes: &[cauterize::ItemSize], format: MessageFormat) {
+    if format == MessageFormat::Json {
+        for size in sizes {
+            let size = serde_json::json!({
+                "reason": "size",
+                "file": size.file_name,
+                "kind": size.kind.name(),
+                "name": size.ident,
+                "bytes": size.bytes,
+            });
+            println!("{size}");
         }
+        return;
+    }
+
+    let known = sizes.iter().filter_map(|size| size.bytes).sum::<usize>();
+    println!(
+        "{title}: {}, with at least {known} bytes {what}",
+        sizes.len()
+    );
+    for size in sizes {
+        let bytes = size
+            .bytes
+            .map_or_else(|| "?".to_owned(), |bytes| bytes.to_string());
+        println!(
+            "{bytes:>10} bytes  {} `{}` ({})",
+            size.kind.name().replace('_', " "),
+            size.ident,
+            size.file_name.display()
+        );
+    }
+}
+
+/// Which packages to minify, from the command line
+fn crate_resolution_from(opts: &MinifyOptions) -> Result<CrateResolutionOptions<'_>> {
+    if opts.follow_path_deps && !opts.workspace {
+        return Err(Error::Args(
+            "--follow-path-deps can only be used in conjunction with --workspace",
+        ));
+    }
+
+    match (
+        opts.workspace,
+        !opts.package.is_empty(),
+        !opts.exclude.is_empty(),
+    ) {
+        (true, false, true) | (true, false, false) => Ok(CrateResolutionOptions::Workspace {
+            exclude: &opts.exclude,
+            follow_path_deps: opts.follow_path_deps,
+        }),
+        (false, true, false) => Ok(CrateResolutionOptions::Package {
+            packages: &opts.package,
+        }),
+        (false, false, false) => Ok(CrateResolutionOptions::Root),
+        (true, true, false) | (false, true, true) | (true, true, true) => Err(Error::Args(
+            "either specify --workspace and optionally --exclude specific targets, or specify \
+             specific targets with --package",
+        )),
+        (false, false, true) => Err(Error::Args(
+            "--exclude can only be used in conjunction with --workspace",
+        )),
+    }
+}
+
+/// Which files may be minified, from the command line and the manifest
+fn file_resolution_from(
+    opts: &MinifyOptions,
+    metadata: &Metadata,
+) -> Result<FileResolutionOptions> {
+    let workspace_root = metadata.workspace_root.as_std_path();
+    let config = &metadata.workspace_metadata["minify"];
+    let config = |key: &str| -> Vec<String> {
+        config[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str())
+            .map(str::to_owned)
+            .collect()
+    };
+
+    // the command line comes last, so its negated globs override the config
+    let files = [config("file"), opts.file.clone()].concat();
+    let ignored = [config("ignore"), opts.ignore.clone()].concat();
+    let excluded_dirs = config("exclude-dir")
+        .iter()
+        .map(|dir| workspace_root.join(dir))
+        .chain(opts.exclude_dir.iter().map(PathBuf::from))
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    let extensions = config("only-ext")
+        .iter()
+        .chain(&opts.only_ext)
+        .flat_map(|extensions| extensions.split(','))
+        .map(|extension| extension.trim().trim_start_matches('.').to_owned())
+        .filter(|extension| !extension.is_empty())
+        .collect();
+
+    Ok(FileResolutionOptions {
+        files,
+        ignored,
+        excluded_dirs,
+        extensions,
+        target_directory: metadata
+            .target_directory
+            .canonicalize()
+            .unwrap_or_else(|_| metadata.target_directory.clone().into()),
+    })
+}
+
+/// A workspace that is minified, see `--manifest-path`
+struct Workspace {
+    metadata: Metadata,
+    options: CauterizeOptions,
+}
+
+/// Reports which items would no longer be used if the given items were
+/// removed, and which items still use them, without changing anything
+fn what_if(args: &[String]) -> Result<()> {
+    let opts = WhatIfOptions::parse_args_default(args)?;
+    if opts.help {
+        println!("Usage: cargo minify what-if [OPTIONS] ITEM...");
+        println!();
+        println!("{}", WhatIfOptions::usage());
+        return Ok(());
+    }
+    if opts.items.is_empty() {
+        return Err(Error::Args("no items given, e.g. mycrate::old_api::Thing"));
+    }
+
+    logging::init(None, None)?;
+
+    let manifest_path = opts.manifest_path.as_ref().map(PathBuf::from);
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let defaults = MinifyOptions::parse_args_default::<&str>(&[])?;
+    let cauterize_options = cauterize_options_from(&defaults, &metadata)?;
+    let what_if = cauterize::what_if(&opts.items, &cauterize_options)?;
+
+    let items = opts.items.join(", ");
+    if what_if.dead.is_empty() {
+        println!("removing {items} would leave no other items unused");
+    } else {
+        let count = what_if.dead.len();
+        let plural = if count == 1 { "" } else { "s" };
+        println!("removing {items} would leave {count} more item{plural} unused:");
+        for node in &what_if.dead {
+            println!("    {} ({})", node.path, node.file.display());
+        }
+    }
+    if !what_if.users.is_empty() {
+        println!();
+        println!("these items still refer to them, and would need to be changed:");
+        for node in &what_if.users {
+            println!("    {} ({})", node.path, node.file.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the item graph of every package to a file, in the format that matches
+/// its extension
+fn emit_graph(file: &Path, workspaces: &[Workspace]) -> Result<()> {
+    let graphs: Vec<_> = workspaces
+        .iter()
+        .flat_map(|workspace| cauterize::package_graphs(&workspace.options))
+        .collect();
+    let content = match file.extension().and_then(|extension| extension.to_str()) {
+        Some("dot" | "gv") => graph::to_dot(&graphs),
+        Some("json") => graph::to_json(&graphs),
+        _ => {
+            return Err(Error::Args(
+                "the file given to --emit-graph should end in .dot or .json",
+            ))
+        }
+    };
+    std::fs::write(file, content)?;
+
+    Ok(())
+}
+
+/// A markdown section that lists the removed public items, to paste into a
+/// changelog or migration notes
+fn changelog(removed: &[cauterize::PublicItem]) -> String {
+    let mut section = "### Removed\n\n".to_owned();
+    if removed.is_empty() {
+        section.push_str("No public items were removed.\n");
+    }
+    for item in removed {
+        section.push_str(&format!("- `{}`: `{}`\n", item.path, item.signature));
+    }
+
+    section
+}
+
+/// Explains why the item at a location, or with a path, is or is not changed
+/// by a run with the given options
+fn explain(args: &[String]) -> Result<()> {
+    let (target, args) = match args.split_first() {
+        Some((target, args)) if !target.starts_with('-') => (Some(target), args),
+        _ => (None, args),
+    };
+    let opts = parse_options(args)?;
+    let Some(target) = target.filter(|_| !opts.help) else {
+        println!("Usage: cargo minify explain FILE:LINE|ITEM [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    };
+    let target: explain::Target = target.parse()?;
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+
+    let manifest_path = single_manifest_path(&opts)?;
+    let crate_resolution = crate_resolution_from(&opts)?;
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let file_resolution = file_resolution_from(&opts, &metadata)?;
+    let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+
+    let test_only = opts.mode == Mode::CfgTest;
+    let diagnostics = unused::get_diagnostics(
+        manifest_path.as_deref(),
+        &crate_resolution,
+        &unused::CheckOptions {
+            backend: backend_kind(&opts)?,
+            test_only,
+            recorded: opts.from_json.as_deref().map(Path::new),
+            keep_going: opts.keep_going,
+            warn_dead_code: warns_dead_code(&cauterize_options),
+            ignore_examples: opts.ignore_examples,
+            ignore_benches: opts.ignore_benches,
+            deadline: check_deadline(&opts),
+        },
+    )?;
+    let filters = explain::Filters {
+        kinds: &opts.kinds,
+        excluded_kinds: &excluded_kinds(&opts)?,
+        files: &file_resolution,
+        severity: opts.severity,
+        expand_macros: opts.expand_macros,
+        test_only,
+    };
+
+    explain::explain(&target, diagnostics, &filters, &cauterize_options)
+}
+
+/// The kinds of diagnostics given to `--exclude-kinds`
+fn excluded_kinds(opts: &MinifyOptions) -> Result<Vec<UnusedDiagnosticKind>> {
+    opts.exclude_kinds
+        .iter()
+        .flat_map(|kinds| kinds.split(','))
+        .filter(|kind| !kind.trim().is_empty())
+        .map(|kind| {
+            kind.trim()
+                .parse()
+                .map_err(|_| Error::Args("unknown kind of diagnostic given to --exclude-kinds"))
+        })
+        .collect()
+}
+
+/// The kinds of diagnostics to apply, taking `--kinds` and `--exclude-kinds`
+/// together; empty if all kinds are
+fn selected_kinds(opts: &MinifyOptions) -> Result<Vec<UnusedDiagnosticKind>> {
+    let excluded = excluded_kinds(opts)?;
+    if excluded.is_empty() {
+        return Ok(opts.kinds.clone());
+    }
+
+    let kinds = match opts.kinds.as_slice() {
+        [] => &UnusedDiagnosticKind::ALL[..],
+        kinds => kinds,
+    };
+    Ok(kinds
+        .iter()
+        .filter(|kind| !excluded.contains(kind))
+        .cloned()
+        .collect())
+}
+
+/// Whether the compiler should report unused items in packages that allow the
+/// `dead_code` lint (which it does not do otherwise), see `--force`
+fn warns_dead_code(options: &CauterizeOptions) -> bool {
+    options.force
+        && options
+            .dead_code_levels
+            .values()
+            .any(|&level| level == lints::Level::Allow)
+}
+
+/// When the checks of a run are stopped (see `--check-timeout`), counting
+/// from now
+fn check_deadline(opts: &MinifyOptions) -> Option<Instant> {
+    opts.check_timeout
+        .map(|secs| Instant::now() + Duration::from_secs(secs))
+}
+
+/// Computes the changes to make, and the files that were skipped, using the
+/// analysis that the options ask for
+fn find_changes(
+    opts: &MinifyOptions,
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+    file_resolution: &FileResolutionOptions,
+    cauterize_options: &CauterizeOptions,
+    deadline: Option<Instant>,
+) -> Result<(Vec<cauterize::Change>, Vec<cauterize::Skipped>)> {
+    let removals = explicit_removals(opts)?;
+    let kept: Vec<_> = opts
+        .keep_only
+        .iter()
+        .flat_map(|paths| paths.split(','))
+        .map(|path| path.trim().to_owned())
+        .filter(|path| !path.is_empty())
+        .collect();
+    let deprecated = opts.remove_deprecated || opts.deprecated_since.is_some();
+    let vendored = opts.vendor_dir.is_some();
+    if [!removals.is_empty(), !kept.is_empty(), deprecated, vendored]
+        .iter()
+        .filter(|&&given| given)
+        .count()
+        > 1
+    {
+        return Err(Error::Args(
+            "--remove, --keep-only, --remove-deprecated and --vendor-dir cannot be combined",
+        ));
+    }
+
+    let analysis = if !removals.is_empty() {
+        "remove"
+    } else if !kept.is_empty() {
+        "keep-only"
+    } else if deprecated {
+        "remove-deprecated"
+    } else if vendored {
+        "vendor-dir"
+    } else if opts.mode == Mode::BinFeature {
+        "bin-feature"
+    } else {
+        match backend_kind(opts)? {
+            backend::BackendKind::Check => "check",
+            backend::BackendKind::Clippy => "clippy",
+            backend::BackendKind::Json => "from-json",
+        }
+    };
+    progress::report(
+        "analysis-started",
+        serde_json::json!({ "analysis": analysis }),
+    );
+
+    let (changes, skipped) = if !removals.is_empty() {
+        let items = cauterize::explicit_removals(&removals, cauterize_options)?;
+        cauterize::process_chosen_items(items, cauterize_options)
+    } else if !kept.is_empty() {
+        let items = cauterize::keep_only(&kept, cauterize_options)?;
+        cauterize::process_chosen_items(items, cauterize_options)
+    } else if deprecated {
+        let since = opts.deprecated_since.as_deref();
+        let items = cauterize::deprecated_removals(since, cauterize_options);
+        cauterize::process_chosen_items(items, cauterize_options)
+    } else if vendored {
+        let items = cauterize::vendored_removals(cauterize_options);
+        cauterize::process_chosen_items(items, cauterize_options)
+    } else if opts.mode == Mode::BinFeature {
+        // gating items by binary does not depend on what the compiler reports
+        cauterize::process_chosen_items(BTreeMap::new(), cauterize_options)
+    } else {
+        if !cauterize_options.force {
+            let allowed = cauterize_options
+                .dead_code_levels
+                .iter()
+                .filter(|(_, &level)| level == lints::Level::Allow);
+            for (root, _) in allowed {
+                info!(
+                    "{} allows dead_code, so the unused items of its package are not reported \
+                     (use --force to override)",
+                    cauterize::relative_path(&root.join("Cargo.toml")).display()
+                );
+            }
+        }
+        // unused items fail the build of packages that deny them, which should
+        // not stop cargo from checking the others
+        let keep_going = opts.keep_going
+            || cauterize_options
+                .dead_code_levels
+                .values()
+                .any(|&level| level >= lints::Level::Deny);
+        let kinds = selected_kinds(opts)?;
+        let unused = unused::get_unused(
+            manifest_path,
+            crate_resolution,
+            file_resolution,
+            &kinds,
+            opts.severity,
+            opts.expand_macros,
+            &unused::CheckOptions {
+                backend: backend_kind(opts)?,
+                test_only: opts.mode == Mode::CfgTest,
+                recorded: opts.from_json.as_deref().map(Path::new),
+                keep_going,
+                warn_dead_code: warns_dead_code(cauterize_options),
+                ignore_examples: opts.ignore_examples,
+                ignore_benches: opts.ignore_benches,
+                deadline,
+            },
+        )?;
+        let suggestions = if opts.apply_suggestions {
+            unused::get_suggestions(
+                manifest_path,
+                crate_resolution,
+                file_resolution,
+                &unused::CheckOptions {
+                    backend: backend_kind(opts)?,
+                    test_only: false,
+                    recorded: opts.from_json.as_deref().map(Path::new),
+                    keep_going,
+                    warn_dead_code: warns_dead_code(cauterize_options),
+                    ignore_examples: opts.ignore_examples,
+                    ignore_benches: opts.ignore_benches,
+                    deadline,
+                },
+            )?
+        } else {
+            Vec::new()
+        };
+        let features = if opts.remove_unused_features {
+            unused::get_unused_features(
+                manifest_path,
+                crate_resolution,
+                file_resolution,
+                &unused::CheckOptions {
+                    backend: backend_kind(opts)?,
+                    test_only: false,
+                    recorded: opts.from_json.as_deref().map(Path::new),
+                    keep_going,
+                    warn_dead_code: warns_dead_code(cauterize_options),
+                    ignore_examples: opts.ignore_examples,
+                    ignore_benches: opts.ignore_benches,
+                    deadline,
+                },
+            )?
+        } else {
+            Vec::new()
+        };
+        cauterize::process_diagnostics(unused, suggestions, features, cauterize_options)
+    };
+
+    for change in &changes {
+        progress::report(
+            "change-computed",
+            serde_json::json!({
+                "file": change.file_name(),
+                "removes_file": change.removes_file(),
+                "lines_removed": change.lines_removed(),
+            }),
+        );
+    }
+    progress::report(
+        "analysis-done",
+        serde_json::json!({ "changes": changes.len(), "skipped": skipped.len() }),
+    );
+
+    Ok((changes, skipped))
+}
+
+fn report_skipped(skipped: &[cauterize::Skipped]) {
+    for skip in skipped {
+        warn!(
+            "skipped {} ({} unused items), since it {}",
+            skip.file_name().display(),
+            skip.unused(),
+            skip.reason()
+        );
+    }
+}
+
+/// The backend that the options select: the one given to `--backend`, or the
+/// recording given to `--from-json` (which goes with `--backend json` only)
+fn backend_kind(opts: &MinifyOptions) -> Result<backend::BackendKind> {
+    use backend::BackendKind;
+
+    match (opts.backend, &opts.from_json) {
+        (None | Some(BackendKind::Json), Some(_)) => Ok(BackendKind::Json),
+        (Some(BackendKind::Json), None) => Err(Error::Args("--backend json requires --from-json")),
+        (Some(_), Some(_)) => Err(Error::Args(
+            "--from-json reads a recording instead of running a backend, so it cannot be used \
+             with --backend check or --backend clippy",
+        )),
+        (backend, None) => Ok(backend.unwrap_or_default()),
+    }
+}
+
+/// Packages a copy of the selected packages with the changes applied, using
+/// `cargo package`, without changing the working tree
+fn package(args: &[String]) -> Result<()> {
+    let opts = parse_options(args)?;
+    if opts.help {
+        println!("Usage: cargo minify package [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    }
+
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+    interrupt::install();
+    progress::init(opts.progress_format);
+
+    let manifest_path = single_manifest_path(&opts)?;
+    let crate_resolution = crate_resolution_from(&opts)?;
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let file_resolution = file_resolution_from(&opts, &metadata)?;
+    let _lock = lock::acquire(metadata.target_directory.as_std_path())?;
+    let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+
+    let (changes, skipped) = find_changes(
+        &opts,
+        manifest_path.as_deref(),
+        &crate_resolution,
+        &file_resolution,
+        &cauterize_options,
+        check_deadline(&opts),
+    )?;
+    if interrupt::requested() {
+        return Err(Error::Interrupted);
+    }
+    report_skipped(&skipped);
+
+    let scratch = cauterize_options
+        .target_directory
+        .join("cargo-minify")
+        .join("package");
+    cauterize::copy_workspace(&changes, &cauterize_options, &scratch)?;
+
+    let packages = packaged_names(manifest_path.as_deref(), &crate_resolution, &metadata)?;
+    let output = match &opts.output {
+        Some(output) => PathBuf::from(output),
+        None => cauterize_options.target_directory.join("minified"),
+    };
+    let files = package::package(&scratch, &packages, &output)?;
+
+    if !opts.quiet {
+        let removed: usize = changes.iter().map(|change| change.lines_removed()).sum();
+        eprintln!("removed {removed} lines from {} files", changes.len());
+        for file in files {
+            println!("wrote {}", file.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// The names of the packages that the package subcommand packages
+fn packaged_names(
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+    metadata: &Metadata,
+) -> Result<Vec<String>> {
+    let packages = match crate_resolution {
+        CrateResolutionOptions::Root => match resolver::default_members(manifest_path) {
+            Some(members) => metadata
+                .workspace_packages()
+                .iter()
+                .filter(|package| members.contains(&package.id))
+                .map(|package| package.name.clone())
+                .collect(),
+            None => match metadata.root_package() {
+                Some(package) => vec![package.name.clone()],
+                None => metadata
+                    .workspace_packages()
+                    .iter()
+                    .map(|package| package.name.clone())
+                    .collect(),
+            },
+        },
+        CrateResolutionOptions::Workspace { exclude, .. } => metadata
+            .workspace_packages()
+            .iter()
+            .map(|package| package.name.clone())
+            .filter(|name| {
+                !exclude
+                    .iter()
+                    .any(|pattern| glob_match::glob_match(pattern, name))
+            })
+            .collect(),
+        CrateResolutionOptions::Package { packages } => {
+            resolver::package_names(&metadata.packages, packages)?
+        }
+    };
+
+    Ok(packages)
+}
+
+/// Prints the packages and targets that a run with the given options would
+/// analyze, without analyzing them
+fn targets(args: &[String]) -> Result<()> {
+    let opts = parse_options(args)?;
+    if opts.help {
+        println!("Usage: cargo minify targets [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    }
+
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+
+    let crate_resolution = crate_resolution_from(&opts)?;
+    let mut targets = Vec::new();
+    for manifest_path in manifest_paths(&opts)? {
+        targets.extend(resolver::get_package_targets(
+            manifest_path.as_deref(),
+            &crate_resolution,
+        )?);
+    }
+    targets.sort_by(|(package, target), (other_package, other)| {
+        (package, &target.kind, &target.name).cmp(&(other_package, &other.kind, &other.name))
+    });
+
+    let mut current = None;
+    for (package, target) in &targets {
+        let src_path = cauterize::relative_path(target.src_path.as_std_path());
+        if opts.message_format == MessageFormat::Json {
+            let target = serde_json::json!({
+                "reason": "target",
+                "package": package,
+                "name": target.name,
+                "kind": target.kind,
+                "src_path": src_path,
+            });
+            println!("{target}");
+            continue;
+        }
+
+        if current != Some(package) {
+            println!("package `{package}`");
+            current = Some(package);
+        }
+        println!(
+            "    {} `{}` ({})",
+            target.kind.join(", "),
+            target.name,
+            src_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Compares the findings of two recorded runs (by default the last two), given
+/// by their ids or the paths of their files
+fn diff_report(args: &[String]) -> Result<()> {
+    let runs: Vec<_> = args
+        .iter()
+        .take_while(|arg| !arg.starts_with('-'))
+        .cloned()
+        .collect();
+    let opts = parse_options(&args[runs.len()..])?;
+    if opts.help {
+        println!("Usage: cargo minify diff-report [OLD NEW] [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    }
+
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+
+    let manifest_path = single_manifest_path(&opts)?;
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+    let target_directory = &cauterize_options.target_directory;
+
+    let (old, new) = match runs.as_slice() {
+        [old, new] => (old.clone(), new.clone()),
+        [] => {
+            match history::runs(target_directory, &cauterize_options.workspace_root)?.as_slice() {
+                [.., old, new] => (old.clone(), new.clone()),
+                _ => {
+                    return Err(Error::Args(
+                        "diff-report needs two recorded runs, but fewer runs were recorded",
+                    ))
+                }
+            }
+        }
+        _ => return Err(Error::Args("diff-report takes either two runs, or none")),
+    };
+    let old_findings = history::load(target_directory, &old)?;
+    let new_findings = history::load(target_directory, &new)?;
+
+    let exists = |finding: &history::Finding| {
+        let Ok(kind) = finding.kind.parse() else {
+            return true;
+        };
+        let file_name = cauterize_options.workspace_root.join(&finding.file);
+        cauterize::contains_item(&file_name, &kind, &finding.name, &cauterize_options)
+    };
+    let report = history::compare(&old_findings, &new_findings, exists);
+
+    if opts.message_format == MessageFormat::Json {
+        for (status, finding) in &report {
+            let finding = serde_json::json!({
+                "reason": "finding",
+                "status": status.name(),
+                "file": finding.file,
+                "kind": finding.kind,
+                "name": finding.name,
+            });
+            println!("{finding}");
+        }
+        return Ok(());
+    }
+
+    let count = |status| report.iter().filter(|(other, _)| *other == status).count();
+    println!(
+        "from run {old} to run {new}: {} appeared, {} fixed, {} disappeared",
+        count(history::Status::Appeared),
+        count(history::Status::Fixed),
+        count(history::Status::Disappeared),
+    );
+    for status in [
+        history::Status::Appeared,
+        history::Status::Fixed,
+        history::Status::Disappeared,
+    ] {
+        for (_, finding) in report.iter().filter(|(other, _)| *other == status) {
+            println!(
+                "    {}: {} `{}` ({})",
+                status.name(),
+                finding.kind,
+                finding.name,
+                finding.file.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves the source files of the workspace, to put them back later using
+/// `cargo minify restore`
+fn take_snapshot(args: &[String]) -> Result<()> {
+    let opts = parse_options(args)?;
+    if opts.help {
+        println!("Usage: cargo minify snapshot [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    }
+
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+
+    let manifest_path = single_manifest_path(&opts)?;
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+    let _lock = lock::acquire(&cauterize_options.target_directory)?;
+
+    let files = cauterize::source_files(&cauterize_options);
+    let id = snapshot::take(
+        &cauterize_options.target_directory,
+        &cauterize_options.workspace_root,
+        &files,
+    )?;
+    if !opts.quiet {
+        eprintln!(
+            "saved {} files as snapshot {id} (use `cargo minify restore {id}` to put them back)",
+            files.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Puts back the source files of a snapshot (the last one, if none is given)
+fn restore_snapshot(args: &[String]) -> Result<()> {
+    let ids: Vec<_> = args
+        .iter()
+        .take_while(|arg| !arg.starts_with('-'))
+        .cloned()
+        .collect();
+    let opts = parse_options(&args[ids.len()..])?;
+    if opts.help {
+        println!("Usage: cargo minify restore [ID] [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    }
+
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+
+    let manifest_path = single_manifest_path(&opts)?;
+    let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+    let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+    let target_directory = &cauterize_options.target_directory;
+    let _lock = lock::acquire(target_directory)?;
+
+    let id = match ids.as_slice() {
+        [id] => id.clone(),
+        [] => snapshot::snapshots(target_directory)?
+            .pop()
+            .ok_or(Error::Args("no snapshot was taken"))?,
+        _ => return Err(Error::Args("restore takes a single snapshot")),
+    };
+    let restored = snapshot::restore(target_directory, &cauterize_options.workspace_root, &id)?;
+    if !opts.quiet {
+        for file in &restored {
+            eprintln!("restored {}", file.display());
+        }
+        eprintln!("restored {} files of snapshot {id}", restored.len());
+    }
+
+    Ok(())
+}
+
+/// Parses the options of a run, with the flags of its preset (if any) before
+/// the given ones
+fn parse_options<S: AsRef<str>>(args: &[S]) -> Result<MinifyOptions> {
+    let opts = MinifyOptions::parse_args_default(args)?;
+    let Some(preset) = opts.preset else {
+        return Ok(opts);
+    };
+
+    let args: Vec<_> = preset
+        .flags()
+        .iter()
+        .copied()
+        .chain(args.iter().map(AsRef::as_ref))
+        .collect();
+    Ok(MinifyOptions::parse_args_default(&args)?)
+}
+
+/// Checks the environment (the toolchain, the workspace, the version control
+/// system and the target directory), and tells how to fix what is wrong
+fn doctor(args: &[String]) -> Result<()> {
+    let opts = parse_options(args)?;
+    if opts.help {
+        println!("Usage: cargo minify doctor [OPTIONS]");
+        println!();
+        println!("{}", MinifyOptions::usage());
+        return Ok(());
+    }
+
+    logging::init(
+        opts.log_level.as_deref(),
+        opts.log_file.as_deref().map(Path::new),
+    )?;
+    cargo::init(opts.offline, opts.locked, opts.frozen);
+
+    let mut checks = doctor::toolchain();
+    checks.extend(doctor::rustflags());
+
+    let manifest_path = single_manifest_path(&opts)?;
+    match resolver::get_cargo_metadata(manifest_path.as_deref()) {
+        Ok(metadata) => {
+            let members = metadata.workspace_members.len();
+            let plural = if members == 1 { "" } else { "s" };
+            checks.push(doctor::Check::passed(format!(
+                "the workspace at {} has {members} member{plural}",
+                metadata.workspace_root
+            )));
+            let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+            for (root, level) in &cauterize_options.dead_code_levels {
+                if *level == lints::Level::Allow {
+                    checks.push(doctor::Check::warning(
+                        format!(
+                            "{} allows dead_code, so the unused items of its package are not \
+                             reported",
+                            root.join("Cargo.toml").display()
+                        ),
+                        "pass --force, or remove dead_code from its [lints] table",
+                    ));
+                }
+            }
+            checks.push(doctor::version_control(&cauterize_options.workspace_root));
+            checks.push(doctor::target_directory(
+                &cauterize_options.target_directory,
+            ));
+        }
+        Err(err) => checks.push(doctor::Check::problem(
+            format!(
+                "the workspace could not be resolved: {}",
+                err.to_string().trim_end()
+            ),
+            "run `cargo metadata` to see why, or point --manifest-path to its Cargo.toml",
+        )),
+    }
+
+    doctor::print(&checks, opts.color);
+    let failed = checks
+        .iter()
+        .filter(|check| check.outcome == doctor::Outcome::Problem)
+        .count();
+    match failed {
+        0 => Ok(()),
+        failed => Err(Error::ChecksFailed(failed)),
+    }
+}
+
+/// The manifests of the workspaces to minify, from `--manifest-path` and the
+/// file given by `--manifest-path-from` (skipping blank lines and `#` comments);
+/// `None` stands for the manifest that cargo finds in the current directory
+fn manifest_paths(opts: &MinifyOptions) -> Result<Vec<Option<PathBuf>>> {
+    let mut paths: Vec<_> = opts.manifest_path.iter().map(PathBuf::from).collect();
+    if let Some(file) = &opts.manifest_path_from {
+        let content = std::fs::read_to_string(file)?;
+        let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+        paths.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| dir.join(line)),
+        );
+    }
+
+    if paths.is_empty() {
+        Ok(vec![None])
+    } else {
+        Ok(paths.into_iter().map(Some).collect())
+    }
+}
+
+/// The manifest of the workspace, for the subcommands that handle one
+/// workspace at a time
+fn single_manifest_path(opts: &MinifyOptions) -> Result<Option<PathBuf>> {
+    let mut paths = manifest_paths(opts)?;
+    if paths.len() > 1 {
+        return Err(Error::Args(
+            "this subcommand handles a single workspace, so give one --manifest-path",
+        ));
+    }
+
+    Ok(paths.pop().flatten())
+}
+
+/// Whether the state of the version control system of a workspace allows
+/// changes to be applied; if not, tells the user why
+fn vcs_allows_apply(cargo_root: &Path, opts: &MinifyOptions) -> bool {
+    use vcs::Status;
+    match vcs::status(cargo_root) {
+        Status::Error(e) => {
+            eprintln!("git problem: {}", e);
+            false
+        }
+        Status::NoVCS if !opts.allow_no_vcs => {
+            eprintln!(
+                "no VCS found for this package and `cargo minify` can potentially perform \
+                 destructive changes; if you'd like to suppress this error pass \
+                 `--allow-no-vcs`"
+            );
+            false
+        }
+        Status::Unclean { dirty, staged }
+            if !(dirty.is_empty() || opts.allow_dirty)
+                || !(staged.is_empty() || opts.allow_staged) =>
+        {
+            eprintln!("working directory contains dirty/staged files:");
+            for file in dirty {
+                eprintln!("\t{} (dirty)", file)
+            }
+            for file in staged {
+                eprintln!("\t{} (staged)", file)
+            }
+            eprintln!(
+                "please fix this or ignore this warning with --allow-dirty and/or \
+                 --allow-staged"
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+/// The paths of the items to remove explicitly, from `--remove` and the file
+/// given by `--remove-from` (skipping blank lines and `#` comments)
+fn explicit_removals(opts: &MinifyOptions) -> Result<Vec<String>> {
+    let mut paths = opts.remove.clone();
+    if let Some(file) = &opts.remove_from {
+        let content = std::fs::read_to_string(file)?;
+        paths.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_owned),
+        );
+    }
+
+    Ok(paths)
+}
+
+/// Whether a target is left out of the analysis (see `--ignore-examples` and
+/// `--ignore-benches`)
+fn is_ignored_target(target: &cargo_metadata::Target, opts: &MinifyOptions) -> bool {
+    (opts.ignore_examples && target.is_example()) || (opts.ignore_benches && target.is_bench())
+}
+
+/// Sorts the targets of a package into its library, binaries and the rest
+fn package_targets(package: &Package, opts: &MinifyOptions) -> features::Targets {
+    let mut targets = features::Targets {
+        manifest_path: package.manifest_path.clone().into(),
+        lib: None,
+        bins: Vec::new(),
+        others: Vec::new(),
+        features: package.features.keys().cloned().collect(),
+        gated_bins: BTreeSet::new(),
+        published: package
+            .publish
+            .as_ref()
+            .is_none_or(|registries| !registries.is_empty()),
+    };
+
+    for target in &package.targets {
+        let Ok(root) = target.src_path.canonicalize() else {
+            continue;
+        };
+        if is_ignored_target(target, opts) {
+            continue;
+        } else if target.is_bin() {
+            if target.required_features.contains(&target.name) {
+                targets.gated_bins.insert(target.name.clone());
+            }
+            targets.bins.push((target.name.clone(), root));
+        } else if target
+            .kind
+            .iter()
+            .any(|kind| matches!(kind.as_str(), "lib" | "rlib" | "dylib"))
+        {
+            targets.lib = Some((target.name.replace('-', "_"), root));
+        } else {
+            targets.others.push((target.name.replace('-', "_"), root));
+        }
+    }
+
+    targets
+}
+
+/// How to minify the files of a workspace, from the command line and its metadata
+fn cauterize_options_from(opts: &MinifyOptions, metadata: &Metadata) -> Result<CauterizeOptions> {
+    let vendored = match &opts.vendor_dir {
+        Some(dir) => vendor::crates(Path::new(dir))?,
+        None => Vec::new(),
+    };
+
+    let editions = metadata
+        .packages
+        .iter()
+        .filter_map(|package| {
+            let root = package.manifest_path.parent()?.canonicalize().ok()?;
+            Some((root, package.edition))
+        })
+        .chain(
+            vendored
+                .iter()
+                .map(|vendored| (vendored.dir.clone(), vendored.edition)),
+        )
+        .collect();
+    let rust_versions = metadata
+        .packages
+        .iter()
+        .filter_map(|package| {
+            let root = package.manifest_path.parent()?.canonicalize().ok()?;
+            Some((root, package.rust_version.clone()?))
+        })
+        .collect();
+    let workspace_manifest =
+        std::fs::read_to_string(metadata.workspace_root.join("Cargo.toml")).unwrap_or_default();
+    let dead_code_levels = metadata
+        .packages
+        .iter()
+        .filter_map(|package| {
+            let root = package.manifest_path.parent()?.canonicalize().ok()?;
+            let manifest = std::fs::read_to_string(&package.manifest_path).ok()?;
+            Some((
+                root,
+                lints::dead_code_level(&manifest, &workspace_manifest)?,
+            ))
+        })
+        .collect();
+    let crate_roots = metadata
+        .packages
+        .iter()
+        .flat_map(|package| &package.targets)
+        .filter(|target| !is_ignored_target(target, opts))
+        .filter_map(|target| target.src_path.canonicalize().ok())
+        .chain(vendored.iter().map(|vendored| vendored.root.clone()))
+        .collect();
+
+    let scope = opts
+        .paths
+        .iter()
+        .map(|path| {
+            Path::new(path)
+                .canonicalize()
+                .map_err(|_| Error::UnknownPath(PathBuf::from(path)))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut options = CauterizeOptions {
+        mode: opts.mode,
+        tombstones: opts.tombstones,
+        max_blank_lines: opts.max_blank_lines,
+        max_removals: opts.max_removals,
+        unit_structs: opts.unit_structs,
+        remove_marker_impls: opts.remove_marker_impls,
+        strip_docs: opts.strip_docs,
+        strip_tests: opts.strip_tests,
+        remove_ignored_tests: opts.remove_ignored_tests || opts.ignored_for.is_some(),
+        ignored_for_days: opts.ignored_for,
+        remove_unused_allows: opts.remove_unused_allows,
+        exported_macros: opts.exported_macros,
+        keep_doctested: opts.keep_doctested,
+        keep_attributes: opts.keep_attribute.clone(),
+        editions,
+        rust_versions,
+        dead_code_levels,
+        crate_roots,
+        workspace_root: metadata
+            .workspace_root
+            .canonicalize()
+            .unwrap_or_else(|_| metadata.workspace_root.clone().into()),
+        target_directory: metadata
+            .target_directory
+            .canonicalize()
+            .unwrap_or_else(|_| metadata.target_directory.clone().into()),
+        allow_external: opts.allow_external,
+        include_generated: opts.include_generated,
+        ignored: BTreeSet::new(),
+        allow_included: opts.allow_included,
+        force: opts.force,
+        scope,
+        packages: metadata
+            .packages
+            .iter()
+            .map(|package| package_targets(package, opts))
+            .collect(),
+        vendored,
+    };
+    if !opts.include_ignored {
+        let files = cauterize::source_files(&options);
+        options.ignored = vcs::ignored(&options.workspace_root, &files)
+            .into_iter()
+            .map(|file| options.workspace_root.join(file))
+            .collect();
+    }
+    // the attributes are evaluated for what `cargo check` builds the
+    // workspace for
+    cfg::configure(cfg::Configuration::of(metadata));
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Held by the tests that run checks, and exclusively by those that look
+    /// at [`unused::timed_out`]: like the lock of the same name in the tests of
+    /// the library, since the running check and whether it timed out are
+    /// global to the process
+    static CHECKS: std::sync::RwLock<()> = std::sync::RwLock::new(());
+
+    const MANIFEST: &str =
+        "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n";
+
+    /// Files in a directory of their own, which is removed afterwards
+    struct Fixture {
+        dir: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = env::temp_dir().join(format!("minify-{name}-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            for (file, content) in files {
+                let path = dir.join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, content).unwrap();
+            }
+
+            Fixture {
+                dir: dir.canonicalize().unwrap(),
+            }
+        }
+
+        /// A package with just a library
+        fn library(name: &str, lib: &str) -> Self {
+            Self::new(name, &[("Cargo.toml", MANIFEST), ("src/lib.rs", lib)])
+        }
+
+        fn path(&self, file: &str) -> String {
+            self.dir.join(file).display().to_string()
+        }
+
+        /// The options of a run with the given arguments, for the workspace
+        /// at the root of the fixture
+        fn options(&self, args: &[&str]) -> Result<CauterizeOptions> {
+            let manifest_path = self.path("Cargo.toml");
+            let opts = parse_options(&[args, &["--manifest-path", &manifest_path]].concat())?;
+            let metadata = resolver::get_cargo_metadata(Some(Path::new(&manifest_path)))?;
+            cauterize_options_from(&opts, &metadata)
+        }
+
+        /// What a run with the given arguments finds for the workspace at the
+        /// root of the fixture
+        fn changes(&self, args: &[&str]) -> Result<Findings> {
+            self.changes_of("Cargo.toml", args)
+        }
+
+        /// What a run with the given arguments finds for the workspace of the
+        /// given manifest (relative to the fixture)
+        fn changes_of(&self, manifest: &str, args: &[&str]) -> Result<Findings> {
+            let _checks = CHECKS.read();
+            self.unlocked_changes_of(manifest, args)
+        }
+
+        /// Like [`Fixture::changes_of`], for a caller that holds
+        /// [`CHECKS`] already
+        fn unlocked_changes_of(&self, manifest: &str, args: &[&str]) -> Result<Findings> {
+            let manifest_path = self.path(manifest);
+            let opts = parse_options(&[args, &["--manifest-path", &manifest_path]].concat())?;
+            let crate_resolution = crate_resolution_from(&opts)?;
+            let manifest_path = single_manifest_path(&opts)?;
+            let metadata = resolver::get_cargo_metadata(manifest_path.as_deref())?;
+            let file_resolution = file_resolution_from(&opts, &metadata)?;
+            let cauterize_options = cauterize_options_from(&opts, &metadata)?;
+            let (changes, skipped) = find_changes(
+                &opts,
+                manifest_path.as_deref(),
+                &crate_resolution,
+                &file_resolution,
+                &cauterize_options,
+                check_deadline(&opts),
+            )?;
+
+            Ok(Findings {
+                root: self.dir.clone(),
+                changes,
+                skipped,
+            })
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Whether two paths lead to the same file, as the paths in the findings
+    /// may go through `..`
+    fn is_same_file(a: &Path, b: &Path) -> bool {
+        a.canonicalize()
+            .ok()
+            .is_some_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+    }
+
+    struct Findings {
+        root: PathBuf,
+        changes: Vec<cauterize::Change>,
+        skipped: Vec<cauterize::Skipped>,
+    }
+
+    impl Findings {
+        /// The files that are changed, relative to the fixture
+        fn changed(&self) -> Vec<String> {
+            let mut changed: Vec<_> = self
+                .changes
+                .iter()
+                .map(|change| {
+                    let file = change.file_name();
+                    file.strip_prefix(&self.root)
+                        .unwrap_or(file)
+                        .display()
+                        .to_string()
+                })
+                .collect();
+            changed.sort();
+            changed
+        }
+
+        /// The proposed content of a file (relative to the fixture), if it
+        /// is changed
+        fn proposed(&self, file: &str) -> Option<&str> {
+            let change = self
+                .changes
+                .iter()
+                .find(|change| is_same_file(change.file_name(), &self.root.join(file)))?;
+            Some(std::str::from_utf8(change.proposed_content()).unwrap())
+        }
+
+        /// Why a file (relative to the fixture) is skipped, if it is
+        fn skipped(&self, file: &str) -> Option<&str> {
+            let skipped = self
+                .skipped
+                .iter()
+                .find(|skipped| is_same_file(skipped.file_name(), &self.root.join(file)))?;
+            Some(skipped.reason())
+        }
+    }
+
+    #[test]
+    fn external_files() {
+        let fixture = Fixture::new(
+            "external",
+            &[
+                ("workspace/Cargo.toml", MANIFEST),
+                (
+                    "workspace/src/lib.rs",
+                    "#[path = \"../../outside.rs\"]\nmod outside;\n",
+                ),
+                ("outside.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        let findings = fixture.changes_of("workspace/Cargo.toml", &[]).unwrap();
+        assert!(findings.changes.is_empty());
+        assert!(findings
+            .skipped("outside.rs")
+            .is_some_and(|reason| reason.contains("outside of the workspace")));
+
+        let findings = fixture
+            .changes_of("workspace/Cargo.toml", &["--allow-external"])
+            .unwrap();
+        assert_eq!(findings.proposed("outside.rs"), Some(""));
+    }
+
+    #[test]
+    fn keep_only() {
+        let fixture = Fixture::library(
+            "keep-only",
+            "pub fn run() {
+    helper();
+}
+
+fn helper() {}
+
+pub mod cli {
+    pub fn parse() {}
+}
+
+pub fn other() {
+    unrelated();
+}
+
+fn unrelated() {}
+",
+        );
+
+        let findings = fixture
+            .changes(&["--keep-only", "fixture::run,fixture::cli::*"])
+            .unwrap();
+        assert_eq!(
+            findings.proposed("src/lib.rs"),
+            Some(
+                "pub fn run() {
+    helper();
+}
+
+fn helper() {}
+
+pub mod cli {
+    pub fn parse() {}
+}
+
+"
+            )
+        );
+
+        assert!(matches!(
+            fixture.changes(&["--keep-only", "fixture::run", "--remove", "fixture::other"]),
+            Err(Error::Args(_))
+        ));
+    }
+
+    #[test]
+    fn keep_only_packages() {
+        let fixture = Fixture::new(
+            "keep-only-packages",
+            &[
+                ("Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]\n"),
+                (
+                    "a/Cargo.toml",
+                    &format!(
+                        "{}\n[dependencies]\nb = {{ path = \"../b\" }}\n",
+                        MANIFEST.replace("fixture", "a")
+                    ),
+                ),
+                (
+                    "a/src/lib.rs",
+                    "pub fn run() {\n    b::helper();\n}\n\npub fn other() {}\n",
+                ),
+                ("b/Cargo.toml", &MANIFEST.replace("fixture", "b")),
+                (
+                    "b/src/lib.rs",
+                    "pub fn helper() {}\n\npub fn unrelated() {}\n",
+                ),
+            ],
+        );
+
+        // what the kept item uses from the other package is kept as well
+        let findings = fixture
+            .changes(&["--workspace", "--keep-only", "a::run"])
+            .unwrap();
+        assert_eq!(
+            findings.proposed("a/src/lib.rs"),
+            Some("pub fn run() {\n    b::helper();\n}\n\n")
+        );
+        assert_eq!(
+            findings.proposed("b/src/lib.rs"),
+            Some("pub fn helper() {}\n\n")
+        );
+    }
+
+    #[test]
+    fn keep_only_attributes() {
+        let fixture = Fixture::library(
+            "keep-only-attributes",
+            "pub fn api() {}
+
+#[inline]
+pub fn kept() {
+    helper();
+}
+
+fn helper() {}
+
+pub fn other() {}
+",
+        );
+
+        // what a kept item uses is kept as well
+        let findings = fixture
+            .changes(&["--keep-only", "fixture::api", "--keep-attribute", "inline"])
+            .unwrap();
+        assert_eq!(
+            findings.proposed("src/lib.rs"),
+            Some(
+                "pub fn api() {}
+
+#[inline]
+pub fn kept() {
+    helper();
+}
+
+fn helper() {}
+
+"
+            )
+        );
+    }
+
+    #[test]
+    fn unused_allows() {
+        let fixture = Fixture::new(
+            "unused-allows",
+            &[
+                (
+                    "app/Cargo.toml",
+                    "[package]
+name = \"fixture\"
+version = \"0.1.0\"
+edition = \"2021\"
+
+[dependencies]
+dep = { path = \"../dep\" }
+",
+                ),
+                (
+                    "app/src/lib.rs",
+                    "#[allow(dead_code)]
+fn test_helper() {}
+
+#[allow(unused_variables)]
+pub fn api() {
+    dep::used();
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn helper() {
+        super::test_helper();
+    }
+}
+",
+                ),
+                ("dep/Cargo.toml", &MANIFEST.replace("fixture", "dep")),
+                ("dep/src/lib.rs", "pub fn used() {}\n"),
+            ],
+        );
+
+        // the allow is only unfulfilled when the library is built without its tests
+        let findings = fixture
+            .changes_of("app/Cargo.toml", &["--remove-unused-allows"])
+            .unwrap();
+        assert_eq!(
+            findings.proposed("app/src/lib.rs"),
+            Some(
+                "#[allow(dead_code)]
+fn test_helper() {}
+
+pub fn api() {
+    dep::used();
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn helper() {
+        super::test_helper();
+    }
+}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn same_named_imports() {
+        let fixture = Fixture::new(
+            "same-named-imports",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/main.rs",
+                    "mod a;
+mod b;
+
+use crate::a::helper as unused_helper;
+use crate::b::helper;
+
+fn main() {
+    helper();
+}
+",
+                ),
+                ("src/a.rs", "pub fn helper() {}\n"),
+                ("src/b.rs", "pub fn helper() {}\n"),
+            ],
+        );
+
+        // only the import of the removed `a::helper` goes, and then `a` itself
+        let findings = fixture.changes(&[]).unwrap();
+        assert_eq!(
+            findings.proposed("src/main.rs"),
+            Some(
+                "mod b;
+
+use crate::b::helper;
+
+fn main() {
+    helper();
+}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn same_named_impls() {
+        let fixture = Fixture::new(
+            "same-named-impls",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/main.rs",
+                    "mod other;
+
+struct Foo;
+
+mod inner {
+    impl crate::other::Foo {
+        pub fn hello(&self) {}
+    }
+}
+
+fn main() {
+    other::Foo.hello();
+}
+",
+                ),
+                ("src/other.rs", "pub struct Foo;\n"),
+            ],
+        );
+
+        let findings = fixture.changes(&[]).unwrap();
+        assert_eq!(
+            findings.proposed("src/main.rs"),
+            Some(
+                "mod other;
+
+mod inner {
+    impl crate::other::Foo {
+        pub fn hello(&self) {}
+    }
+}
+
+fn main() {
+    other::Foo.hello();
+}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn default_features() {
+        let manifest = format!("{MANIFEST}\n[features]\ndefault = [\"std\"]\nstd = []\n");
+        let fixture = Fixture::new(
+            "default-features",
+            &[
+                ("Cargo.toml", &manifest),
+                (
+                    "src/main.rs",
+                    "#[cfg(feature = \"std\")]
+fn unused() {}
+
+#[cfg(not(feature = \"std\"))]
+fn unused() {}
+
+fn main() {}
+",
+                ),
+            ],
+        );
+
+        let findings = fixture.changes(&[]).unwrap();
+        assert_eq!(
+            findings.proposed("src/main.rs"),
+            Some("\n#[cfg(not(feature = \"std\"))]\nfn unused() {}\n\nfn main() {}\n")
+        );
+    }
+
+    #[test]
+    fn bin_feature() {
+        fn files(manifest: &str) -> [(&str, &str); 4] {
+            [
+                ("Cargo.toml", manifest),
+                ("src/lib.rs", "pub fn shared() {}\n\npub fn only_a() {}\n"),
+                (
+                    "src/bin/a.rs",
+                    "fn main() {\n    fixture::shared();\n    fixture::only_a();\n}\n",
+                ),
+                ("src/bin/b.rs", "fn main() {\n    fixture::shared();\n}\n"),
+            ]
+        }
+        let gated = "pub fn shared() {}\n\n#[cfg(feature = \"a\")]\npub fn only_a() {}\n";
+
+        // the users of a published library may need the item
+        let fixture = Fixture::new("bin-feature", &files(MANIFEST));
+        let findings = fixture.changes(&["--mode", "bin-feature"]).unwrap();
+        assert!(findings.changes.is_empty());
+        let findings = fixture
+            .changes(&["--mode", "bin-feature", "--force"])
+            .unwrap();
+        assert_eq!(findings.proposed("src/lib.rs"), Some(gated));
+        drop(fixture);
+
+        let unpublished = format!("{MANIFEST}publish = false\n");
+        let fixture = Fixture::new("bin-feature", &files(&unpublished));
+        let findings = fixture.changes(&["--mode", "bin-feature"]).unwrap();
+        assert_eq!(findings.proposed("src/lib.rs"), Some(gated));
+    }
+
+    #[test]
+    fn what_if() {
+        let fixture = Fixture::library(
+            "what-if",
+            "pub fn old_api() {
+    helper();
+    shared();
+}
+
+fn helper() {}
+
+fn shared() {}
+
+pub fn new_api() {
+    shared();
+}
+
+pub fn caller() {
+    old_api();
+}
+",
+        );
+        let options = fixture.options(&[]).unwrap();
+        let paths = |nodes: &[graph::Node]| -> Vec<String> {
+            nodes.iter().map(|node| node.path.clone()).collect()
+        };
+
+        let what_if = cauterize::what_if(&["fixture::old_api".to_owned()], &options).unwrap();
+        assert_eq!(paths(&what_if.dead), ["fixture::helper"]);
+        assert_eq!(paths(&what_if.users), ["fixture::caller"]);
+
+        assert!(matches!(
+            cauterize::what_if(&["fixture::missing".to_owned()], &options),
+            Err(Error::UnknownItem(_))
+        ));
+    }
+
+    #[test]
+    fn package() {
+        let fixture = Fixture::library("package", "pub fn used() {}\n\nfn unused() {}\n");
+        let findings = fixture.changes(&[]).unwrap();
+        let options = fixture.options(&[]).unwrap();
+
+        let scratch = options.target_directory.join("cargo-minify/package");
+        cauterize::copy_workspace(&findings.changes, &options, &scratch).unwrap();
+        let output = fixture.dir.join("minified");
+        let files = package::package(&scratch, &["fixture".to_owned()], &output).unwrap();
+        assert_eq!(files, [output.join("fixture-0.1.0.crate")]);
+
+        let status = std::process::Command::new("tar")
+            .arg("-xzf")
+            .arg(&files[0])
+            .arg("-C")
+            .arg(&output)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            std::fs::read_to_string(output.join("fixture-0.1.0/src/lib.rs")).unwrap(),
+            "pub fn used() {}\n\n"
+        );
+        // the working tree is left alone
+        assert_eq!(
+            std::fs::read_to_string(fixture.path("src/lib.rs")).unwrap(),
+            "pub fn used() {}\n\nfn unused() {}\n"
+        );
+    }
+
+    #[test]
+    fn packaged_packages() {
+        let fixture = Fixture::new(
+            "packaged-packages",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"first\", \"second\", \"sequel\"]\n",
+                ),
+                ("first/Cargo.toml", &MANIFEST.replace("fixture", "first")),
+                ("first/src/lib.rs", ""),
+                ("second/Cargo.toml", &MANIFEST.replace("fixture", "second")),
+                ("second/src/lib.rs", ""),
+                ("sequel/Cargo.toml", &MANIFEST.replace("fixture", "sequel")),
+                ("sequel/src/lib.rs", ""),
+            ],
+        );
+        let manifest_path = fixture.path("Cargo.toml");
+        let packaged = |args: &[&str]| {
+            let opts = parse_options(args).unwrap();
+            let crate_resolution = crate_resolution_from(&opts).unwrap();
+            let metadata = resolver::get_cargo_metadata(Some(Path::new(&manifest_path))).unwrap();
+            let mut packages = packaged_names(
+                Some(Path::new(&manifest_path)),
+                &crate_resolution,
+                &metadata,
+            )
+            .unwrap();
+            packages.sort();
+            packages
+        };
+
+        assert_eq!(packaged(&["--workspace"]), ["first", "second", "sequel"]);
+        assert_eq!(packaged(&["--workspace", "--exclude", "se*"]), ["first"]);
+        assert_eq!(packaged(&["--package", "s*"]), ["second", "sequel"]);
+    }
+
+    #[test]
+    fn recorded_diagnostics() {
+        let fixture = Fixture::library("from-json", "pub fn used() {}\n\nfn unused() {}\n");
+        let output = std::process::Command::new(env::var("CARGO").unwrap_or("cargo".to_owned()))
+            .args(["check", "--quiet", "--message-format", "json"])
+            .current_dir(&fixture.dir)
+            .output()
+            .unwrap();
+        let recorded = fixture.path("check.json");
+        std::fs::write(&recorded, output.stdout).unwrap();
+        std::fs::remove_dir_all(fixture.dir.join("target")).unwrap();
+
+        let findings = fixture.changes(&["--from-json", &recorded]).unwrap();
+        assert_eq!(
+            findings.proposed("src/lib.rs"),
+            Some("pub fn used() {}\n\n")
+        );
+        // cargo did not check the package again
+        assert!(!fixture.dir.join("target/debug").exists());
+    }
+
+    #[test]
+    fn packages() {
+        let fixture = Fixture::new(
+            "packages",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"first\", \"second\"]\n",
+                ),
+                ("first/Cargo.toml", &MANIFEST.replace("fixture", "first")),
+                ("first/src/lib.rs", "mod inner;\n\nfn unused() {}\n"),
+                ("first/src/inner.rs", "pub fn used() {}\n\nfn unused() {}\n"),
+                ("second/Cargo.toml", &MANIFEST.replace("fixture", "second")),
+                ("second/src/lib.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        let findings = fixture.changes(&["--workspace"]).unwrap();
+        let workspace = Workspace {
+            metadata: resolver::get_cargo_metadata(Some(Path::new(&fixture.path("Cargo.toml"))))
+                .unwrap(),
+            options: fixture.options(&[]).unwrap(),
+        };
+        let packages: Vec<_> = group_by_package(&findings.changes, &[workspace])
+            .into_iter()
+            .map(|(package, indices)| {
+                let files: Vec<_> = indices
+                    .iter()
+                    .map(|&index| findings.changes[index].file_name().to_path_buf())
+                    .collect();
+                (package, files)
+            })
+            .collect();
+        assert_eq!(
+            packages,
+            [
+                (
+                    Some("first".to_owned()),
+                    vec![
+                        fixture.dir.join("first/src/inner.rs"),
+                        fixture.dir.join("first/src/lib.rs"),
+                    ]
+                ),
+                (
+                    Some("second".to_owned()),
+                    vec![fixture.dir.join("second/src/lib.rs")]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_messages() {
+        let fixture = Fixture::library(
+            "json-messages",
+            "pub fn used() {}\n\nfn unused() {}\n\nconst UNUSED: u8 = 0;\n",
+        );
+
+        let findings = fixture.changes(&["--message-format", "json"]).unwrap();
+        let [change] = &findings.changes[..] else {
+            panic!("one file should change");
+        };
+        let json = diff_format::to_json(change, Some("fixture"));
+        assert_eq!(json["reason"], "change");
+        assert_eq!(json["file"], fixture.path("src/lib.rs"));
+        assert_eq!(json["package"], "fixture");
+        assert_eq!(json["removes_file"], false);
+        assert_eq!(json["lines_removed"], 3);
+        assert_eq!(
+            json["items"],
+            serde_json::json!([
+                { "kind": "constant", "name": "UNUSED", "severity": "unused" },
+                { "kind": "function", "name": "unused", "severity": "unused" },
+            ])
+        );
+        // a message is a single line
+        assert!(!json.to_string().contains('\n'));
+
+        assert!(parse_options(&["--message-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn severities() {
+        let fixture = Fixture::library(
+            "severities",
+            "pub fn used() {
+    let _ = Point { x: 1 };
+}
+
+struct Point {
+    x: u8,
+}
+
+fn unused() {}
+",
+        );
+
+        let args = ["--unit-structs", "--message-format", "github"];
+        let findings = fixture.changes(&args).unwrap();
+        let options = fixture.options(&args).unwrap();
+        let listed = cauterize::listed_items(&findings.changes, &options);
+        let severities: Vec<_> = listed
+            .iter()
+            .map(|item| (item.line, item.ident.as_str(), item.severity))
+            .collect();
+        assert_eq!(
+            severities,
+            [
+                (Some(6), "Point::x", unused::Severity::Unread),
+                (Some(9), "unused", unused::Severity::Unused),
+            ]
+        );
+        assert_eq!(
+            quickfix_line(&listed[0]),
+            format!(
+                "{}:6:5: field Point::x is unread (would remove 1 line)",
+                fixture.path("src/lib.rs")
+            )
+        );
+
+        let [change] = &findings.changes[..] else {
+            panic!("one file should change");
+        };
+        let json = diff_format::to_json(change, Some("fixture"));
+        assert_eq!(json["items"][0]["severity"], "unused");
+        assert_eq!(json["items"][1]["severity"], "unread");
+
+        let bodies: Vec<_> = diff_format::to_suggestions(change, &listed)
+            .into_iter()
+            .map(|comment| comment["body"].as_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            bodies,
+            [
+                "```suggestion\n    let _ = Point;\n```",
+                "Removes the unread field `Point::x`, the unused function `unused`.\n\n\
+                 ```suggestion\nstruct Point;\n\n```",
+            ]
+        );
+    }
+
+    #[test]
+    fn path_scope() {
+        let fixture = Fixture::new(
+            "path-scope",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "mod legacy;\n\nfn unused() {}\n"),
+                ("src/legacy/mod.rs", "mod old;\n\nfn unused() {}\n"),
+                ("src/legacy/old.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        let findings = fixture.changes(&[&fixture.path("src/legacy")]).unwrap();
+        assert_eq!(
+            findings.changed(),
+            ["src/legacy/mod.rs", "src/legacy/old.rs"]
+        );
+
+        assert!(matches!(
+            fixture.changes(&[&fixture.path("src/missing")]),
+            Err(Error::UnknownPath(_))
+        ));
+    }
+
+    #[test]
+    fn file_selection() {
+        let fixture = Fixture::new(
+            "file-selection",
+            &[
+                (
+                    "Cargo.toml",
+                    &format!(
+                        "{MANIFEST}\n[workspace]\n\n[workspace.metadata.minify]\n\
+                         exclude-dir = [\"src/generated\"]\n"
+                    ),
+                ),
+                (
+                    "src/lib.rs",
+                    "mod generated;\nmod kept;\nmod other;\n\nfn unused() {}\n",
+                ),
+                ("src/generated/mod.rs", "fn unused() {}\n"),
+                ("src/kept.rs", "pub fn used() {}\n\nfn unused() {}\n"),
+                ("src/other.rs", "fn unused() {}\n"),
+            ],
+        );
+        let changed = |args: &[&str]| fixture.changes(args).unwrap().changed();
+
+        assert_eq!(
+            changed(&["--file", "!**/other.rs"]),
+            ["src/kept.rs", "src/lib.rs"]
+        );
+        assert!(changed(&["--exclude-dir", &fixture.path("src")]).is_empty());
+        assert!(changed(&["--only-ext", "txt"]).is_empty());
+    }
+
+    #[test]
+    fn package_globs() {
+        let fixture = Fixture::new(
+            "package-globs",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"my-org-a\", \"my-org-b\", \"other\"]\n",
+                ),
+                (
+                    "my-org-a/Cargo.toml",
+                    &MANIFEST.replace("fixture", "my-org-a"),
+                ),
+                ("my-org-a/src/lib.rs", "fn unused() {}\n"),
+                (
+                    "my-org-b/Cargo.toml",
+                    &MANIFEST.replace("fixture", "my-org-b"),
+                ),
+                ("my-org-b/src/lib.rs", "fn unused() {}\n"),
+                ("other/Cargo.toml", &MANIFEST.replace("fixture", "other")),
+                ("other/src/lib.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        let findings = fixture.changes(&["-p", "my-org-*"]).unwrap();
+        assert_eq!(
+            findings.changed(),
+            ["my-org-a/src/lib.rs", "my-org-b/src/lib.rs"]
+        );
+        let findings = fixture.changes(&["-p", "other", "-p", "my-org-b"]).unwrap();
+        assert_eq!(
+            findings.changed(),
+            ["my-org-b/src/lib.rs", "other/src/lib.rs"]
+        );
+
+        // every pattern has to match a member
+        assert!(fixture
+            .changes(&["-p", "my-org-*", "-p", "their-org-*"])
+            .is_err());
+    }
+
+    #[test]
+    fn default_members() {
+        let fixture = Fixture::new(
+            "default-members",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"app\", \"tools\"]\ndefault-members = [\"app\"]\n",
+                ),
+                ("app/Cargo.toml", &MANIFEST.replace("fixture", "app")),
+                ("app/src/lib.rs", "fn unused() {}\n"),
+                ("tools/Cargo.toml", &MANIFEST.replace("fixture", "tools")),
+                ("tools/src/lib.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        assert_eq!(fixture.changes(&[]).unwrap().changed(), ["app/src/lib.rs"]);
+        assert_eq!(
+            fixture.changes(&["--workspace"]).unwrap().changed(),
+            ["app/src/lib.rs", "tools/src/lib.rs"]
+        );
+    }
+
+    #[test]
+    fn path_dependencies() {
+        let fixture = Fixture::new(
+            "path-deps",
+            &[
+                (
+                    "workspace/Cargo.toml",
+                    &format!("{MANIFEST}\n[dependencies]\ndep = {{ path = \"../dep\" }}\n"),
+                ),
+                ("workspace/src/lib.rs", "fn unused() {}\n"),
+                ("dep/Cargo.toml", &MANIFEST.replace("fixture", "dep")),
+                ("dep/src/lib.rs", "fn unused() {}\n"),
+            ],
+        );
+        let changes = |args: &[&str]| {
+            fixture
+                .changes_of("workspace/Cargo.toml", &[&["--workspace"], args].concat())
+                .unwrap()
+        };
+
+        let findings = changes(&[]);
+        assert_eq!(findings.changed(), ["workspace/src/lib.rs"]);
+        assert!(findings.skipped("dep/src/lib.rs").is_none());
+
+        // the dependency lies outside of the workspace as well
+        let findings = changes(&["--follow-path-deps"]);
+        assert_eq!(findings.changed(), ["workspace/src/lib.rs"]);
+        assert!(findings.skipped("dep/src/lib.rs").is_some());
+        let findings = changes(&["--follow-path-deps", "--allow-external"]);
+        assert_eq!(
+            findings.changed(),
+            ["dep/src/lib.rs", "workspace/src/lib.rs"]
+        );
+
+        assert!(matches!(
+            fixture.changes_of("workspace/Cargo.toml", &["--follow-path-deps"]),
+            Err(Error::Args(_))
+        ));
+    }
+
+    #[test]
+    fn listed_targets() {
+        let fixture = Fixture::new(
+            "targets",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"app\", \"tools\"]\n",
+                ),
+                ("app/Cargo.toml", &MANIFEST.replace("fixture", "app")),
+                ("app/src/lib.rs", ""),
+                ("app/src/main.rs", "fn main() {}\n"),
+                ("tools/Cargo.toml", &MANIFEST.replace("fixture", "tools")),
+                ("tools/src/lib.rs", ""),
+            ],
+        );
+        let manifest_path = PathBuf::from(fixture.path("Cargo.toml"));
+        let targets = |args: &[&str]| {
+            let opts = parse_options(args).unwrap();
+            let crate_resolution = crate_resolution_from(&opts).unwrap();
+            let mut targets: Vec<_> =
+                resolver::get_package_targets(Some(&manifest_path), &crate_resolution)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(package, target)| {
+                        format!("{package} {} {}", target.kind[0], target.name)
+                    })
+                    .collect();
+            targets.sort();
+            targets
+        };
+
+        assert_eq!(
+            targets(&["--workspace"]),
+            ["app bin app", "app lib app", "tools lib tools"]
+        );
+        assert_eq!(
+            targets(&["--workspace", "--exclude", "tools"]),
+            ["app bin app", "app lib app"]
+        );
+        assert_eq!(targets(&["-p", "tools"]), ["tools lib tools"]);
+    }
+
+    #[test]
+    fn keep_going() {
+        let fixture = Fixture::new(
+            "keep-going",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\"broken\", \"fine\"]\n",
+                ),
+                ("broken/Cargo.toml", &MANIFEST.replace("fixture", "broken")),
+                (
+                    "broken/src/lib.rs",
+                    "fn unused() {}\n\npub fn broken() -> u8 {\n    \"\"\n}\n",
+                ),
+                ("fine/Cargo.toml", &MANIFEST.replace("fixture", "fine")),
+                ("fine/src/lib.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        // the unused items of a crate that does not compile are not known
+        let findings = fixture.changes(&["--workspace", "--keep-going"]).unwrap();
+        assert_eq!(findings.changed(), ["fine/src/lib.rs"]);
+    }
+
+    #[test]
+    fn cargo_config() {
+        // cargo reads the configuration of the workspace, wherever it is run from
+        let fixture = Fixture::new(
+            "cargo-config",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "fn unused() {}\n"),
+                (
+                    ".cargo/config.toml",
+                    "[build]\nrustflags = [\"-Adead_code\"]\n",
+                ),
+            ],
+        );
+
+        assert!(fixture.changes(&[]).unwrap().changed().is_empty());
+    }
+
+    #[test]
+    fn rust_version() {
+        let src = "pub(crate) fn helper() -> u8 {\n    1\n}\n";
+        let stubbed = |name: &str, manifest: &str| {
+            let fixture = Fixture::new(name, &[("Cargo.toml", manifest), ("src/lib.rs", src)]);
+            let findings = fixture.changes(&["--mode", "stub"]).unwrap();
+            findings.proposed("src/lib.rs").map(str::to_owned)
+        };
+
+        assert_eq!(
+            stubbed("rust-version", MANIFEST).as_deref(),
+            Some("pub(crate) fn helper() -> u8 {\n    todo!()\n}\n")
+        );
+        // `todo!()` is not available before Rust 1.40
+        let manifest = MANIFEST.replace("2021", "2018") + "rust-version = \"1.39\"\n";
+        assert_eq!(
+            stubbed("old-rust-version", &manifest).as_deref(),
+            Some("pub(crate) fn helper() -> u8 {\n    unimplemented!()\n}\n")
+        );
+    }
+
+    #[test]
+    fn ignored_targets() {
+        let fixture = Fixture::new(
+            "ignored-targets",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn used() {}\n"),
+                ("examples/demo.rs", "fn main() {}\n\nfn unused() {}\n"),
+                ("benches/bench.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        assert_eq!(
+            fixture.changes(&[]).unwrap().changed(),
+            ["benches/bench.rs", "examples/demo.rs"]
+        );
+        assert_eq!(
+            fixture.changes(&["--ignore-examples"]).unwrap().changed(),
+            ["benches/bench.rs"]
+        );
+        assert_eq!(
+            fixture.changes(&["--ignore-benches"]).unwrap().changed(),
+            ["examples/demo.rs"]
+        );
+    }
+
+    #[test]
+    fn ignored_example_uses() {
+        let fixture = Fixture::new(
+            "ignored-example-uses",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn demo_only() {}\n"),
+                (
+                    "examples/demo.rs",
+                    "fn main() {\n    fixture::demo_only();\n}\n",
+                ),
+            ],
+        );
+
+        // the compiler never reports public items, whatever uses them
+        let findings = fixture.changes(&["--ignore-examples"]).unwrap();
+        assert_eq!(findings.proposed("src/lib.rs"), None);
+
+        let deprecate = ["--mode", "deprecate-pub"];
+        let findings = fixture.changes(&deprecate).unwrap();
+        assert_eq!(findings.proposed("src/lib.rs"), None);
+        let findings = fixture
+            .changes(&[&deprecate[..], &["--ignore-examples"]].concat())
+            .unwrap();
+        assert_eq!(
+            findings.proposed("src/lib.rs"),
+            Some(
+                "#[deprecated(note = \"unused internally, scheduled for removal\")]
+#[allow(deprecated)]
+pub fn demo_only() {}
+"
+            )
+        );
+    }
+
+    #[test]
+    fn listed_items() {
+        let fixture = Fixture::library(
+            "listed-items",
+            "pub fn used() {}\n\nconst UNUSED: u8 = 1;\n\nfn unused() {\n    used();\n}\n",
+        );
+
+        let findings = fixture.changes(&["--list"]).unwrap();
+        let options = fixture.options(&["--list"]).unwrap();
+        let listed: Vec<_> = cauterize::listed_items(&findings.changes, &options)
+            .into_iter()
+            .map(|item| {
+                assert!(is_same_file(
+                    &item.file_name,
+                    &fixture.dir.join("src/lib.rs")
+                ));
+                (item.line, item.kind, item.ident, item.lines)
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (
+                    Some(3),
+                    UnusedDiagnosticKind::Constant,
+                    "UNUSED".to_owned(),
+                    Some(1)
+                ),
+                (
+                    Some(5),
+                    UnusedDiagnosticKind::Function,
+                    "unused".to_owned(),
+                    Some(3)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_files() {
+        let fixture = Fixture::new(
+            "ignored-files",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (".gitignore", "/target\n/src/generated.rs\n"),
+                ("src/lib.rs", "mod generated;\n\npub fn used() {}\n"),
+                ("src/generated.rs", "fn unused() {}\n"),
+            ],
+        );
+        let init = std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(&fixture.dir)
+            .status()
+            .unwrap();
+        assert!(init.success());
+
+        let findings = fixture.changes(&[]).unwrap();
+        assert!(findings.changed().is_empty());
+        assert_eq!(
+            findings.skipped("src/generated.rs"),
+            Some("is ignored by version control (use --include-ignored to override)")
+        );
+        assert_eq!(
+            fixture.changes(&["--include-ignored"]).unwrap().changed(),
+            ["src/generated.rs", "src/lib.rs"]
+        );
+    }
+
+    #[test]
+    fn check_timeout() {
+        let fixture = Fixture::library("check-timeout", "pub fn used() {}\n\nfn unused() {}\n");
+        // a run that times out stops the checks of the other tests
+        let _checks = CHECKS.write();
+
+        let findings = fixture
+            .unlocked_changes_of("Cargo.toml", &["--check-timeout", "0"])
+            .unwrap();
+        assert!(findings.changed().is_empty());
+        assert!(unused::timed_out());
+
+        let findings = fixture
+            .unlocked_changes_of("Cargo.toml", &["--check-timeout", "60"])
+            .unwrap();
+        assert_eq!(findings.changed(), ["src/lib.rs"]);
+        assert!(!unused::timed_out());
+    }
+
+    #[test]
+    fn apply_suggestions() {
+        let fixture = Fixture::library(
+            "apply-suggestions",
+            "pub fn used() -> u8 {\n    let mut x = 1;\n    x\n}\n",
+        );
+
+        assert!(fixture.changes(&[]).unwrap().changed().is_empty());
+        assert_eq!(
+            fixture
+                .changes(&["--apply-suggestions"])
+                .unwrap()
+                .proposed("src/lib.rs"),
+            Some("pub fn used() -> u8 {\n    let x = 1;\n    x\n}\n")
+        );
+    }
+
+    #[test]
+    fn unparsable_files() {
+        // syntax that is newer than the parser
+        let fixture = Fixture::new(
+            "unparsable",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "mod new;\nmod old;\n"),
+                ("src/new.rs", "\nfn unused() -> impl Sized + use<> {}\n"),
+                ("src/old.rs", "fn unused() {}\n"),
+            ],
+        );
+
+        let findings = fixture.changes(&[]).unwrap();
+        assert!(findings
+            .skipped("src/new.rs")
+            .is_some_and(|reason| reason.starts_with("could not be parsed (line 2")));
+        assert_eq!(findings.proposed("src/old.rs"), Some(""));
+    }
+
+    #[test]
+    fn backends() {
+        use backend::BackendKind;
+
+        let backend = |args: &[&str]| backend_kind(&parse_options(args).unwrap()).ok();
+        assert_eq!(backend(&[]), Some(BackendKind::Check));
+        assert_eq!(backend(&["--backend", "clippy"]), Some(BackendKind::Clippy));
+        assert_eq!(
+            backend(&["--from-json", "check.json"]),
+            Some(BackendKind::Json)
+        );
+        assert_eq!(backend(&["--backend", "json"]), None);
+        assert_eq!(
+            backend(&["--backend", "clippy", "--from-json", "check.json"]),
+            None
+        );
+        assert!(parse_options(&["--backend", "rust-analyzer"]).is_err());
+    }
+
+    #[test]
+    fn several_workspaces() {
+        let list = env::temp_dir().join(format!("minify-manifests-{}", std::process::id()));
+        std::fs::write(
+            &list,
+            "# the tools\ntools/Cargo.toml\n\n  site/Cargo.toml\n",
+        )
+        .unwrap();
+        let list_dir = list.parent().unwrap();
+
+        let opts = parse_options(&[
+            "--manifest-path",
+            "core/Cargo.toml",
+            "--manifest-path",
+            "cli/Cargo.toml",
+            "--manifest-path-from",
+            list.to_str().unwrap(),
+        ])
+        .unwrap();
+        assert_eq!(
+            manifest_paths(&opts).unwrap(),
+            [
+                Some(PathBuf::from("core/Cargo.toml")),
+                Some(PathBuf::from("cli/Cargo.toml")),
+                Some(list_dir.join("tools/Cargo.toml")),
+                Some(list_dir.join("site/Cargo.toml")),
+            ]
+        );
+        // the subcommands take a single workspace
+        assert!(matches!(single_manifest_path(&opts), Err(Error::Args(_))));
+
+        let opts = parse_options::<&str>(&[]).unwrap();
+        assert_eq!(manifest_paths(&opts).unwrap(), [None]);
+        assert_eq!(single_manifest_path(&opts).unwrap(), None);
+
+        std::fs::remove_file(&list).unwrap();
+    }
+
+    #[test]
+    fn progress_format() {
+        let format = |args: &[&str]| parse_options(args).map(|opts| opts.progress_format);
+        assert_eq!(format(&[]).unwrap(), ProgressFormat::Human);
+        assert_eq!(
+            format(&["--progress-format", "json"]).unwrap(),
+            ProgressFormat::Json
+        );
+        assert!(format(&["--progress-format", "ndjson"]).is_err());
+    }
+
+    #[test]
+    fn cargo_flags() {
+        let opts = parse_options(&["--offline", "--frozen"]).unwrap();
+        assert!(opts.offline && !opts.locked && opts.frozen);
+    }
+
+    #[test]
+    fn presets() {
+        let opts = parse_options(&["--preset", "conservative"]).unwrap();
+        assert!(opts.keep_doctested && opts.tombstones && !opts.apply_suggestions);
+
+        let opts = parse_options(&["--preset", "bundle", "--tombstones"]).unwrap();
+        assert!(opts.apply_suggestions && opts.strip_docs && opts.strip_tests && opts.tombstones);
+
+        assert!(!parse_options::<&str>(&[]).unwrap().apply_suggestions);
+        assert!(parse_options(&["--preset", "reckless"]).is_err());
+    }
+
+    #[test]
+    fn quickfix() {
+        let opts = parse_options(&["--message-format", "quickfix"]).unwrap();
+        assert_eq!(opts.message_format, MessageFormat::Quickfix);
+
+        let item = |line, column, lines| cauterize::ListedItem {
+            file_name: PathBuf::from("src/lib.rs"),
+            line,
+            column,
+            kind: UnusedDiagnosticKind::Function,
+            ident: "unused".to_owned(),
+            severity: unused::Severity::Unused,
+            lines,
+        };
+        assert_eq!(
+            quickfix_line(&item(Some(5), Some(1), Some(3))),
+            "src/lib.rs:5:1: function unused is unused (would remove 3 lines)"
+        );
+        assert_eq!(
+            quickfix_line(&item(Some(2), Some(5), Some(1))),
+            "src/lib.rs:2:5: function unused is unused (would remove 1 line)"
+        );
+        assert_eq!(
+            quickfix_line(&item(None, None, None)),
+            "src/lib.rs: function unused is unused"
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        let opts = parse_options(&["--exclude-kinds", "type-alias,macro-definition"]).unwrap();
+        let kinds = selected_kinds(&opts).unwrap();
+        assert_eq!(kinds.len(), UnusedDiagnosticKind::ALL.len() - 2);
+        assert!(!kinds.contains(&UnusedDiagnosticKind::TypeAlias));
+
+        let opts = parse_options(&[
+            "--kinds",
+            "struct",
+            "--kinds",
+            "enum",
+            "--exclude-kinds",
+            "enum",
+        ])
+        .unwrap();
+        assert_eq!(
+            selected_kinds(&opts).unwrap(),
+            [UnusedDiagnosticKind::Struct]
+        );
+
+        let opts = parse_options(&["--exclude-kinds", "functions"]).unwrap();
+        assert!(selected_kinds(&opts).is_err());
     }
 }
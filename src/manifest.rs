@@ -0,0 +1,204 @@
+//! This module edits manifests (`Cargo.toml`) using toml_edit, so the comments,
+//! the order of the keys and the formatting of a manifest are preserved: only
+//! what is added or changed is formatted anew. The edited manifest becomes a
+//! change like any other, so it is shown as a diff and only written with
+//! `--apply`.
+
+use std::{
+    fmt,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, TableLike};
+
+/// The tables that hold dependencies, at the top level of a manifest or of a
+/// `[target.'cfg(...)']` table
+const DEPENDENCY_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+pub struct Manifest {
+    document: DocumentMut,
+}
+
+impl FromStr for Manifest {
+    type Err = toml_edit::TomlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Manifest {
+            document: s.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.document.fmt(f)
+    }
+}
+
+impl Manifest {
+    /// Adds a feature that enables nothing else, unless it exists already;
+    /// returns `None` if the `features` key is not a table
+    pub fn add_feature(&mut self, name: &str) -> Option<()> {
+        let features = self
+            .document
+            .entry("features")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()?;
+        if !features.contains_key(name) {
+            features.insert(name, toml_edit::value(Array::new()));
+        }
+
+        Some(())
+    }
+
+    /// The `[[bin]]` table of the binary with the given name, which is added
+    /// if there is none; returns `None` if the binaries are not declared as an
+    /// array of tables
+    pub fn bin_mut(&mut self, name: &str) -> Option<&mut Table> {
+        let bins = self
+            .document
+            .entry("bin")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()?;
+        let index = bins
+            .iter()
+            .position(|bin| bin.get("name").and_then(Item::as_str) == Some(name));
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let mut bin = Table::new();
+                bin.insert("name", toml_edit::value(name));
+                bins.push(bin);
+                bins.len() - 1
+            }
+        };
+
+        bins.get_mut(index)
+    }
+
+    /// Makes the `path` of the dependencies (and patches) that lie outside of
+    /// `inside` absolute, resolving them from `dir`, the directory of the
+    /// manifest; this keeps them working in a copy of the directory `inside`.
+    /// Returns whether anything changed
+    pub fn rebase_path_dependencies(&mut self, dir: &Path, inside: &Path) -> bool {
+        let document = self.document.as_table_mut();
+        let mut tables: Vec<&mut dyn TableLike> = Vec::new();
+        let mut target_tables = Vec::new();
+        for (key, item) in document.iter_mut() {
+            let Some(table) = item.as_table_like_mut() else {
+                continue;
+            };
+            match key.get() {
+                "target" => target_tables.push(table),
+                "workspace" => tables.extend(
+                    table
+                        .get_mut("dependencies")
+                        .and_then(Item::as_table_like_mut),
+                ),
+                // a table for every registry
+                "patch" => tables.extend(
+                    table
+                        .iter_mut()
+                        .filter_map(|(_, registry)| registry.as_table_like_mut()),
+                ),
+                key if DEPENDENCY_TABLES.contains(&key) => tables.push(table),
+                _ => {}
+            }
+        }
+        for targets in target_tables {
+            for (_, target) in targets.iter_mut() {
+                let Some(target) = target.as_table_like_mut() else {
+                    continue;
+                };
+                for (key, item) in target.iter_mut() {
+                    if DEPENDENCY_TABLES.contains(&key.get()) {
+                        tables.extend(item.as_table_like_mut());
+                    }
+                }
+            }
+        }
+
+        let mut changed = false;
+        for table in tables {
+            for (_, dependency) in table.iter_mut() {
+                let Some(path) = dependency
+                    .as_table_like_mut()
+                    .and_then(|dependency| dependency.get_mut("path"))
+                else {
+                    continue;
+                };
+                let Some(relative) = path
+                    .as_str()
+                    .map(Path::new)
+                    .filter(|path| path.is_relative())
+                else {
+                    continue;
+                };
+                let resolved = normalize(&dir.join(relative));
+                if !resolved.starts_with(inside) {
+                    *path = toml_edit::value(resolved.to_string_lossy().into_owned());
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+/// Resolves the `.` and `..` components of a path, without following links
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preserves_formatting() {
+        let src = "[package]\nname = \"app\" # the name\n\n[features]\n# on by default\ndefault = [ \"cli\" ]\ncli = []\n";
+        let mut manifest: Manifest = src.parse().unwrap();
+        manifest.add_feature("cli").unwrap();
+        assert_eq!(manifest.to_string(), src);
+
+        manifest.add_feature("server").unwrap();
+        manifest.bin_mut("server").unwrap();
+        assert_eq!(
+            manifest.to_string(),
+            format!("{src}server = []\n\n[[bin]]\nname = \"server\"\n")
+        );
+    }
+
+    #[test]
+    fn path_dependencies() {
+        let src = "[package]\nname = \"app\"\n\n\
+                   [dependencies]\ninner = { path = \"crates/inner\" }\n\
+                   outer = { path = \"../outer\", version = \"1\" } # outside\nserde = \"1\"\n\n\
+                   [target.'cfg(unix)'.dev-dependencies]\nunix = { path = \"../unix\" }\n\n\
+                   [patch.crates-io]\nfoo = { path = \"../foo\" }\n";
+        let mut manifest: Manifest = src.parse().unwrap();
+
+        assert!(manifest.rebase_path_dependencies(Path::new("/ws/app"), Path::new("/ws/app")));
+        assert_eq!(
+            manifest.to_string(),
+            "[package]\nname = \"app\"\n\n\
+             [dependencies]\ninner = { path = \"crates/inner\" }\n\
+             outer = { path = \"/ws/outer\", version = \"1\" } # outside\nserde = \"1\"\n\n\
+             [target.'cfg(unix)'.dev-dependencies]\nunix = { path = \"/ws/unix\" }\n\n\
+             [patch.crates-io]\nfoo = { path = \"/ws/foo\" }\n"
+        );
+        assert!(!manifest.rebase_path_dependencies(Path::new("/ws/app"), Path::new("/ws/app")));
+    }
+}
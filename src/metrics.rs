@@ -0,0 +1,138 @@
+//! This module appends the totals of a run to a metrics file (see
+//! `--metrics-out`), as a row of a CSV file or a line of JSON, so the amount of
+//! dead code can be charted over time.
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    error::{Error, Result},
+    unused::UnusedDiagnosticKind,
+};
+
+/// The totals of the unused code that a run found
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub items: usize,
+    pub lines: usize,
+    pub kinds: BTreeMap<UnusedDiagnosticKind, usize>,
+    /// The number of items by package; items outside of any package are
+    /// counted under an empty name
+    pub packages: BTreeMap<String, usize>,
+}
+
+/// Appends the metrics to a `.csv` or `.json` file, with the current time
+pub fn append(file: &Path, metrics: &Metrics) -> Result<()> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let row = match file.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => {
+            let mut row = String::new();
+            if std::fs::metadata(file).map_or(true, |metadata| metadata.len() == 0) {
+                row.push_str(&csv_header());
+            }
+            row.push_str(&csv_row(time, metrics));
+            row
+        }
+        Some("json") => format!("{}\n", json_line(time, metrics)),
+        _ => {
+            return Err(Error::Args(
+                "the file given to --metrics-out should end in .csv or .json",
+            ))
+        }
+    };
+
+    let mut file = OpenOptions::new().create(true).append(true).open(file)?;
+    file.write_all(row.as_bytes())?;
+
+    Ok(())
+}
+
+fn csv_header() -> String {
+    let kinds: Vec<_> = UnusedDiagnosticKind::ALL
+        .iter()
+        .map(UnusedDiagnosticKind::name)
+        .collect();
+    format!("time,items,lines,{},packages\n", kinds.join(","))
+}
+
+/// A row of the CSV file; as the packages differ between workspaces, these
+/// are listed in a single column, as `name=count` separated by `;`
+fn csv_row(time: u64, metrics: &Metrics) -> String {
+    let kinds: Vec<_> = UnusedDiagnosticKind::ALL
+        .iter()
+        .map(|kind| metrics.kinds.get(kind).unwrap_or(&0).to_string())
+        .collect();
+    let packages: Vec<_> = metrics
+        .packages
+        .iter()
+        .map(|(package, count)| format!("{package}={count}"))
+        .collect();
+
+    format!(
+        "{time},{},{},{},{}\n",
+        metrics.items,
+        metrics.lines,
+        kinds.join(","),
+        packages.join(";")
+    )
+}
+
+fn json_line(time: u64, metrics: &Metrics) -> serde_json::Value {
+    let kinds: serde_json::Map<_, _> = UnusedDiagnosticKind::ALL
+        .iter()
+        .map(|kind| {
+            let count = metrics.kinds.get(kind).copied().unwrap_or(0);
+            (kind.name().to_owned(), count.into())
+        })
+        .collect();
+
+    serde_json::json!({
+        "time": time,
+        "items": metrics.items,
+        "lines": metrics.lines,
+        "kinds": kinds,
+        "packages": metrics.packages,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let metrics = Metrics {
+            items: 3,
+            lines: 12,
+            kinds: [
+                (UnusedDiagnosticKind::Function, 2),
+                (UnusedDiagnosticKind::Field, 1),
+            ]
+            .into(),
+            packages: [("app".to_owned(), 1), ("core".to_owned(), 2)].into(),
+        };
+
+        assert_eq!(
+            csv_header(),
+            "time,items,lines,constant,static,function,struct,enum,union,type_alias,\
+             associated_function,associated_item,macro_definition,macro_invocation,field,packages\n"
+        );
+        assert_eq!(
+            csv_row(1700000000, &metrics),
+            "1700000000,3,12,0,0,2,0,0,0,0,0,0,0,0,1,app=1;core=2\n"
+        );
+
+        let line = json_line(1700000000, &metrics);
+        assert_eq!(line["kinds"]["function"], 2);
+        assert_eq!(line["kinds"]["struct"], 0);
+        assert_eq!(line["packages"]["core"], 2);
+    }
+}
@@ -0,0 +1,254 @@
+//! This module resolves the module tree of a crate, i.e. which file each
+//! out-of-line `mod` declaration refers to, taking `#[path]` attributes into
+//! account.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use syn::ext::IdentExt;
+
+/// An out-of-line `mod name;` declaration, and the file it refers to
+#[derive(Debug, PartialEq)]
+pub struct Declaration {
+    /// The file that contains the declaration
+    pub parent: PathBuf,
+    /// The inline modules the declaration is nested in, outermost first
+    pub inline_path: Vec<String>,
+    pub name: String,
+    pub file: PathBuf,
+}
+
+/// Finds all module declarations reachable from the given crate roots; `parse`
+/// provides the (current) syntax tree of a file, and `exists` tells whether a
+/// file exists (and is not about to be removed)
+pub fn declarations(
+    roots: &[PathBuf],
+    parse: impl Fn(&Path) -> Option<syn::File>,
+    exists: impl Fn(&Path) -> bool,
+) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    let mut visited = BTreeSet::new();
+    // a file, and whether it is a "mod-rs" file (a crate root, a `mod.rs`, or
+    // a file loaded through `#[path]`), which owns the directory it is in
+    let mut pending: Vec<(PathBuf, bool)> = roots.iter().map(|root| (root.clone(), true)).collect();
+
+    while let Some((file, is_mod_rs)) = pending.pop() {
+        if !visited.insert(file.clone()) {
+            continue;
+        }
+        let Some(ast) = parse(&file) else {
+            continue;
+        };
+
+        let file_dir = file.parent().unwrap_or(Path::new("")).to_path_buf();
+        let dir = match file.file_stem() {
+            Some(stem) if !is_mod_rs => file_dir.join(stem),
+            _ => file_dir.clone(),
+        };
+
+        let mut walker = Walker {
+            exists: &exists,
+            file: &file,
+            file_dir: &file_dir,
+            inline_path: Vec::new(),
+            declarations: Vec::new(),
+        };
+        walker.walk(&ast.items, &dir);
+
+        for declaration in walker.declarations {
+            pending.push((declaration.file.clone(), declaration.is_mod_rs));
+            declarations.push(Declaration {
+                parent: file.clone(),
+                inline_path: declaration.inline_path,
+                name: declaration.name,
+                file: declaration.file,
+            });
+        }
+    }
+
+    declarations
+}
+
+/// The crate root each file belongs to, and the path of its module in that
+/// crate (e.g. `["a", "f"]` for `src/a/f.rs`, and nothing for the root itself),
+/// given the declarations of the crates; a file that is part of several crates
+/// is taken to belong to the first
+pub fn module_paths(
+    roots: &[PathBuf],
+    declarations: &[Declaration],
+) -> BTreeMap<PathBuf, (PathBuf, Vec<String>)> {
+    let mut paths: BTreeMap<PathBuf, (PathBuf, Vec<String>)> = BTreeMap::new();
+    for root in roots {
+        paths
+            .entry(root.clone())
+            .or_insert_with(|| (root.clone(), Vec::new()));
+    }
+
+    // the declarations are not in order, so repeat until all are placed
+    loop {
+        let count = paths.len();
+        for declaration in declarations {
+            if paths.contains_key(&declaration.file) {
+                continue;
+            }
+            let Some((root, parent)) = paths.get(&declaration.parent) else {
+                continue;
+            };
+
+            let mut path = parent.clone();
+            path.extend(declaration.inline_path.iter().cloned());
+            path.push(declaration.name.clone());
+            let root = root.clone();
+            paths.insert(declaration.file.clone(), (root, path));
+        }
+
+        if paths.len() == count {
+            return paths;
+        }
+    }
+}
+
+struct Found {
+    inline_path: Vec<String>,
+    name: String,
+    file: PathBuf,
+    is_mod_rs: bool,
+}
+
+struct Walker<'a> {
+    exists: &'a dyn Fn(&Path) -> bool,
+    file: &'a Path,
+    file_dir: &'a Path,
+    inline_path: Vec<String>,
+    declarations: Vec<Found>,
+}
+
+impl Walker<'_> {
+    /// Walks the items of a module whose children live in `dir`
+    fn walk(&mut self, items: &[syn::Item], dir: &Path) {
+        for item in items {
+            let syn::Item::Mod(module) = item else {
+                continue;
+            };
+            let name = module.ident.unraw().to_string();
+            let path = path_attribute(&module.attrs);
+
+            match &module.content {
+                Some((_, items)) => {
+                    let dir = dir.join(path.unwrap_or_else(|| name.clone()));
+                    self.inline_path.push(name);
+                    self.walk(items, &dir);
+                    self.inline_path.pop();
+                }
+                None => {
+                    let found = match path {
+                        // outside of inline modules, paths are relative to the
+                        // directory of the file itself
+                        Some(path) if self.inline_path.is_empty() => {
+                            Some((self.file_dir.join(path), true))
+                        }
+                        Some(path) => Some((dir.join(path), true)),
+                        None => [
+                            (dir.join(format!("{name}.rs")), false),
+                            (dir.join(&name).join("mod.rs"), true),
+                        ]
+                        .into_iter()
+                        .find(|(file, _)| (self.exists)(file)),
+                    };
+
+                    if let Some((file, is_mod_rs)) = found {
+                        // a module cannot be its own parent
+                        if file != self.file {
+                            self.declarations.push(Found {
+                                inline_path: self.inline_path.clone(),
+                                name,
+                                file,
+                                is_mod_rs,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The value of a `#[path = "..."]` attribute
+fn path_attribute(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(pair) if pair.path.is_ident("path") => match &pair.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(value),
+                ..
+            }) => Some(value.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn module_tree() {
+        let files = BTreeMap::from([
+            (
+                "src/lib.rs",
+                "mod a;\nmod b;\n#[path = \"other/c.rs\"]\nmod c;\nmod d { mod e; }",
+            ),
+            ("src/a.rs", "mod f;"),
+            ("src/a/f.rs", ""),
+            ("src/b/mod.rs", "mod g;"),
+            ("src/b/g.rs", ""),
+            ("src/other/c.rs", "mod h;"),
+            ("src/other/h.rs", ""),
+            ("src/d/e.rs", ""),
+        ]);
+        let source = |path: &Path| files.get(path.to_str()?).copied();
+
+        let declarations = declarations(
+            &[PathBuf::from("src/lib.rs")],
+            |path| syn::parse_str(source(path)?).ok(),
+            |path| source(path).is_some(),
+        );
+
+        let mut found: Vec<_> = declarations
+            .iter()
+            .map(|declaration| {
+                (
+                    declaration.parent.to_str().unwrap(),
+                    declaration.inline_path.join("::"),
+                    declaration.name.as_str(),
+                    declaration.file.to_str().unwrap(),
+                )
+            })
+            .collect();
+        found.sort();
+
+        assert_eq!(
+            found,
+            vec![
+                ("src/a.rs", "".to_owned(), "f", "src/a/f.rs"),
+                ("src/b/mod.rs", "".to_owned(), "g", "src/b/g.rs"),
+                ("src/lib.rs", "".to_owned(), "a", "src/a.rs"),
+                ("src/lib.rs", "".to_owned(), "b", "src/b/mod.rs"),
+                ("src/lib.rs", "".to_owned(), "c", "src/other/c.rs"),
+                ("src/lib.rs", "d".to_owned(), "e", "src/d/e.rs"),
+                ("src/other/c.rs", "".to_owned(), "h", "src/other/h.rs"),
+            ]
+        );
+
+        let paths = module_paths(&[PathBuf::from("src/lib.rs")], &declarations);
+        let path = |file: &str| paths.get(Path::new(file)).map(|(_, path)| path.join("::"));
+        assert_eq!(path("src/lib.rs").as_deref(), Some(""));
+        assert_eq!(path("src/b/g.rs").as_deref(), Some("b::g"));
+        assert_eq!(path("src/other/h.rs").as_deref(), Some("c::h"));
+        assert_eq!(path("src/d/e.rs").as_deref(), Some("d::e"));
+    }
+}
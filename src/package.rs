@@ -0,0 +1,50 @@
+//! This module packages a (minified) copy of the workspace into `.crate` files
+//! using `cargo package`.
+
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+use crate::{
+    cargo,
+    error::{Error, Result},
+};
+
+/// Runs `cargo package` for the given packages of the workspace copy in
+/// `scratch`, and moves the resulting `.crate` files to `output`
+pub fn package(scratch: &Path, packages: &[String], output: &Path) -> Result<Vec<PathBuf>> {
+    let target_directory = scratch.with_file_name("package-target");
+    let packaged = target_directory.join("package");
+    if packaged.exists() {
+        std::fs::remove_dir_all(&packaged)?;
+    }
+
+    let mut command = cargo::command("package", Some(&scratch.join("Cargo.toml")))?;
+    // the copy is not under version control, and its dependencies do not have
+    // to be built again
+    command.args(["--quiet", "--no-verify", "--allow-dirty"]);
+    command.arg("--target-dir").arg(&target_directory);
+    for package in packages {
+        command.args(["-p", package]);
+    }
+    debug!("running {command:?}");
+    if !command.status()?.success() {
+        return Err(Error::CommandFailed("cargo package"));
+    }
+
+    std::fs::create_dir_all(output)?;
+    let mut files = Vec::new();
+    for path in std::fs::read_dir(&packaged)?
+        .flatten()
+        .map(|entry| entry.path())
+    {
+        if path.extension().is_some_and(|ext| ext == "crate") {
+            let file = output.join(path.file_name().unwrap_or_default());
+            std::fs::copy(&path, &file)?;
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
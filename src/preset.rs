@@ -0,0 +1,69 @@
+//! This module defines the presets of `--preset`: combinations of flags that
+//! are useful together, so a run does not need all of them spelled out. A
+//! preset stands for its flags, as if they were given before the ones on the
+//! command line.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Preset {
+    /// Remove what the compiler reports, but leave a trace of it, and keep
+    /// what doc examples use
+    Conservative,
+    /// Remove what the compiler reports, and apply its fixes (e.g. removing
+    /// unused imports)
+    Standard,
+    /// Also remove the fields that are never read, macro invocations that only
+    /// expand to unused items, unused exported macros, and the lints that no
+    /// longer suppress anything
+    Aggressive,
+    /// Cut a workspace down to its code, e.g. to ship or to reproduce a bug
+    /// with: like `Aggressive`, and also remove documentation and tests
+    Bundle,
+}
+
+impl Preset {
+    /// The flags that this preset stands for
+    pub fn flags(&self) -> &'static [&'static str] {
+        match self {
+            Preset::Conservative => &["--keep-doctested", "--tombstones"],
+            Preset::Standard => &["--apply-suggestions"],
+            Preset::Aggressive => &[
+                "--apply-suggestions",
+                "--unit-structs",
+                "--expand-macros",
+                "--exported-macros",
+                "--remove-unused-allows",
+            ],
+            Preset::Bundle => &[
+                "--apply-suggestions",
+                "--unit-structs",
+                "--expand-macros",
+                "--exported-macros",
+                "--remove-unused-allows",
+                "--strip-docs",
+                "--strip-tests",
+            ],
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = UnsupportedPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "conservative" => Ok(Preset::Conservative),
+            "standard" => Ok(Preset::Standard),
+            "aggressive" => Ok(Preset::Aggressive),
+            "bundle" => Ok(Preset::Bundle),
+            _ => Err(UnsupportedPreset),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported preset, pick any of: conservative, standard, aggressive, bundle")]
+pub struct UnsupportedPreset;
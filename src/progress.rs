@@ -0,0 +1,92 @@
+//! This module reports the progress of a run as newline-delimited JSON events
+//! on stderr (see `--progress-format json`), so IDE plugins and other wrappers
+//! can show what is going on. Every event is an object on a line of its own,
+//! with the name of the event in its `event` field.
+
+use std::{
+    io::Write,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use thiserror::Error;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ProgressFormat {
+    /// Only the regular messages
+    #[default]
+    Human,
+    /// JSON events, in addition to the regular messages
+    Json,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = UnsupportedProgressFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ProgressFormat::Human),
+            "json" => Ok(ProgressFormat::Json),
+            _ => Err(UnsupportedProgressFormat),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported progress format, pick any of: human, json")]
+pub struct UnsupportedProgressFormat;
+
+pub fn init(format: ProgressFormat) {
+    JSON.store(format == ProgressFormat::Json, Ordering::SeqCst);
+}
+
+/// Reports an event with the given fields (which should be a JSON object), if
+/// JSON events were asked for
+pub fn report(event: &str, fields: serde_json::Value) {
+    if !JSON.load(Ordering::SeqCst) {
+        return;
+    }
+
+    // a whole line at once, so events do not interleave with other output
+    let _ = std::io::stderr()
+        .lock()
+        .write_all(event_line(event, fields).as_bytes());
+}
+
+/// The line of an event, with its name in the `event` field
+fn event_line(event: &str, fields: serde_json::Value) -> String {
+    let mut object = serde_json::Map::new();
+    object.insert("event".to_owned(), event.into());
+    if let serde_json::Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+
+    format!("{}\n", serde_json::Value::Object(object))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn events() {
+        let line = event_line(
+            "change-computed",
+            serde_json::json!({ "file": "src/lib.rs", "lines_removed": 3, "removes_file": false }),
+        );
+        assert_eq!(
+            line,
+            "{\"event\":\"change-computed\",\"file\":\"src/lib.rs\",\"lines_removed\":3,\
+             \"removes_file\":false}\n"
+        );
+        assert_eq!(
+            event_line(
+                "analysis-started",
+                serde_json::json!({ "analysis": "check" })
+            ),
+            "{\"analysis\":\"check\",\"event\":\"analysis-started\"}\n"
+        );
+    }
+}
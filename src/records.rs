@@ -0,0 +1,65 @@
+//! This module stores records (such as the findings of a run, or a snapshot)
+//! as JSON files in a directory of the target directory, each named after the
+//! time at which it was written; see [`crate::history`] and `cargo minify
+//! restore`.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The current time, in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Writes a record to the given directory, which must exist, and returns its
+/// id: the given time, with a suffix if another record was written in the same
+/// second
+pub fn write(directory: &Path, time: u64, content: &serde_json::Value) -> io::Result<String> {
+    for suffix in 0.. {
+        let id = match suffix {
+            0 => time.to_string(),
+            _ => format!("{time}-{suffix}"),
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(directory.join(format!("{id}.json")))
+        {
+            Ok(mut file) => {
+                writeln!(file, "{content}")?;
+                return Ok(id);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    unreachable!()
+}
+
+/// The ids of the records in the given directory, oldest first
+pub fn ids(directory: &Path) -> io::Result<Vec<String>> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids: Vec<_> = std::fs::read_dir(directory)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .collect();
+    ids.sort_by_key(|id| {
+        let (time, suffix) = id.split_once('-').unwrap_or((id, "0"));
+        (time.parse::<u64>().ok(), suffix.parse::<u64>().ok())
+    });
+
+    Ok(ids)
+}
@@ -10,20 +10,60 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use cargo_metadata::Target;
+use cargo_metadata::{Package, PackageId, Target};
+use tracing::{debug, info, warn};
 
-use crate::{error::Result, CrateResolutionOptions};
+use crate::{cargo, error::Result, CrateResolutionOptions};
 
 pub fn get_targets(
     manifest_path: Option<&Path>,
     crate_resolution: &CrateResolutionOptions,
 ) -> Result<HashSet<Target>> {
+    let targets = get_package_targets(manifest_path, crate_resolution)?;
+    Ok(targets.into_iter().map(|(_, target)| target).collect())
+}
+
+/// Whether two descriptions of a target, such as the one of `cargo metadata`
+/// and the one that comes with a compiler message, refer to the same target;
+/// these need not agree on all details (e.g. proc-macro crates are built for
+/// the host, with crate types of their own), so targets are matched by name
+/// and kind, where a proc-macro crate is a library like any other. They are
+/// not matched by location, since a recording (see `--from-json`) may come
+/// from another checkout
+pub fn is_same_target(a: &Target, b: &Target) -> bool {
+    fn is_lib(target: &Target) -> bool {
+        target.kind.iter().any(|kind| {
+            matches!(
+                kind.as_str(),
+                "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro"
+            )
+        })
+    }
+
+    a.name == b.name && (a.kind == b.kind || is_lib(a) && is_lib(b))
+}
+
+/// The targets to analyze, along with the names of their packages
+pub fn get_package_targets(
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+) -> Result<HashSet<(String, Target)>> {
     let mut targets = HashSet::new();
 
     match crate_resolution {
         CrateResolutionOptions::Root => root_targets(manifest_path, &mut targets)?,
-        CrateResolutionOptions::Workspace { exclude } => {
-            workspace_targets(manifest_path, exclude, &mut targets, &mut BTreeSet::new())?
+        CrateResolutionOptions::Workspace {
+            exclude,
+            follow_path_deps,
+        } => {
+            let follow = follow_path_deps.then_some(0);
+            workspace_targets(
+                manifest_path,
+                exclude,
+                follow,
+                &mut targets,
+                &mut BTreeSet::new(),
+            )?
         }
         CrateResolutionOptions::Package { packages } => {
             package_targets(manifest_path, packages, &mut targets)?
@@ -31,19 +71,23 @@ pub fn get_targets(
     }
 
     if targets.is_empty() {
-        eprintln!("crate resolution found no targets");
+        warn!("crate resolution found no targets");
     }
 
     Ok(targets)
 }
 
-fn root_targets(manifest_path: Option<&Path>, targets: &mut HashSet<Target>) -> Result<()> {
+fn root_targets(
+    manifest_path: Option<&Path>,
+    targets: &mut HashSet<(String, Target)>,
+) -> Result<()> {
     let metadata = get_cargo_metadata(manifest_path)?;
     let workspace_root_path = PathBuf::from(&metadata.workspace_root).canonicalize()?;
     let (in_workspace_root, current_dir_manifest) = if let Some(target_manifest) = manifest_path {
+        let target_manifest = target_manifest.canonicalize()?;
         (
-            workspace_root_path == target_manifest,
-            target_manifest.canonicalize()?,
+            workspace_root_path.join("Cargo.toml") == target_manifest,
+            target_manifest,
         )
     } else {
         let current_dir = env::current_dir()?.canonicalize()?;
@@ -53,33 +97,43 @@ fn root_targets(manifest_path: Option<&Path>, targets: &mut HashSet<Target>) ->
         )
     };
 
-    let package_targets = match metadata.packages.len() {
-        1 => metadata.packages.into_iter().next().unwrap().targets,
+    // at the root, cargo checks the default members of the workspace
+    let default_members = default_members(manifest_path).filter(|_| in_workspace_root);
+    let packages = match metadata.packages.len() {
+        1 => metadata.packages,
         _ => metadata
             .packages
             .into_iter()
             .filter(|p| {
+                if let Some(default_members) = &default_members {
+                    return default_members.contains(&p.id);
+                }
                 in_workspace_root
                     || PathBuf::from(&p.manifest_path)
                         .canonicalize()
                         .unwrap_or_default()
                         == current_dir_manifest
             })
-            .flat_map(|p| p.targets)
             .collect(),
     };
 
-    for target in package_targets {
-        targets.insert(target);
+    for package in packages {
+        for target in package.targets {
+            targets.insert((package.name.clone(), target));
+        }
     }
 
     Ok(())
 }
 
+/// Adds the targets of the members of the workspace and, if `depth` is given
+/// (the number of path dependencies followed to get here), of the path
+/// dependencies outside of it
 fn workspace_targets(
     manifest_path: Option<&Path>,
     exclude: &[String],
-    targets: &mut HashSet<Target>,
+    depth: Option<usize>,
+    targets: &mut HashSet<(String, Target)>,
     visited: &mut BTreeSet<String>,
 ) -> Result<()> {
     let metadata = get_cargo_metadata(manifest_path)?;
@@ -89,9 +143,12 @@ fn workspace_targets(
             .any(|name| glob_match::glob_match(name, &package.name))
         {
             for target in &package.targets {
-                targets.insert(target.clone());
+                targets.insert((package.name.clone(), target.clone()));
             }
 
+            let Some(depth) = depth else {
+                continue;
+            };
             for dependency in &package.dependencies {
                 if dependency.path.is_none() || visited.contains(&dependency.name) {
                     continue;
@@ -106,7 +163,19 @@ fn workspace_targets(
                         .any(|p| p.manifest_path.eq(&manifest_path))
                 {
                     visited.insert(dependency.name.to_owned());
-                    workspace_targets(Some(&manifest_path), exclude, targets, visited)?;
+                    info!(
+                        "following path dependency `{}` of `{}` (depth {})",
+                        dependency.name,
+                        package.name,
+                        depth + 1
+                    );
+                    workspace_targets(
+                        Some(&manifest_path),
+                        exclude,
+                        Some(depth + 1),
+                        targets,
+                        visited,
+                    )?;
                 }
             }
         }
@@ -118,47 +187,114 @@ fn workspace_targets(
 fn package_targets(
     manifest_path: Option<&Path>,
     packages: &[String],
-    targets: &mut HashSet<Target>,
+    targets: &mut HashSet<(String, Target)>,
 ) -> Result<()> {
     let metadata = get_cargo_metadata(manifest_path)?;
-    let mut workspace_hitlist: BTreeSet<&String> = BTreeSet::from_iter(packages);
+    let names = package_names(&metadata.packages, packages)?;
 
     for package in metadata.packages {
-        if workspace_hitlist.remove(&package.name) {
+        if names.contains(&package.name) {
             for target in package.targets {
-                targets.insert(target);
+                targets.insert((package.name.clone(), target));
             }
         }
     }
 
-    if workspace_hitlist.is_empty() {
-        Ok(())
-    } else {
-        let package = workspace_hitlist.iter().next().unwrap();
-        Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("package `{}` is not a member of the workspace", package),
-        )
-        .into())
+    Ok(())
+}
+
+/// The names of the workspace members selected by `--package`, of which each
+/// is a name or a glob pattern (such as `my-org-*`) that should match at least
+/// one member
+pub fn package_names(members: &[Package], patterns: &[String]) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for pattern in patterns {
+        let mut matched = false;
+        for package in members
+            .iter()
+            .filter(|package| glob_match::glob_match(pattern, &package.name))
+        {
+            matched = true;
+            if !names.contains(&package.name) {
+                names.push(package.name.clone());
+            }
+        }
+
+        if !matched {
+            let message = if pattern.contains(['*', '?', '[', '{']) {
+                format!(
+                    "package pattern `{}` matches no member of the workspace",
+                    pattern
+                )
+            } else {
+                format!("package `{}` is not a member of the workspace", pattern)
+            };
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+        }
     }
+
+    Ok(names)
+}
+
+/// The package a file belongs to, i.e. the one with the nearest manifest
+pub fn package_of<'a>(file: &Path, packages: &'a [Package]) -> Option<&'a Package> {
+    let file = file.canonicalize().ok()?;
+
+    packages
+        .iter()
+        .filter_map(|package| {
+            let root = package.manifest_path.parent()?.canonicalize().ok()?;
+            file.starts_with(&root).then_some((root, package))
+        })
+        .max_by_key(|(root, _)| root.components().count())
+        .map(|(_, package)| package)
+}
+
+/// The members that cargo selects in the workspace root if no packages are
+/// given: those in `workspace.default-members`, or else the root package, or
+/// else all members; `None` if cargo does not tell (before Rust 1.71)
+pub fn default_members(manifest_path: Option<&Path>) -> Option<Vec<PackageId>> {
+    // cargo_metadata does not read this field (yet)
+    let output = metadata_command(manifest_path)
+        .cargo_command()
+        .output()
+        .ok()?;
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let members = metadata["workspace_default_members"].as_array()?;
+    let members: Vec<_> = members
+        .iter()
+        .filter_map(|id| id.as_str())
+        .map(|id| PackageId {
+            repr: id.to_owned(),
+        })
+        .collect();
+    debug!("default members: {members:?}");
+
+    Some(members)
 }
 
 pub fn get_cargo_metadata(manifest_path: Option<&Path>) -> Result<cargo_metadata::Metadata> {
+    metadata_command(manifest_path)
+        .exec()
+        .map_err(|error| io::Error::other(error.to_string()).into())
+}
+
+/// `cargo metadata` for the workspace members only, with the flags of the run
+fn metadata_command(manifest_path: Option<&Path>) -> cargo_metadata::MetadataCommand {
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.no_deps();
     if let Some(manifest_path) = manifest_path {
-        cmd.manifest_path(manifest_path);
-    }
-    cmd.other_options(vec![String::from("--offline")]);
-
-    match cmd.exec() {
-        Ok(metadata) => Ok(metadata),
-        Err(_) => {
-            cmd.other_options(vec![]);
-            match cmd.exec() {
-                Ok(metadata) => Ok(metadata),
-                Err(error) => Err(io::Error::new(io::ErrorKind::Other, error.to_string()).into()),
-            }
+        // like `cargo::command`, to use the configuration of the workspace
+        let manifest_path = manifest_path
+            .canonicalize()
+            .unwrap_or_else(|_| manifest_path.to_path_buf());
+        if let Some(dir) = manifest_path.parent() {
+            cmd.current_dir(dir);
         }
+        cmd.manifest_path(manifest_path);
     }
+    let flags: Vec<String> = cargo::flags().iter().map(|flag| flag.to_string()).collect();
+    cmd.other_options(flags);
+
+    cmd
 }
@@ -0,0 +1,84 @@
+//! This module runs rustfmt on the files that were changed, so the result
+//! matches the formatting of the project.
+
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use cargo_metadata::Package;
+use tracing::{debug, warn};
+
+use crate::{cauterize, error::Result, resolver};
+
+/// Formats the given files, using the edition of the package they belong to.
+/// Every file is formatted on its own through stdin, as rustfmt would format
+/// the files of the modules they declare as well; it runs in the directory of
+/// the file, so it picks up any `rustfmt.toml` by itself
+pub fn format_files(files: &[PathBuf], packages: &[Package]) -> Result<()> {
+    for file in files {
+        let mut command = Command::new("rustfmt");
+        if let Some(package) = resolver::package_of(file, packages) {
+            command.args(["--edition", &package.edition.to_string()]);
+        }
+        command.args(["--emit", "stdout"]);
+        if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            command.current_dir(dir);
+        }
+
+        debug!("running {command:?} on {}", file.display());
+        match format(command, &std::fs::read(file)?)? {
+            Some(formatted) => cauterize::write_atomically(file, &formatted)?,
+            None => warn!("rustfmt failed, {} has not been formatted", file.display()),
+        }
+    }
+
+    Ok(())
+}
+
+/// The formatted source; `None` if rustfmt fails, e.g. because the source does
+/// not parse
+fn format(mut command: Command, src: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // rustfmt reads all of its input before it writes anything
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(src)?;
+    }
+    let output = child.wait_with_output()?;
+
+    Ok(output.status.success().then_some(output.stdout))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formats_only_given_files() {
+        let dir = std::env::temp_dir().join(format!("minify-rustfmt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("rustfmt.toml"), "tab_spaces = 2\n").unwrap();
+        let modified = dir.join("modified.rs");
+        let untouched = dir.join("untouched.rs");
+        // rustfmt formats the files of declared modules as well, if given a path
+        std::fs::write(&modified, "mod untouched;\nfn foo(){let x=1;}\n").unwrap();
+        std::fs::write(&untouched, "fn bar(){let y=2;}\n").unwrap();
+
+        format_files(std::slice::from_ref(&modified), &[]).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&modified).unwrap(),
+            "mod untouched;\nfn foo() {\n  let x = 1;\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&untouched).unwrap(),
+            "fn bar(){let y=2;}\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+//! This module estimates how many bytes of data a `static` or `const` takes,
+//! to tell which removals actually shrink a binary. Only types of which the
+//! size follows from the source are supported: primitives, arrays and tuples
+//! of these, and references to literals; padding is not taken into account.
+
+use std::mem::size_of;
+
+use syn::{Expr, Lit, Type};
+
+/// The estimated size in bytes of an item with the given type and value, or
+/// `None` if it cannot be told from the source
+pub fn estimate(ty: &Type, value: &Expr) -> Option<usize> {
+    match (ty, value) {
+        (Type::Reference(reference), value) => {
+            let data = match (&*reference.elem, value) {
+                (_, Expr::Reference(value)) => estimate(&reference.elem, &value.expr),
+                (Type::Path(path), Expr::Lit(lit)) if path.path.is_ident("str") => {
+                    literal_size(&lit.lit)
+                }
+                (Type::Slice(_), Expr::Lit(lit)) => literal_size(&lit.lit),
+                (elem, Expr::Lit(_)) => type_size(elem),
+                _ => None,
+            }?;
+            // a slice or `str` is referred to by a pointer and a length
+            let pointer = match &*reference.elem {
+                Type::Slice(_) => 2 * size_of::<usize>(),
+                Type::Path(path) if path.path.is_ident("str") => 2 * size_of::<usize>(),
+                _ => size_of::<usize>(),
+            };
+            Some(pointer + data)
+        }
+        (Type::Slice(slice), Expr::Array(array)) => {
+            Some(type_size(&slice.elem)? * array.elems.len())
+        }
+        (Type::Array(array), value) => {
+            let len = match &array.len {
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::Int(len), ..
+                }) => len.base10_parse().ok(),
+                _ => None,
+            };
+            let len = len.or(match value {
+                Expr::Array(value) => Some(value.elems.len()),
+                Expr::Lit(syn::ExprLit {
+                    lit: Lit::ByteStr(bytes),
+                    ..
+                }) => Some(bytes.value().len()),
+                _ => None,
+            })?;
+            Some(type_size(&array.elem)? * len)
+        }
+        (Type::Group(group), value) => estimate(&group.elem, value),
+        (Type::Paren(paren), value) => estimate(&paren.elem, value),
+        (ty, _) => type_size(ty),
+    }
+}
+
+/// The size of a value of the given type, if it does not depend on the value
+fn type_size(ty: &Type) -> Option<usize> {
+    match ty {
+        Type::Path(path) => {
+            let ident = path.path.get_ident()?.to_string();
+            let size = match ident.as_str() {
+                "u8" | "i8" | "bool" => 1,
+                "u16" | "i16" => 2,
+                "u32" | "i32" | "f32" | "char" => 4,
+                "u64" | "i64" | "f64" => 8,
+                "u128" | "i128" => 16,
+                "usize" | "isize" => size_of::<usize>(),
+                _ => return None,
+            };
+            Some(size)
+        }
+        Type::Array(array) => {
+            let Expr::Lit(syn::ExprLit {
+                lit: Lit::Int(len), ..
+            }) = &array.len
+            else {
+                return None;
+            };
+            Some(type_size(&array.elem)? * len.base10_parse::<usize>().ok()?)
+        }
+        Type::Tuple(tuple) => tuple.elems.iter().map(type_size).sum(),
+        Type::Reference(_) | Type::Ptr(_) | Type::BareFn(_) => Some(size_of::<usize>()),
+        Type::Group(group) => type_size(&group.elem),
+        Type::Paren(paren) => type_size(&paren.elem),
+        _ => None,
+    }
+}
+
+fn literal_size(lit: &Lit) -> Option<usize> {
+    match lit {
+        Lit::Str(string) => Some(string.value().len()),
+        Lit::ByteStr(bytes) => Some(bytes.value().len()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn estimate_item(src: &str) -> Option<usize> {
+        match syn::parse_str::<syn::Item>(src).unwrap() {
+            syn::Item::Static(item) => estimate(&item.ty, &item.expr),
+            syn::Item::Const(item) => estimate(&item.ty, &item.expr),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let pointer = size_of::<usize>();
+
+        assert_eq!(estimate_item("static A: u32 = 1;"), Some(4));
+        assert_eq!(
+            estimate_item("static A: [u16; 1024] = [0; 1024];"),
+            Some(2048)
+        );
+        assert_eq!(
+            estimate_item("const A: [[u8; 4]; 2] = [[0; 4]; 2];"),
+            Some(8)
+        );
+        assert_eq!(estimate_item("const A: (u8, u64) = (1, 2);"), Some(9));
+        assert_eq!(
+            estimate_item("static A: &str = \"hello\";"),
+            Some(2 * pointer + 5)
+        );
+        assert_eq!(
+            estimate_item("static A: &[u8] = b\"abc\";"),
+            Some(2 * pointer + 3)
+        );
+        assert_eq!(
+            estimate_item("static A: &[u32] = &[1, 2, 3];"),
+            Some(2 * pointer + 12)
+        );
+        assert_eq!(
+            estimate_item("static A: &[u8; 3] = b\"abc\";"),
+            Some(pointer + 3)
+        );
+        assert_eq!(estimate_item("static A: Config = Config::new();"), None);
+    }
+}
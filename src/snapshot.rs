@@ -0,0 +1,187 @@
+//! This module saves the contents of the source files of a workspace in the
+//! target directory (under `minify/snapshots`), so `cargo minify restore` can
+//! put them back after an experiment, without committing anything. Contents
+//! are stored once (under `minify/objects`, named after their hash), so taking
+//! many snapshots of a workspace is cheap.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::Hasher,
+    io,
+    path::{Path, PathBuf},
+};
+
+use cargo_minify::records;
+
+use crate::error::{Error, Result};
+
+fn snapshot_directory(target_directory: &Path) -> PathBuf {
+    target_directory.join("minify").join("snapshots")
+}
+
+fn object_directory(target_directory: &Path) -> PathBuf {
+    target_directory.join("minify").join("objects")
+}
+
+/// The name under which a content is stored; the length is part of it, to
+/// make collisions even less likely. The hash is not stable across Rust
+/// releases, which only keeps objects from being shared with older snapshots
+fn object_name(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(content);
+    format!("{:016x}-{}", hasher.finish(), content.len())
+}
+
+/// Stores a content, unless it is stored already, and returns the name of its
+/// object; another content with the same name gets a suffix
+fn store(objects: &Path, content: &[u8]) -> io::Result<String> {
+    let name = object_name(content);
+    for suffix in 0.. {
+        let name = match suffix {
+            0 => name.clone(),
+            _ => format!("{name}-{suffix}"),
+        };
+        let object = objects.join(&name);
+        match std::fs::read(&object) {
+            Ok(stored) if stored == content => return Ok(name),
+            Ok(_) => continue,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                std::fs::write(&object, content)?;
+                return Ok(name);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    unreachable!()
+}
+
+/// Saves the given files (relative to the workspace root), and returns the id
+/// of the snapshot (the time at which it was taken, in seconds since the Unix
+/// epoch)
+pub fn take(target_directory: &Path, workspace_root: &Path, files: &[PathBuf]) -> Result<String> {
+    let objects = object_directory(target_directory);
+    std::fs::create_dir_all(&objects)?;
+
+    let mut names = serde_json::Map::new();
+    for file in files {
+        let content = std::fs::read(workspace_root.join(file))?;
+        let name = store(&objects, &content)?;
+        names.insert(file.to_string_lossy().into_owned(), name.into());
+    }
+
+    let directory = snapshot_directory(target_directory);
+    std::fs::create_dir_all(&directory)?;
+    let time = records::now();
+    let content = serde_json::json!({ "time": time, "files": names });
+
+    Ok(records::write(&directory, time, &content)?)
+}
+
+/// The ids of the snapshots, oldest first
+pub fn snapshots(target_directory: &Path) -> io::Result<Vec<String>> {
+    records::ids(&snapshot_directory(target_directory))
+}
+
+/// Reads the contents of the files in a snapshot, by their path relative to
+/// the workspace root
+pub fn load(target_directory: &Path, id: &str) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let path = snapshot_directory(target_directory).join(format!("{id}.json"));
+    let content = std::fs::read_to_string(path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Error::UnknownSnapshot(id.to_owned()),
+        _ => err.into(),
+    })?;
+    let snapshot: serde_json::Value = serde_json::from_str(&content)?;
+
+    let objects = object_directory(target_directory);
+    snapshot["files"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(file, name)| Some((PathBuf::from(file), name.as_str()?)))
+        .map(|(file, name)| Ok((file, std::fs::read(objects.join(name))?)))
+        .collect()
+}
+
+/// Puts back the files of a snapshot that changed (or were removed) since, and
+/// returns these files
+pub fn restore(target_directory: &Path, workspace_root: &Path, id: &str) -> Result<Vec<PathBuf>> {
+    let mut restored = Vec::new();
+    for (file, content) in load(target_directory, id)? {
+        let path = workspace_root.join(&file);
+        if std::fs::read(&path).is_ok_and(|current| current == content) {
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)?;
+        restored.push(file);
+    }
+
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn colliding_objects() {
+        let objects = std::env::temp_dir().join(format!("minify-objects-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&objects);
+        std::fs::create_dir_all(&objects).unwrap();
+
+        // another content that happens to have the same name
+        let name = object_name(b"fn f() {}\n");
+        std::fs::write(objects.join(&name), b"fn g() {}\n").unwrap();
+
+        let stored = store(&objects, b"fn f() {}\n").unwrap();
+        assert_eq!(stored, format!("{name}-1"));
+        assert_eq!(store(&objects, b"fn f() {}\n").unwrap(), stored);
+        assert_eq!(
+            std::fs::read(objects.join(&stored)).unwrap(),
+            b"fn f() {}\n"
+        );
+
+        std::fs::remove_dir_all(&objects).unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let directory =
+            std::env::temp_dir().join(format!("minify-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let workspace_root = directory.join("workspace");
+        let target_directory = workspace_root.join("target");
+        std::fs::create_dir_all(workspace_root.join("src/module")).unwrap();
+        std::fs::write(workspace_root.join("src/lib.rs"), "mod module;\n").unwrap();
+        std::fs::write(workspace_root.join("src/module/mod.rs"), "fn f() {}\n").unwrap();
+
+        let files = [
+            PathBuf::from("src/lib.rs"),
+            PathBuf::from("src/module/mod.rs"),
+        ];
+        let id = take(&target_directory, &workspace_root, &files).unwrap();
+        assert_eq!(snapshots(&target_directory).unwrap(), [id.as_str()]);
+
+        std::fs::write(workspace_root.join("src/lib.rs"), "").unwrap();
+        std::fs::remove_dir_all(workspace_root.join("src/module")).unwrap();
+        let restored = restore(&target_directory, &workspace_root, &id).unwrap();
+        assert_eq!(restored, files);
+        assert_eq!(
+            std::fs::read_to_string(workspace_root.join("src/module/mod.rs")).unwrap(),
+            "fn f() {}\n"
+        );
+        assert!(restore(&target_directory, &workspace_root, &id)
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            restore(&target_directory, &workspace_root, "0"),
+            Err(Error::UnknownSnapshot(_))
+        ));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
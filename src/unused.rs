@@ -1,81 +1,899 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fmt::{Display, Formatter},
     io::BufReader,
-    path::Path,
-    process::{Command, Stdio},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Stdio,
     str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
 };
 
 use cargo_metadata::{
-    diagnostic::{Diagnostic, DiagnosticSpan},
-    Message,
+    diagnostic::{Applicability, Diagnostic, DiagnosticLevel, DiagnosticSpan},
+    Message, Target,
 };
+use thiserror::Error;
+use tracing::{debug, info, warn};
 
-use crate::{error::Result, resolver, CrateResolutionOptions, FileResolutionOptions};
+use crate::{
+    backend::{self, BackendKind},
+    cargo, cauterize,
+    error::{Error, Result},
+    expansion, interrupt, progress, resolver, CrateResolutionOptions, FileResolutionOptions,
+};
+
+/// Whether `cargo check` was stopped because it took too long
+pub(crate) static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Held by the tests that run checks, and exclusively by those that look at
+/// [`TIMED_OUT`], which the checks reset. The state of a check is global rather
+/// than kept by its backend, since an interrupt has to stop the check wherever
+/// it runs: the running child process lives in [`interrupt`], so a test that
+/// stops its check (or times out) would stop the check of another test.
+#[cfg(test)]
+pub(crate) static CHECKS: std::sync::RwLock<()> = std::sync::RwLock::new(());
+
+/// How the diagnostics are obtained from `cargo check` (or another backend)
+pub struct CheckOptions<'a> {
+    /// What reports the diagnostics (see `--backend`)
+    pub backend: BackendKind,
+    /// Find the items that are only used by tests, instead of the unused ones
+    pub test_only: bool,
+    /// The output of `cargo check --message-format json`, recorded earlier
+    pub recorded: Option<&'a Path>,
+    /// Check the crates that compile, even if others do not
+    pub keep_going: bool,
+    /// Report unused items in packages that allow `dead_code` (see `--force`)
+    pub warn_dead_code: bool,
+    /// Leave out the examples (see `--ignore-examples`)
+    pub ignore_examples: bool,
+    /// Leave out the benchmarks (see `--ignore-benches`)
+    pub ignore_benches: bool,
+    /// When `cargo check` is stopped, after which only the diagnostics it
+    /// reported so far are used (see `--check-timeout`); this covers all of
+    /// the checks of a run
+    pub deadline: Option<Instant>,
+}
+
+impl CheckOptions<'_> {
+    /// Whether the diagnostics of a target are left out; the target is still
+    /// checked, as cargo cannot leave out examples while checking all other
+    /// targets, but whatever it reports is not used
+    fn ignores(&self, target: &Target) -> bool {
+        target.kind.iter().any(|kind| {
+            (self.ignore_examples && kind == "example") || (self.ignore_benches && kind == "bench")
+        })
+    }
+}
 
+/// Runs `cargo check` (or reads its recorded output) to find the unused
+/// items; with `test_only`, these are the items that are only used by tests
+/// instead
 pub fn get_unused<'a>(
     manifest_path: Option<&Path>,
     crate_resolution: &CrateResolutionOptions,
     file_resolution: &'a FileResolutionOptions,
     kinds: &'a [UnusedDiagnosticKind],
+    severity: Option<Severity>,
+    expand_macros: bool,
+    check: &CheckOptions,
 ) -> Result<impl Iterator<Item = UnusedDiagnostic> + 'a> {
-    let mut command = Command::new("cargo");
+    let diagnostics = get_diagnostics(manifest_path, crate_resolution, check)?;
 
-    command.args(["check", "--all-targets", "--quiet", "--message-format", "json"]);
+    let (unused, expanded): (Vec<_>, Vec<_>) = diagnostics
+        .into_iter()
+        .partition(|diagnostic| diagnostic.span.expansion.is_none());
 
-    match crate_resolution {
-        CrateResolutionOptions::Root => {}
-        CrateResolutionOptions::Workspace { exclude } => {
-            command.arg("--workspace");
+    // unused items originating from macro expansions are ignored, unless their
+    // invocations can be removed as a whole
+    let invocations = if expand_macros {
+        expansion::unused_invocations(expanded)
+    } else {
+        Vec::new()
+    };
 
-            for package in *exclude {
-                command.args(["--exclude", package]);
-            }
+    let unused = unused
+        .into_iter()
+        .chain(invocations)
+        .filter(|diagnostic| kinds.is_empty() || kinds.contains(&diagnostic.kind))
+        .filter(move |diagnostic| severity.is_none_or(|severity| diagnostic.severity() == severity))
+        .filter(|diagnostic| file_resolution.is_included(&diagnostic.span.file_name));
+
+    Ok(unused)
+}
+
+/// Runs `cargo check` to find the unused items (or the items that are only
+/// used by tests), before any of the options filter them; if the output of
+/// `cargo check --message-format json` was recorded to a file already, it is
+/// read from that file instead. Targets that do not compile are reported.
+pub fn get_diagnostics(
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+    check: &CheckOptions,
+) -> Result<Vec<UnusedDiagnostic>> {
+    if check.test_only && check.backend == BackendKind::Json {
+        return Err(Error::Args(
+            "--from-json cannot be used with --mode cfg-test, which checks the crates both \
+             with and without tests",
+        ));
+    }
+
+    let (diagnostics, failed) = if check.test_only {
+        // items that are unused, unless the tests are compiled as well
+        let (without_tests, mut failed) = collect(
+            manifest_path,
+            crate_resolution,
+            &[],
+            check,
+            UnusedDiagnostic::from_diagnostic,
+        )?;
+        let (with_tests, failed_tests) = collect(
+            manifest_path,
+            crate_resolution,
+            &["--tests"],
+            check,
+            UnusedDiagnostic::from_diagnostic,
+        )?;
+        failed.extend(failed_tests);
+
+        let diagnostics = without_tests
+            .into_iter()
+            .filter(|diagnostic| !with_tests.iter().any(|other| diagnostic.same_item(other)))
+            .collect();
+        (diagnostics, failed)
+    } else {
+        collect(
+            manifest_path,
+            crate_resolution,
+            &["--all-targets"],
+            check,
+            UnusedDiagnostic::from_diagnostic,
+        )?
+    };
+    // a recording cannot be redone with --keep-going
+    let keep_going = (check.backend != BackendKind::Json).then_some(check.keep_going);
+    report_failed(&failed, keep_going);
+
+    relative_to_current_dir(dedup(diagnostics), manifest_path)
+}
+
+/// Leaves out the diagnostics that repeat an earlier one: a file that is part
+/// of several targets (such as a library and its unit tests) is reported on
+/// for each of them. Diagnostics are the same if they point at the same span,
+/// from the same macro invocation if any, and name the same item
+fn dedup(diagnostics: Vec<UnusedDiagnostic>) -> Vec<UnusedDiagnostic> {
+    let mut seen = BTreeSet::new();
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            let span = &diagnostic.span;
+            let invocation = span
+                .expansion
+                .as_ref()
+                .map(|expansion| (expansion.span.file_name.clone(), expansion.span.byte_start));
+            seen.insert((
+                span.file_name.clone(),
+                span.byte_start,
+                span.byte_end,
+                invocation,
+                diagnostic.ident.clone(),
+            ))
+        })
+        .collect()
+}
+
+/// Runs `cargo check` (or reads its recorded output) to find the fixes that
+/// the compiler suggests for its warnings and is sure of (see [`Suggestion`]);
+/// overlapping suggestions are left out, except for the first one
+pub fn get_suggestions(
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+    file_resolution: &FileResolutionOptions,
+    check: &CheckOptions,
+) -> Result<Vec<Suggestion>> {
+    let (mut suggestions, _) = collect(
+        manifest_path,
+        crate_resolution,
+        &["--all-targets"],
+        check,
+        Suggestion::from_diagnostic,
+    )?;
+
+    let workspace_root = workspace_root(manifest_path)?;
+    for suggestion in &mut suggestions {
+        let file_name = cauterize::relative_path(&workspace_root.join(&suggestion.file_name));
+        suggestion.file_name = file_name.to_string_lossy().into_owned();
+    }
+    suggestions.retain(|suggestion| file_resolution.is_included(&suggestion.file_name));
+
+    // the compiler reports the same suggestion for every target of a file
+    let mut kept: Vec<Suggestion> = Vec::new();
+    for suggestion in suggestions {
+        if kept.contains(&suggestion) {
+            continue;
+        }
+        let overlaps = kept.iter().any(|other| {
+            other.file_name == suggestion.file_name
+                && other.edits.iter().any(|(range, _)| {
+                    suggestion
+                        .edits
+                        .iter()
+                        .any(|(other, _)| range.start < other.end && other.start < range.end)
+                })
+        });
+        if overlaps {
+            debug!("leaving out {suggestion:?}, which overlaps with another suggestion");
+        } else {
+            kept.push(suggestion);
+        }
+    }
+    debug!("the compiler suggested {} fixes", kept.len());
+
+    Ok(kept)
+}
+
+/// Runs `cargo check` (or reads its recorded output) to find the feature gates
+/// that are not used, which only nightly compilers report (see
+/// [`UnusedFeature`])
+pub fn get_unused_features(
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+    file_resolution: &FileResolutionOptions,
+    check: &CheckOptions,
+) -> Result<Vec<UnusedFeature>> {
+    let (mut features, _) = collect(
+        manifest_path,
+        crate_resolution,
+        &["--all-targets"],
+        check,
+        UnusedFeature::from_diagnostic,
+    )?;
+
+    let workspace_root = workspace_root(manifest_path)?;
+    for feature in &mut features {
+        let file_name = cauterize::relative_path(&workspace_root.join(&feature.file_name));
+        feature.file_name = file_name.to_string_lossy().into_owned();
+    }
+    features.retain(|feature| file_resolution.is_included(&feature.file_name));
+    // the compiler reports the gates of a crate for each of its targets
+    features.sort_by(|a, b| (&a.file_name, a.line, &a.name).cmp(&(&b.file_name, b.line, &b.name)));
+    features.dedup();
+    debug!("the compiler reported {} unused features", features.len());
+
+    Ok(features)
+}
+
+/// The (canonical) root of the workspace, relative to which cargo reports
+/// files
+fn workspace_root(manifest_path: Option<&Path>) -> Result<std::path::PathBuf> {
+    let metadata = resolver::get_cargo_metadata(manifest_path)?;
+    Ok(metadata.workspace_root.as_std_path().canonicalize()?)
+}
+
+/// Warns about the targets that do not compile, of which the unused items are
+/// not known; `keep_going` tells whether cargo was asked to check the other
+/// targets anyway (if cargo was run at all)
+fn report_failed(failed: &BTreeSet<String>, keep_going: Option<bool>) {
+    for target in failed {
+        warn!(
+            "could not check {target}, since it does not compile; its unused items are not \
+             reported"
+        );
+    }
+    if !failed.is_empty() && keep_going == Some(false) {
+        info!(
+            "other targets may not have been checked either, use --keep-going to check all \
+             targets that compile"
+        );
+    }
+}
+
+/// Cargo reports files relative to the workspace root, which is not the
+/// current directory if the workspace lies elsewhere (see `--manifest-path`);
+/// this makes them relative to the current directory, as the rest of the tool
+/// expects
+fn relative_to_current_dir(
+    mut diagnostics: Vec<UnusedDiagnostic>,
+    manifest_path: Option<&Path>,
+) -> Result<Vec<UnusedDiagnostic>> {
+    let metadata = resolver::get_cargo_metadata(manifest_path)?;
+    let workspace_root = metadata.workspace_root.as_std_path().canonicalize()?;
+    for diagnostic in &mut diagnostics {
+        rebase(&mut diagnostic.span, &workspace_root);
+    }
+
+    Ok(diagnostics)
+}
+
+fn rebase(span: &mut DiagnosticSpan, workspace_root: &Path) {
+    let file_name = cauterize::relative_path(&workspace_root.join(&span.file_name));
+    span.file_name = file_name.to_string_lossy().into_owned();
+    if let Some(expansion) = &mut span.expansion {
+        rebase(&mut expansion.span, workspace_root);
+        if let Some(definition) = &mut expansion.def_site_span {
+            rebase(definition, workspace_root);
         }
-        CrateResolutionOptions::Package { packages } => {
-            for package in *packages {
-                command.args(["-p", package]);
+    }
+}
+
+/// Runs the backend (see [`backend::backend`]) for the given targets, and
+/// collects what `extract` takes from the diagnostics (such as the unused
+/// items), and the targets that do not compile; any other targets than the
+/// selected ones that the backend reports on are left out
+fn collect<T>(
+    manifest_path: Option<&Path>,
+    crate_resolution: &CrateResolutionOptions,
+    targets: &[&str],
+    check: &CheckOptions,
+    extract: impl Fn(Diagnostic) -> Vec<T>,
+) -> Result<(Vec<T>, BTreeSet<String>)> {
+    let backend = backend::backend(manifest_path, crate_resolution, check);
+    let selected = resolver::get_targets(manifest_path, crate_resolution)?;
+    let is_included = |target: &Target| {
+        !check.ignores(target)
+            && selected
+                .iter()
+                .any(|other| resolver::is_same_target(other, target))
+    };
+
+    let mut diagnostics = Vec::new();
+    let mut failed = BTreeSet::new();
+    let mut checked = BTreeSet::new();
+    // the checks of a run share their deadline, so once one is stopped, the
+    // ones after it are as well
+    TIMED_OUT.store(false, Ordering::SeqCst);
+    backend.messages(targets, &mut |message| match message {
+        Message::CompilerMessage(message) if is_included(&message.target) => {
+            // denied unused items are reported as errors, but the target is
+            // checked all the same
+            let denied_lint = message
+                .message
+                .code
+                .as_ref()
+                .is_some_and(|code| UNUSED_LINTS.contains(&code.code.as_str()));
+            if message.message.level == DiagnosticLevel::Error && !denied_lint {
+                let target = &message.target;
+                failed.insert(format!("{} `{}`", target.kind.join(", "), target.name));
             }
+            diagnostics.extend(extract(message.message));
         }
+        Message::CompilerArtifact(artifact) if is_included(&artifact.target) => {
+            let target = &artifact.target;
+            checked.insert(format!("{} `{}`", target.kind.join(", "), target.name));
+            progress::report(
+                "package-checked",
+                serde_json::json!({
+                    "package_id": artifact.package_id.repr,
+                    "target": artifact.target.name,
+                    "kind": artifact.target.kind,
+                    "test": artifact.profile.test,
+                }),
+            );
+        }
+        _ => {}
+    })?;
+
+    if timed_out() {
+        warn!(
+            "{} was stopped when the time given by --check-timeout ran out, having checked {} of \
+             {} targets; the unused items of the other targets are not known",
+            backend.name(),
+            checked.len().min(selected.len()),
+            selected.len()
+        );
     }
+    debug!("{} reported {} findings", backend.name(), diagnostics.len());
+    Ok((diagnostics, failed))
+}
+
+/// Whether `cargo check` took longer than its timeout and was stopped, in
+/// which case the diagnostics are partial
+pub fn timed_out() -> bool {
+    TIMED_OUT.load(Ordering::SeqCst)
+}
 
-    let mut child = command.stdout(Stdio::piped()).spawn()?;
-    let stdout = child.stdout.take().unwrap();
+/// Runs `cargo check` on all targets of the workspace with the given manifest
+/// (using the given target directory), and collects the `#[expect(...)]`
+/// attributes that are not fulfilled: the file, and the line and name of the
+/// lint in the attribute. A file is compiled several times (e.g. a library
+/// with and without its tests), and an expectation is only collected if it is
+/// unfulfilled in every compilation of its file
+pub fn unfulfilled_expectations(
+    manifest_path: &Path,
+    target_directory: &Path,
+) -> Result<Vec<(String, usize, String)>> {
+    let mut command = cargo::command("check", Some(manifest_path))?;
+    command.args(["--workspace", "--all-targets"]);
+    command.args(["--quiet", "--message-format", "json"]);
+    command.env("CARGO_TARGET_DIR", target_directory);
+
+    let stdout = interrupt::spawn(command.stdout(Stdio::piped()))?;
     let reader = BufReader::new(stdout);
 
-    let targets = resolver::get_targets(manifest_path, crate_resolution)?;
+    // compiler messages only tell the target they are for, not which of its
+    // compilations; the expectations are counted instead, for every target
+    let mut reports = BTreeMap::<(String, usize, String), BTreeMap<String, usize>>::new();
+    let mut compilations = BTreeMap::<(PathBuf, String), usize>::new();
+    let root = manifest_path.parent().unwrap_or(Path::new(""));
+    for message in Message::parse_stream(reader).flatten() {
+        match message {
+            Message::CompilerMessage(message) => {
+                let target = target_key(&message.package_id.repr, &message.target);
+                let is_expectation = message
+                    .message
+                    .code
+                    .as_ref()
+                    .is_some_and(|code| code.code == "unfulfilled_lint_expectations");
+                if !is_expectation {
+                    continue;
+                }
 
-    let unused = Message::parse_stream(reader)
-        .flatten()
-        .filter_map(|message| {
-            if let Message::CompilerMessage(message) = message {
-                Some(message)
-            } else {
-                None
+                let spans = message.message.spans.into_iter();
+                for span in spans.filter(|span| span.is_primary) {
+                    let Some(line) = span.text.first() else {
+                        continue;
+                    };
+                    let lint: String = line
+                        .text
+                        .chars()
+                        .skip(line.highlight_start.saturating_sub(1))
+                        .take(line.highlight_end.saturating_sub(line.highlight_start))
+                        .collect();
+                    let key = (span.file_name, span.line_start, lint);
+                    *reports
+                        .entry(key)
+                        .or_default()
+                        .entry(target.clone())
+                        .or_default() += 1;
+                }
             }
+            Message::CompilerArtifact(artifact) => {
+                let target = target_key(&artifact.package_id.repr, &artifact.target);
+                for file in artifact
+                    .filenames
+                    .iter()
+                    .flat_map(|file| dependencies(file.as_std_path(), root))
+                    .collect::<BTreeSet<_>>()
+                {
+                    *compilations.entry((file, target.clone())).or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    interrupt::wait_child();
+
+    let expectations = reports
+        .into_iter()
+        .filter(|((file_name, ..), reported)| {
+            let file = root.join(file_name);
+            let compiled: Vec<_> = compilations
+                .range((file.clone(), String::new())..)
+                .take_while(|((compiled, _), _)| *compiled == file)
+                .collect();
+            // without dep-info, it cannot be told where the file is compiled
+            !compiled.is_empty()
+                && compiled
+                    .iter()
+                    .all(|((_, target), &count)| reported.get(target) == Some(&count))
         })
-        .filter(move |message| targets.contains(&message.target))
-        .map(|message| message.message)
-        .filter_map(|diagnostic| UnusedDiagnostic::try_from(diagnostic).ok())
-        // Ignore unused warnings originating from macro expansions
-        .filter(|diagnostic| diagnostic.span.expansion.is_none())
-        .filter(|diagnostic| kinds.is_empty() || kinds.contains(&diagnostic.kind))
-        .filter(|diagnostic| file_resolution.is_included(&diagnostic.span.file_name));
+        .map(|(expectation, _)| expectation)
+        .collect();
 
-    Ok(unused)
+    Ok(expectations)
 }
 
-#[derive(Debug)]
+fn target_key(package_id: &str, target: &Target) -> String {
+    format!("{package_id} {} {}", target.name, target.kind.join(","))
+}
+
+/// The source files that an artifact of `cargo check` (`lib<name>.rmeta`) was
+/// compiled from, according to the dep-info file that rustc writes next to it
+/// (`<name>.d`); relative paths are taken from `root`, the workspace root
+fn dependencies(artifact: &Path, root: &Path) -> Vec<PathBuf> {
+    let Some(name) = artifact
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem.strip_prefix("lib").unwrap_or(stem))
+    else {
+        return Vec::new();
+    };
+    let Ok(dep_info) = std::fs::read_to_string(artifact.with_file_name(format!("{name}.d"))) else {
+        return Vec::new();
+    };
+
+    dep_info
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .flat_map(|(_, files)| dep_info_files(files))
+        .map(|file| root.join(file))
+        .collect()
+}
+
+/// The files in a line of a dep-info file, separated by spaces; spaces in file
+/// names are escaped with a backslash
+fn dep_info_files(line: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut file = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => file.extend(chars.next()),
+            ' ' => files.extend((!file.is_empty()).then(|| std::mem::take(&mut file))),
+            c => file.push(c),
+        }
+    }
+    files.extend((!file.is_empty()).then_some(file));
+    files
+}
+
+/// A fix of a warning that the compiler suggests, and marks as
+/// machine-applicable: it can be applied as is
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub file_name: String,
+    /// The bytes of the file to replace, and their replacements; these only
+    /// make sense together (e.g. removing one of several imports)
+    pub edits: Vec<(Range<usize>, String)>,
+    /// The lint that the fix is for, e.g. `unused_imports`
+    pub lint: Option<String>,
+}
+
+impl Suggestion {
+    /// The machine-applicable suggestions of a warning (which are found in
+    /// its children, such as "remove the unused import"); code that macros
+    /// expand to is left alone, as are suggestions that span several files
+    pub fn from_diagnostic(diagnostic: Diagnostic) -> Vec<Self> {
+        if diagnostic.level != DiagnosticLevel::Warning {
+            return Vec::new();
+        }
+
+        let lint = diagnostic.code.map(|code| code.code);
+        diagnostic
+            .children
+            .into_iter()
+            .map(|child| child.spans)
+            .chain(std::iter::once(diagnostic.spans))
+            .filter_map(|spans| {
+                let spans: Vec<_> = spans
+                    .into_iter()
+                    .filter(|span| {
+                        span.suggestion_applicability == Some(Applicability::MachineApplicable)
+                    })
+                    .collect();
+                let file_name = spans.first()?.file_name.clone();
+                if spans
+                    .iter()
+                    .any(|span| span.expansion.is_some() || span.file_name != file_name)
+                {
+                    return None;
+                }
+
+                let edits = spans
+                    .into_iter()
+                    .map(|span| {
+                        let range = span.byte_start as usize..span.byte_end as usize;
+                        Some((range, span.suggested_replacement?))
+                    })
+                    .collect::<Option<_>>()?;
+                Some(Suggestion {
+                    file_name,
+                    edits,
+                    lint: lint.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A feature that a `#![feature(...)]` attribute enables, but the crate does
+/// not use, as reported by the `unused_features` lint of nightly compilers
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnusedFeature {
+    pub file_name: String,
+    /// The line of the name of the feature in the attribute
+    pub line: usize,
+    pub name: String,
+}
+
+impl UnusedFeature {
+    /// The features of an `unused_features` warning, of which the spans point
+    /// at the names of the features
+    pub fn from_diagnostic(diagnostic: Diagnostic) -> Vec<Self> {
+        let is_lint = diagnostic
+            .code
+            .as_ref()
+            .is_some_and(|code| code.code == "unused_features");
+        if !is_lint {
+            return Vec::new();
+        }
+
+        diagnostic
+            .spans
+            .iter()
+            .filter(|span| span.is_primary && span.expansion.is_none())
+            .filter_map(|span| {
+                Some(UnusedFeature {
+                    file_name: span.file_name.clone(),
+                    line: span.line_start,
+                    name: highlighted_text(span)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct UnusedDiagnostic {
     pub kind: UnusedDiagnosticKind,
     pub ident: String,
     pub span: DiagnosticSpan,
+    /// The item this item is part of, e.g. the struct that contains a field
+    pub parent: Option<String>,
+    /// The message of the compiler, e.g. "function `foo` is never used"
+    pub message: String,
+}
+
+impl UnusedDiagnostic {
+    /// Whether two diagnostics are about the same item
+    pub fn same_item(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.ident == other.ident
+            && self.span.file_name == other.span.file_name
+            && self.span.byte_start == other.span.byte_start
+    }
+
+    /// Whether the item is not referred to at all, or only written to; the
+    /// lint is the same for both, so this is told by the message ("is never
+    /// read" instead of "is never used" or "is never constructed"). Only fields
+    /// get the former: any use of a static, including assigning to it, counts
+    /// as a use. For a message worded otherwise, fields are taken to be unread
+    pub fn severity(&self) -> Severity {
+        let message = self.message.as_str();
+        if message.contains(" never read") {
+            Severity::Unread
+        } else if message.contains(" never used") || message.contains(" never constructed") {
+            Severity::Unused
+        } else if self.kind == UnusedDiagnosticKind::Field {
+            Severity::Unread
+        } else {
+            Severity::Unused
+        }
+    }
+
+    /// Parses a diagnostic that reports one or more unused items; rustc groups
+    /// unused associated items of the same impl block into a single diagnostic
+    pub fn from_diagnostic(value: Diagnostic) -> Vec<Self> {
+        if let Some(diagnostics) = Self::from_lint(&value) {
+            return diagnostics;
+        }
+
+        // the message is only parsed for what the spans do not tell, such as
+        // the index of an unread tuple field
+        match Self::try_from(value.clone()) {
+            Ok(diagnostic) => vec![diagnostic],
+            Err(_) => Self::try_from_grouped(value).unwrap_or_default(),
+        }
+    }
+
+    /// Classifies the items of a `dead_code` or `unused_macros` warning by the
+    /// code that its spans point at (e.g. the `fn` before the name of a
+    /// function), instead of by its message, which may change between compiler
+    /// versions; `None` if any of its items cannot be classified this way
+    fn from_lint(value: &Diagnostic) -> Option<Vec<Self>> {
+        let code = value.code.as_ref()?;
+        if !UNUSED_LINTS.contains(&code.code.as_str()) {
+            return None;
+        }
+
+        // the span of the impl block or struct that the items are part of
+        let container = value.spans.iter().find(|span| !span.is_primary);
+        let in_impl = container.and_then(highlighted_text).is_some_and(|text| {
+            text.strip_prefix("impl")
+                .is_some_and(|rest| !rest.starts_with(is_ident_char))
+        });
+        let parent = container
+            .filter(|span| preceding_keyword(span).as_deref() == Some("struct"))
+            .and_then(highlighted_text)
+            .map(|parent| unraw(&parent));
+        // such as the trait of an unused method, which the message tells
+        if container.is_some() && !in_impl && parent.is_none() {
+            return None;
+        }
+
+        let diagnostics = value
+            .spans
+            .iter()
+            .filter(|span| span.is_primary)
+            .map(|span| {
+                let kind = match preceding_keyword(span).as_deref() {
+                    Some("fn") if in_impl => UnusedDiagnosticKind::AssociatedFunction,
+                    Some("fn") => UnusedDiagnosticKind::Function,
+                    Some("const") => UnusedDiagnosticKind::Constant,
+                    Some("static") => UnusedDiagnosticKind::Static,
+                    Some("struct") => UnusedDiagnosticKind::Struct,
+                    Some("enum") => UnusedDiagnosticKind::Enum,
+                    Some("union") => UnusedDiagnosticKind::Union,
+                    Some("type") => UnusedDiagnosticKind::TypeAlias,
+                    Some("macro_rules!" | "macro") => UnusedDiagnosticKind::MacroDefinition,
+                    keyword
+                        if keyword.is_none_or(|keyword| keyword.starts_with("pub"))
+                            && following_text(span)?.trim_start().starts_with(':') =>
+                    {
+                        UnusedDiagnosticKind::Field
+                    }
+                    _ => return None,
+                };
+
+                Some(UnusedDiagnostic {
+                    parent: match kind {
+                        UnusedDiagnosticKind::Field => parent.clone(),
+                        _ => None,
+                    },
+                    kind,
+                    ident: unraw(&highlighted_text(span)?),
+                    span: span.clone(),
+                    message: value.message.clone(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        (!diagnostics.is_empty()).then_some(diagnostics)
+    }
+
+    /// Parses messages such as "associated items `a`, `b`, and `c` are never
+    /// used" or "fields `a` and `b` are never read", where each item has its
+    /// own primary span
+    fn try_from_grouped(value: Diagnostic) -> Result<Vec<Self>, NotUnusedDiagnostic> {
+        let full_message = value.message.clone();
+        let (kind, message) = match value.message.strip_prefix("associated ") {
+            Some(message) => {
+                let (kind, message) = message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
+                let kind = match kind {
+                    "function" | "functions" | "method" | "methods" => {
+                        UnusedDiagnosticKind::AssociatedFunction
+                    }
+                    "constant" | "constants" => UnusedDiagnosticKind::Constant,
+                    "type" | "types" => UnusedDiagnosticKind::TypeAlias,
+                    "item" | "items" => UnusedDiagnosticKind::AssociatedItem,
+                    _ => return Err(NotUnusedDiagnostic),
+                };
+
+                let message = message
+                    .strip_suffix(" are never used")
+                    .or_else(|| message.strip_suffix(" is never used"))
+                    .ok_or(NotUnusedDiagnostic)?;
+
+                (kind, message)
+            }
+            None => {
+                let (kind, message) = value.message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
+                if kind != "field" && kind != "fields" {
+                    return Err(NotUnusedDiagnostic);
+                }
+
+                let message = message
+                    .strip_suffix(" are never read")
+                    .or_else(|| message.strip_suffix(" is never read"))
+                    .ok_or(NotUnusedDiagnostic)?;
+
+                (UnusedDiagnosticKind::Field, message)
+            }
+        };
+
+        let parent = value
+            .spans
+            .iter()
+            .find(|span| {
+                !span.is_primary
+                    && span
+                        .label
+                        .as_deref()
+                        .is_some_and(|label| label.ends_with("in this struct"))
+            })
+            .and_then(highlighted_text)
+            .map(|parent| unraw(&parent));
+
+        let idents = message
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(unraw)
+            .collect::<Vec<_>>();
+
+        let spans = value
+            .spans
+            .into_iter()
+            .filter(|span| span.is_primary)
+            .collect::<Vec<_>>();
+
+        if idents.is_empty() || idents.len() != spans.len() {
+            return Err(NotUnusedDiagnostic);
+        }
+
+        Ok(idents
+            .into_iter()
+            .zip(spans)
+            .map(|(ident, span)| UnusedDiagnostic {
+                kind: kind.clone(),
+                ident,
+                span,
+                parent: parent.clone(),
+                message: full_message.clone(),
+            })
+            .collect())
+    }
+}
+
+/// The span a diagnostic is about; other spans are labels, such as "method in
+/// this implementation"
+fn primary_span(spans: Vec<DiagnosticSpan>) -> Result<DiagnosticSpan, NotUnusedDiagnostic> {
+    let primary = spans.iter().position(|span| span.is_primary).unwrap_or(0);
+    spans.into_iter().nth(primary).ok_or(NotUnusedDiagnostic)
+}
+
+/// Strips the `r#` of a raw identifier, since rustc may or may not report it
+fn unraw(ident: &str) -> String {
+    ident.strip_prefix("r#").unwrap_or(ident).to_owned()
+}
+
+/// The lints that report unused items
+const UNUSED_LINTS: [&str; 2] = ["dead_code", "unused_macros"];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The word before a span on its line (e.g. `fn` for the name of a function),
+/// skipping the `mut` of a `static mut`; `None` if it is the first on its line
+fn preceding_keyword(span: &DiagnosticSpan) -> Option<String> {
+    let line = span.text.first()?;
+    let before: String = line
+        .text
+        .chars()
+        .take(line.highlight_start.checked_sub(1)?)
+        .collect();
+    let mut words = before.split_whitespace().rev();
+    let keyword = match words.next()? {
+        "mut" => words.next()?,
+        keyword => keyword,
+    };
+
+    Some(keyword.to_owned())
+}
+
+/// The source text after a span, on the line where the span ends
+fn following_text(span: &DiagnosticSpan) -> Option<String> {
+    let line = span.text.last()?;
+
+    Some(
+        line.text
+            .chars()
+            .skip(line.highlight_end.checked_sub(1)?)
+            .collect(),
+    )
+}
+
+/// The source text a (single-line) span points at
+fn highlighted_text(span: &DiagnosticSpan) -> Option<String> {
+    let line = span.text.first()?;
+
+    Some(
+        line.text
+            .chars()
+            .skip(line.highlight_start.checked_sub(1)?)
+            .take(line.highlight_end.checked_sub(line.highlight_start)?)
+            .collect(),
+    )
 }
 
 impl TryFrom<Diagnostic> for UnusedDiagnostic {
     type Error = NotUnusedDiagnostic;
 
     fn try_from(value: Diagnostic) -> Result<Self, Self::Error> {
+        let full_message = value.message.clone();
         let message = value.message;
 
         let (first, message) = message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
@@ -108,13 +926,16 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
 
                         message
                     }
-                    UnusedDiagnosticKind::MacroDefinition => return Err(NotUnusedDiagnostic),
+                    UnusedDiagnosticKind::AssociatedItem
+                    | UnusedDiagnosticKind::MacroDefinition
+                    | UnusedDiagnosticKind::MacroInvocation
+                    | UnusedDiagnosticKind::Field => return Err(NotUnusedDiagnostic),
                 };
 
                 let (mut ident, message) = message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
                 ident = ident.strip_prefix('`').ok_or(NotUnusedDiagnostic)?;
                 ident = ident.strip_suffix('`').ok_or(NotUnusedDiagnostic)?;
-                let ident = ident.to_owned();
+                let ident = unraw(ident);
 
                 let suffix = match kind {
                     UnusedDiagnosticKind::Constant
@@ -125,16 +946,25 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
                     | UnusedDiagnosticKind::TypeAlias
                     | UnusedDiagnosticKind::AssociatedFunction => "is never used",
                     UnusedDiagnosticKind::Struct => "is never constructed",
-                    UnusedDiagnosticKind::MacroDefinition => return Err(NotUnusedDiagnostic),
+                    UnusedDiagnosticKind::AssociatedItem
+                    | UnusedDiagnosticKind::MacroDefinition
+                    | UnusedDiagnosticKind::MacroInvocation
+                    | UnusedDiagnosticKind::Field => return Err(NotUnusedDiagnostic),
                 };
 
                 if message != suffix {
                     return Err(NotUnusedDiagnostic);
                 }
 
-                let span = value.spans.into_iter().next().ok_or(NotUnusedDiagnostic)?;
+                let span = primary_span(value.spans)?;
 
-                Ok(UnusedDiagnostic { kind, ident, span })
+                Ok(UnusedDiagnostic {
+                    kind,
+                    ident,
+                    span,
+                    parent: None,
+                    message: full_message,
+                })
             }
             Err(_) => {
                 if first != "unused" {
@@ -153,7 +983,10 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
                     | UnusedDiagnosticKind::Enum
                     | UnusedDiagnosticKind::Union
                     | UnusedDiagnosticKind::TypeAlias
-                    | UnusedDiagnosticKind::AssociatedFunction => return Err(NotUnusedDiagnostic),
+                    | UnusedDiagnosticKind::AssociatedFunction
+                    | UnusedDiagnosticKind::AssociatedItem
+                    | UnusedDiagnosticKind::MacroInvocation
+                    | UnusedDiagnosticKind::Field => return Err(NotUnusedDiagnostic),
                     UnusedDiagnosticKind::MacroDefinition => {
                         let (definition, message) =
                             message.split_once(' ').ok_or(NotUnusedDiagnostic)?;
@@ -172,21 +1005,27 @@ impl TryFrom<Diagnostic> for UnusedDiagnostic {
 
                 ident = ident.strip_prefix('`').ok_or(NotUnusedDiagnostic)?;
                 ident = ident.strip_suffix('`').ok_or(NotUnusedDiagnostic)?;
-                let ident = ident.to_owned();
+                let ident = unraw(ident);
 
                 if !message.is_empty() {
                     return Err(NotUnusedDiagnostic);
                 }
 
-                let span = value.spans.into_iter().next().ok_or(NotUnusedDiagnostic)?;
+                let span = primary_span(value.spans)?;
 
-                Ok(UnusedDiagnostic { kind, ident, span })
+                Ok(UnusedDiagnostic {
+                    kind,
+                    ident,
+                    span,
+                    parent: None,
+                    message: full_message,
+                })
             }
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum UnusedDiagnosticKind {
     Constant,
     Static,
@@ -196,7 +1035,65 @@ pub enum UnusedDiagnosticKind {
     Union,
     TypeAlias,
     AssociatedFunction,
+    AssociatedItem,
     MacroDefinition,
+    /// An invocation of a macro of which all expanded items are unused
+    MacroInvocation,
+    Field,
+}
+
+impl UnusedDiagnosticKind {
+    pub const ALL: [UnusedDiagnosticKind; 12] = [
+        UnusedDiagnosticKind::Constant,
+        UnusedDiagnosticKind::Static,
+        UnusedDiagnosticKind::Function,
+        UnusedDiagnosticKind::Struct,
+        UnusedDiagnosticKind::Enum,
+        UnusedDiagnosticKind::Union,
+        UnusedDiagnosticKind::TypeAlias,
+        UnusedDiagnosticKind::AssociatedFunction,
+        UnusedDiagnosticKind::AssociatedItem,
+        UnusedDiagnosticKind::MacroDefinition,
+        UnusedDiagnosticKind::MacroInvocation,
+        UnusedDiagnosticKind::Field,
+    ];
+
+    /// The name of this kind, as accepted by `--kinds`
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnusedDiagnosticKind::Constant => "constant",
+            UnusedDiagnosticKind::Static => "static",
+            UnusedDiagnosticKind::Function => "function",
+            UnusedDiagnosticKind::Struct => "struct",
+            UnusedDiagnosticKind::Enum => "enum",
+            UnusedDiagnosticKind::Union => "union",
+            UnusedDiagnosticKind::TypeAlias => "type_alias",
+            UnusedDiagnosticKind::AssociatedFunction => "associated_function",
+            UnusedDiagnosticKind::AssociatedItem => "associated_item",
+            UnusedDiagnosticKind::MacroDefinition => "macro_definition",
+            UnusedDiagnosticKind::MacroInvocation => "macro_invocation",
+            UnusedDiagnosticKind::Field => "field",
+        }
+    }
+
+    /// Whether this kind of item is a type that can have impl blocks
+    pub fn is_type_definition(&self) -> bool {
+        matches!(
+            self,
+            UnusedDiagnosticKind::Struct | UnusedDiagnosticKind::Enum | UnusedDiagnosticKind::Union
+        )
+    }
+
+    /// Whether items of this kind can be brought into scope with `use`
+    pub fn is_importable(&self) -> bool {
+        !matches!(
+            self,
+            UnusedDiagnosticKind::AssociatedFunction
+                | UnusedDiagnosticKind::AssociatedItem
+                | UnusedDiagnosticKind::MacroInvocation
+                | UnusedDiagnosticKind::Field
+        )
+    }
 }
 
 impl FromStr for UnusedDiagnosticKind {
@@ -218,12 +1115,51 @@ impl FromStr for UnusedDiagnosticKind {
             "union" => Ok(UnusedDiagnosticKind::Union),
             "type" | "typealias" => Ok(UnusedDiagnosticKind::TypeAlias),
             "associated" | "associatedfunction" => Ok(UnusedDiagnosticKind::AssociatedFunction),
+            "associateditem" => Ok(UnusedDiagnosticKind::AssociatedItem),
             "macro" | "macrodefinition" => Ok(UnusedDiagnosticKind::MacroDefinition),
+            "macroinvocation" => Ok(UnusedDiagnosticKind::MacroInvocation),
+            "field" => Ok(UnusedDiagnosticKind::Field),
             _ => Err(NotUnusedDiagnostic),
         }
     }
 }
 
+/// How unused an item is
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Severity {
+    /// Nothing refers to the item, e.g. a function that is never called
+    Unused,
+    /// The item is written, but never read, e.g. a field that is only set;
+    /// this is often intentional, such as for structs that mirror a C type
+    Unread,
+}
+
+impl Severity {
+    /// The name of this severity, as accepted by `--severity`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Unused => "unused",
+            Severity::Unread => "unread",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = UnsupportedSeverity;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unused" => Ok(Severity::Unused),
+            "unread" => Ok(Severity::Unread),
+            _ => Err(UnsupportedSeverity),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported severity, pick any of: unused, unread")]
+pub struct UnsupportedSeverity;
+
 #[derive(Debug)]
 pub struct NotUnusedDiagnostic;
 
@@ -234,3 +1170,189 @@ impl Display for NotUnusedDiagnostic {
 }
 
 impl std::error::Error for NotUnusedDiagnostic {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The warnings of `tests/rustc/corpus.rs` as reported by each version of
+    /// the compiler (using `--error-format json`)
+    const CORPUS: [(&str, &str); 2] = [
+        ("1.70.0", include_str!("../tests/rustc/1.70.0.jsonl")),
+        ("1.95.0", include_str!("../tests/rustc/1.95.0.jsonl")),
+    ];
+
+    /// Whether a version of the compiler reports the unread fields of tuple
+    /// structs, which older versions left to a lint that is allowed by default
+    fn reports_tuple_fields(version: &str) -> bool {
+        use cargo_metadata::semver::Version;
+
+        Version::parse(version).unwrap() >= Version::new(1, 77, 0)
+    }
+
+    fn items(
+        json: &str,
+        message: Option<&str>,
+    ) -> Vec<(UnusedDiagnosticKind, String, Option<String>)> {
+        json.lines()
+            .map(|line| serde_json::from_str::<Diagnostic>(line).unwrap())
+            .map(|mut diagnostic| {
+                if let Some(message) = message {
+                    diagnostic.message = message.to_owned();
+                }
+                diagnostic
+            })
+            .flat_map(UnusedDiagnostic::from_diagnostic)
+            .map(|diagnostic| (diagnostic.kind, diagnostic.ident, diagnostic.parent))
+            .collect()
+    }
+
+    #[test]
+    fn corpus() {
+        use UnusedDiagnosticKind::*;
+
+        let item = |kind, ident: &str, parent: Option<&str>| {
+            (kind, ident.to_owned(), parent.map(str::to_owned))
+        };
+        let expected = [
+            item(MacroDefinition, "unused_macro", None),
+            item(Constant, "UNUSED_CONST", None),
+            item(Static, "UNUSED_STATIC", None),
+            item(Function, "unused_function", None),
+            item(Function, "match", None),
+            item(Struct, "UnusedStruct", None),
+            item(Enum, "UnusedEnum", None),
+            item(Union, "UnusedUnion", None),
+            item(TypeAlias, "UnusedAlias", None),
+            item(Field, "unread", Some("Partly")),
+            item(Field, "also_unread", Some("Partly")),
+            item(Field, "0", Some("Tuple")),
+            item(Field, "x", None),
+            item(Constant, "ASSOC", None),
+            item(AssociatedFunction, "method", None),
+            item(AssociatedFunction, "single", None),
+            item(AssociatedFunction, "first", None),
+            item(AssociatedFunction, "second", None),
+            item(Constant, "C", None),
+        ];
+
+        for (version, json) in CORPUS {
+            let mut expected = expected.to_vec();
+            expected.retain(|(_, ident, _)| ident != "0" || reports_tuple_fields(version));
+            assert_eq!(items(json, None), expected, "rustc {version}");
+
+            // only the fields of tuple structs and enum variants depend on the
+            // message
+            let mut by_lint = expected.clone();
+            by_lint.retain(|(_, ident, _)| ident != "0" && ident != "x");
+            assert_eq!(items(json, Some("?")), by_lint, "rustc {version}");
+        }
+    }
+
+    #[test]
+    fn severity() {
+        for (version, json) in CORPUS {
+            let unread: Vec<_> = json
+                .lines()
+                .map(|line| serde_json::from_str::<Diagnostic>(line).unwrap())
+                .flat_map(UnusedDiagnostic::from_diagnostic)
+                .filter(|diagnostic| diagnostic.severity() == Severity::Unread)
+                .map(|diagnostic| diagnostic.ident)
+                .collect();
+            let mut expected = vec!["unread", "also_unread", "0", "x"];
+            expected.retain(|ident| *ident != "0" || reports_tuple_fields(version));
+            assert_eq!(unread, expected, "rustc {version}");
+
+            // the wording of the message does not matter
+            let unread: Vec<_> = json
+                .lines()
+                .map(|line| serde_json::from_str::<Diagnostic>(line).unwrap())
+                .map(|mut diagnostic| {
+                    diagnostic.message = "?".to_owned();
+                    diagnostic
+                })
+                .flat_map(UnusedDiagnostic::from_diagnostic)
+                .filter(|diagnostic| diagnostic.severity() == Severity::Unread)
+                .map(|diagnostic| diagnostic.ident)
+                .collect();
+            assert_eq!(unread, ["unread", "also_unread"], "rustc {version}");
+
+            // but what it says does
+            let unread = json
+                .lines()
+                .map(|line| serde_json::from_str::<Diagnostic>(line).unwrap())
+                .map(|mut diagnostic| {
+                    diagnostic.message = diagnostic.message.replace("never read", "never used");
+                    diagnostic
+                })
+                .flat_map(UnusedDiagnostic::from_diagnostic)
+                .filter(|diagnostic| diagnostic.severity() == Severity::Unread)
+                .count();
+            assert_eq!(unread, 0, "rustc {version}");
+        }
+    }
+
+    #[test]
+    fn deduplicates() {
+        for (version, json) in CORPUS {
+            let diagnostics: Vec<_> = json
+                .lines()
+                .map(|line| serde_json::from_str::<Diagnostic>(line).unwrap())
+                .flat_map(UnusedDiagnostic::from_diagnostic)
+                .collect();
+
+            // as for a library and its unit tests
+            let deduplicated = dedup([diagnostics.clone(), diagnostics.clone()].concat());
+            assert_eq!(deduplicated.len(), diagnostics.len(), "rustc {version}");
+            assert!(
+                deduplicated
+                    .iter()
+                    .zip(&diagnostics)
+                    .all(|(a, b)| a.same_item(b)),
+                "rustc {version}"
+            );
+        }
+    }
+
+    #[test]
+    fn deadline() {
+        let _checks = CHECKS.write();
+        let recorded =
+            std::env::temp_dir().join(format!("minify-check-{}.json", std::process::id()));
+        std::fs::write(&recorded, "").unwrap();
+        let check = |backend, deadline| CheckOptions {
+            backend,
+            test_only: false,
+            recorded: Some(&recorded),
+            keep_going: false,
+            warn_dead_code: false,
+            ignore_examples: false,
+            ignore_benches: false,
+            deadline,
+        };
+        let collect = |check: &CheckOptions| {
+            let (found, _) = collect(
+                None,
+                &CrateResolutionOptions::Root,
+                &[],
+                check,
+                |_| vec![()],
+            )
+            .unwrap();
+            found
+        };
+
+        // cargo is not even started once the time is up
+        assert_eq!(
+            collect(&check(BackendKind::Check, Some(Instant::now()))),
+            []
+        );
+        assert!(timed_out());
+
+        // a later run starts afresh
+        assert_eq!(collect(&check(BackendKind::Json, None)), []);
+        assert!(!timed_out());
+
+        std::fs::remove_file(&recorded).unwrap();
+    }
+}
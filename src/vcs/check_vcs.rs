@@ -14,8 +14,10 @@ pub fn existing_vcs_repo(path: &Path, cwd: &Path) -> bool {
 
 fn in_git_repo(path: &Path) -> bool {
     if let Ok(repo) = git2::Repository::discover(path) {
-        // Don't check if the working directory itself is ignored.
-        if repo.workdir().map_or(false, |workdir| workdir == path) {
+        // Don't check if the working directory itself is ignored; it may be
+        // named differently than the path (e.g. with a trailing slash).
+        let workdir = repo.workdir().and_then(|dir| dir.canonicalize().ok());
+        if workdir.is_some() && workdir == path.canonicalize().ok() {
             true
         } else {
             !repo.is_path_ignored(path).unwrap_or(false)
@@ -1,11 +1,143 @@
-use std::path::Path;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
 
+#[cfg(not(target_arch = "wasm32"))]
 mod check_vcs;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn status(path: impl AsRef<Path>) -> Status {
     check_version_control(path.as_ref())
 }
 
+/// There is no git in the browser, where only the engine is used
+#[cfg(target_arch = "wasm32")]
+pub fn status(_path: impl AsRef<Path>) -> Status {
+    Status::NoVCS
+}
+
+/// The number of days since a line of a file (with the given, possibly
+/// modified, content) was last changed according to `git blame`; lines that
+/// are not committed yet are zero days old
+#[cfg(not(target_arch = "wasm32"))]
+pub fn line_age_days(file: &Path, content: &[u8], line: usize) -> Option<u64> {
+    let file = file.canonicalize().ok()?;
+    let repo = git2::Repository::discover(&file).ok()?;
+    let path = file.strip_prefix(repo.workdir()?).ok()?;
+
+    // find the line in the committed version of the file, if it is there
+    let tree = repo.head().ok()?.peel_to_tree().ok()?;
+    let blob = tree
+        .get_path(path)
+        .ok()?
+        .to_object(&repo)
+        .ok()?
+        .peel_to_blob()
+        .ok()?;
+    let committed_lines: Vec<_> = blob.content().split(|&c| c == b'\n').collect();
+    let lines: Vec<_> = content.split(|&c| c == b'\n').collect();
+    let (mut old, mut new) = (0, 0);
+    let mut committed_line = None;
+    for result in diff::slice(&committed_lines, &lines) {
+        match result {
+            diff::Result::Left(_) => old += 1,
+            diff::Result::Right(_) => new += 1,
+            diff::Result::Both(..) => {
+                old += 1;
+                new += 1;
+            }
+        }
+        if new == line {
+            committed_line = matches!(result, diff::Result::Both(..)).then_some(old);
+            break;
+        }
+    }
+    let Some(committed_line) = committed_line else {
+        return Some(0);
+    };
+
+    let blame = repo.blame_file(path, None).ok()?;
+    let hunk = blame.get_line(committed_line)?;
+
+    let committed = repo
+        .find_commit(hunk.final_commit_id())
+        .ok()?
+        .time()
+        .seconds();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    Some((now - committed).max(0) as u64 / (24 * 60 * 60))
+}
+
+/// The files (relative to the given directory) that version control ignores,
+/// according to `git check-ignore`; files that git tracks are never ignored,
+/// and outside of a git repository no files are
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ignored(dir: &Path, files: &[PathBuf]) -> BTreeSet<PathBuf> {
+    let child = Command::new("git")
+        .current_dir(dir)
+        .args(["check-ignore", "--stdin", "-z"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return BTreeSet::new();
+    };
+
+    let mut input = Vec::new();
+    for file in files {
+        input.extend_from_slice(file.to_string_lossy().as_bytes());
+        input.push(0);
+    }
+    // git answers each path as soon as it has read it, so writing all of them
+    // before reading the answers would block once the pipe of stdout is full;
+    // they are written from another thread instead
+    let writer = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        })
+    });
+    let output = child.wait_with_output();
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    let Ok(output) = output else {
+        return BTreeSet::new();
+    };
+
+    // git exits with 1 if no file is ignored, and with 128 on errors
+    if !output.status.success() {
+        return BTreeSet::new();
+    }
+    output
+        .stdout
+        .split(|&c| c == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(String::from_utf8_lossy(path).into_owned()))
+        .collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn ignored(_dir: &Path, _files: &[PathBuf]) -> BTreeSet<PathBuf> {
+    BTreeSet::new()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn line_age_days(_file: &Path, _content: &[u8], _line: usize) -> Option<u64> {
+    None
+}
+
+// only the engine is used in the browser, where there is no git
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
 pub enum Status {
     Clean,
     Unclean {
@@ -13,12 +145,13 @@ pub enum Status {
         staged: Vec<String>,
     },
     NoVCS,
-    Error(git2::Error),
+    Error(String),
 }
 
 // Portions of the below code are inspired by/taken from Cargo, https://github.com/rust-lang/cargo/
 // Copyright (c) 2016-2021 The Cargo Developers
 
+#[cfg(not(target_arch = "wasm32"))]
 fn check_version_control(path: &Path) -> Status {
     if !check_vcs::existing_vcs_repo(path, path) {
         return Status::NoVCS;
@@ -26,26 +159,29 @@ fn check_version_control(path: &Path) -> Status {
 
     let mut dirty = Vec::new();
     let mut staged = Vec::new();
-    if let Ok(repo) = git2::Repository::discover(path) {
-        let mut repo_opts = git2::StatusOptions::new();
-        repo_opts.include_ignored(false);
-        repo_opts.include_untracked(true);
-        let statuses = match repo.statuses(Some(&mut repo_opts)) {
-            Ok(value) => value,
-            Err(error) => return Status::Error(error),
+    // a worktree or submodule has a `.git` file instead of a directory, which
+    // `discover` follows to the repository that the workspace belongs to
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if let Ok(repo) = git2::Repository::discover(&path) {
+        let Some(workdir) = repo.workdir().and_then(|dir| dir.canonicalize().ok()) else {
+            return Status::Error("the repository has no working directory".to_owned());
         };
-        for status in statuses.iter() {
-            if let Some(path) = status.path() {
-                match status.status() {
-                    git2::Status::CURRENT => (),
-                    git2::Status::INDEX_NEW
-                    | git2::Status::INDEX_MODIFIED
-                    | git2::Status::INDEX_DELETED
-                    | git2::Status::INDEX_RENAMED
-                    | git2::Status::INDEX_TYPECHANGE => staged.push(path.to_string()),
-                    _ => dirty.push(path.to_string()),
-                };
-            }
+        if repo.is_worktree() {
+            tracing::debug!("{} is a linked worktree", workdir.display());
+        }
+        if let Some(parent) = superproject(&workdir) {
+            tracing::debug!(
+                "{} is a submodule of {}",
+                workdir.display(),
+                parent.display()
+            );
+        }
+
+        // only the files of the workspace matter, not those elsewhere in the
+        // repository
+        let scope = path.strip_prefix(&workdir).unwrap_or(Path::new(""));
+        if let Err(error) = statuses(&repo, scope, Path::new(""), &mut dirty, &mut staged) {
+            return Status::Error(error.to_string());
         }
     }
 
@@ -55,3 +191,202 @@ fn check_version_control(path: &Path) -> Status {
         Status::Unclean { dirty, staged }
     }
 }
+
+/// The working directory of the repository of which the given working
+/// directory is a submodule, if it is one
+#[cfg(not(target_arch = "wasm32"))]
+fn superproject(workdir: &Path) -> Option<PathBuf> {
+    let parent = git2::Repository::discover(workdir.parent()?).ok()?;
+    let parent_workdir = parent.workdir()?.canonicalize().ok()?;
+    let path = workdir.strip_prefix(&parent_workdir).ok()?.to_str()?;
+    parent.find_submodule(path).ok()?;
+
+    Some(parent_workdir)
+}
+
+/// Collects the dirty and staged files of a repository that lie in the given
+/// directory (relative to its working directory), including those in its
+/// submodules, which its own status only reports as a whole; the files are
+/// named relative to the outermost repository, given the path of this one
+#[cfg(not(target_arch = "wasm32"))]
+fn statuses(
+    repo: &git2::Repository,
+    scope: &Path,
+    prefix: &Path,
+    dirty: &mut Vec<String>,
+    staged: &mut Vec<String>,
+) -> Result<(), git2::Error> {
+    let mut repo_opts = git2::StatusOptions::new();
+    repo_opts.include_ignored(false);
+    repo_opts.include_untracked(true);
+    repo_opts.exclude_submodules(true);
+    // the status of all of a large repository takes long; the scope is a
+    // literal path (not a pattern), which also matches the files below it
+    if !scope.as_os_str().is_empty() {
+        repo_opts.pathspec(scope);
+        repo_opts.disable_pathspec_match(true);
+    }
+    for status in repo.statuses(Some(&mut repo_opts))?.iter() {
+        let Some(path) = status.path() else {
+            continue;
+        };
+
+        let path = prefix.join(path).to_string_lossy().into_owned();
+        match status.status() {
+            git2::Status::CURRENT => (),
+            git2::Status::INDEX_NEW
+            | git2::Status::INDEX_MODIFIED
+            | git2::Status::INDEX_DELETED
+            | git2::Status::INDEX_RENAMED
+            | git2::Status::INDEX_TYPECHANGE => staged.push(path),
+            _ => dirty.push(path),
+        };
+    }
+
+    for submodule in repo.submodules()? {
+        let path = submodule.path();
+        let scope = if path.starts_with(scope) {
+            Path::new("")
+        } else if let Ok(scope) = scope.strip_prefix(path) {
+            scope
+        } else {
+            continue;
+        };
+        // submodules that are not checked out have no files to change
+        if let Ok(submodule) = submodule.open() {
+            statuses(&submodule, scope, &prefix.join(path), dirty, staged)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs git in the given directory, as a user that can commit
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=minify",
+                "-c",
+                "user.email=minify@example.com",
+            ])
+            .args(["-c", "protocol.file.allow=always"])
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    /// A fresh directory with a repository with the given files committed
+    fn repository(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("minify-vcs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "fn main() {}\n").unwrap();
+        }
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "initial"]);
+
+        dir.canonicalize().unwrap()
+    }
+
+    fn dirty(status: Status) -> Vec<String> {
+        match status {
+            Status::Clean => Vec::new(),
+            Status::Unclean { dirty, .. } => dirty,
+            Status::NoVCS => panic!("no repository found"),
+            Status::Error(error) => panic!("{error}"),
+        }
+    }
+
+    #[test]
+    fn worktree() {
+        let repo = repository("worktree", &["src/main.rs"]);
+        let worktree = repo.with_file_name(format!("{}-linked", repo.display()));
+        let _ = std::fs::remove_dir_all(&worktree);
+        git(
+            &repo,
+            &["worktree", "add", "-q", &worktree.to_string_lossy()],
+        );
+
+        assert!(dirty(status(&worktree)).is_empty());
+        std::fs::write(worktree.join("src/main.rs"), "").unwrap();
+        assert_eq!(dirty(status(&worktree)), ["src/main.rs"]);
+        assert!(dirty(status(&repo)).is_empty());
+
+        std::fs::remove_dir_all(&worktree).unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn submodule() {
+        let sub = repository("submodule", &["src/lib.rs"]);
+        let repo = repository("superproject", &["src/main.rs"]);
+        git(
+            &repo,
+            &["submodule", "add", "-q", &sub.to_string_lossy(), "sub"],
+        );
+        git(&repo, &["commit", "-q", "-m", "add submodule"]);
+
+        assert!(dirty(status(&repo)).is_empty());
+        std::fs::write(repo.join("sub/src/lib.rs"), "").unwrap();
+        // the file in the submodule, rather than the submodule as a whole
+        assert_eq!(dirty(status(&repo)), ["sub/src/lib.rs"]);
+        assert_eq!(dirty(status(repo.join("sub"))), ["src/lib.rs"]);
+
+        std::fs::remove_dir_all(&repo).unwrap();
+        std::fs::remove_dir_all(&sub).unwrap();
+    }
+
+    #[test]
+    fn workspace_scope() {
+        let files = [
+            "workspace/src/main.rs",
+            "workspace-old/src/main.rs",
+            "other/src/main.rs",
+        ];
+        let repo = repository("scope", &files);
+
+        // the pathspec is a literal path, which does not match siblings that
+        // merely start with the same name
+        std::fs::write(repo.join("workspace-old/src/main.rs"), "").unwrap();
+        std::fs::write(repo.join("other/src/main.rs"), "").unwrap();
+        std::fs::write(repo.join("other.txt"), "").unwrap();
+        assert!(dirty(status(repo.join("workspace"))).is_empty());
+
+        std::fs::write(repo.join("workspace/src/main.rs"), "").unwrap();
+        assert_eq!(
+            dirty(status(repo.join("workspace"))),
+            ["workspace/src/main.rs"]
+        );
+
+        std::fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn many_ignored_files() {
+        let repo = repository("ignored", &["src/main.rs"]);
+        std::fs::write(repo.join(".gitignore"), "generated/\n").unwrap();
+
+        // more answers than fit in the buffer of a pipe
+        let files: Vec<_> = (0..10_000)
+            .map(|i| PathBuf::from(format!("generated/a/long/path/to/file_{i}.rs")))
+            .chain([PathBuf::from("src/main.rs")])
+            .collect();
+        let ignored = ignored(&repo, &files);
+        assert_eq!(ignored.len(), 10_000);
+        assert!(!ignored.contains(Path::new("src/main.rs")));
+
+        std::fs::remove_dir_all(&repo).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+//! This module finds the crates in a directory of vendored dependencies (as
+//! created by `cargo vendor`), which are not members of the workspace, so the
+//! items that the workspace does not use can be removed from them. Cargo checks
+//! vendored sources against the checksums in their `.cargo-checksum.json`, so
+//! these are updated along with the sources.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use cargo_metadata::Edition;
+
+use crate::error::Result;
+
+/// The library of a vendored package
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vendored {
+    /// The name of the library crate, as used in paths
+    pub name: String,
+    /// The (canonical) directory of the package
+    pub dir: PathBuf,
+    /// The (canonical) root file of the library
+    pub root: PathBuf,
+    pub edition: Edition,
+}
+
+impl Vendored {
+    pub fn checksum_file(&self) -> PathBuf {
+        self.dir.join(".cargo-checksum.json")
+    }
+}
+
+/// The libraries of the packages in the vendor directory; procedural macro
+/// crates are left out, since their items are not used by name
+pub fn crates(vendor_dir: &Path) -> Result<Vec<Vendored>> {
+    let mut crates = Vec::new();
+    for dir in std::fs::read_dir(vendor_dir)?
+        .flatten()
+        .map(|entry| entry.path())
+    {
+        let Ok(manifest) = std::fs::read_to_string(dir.join("Cargo.toml")) else {
+            continue;
+        };
+        if let Some(vendored) = library(&dir.canonicalize()?, &manifest) {
+            crates.push(vendored);
+        }
+    }
+    crates.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+    Ok(crates)
+}
+
+/// The library of a package, given the directory and contents of its manifest
+fn library(dir: &Path, manifest: &str) -> Option<Vendored> {
+    let lines: Vec<&str> = manifest.lines().collect();
+    if value(&lines, "lib", "proc-macro").as_deref() == Some("true") {
+        return None;
+    }
+
+    let name = value(&lines, "lib", "name").or_else(|| value(&lines, "package", "name"))?;
+    let path = value(&lines, "lib", "path").unwrap_or_else(|| "src/lib.rs".to_owned());
+    let root = dir.join(path).canonicalize().ok()?;
+    let edition = value(&lines, "package", "edition")
+        .and_then(|edition| serde_json::from_value(edition.into()).ok())
+        .unwrap_or(Edition::E2015);
+
+    Some(Vendored {
+        name: name.replace('-', "_"),
+        dir: dir.to_path_buf(),
+        root,
+        edition,
+    })
+}
+
+/// The value of a key in a table of a manifest (only for simple values that fit
+/// on a line), without quotes
+fn value(lines: &[&str], table: &str, key: &str) -> Option<String> {
+    let header = format!("[{table}]");
+    let mut in_table = false;
+    for line in lines {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_table = line == header;
+        } else if let Some((name, value)) = line.split_once('=').filter(|_| in_table) {
+            if name.trim() == key {
+                let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                return Some(value.to_owned());
+            }
+        }
+    }
+
+    None
+}
+
+/// Leaves the given (canonical) files of a vendored package out of its
+/// checksums, since they are changed or removed; cargo does not check the files
+/// that are not listed
+pub fn update_checksum(
+    checksum: &str,
+    vendored: &Vendored,
+    changed: &BTreeSet<PathBuf>,
+) -> Result<String> {
+    let mut checksum: serde_json::Value = serde_json::from_str(checksum)?;
+    if let Some(files) = checksum["files"].as_object_mut() {
+        files.retain(|file, _| !changed.contains(&vendored.dir.join(file)));
+    }
+
+    Ok(checksum.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manifest() {
+        let dir = std::env::temp_dir().join(format!("minify-vendor-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/parse.rs"), "").unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        let manifest = "[package]\nedition = \"2018\"\nname = \"small-parser\"\n\n\
+                        [lib]\npath = \"src/parse.rs\"\n\n[dependencies.memchr]\nversion = \"2\"\n";
+        assert_eq!(
+            library(&dir, manifest),
+            Some(Vendored {
+                name: "small_parser".to_owned(),
+                dir: dir.clone(),
+                root: dir.join("src/parse.rs"),
+                edition: Edition::E2018,
+            })
+        );
+
+        let manifest = "[package]\nname = \"derive\"\n\n[lib]\nproc-macro = true\n";
+        assert_eq!(library(&dir, manifest), None);
+        // there is no src/lib.rs
+        assert_eq!(library(&dir, "[package]\nname = \"other\"\n"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checksum() {
+        let vendored = Vendored {
+            name: "small_parser".to_owned(),
+            dir: PathBuf::from("/vendor/small-parser"),
+            root: PathBuf::from("/vendor/small-parser/src/lib.rs"),
+            edition: Edition::E2021,
+        };
+        let checksum =
+            r#"{"files":{"Cargo.toml":"ab","src/lib.rs":"cd","src/old.rs":"ef"},"package":"01"}"#;
+        let changed = [
+            PathBuf::from("/vendor/small-parser/src/lib.rs"),
+            PathBuf::from("/vendor/small-parser/src/old.rs"),
+        ]
+        .into();
+
+        assert_eq!(
+            update_checksum(checksum, &vendored, &changed).unwrap(),
+            r#"{"files":{"Cargo.toml":"ab"},"package":"01"}"#
+        );
+    }
+}
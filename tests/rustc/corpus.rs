@@ -0,0 +1,77 @@
+const UNUSED_CONST: u8 = 1;
+static UNUSED_STATIC: u8 = 2;
+fn unused_function() {}
+fn r#match() {}
+struct UnusedStruct;
+enum UnusedEnum {
+    A,
+}
+union UnusedUnion {
+    a: u8,
+}
+type UnusedAlias = u8;
+macro_rules! unused_macro {
+    () => {};
+}
+
+struct Partly {
+    read: u8,
+    unread: u8,
+    also_unread: u8,
+}
+
+struct Tuple(u8, u16);
+
+enum Variants {
+    Used,
+    Unused,
+    Fields { x: u8 },
+}
+
+struct Thing;
+
+impl Thing {
+    const ASSOC: u8 = 3;
+    fn method(&self) {}
+}
+
+struct Other;
+
+impl Other {
+    fn single() {}
+}
+
+struct Many;
+
+impl Many {
+    fn first() {}
+    fn second(&self) {}
+    const C: u8 = 1;
+}
+
+trait Tr {
+    fn never_called(&self) {}
+}
+
+impl Tr for Thing {}
+
+trait Called {
+    fn called(&self);
+    fn not_called(&self) {}
+}
+
+impl Called for Thing {
+    fn called(&self) {}
+}
+
+fn main() {
+    let p = Partly { read: 1, unread: 2, also_unread: 3 };
+    let _ = p.read;
+    let t = Tuple(1, 2);
+    let _ = t.1;
+    let _ = Variants::Used;
+    let _ = Variants::Fields { x: 1 };
+    Thing.called();
+    let _ = Other;
+    let _ = Many;
+}